@@ -0,0 +1,7 @@
+DELETE FROM emails WHERE NOT is_primary;
+DROP INDEX emails_notifications_user_id;
+DROP INDEX emails_primary_user_id;
+ALTER TABLE emails DROP CONSTRAINT emails_user_id_email_key;
+ALTER TABLE emails ADD CONSTRAINT emails_user_id_key UNIQUE (user_id);
+ALTER TABLE emails DROP COLUMN notifications;
+ALTER TABLE emails DROP COLUMN is_primary;
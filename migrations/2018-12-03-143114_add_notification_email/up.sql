@@ -0,0 +1,9 @@
+-- Users may now have several email addresses. The primary one is the
+-- address tied to their account, and notifications can be routed to any
+-- other verified address.
+ALTER TABLE emails ADD COLUMN is_primary BOOLEAN NOT NULL DEFAULT 't';
+ALTER TABLE emails ADD COLUMN notifications BOOLEAN NOT NULL DEFAULT 'f';
+ALTER TABLE emails DROP CONSTRAINT emails_user_id_key;
+ALTER TABLE emails ADD CONSTRAINT emails_user_id_email_key UNIQUE (user_id, email);
+CREATE UNIQUE INDEX emails_primary_user_id ON emails (user_id) WHERE is_primary;
+CREATE UNIQUE INDEX emails_notifications_user_id ON emails (user_id) WHERE notifications;
@@ -0,0 +1,3 @@
+-- A NULL `crate_scopes` means the token may be used for any crate the user owns.
+ALTER TABLE api_tokens ADD COLUMN crate_scopes TEXT[];
+ALTER TABLE api_tokens ADD COLUMN expires_at TIMESTAMP;
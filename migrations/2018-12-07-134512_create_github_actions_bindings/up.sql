@@ -0,0 +1,18 @@
+-- Each row lets GitHub Actions workflows in `repository` (`owner/name`,
+-- lowercased) exchange their OIDC token for an API token of `user_id` that
+-- can only be used for the crate. Workflows are matched by `repository_id`
+-- and `repository_owner_id`, GitHub's ids for the repository and its owner,
+-- which stay the same across renames and transfers and aren't reused when
+-- someone else later takes the same `owner/name`.
+CREATE TABLE github_actions_bindings (
+    id SERIAL PRIMARY KEY,
+    crate_id INTEGER NOT NULL REFERENCES crates (id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    repository VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    repository_id VARCHAR NOT NULL,
+    repository_owner_id VARCHAR NOT NULL,
+    UNIQUE (crate_id, repository_id)
+);
+
+CREATE INDEX github_actions_bindings_repository_id ON github_actions_bindings (repository_id);
@@ -0,0 +1,8 @@
+-- Users listed here can't publish, even to crates they own. Rows are added
+-- and removed by the crates.io team, for example while a compromised account
+-- is being investigated.
+CREATE TABLE blocked_users (
+    user_id INTEGER PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    reason VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
@@ -0,0 +1,13 @@
+CREATE TABLE api_token_events (
+    id SERIAL PRIMARY KEY,
+    api_token_id INTEGER NOT NULL REFERENCES api_tokens (id) ON DELETE CASCADE,
+    kind VARCHAR NOT NULL,
+    detail VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX api_token_events_api_token_id ON api_token_events (api_token_id);
+
+-- Creation is the only event whose time is known for existing tokens
+INSERT INTO api_token_events (api_token_id, kind, created_at)
+    SELECT id, 'created', created_at FROM api_tokens;
@@ -0,0 +1,6 @@
+-- Users listed here can use the admin endpoints under `/api/v1/admin`. Rows
+-- are added and removed by hand by the crates.io team.
+CREATE TABLE admin_users (
+    user_id INTEGER PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
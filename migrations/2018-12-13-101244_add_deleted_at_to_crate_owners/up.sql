@@ -0,0 +1,3 @@
+-- Only set while `deleted` is true. Owners removed before this column existed
+-- have no recorded removal time.
+ALTER TABLE crate_owners ADD COLUMN deleted_at TIMESTAMP;
@@ -0,0 +1 @@
+DROP TABLE rights_elevations;
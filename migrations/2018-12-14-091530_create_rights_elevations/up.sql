@@ -0,0 +1,13 @@
+-- Temporary full rights over a crate taken by a member of one of its owning
+-- teams, for example to repair its ownership in an emergency. Rows are kept
+-- once they expire, as a record of who was elevated and why.
+CREATE TABLE rights_elevations (
+    id SERIAL PRIMARY KEY,
+    crate_id INTEGER NOT NULL REFERENCES crates (id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    justification VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    expires_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX rights_elevations_crate_id_user_id ON rights_elevations (crate_id, user_id);
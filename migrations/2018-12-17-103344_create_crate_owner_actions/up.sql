@@ -0,0 +1,26 @@
+-- A log of every owner added to or removed from a crate, so that its owners
+-- at any point in the past can be worked out. `performed_by` is the user who
+-- made the change, when it's known.
+CREATE TABLE crate_owner_actions (
+    id SERIAL PRIMARY KEY,
+    crate_id INTEGER NOT NULL REFERENCES crates (id) ON DELETE CASCADE,
+    owner_id INTEGER NOT NULL,
+    owner_kind INTEGER NOT NULL,
+    action VARCHAR NOT NULL,
+    performed_by INTEGER REFERENCES users (id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX crate_owner_actions_crate_id ON crate_owner_actions (crate_id, created_at);
+
+-- The existing owners are backfilled as well as their rows allow: every owner
+-- was added when their row was created, and removed owners were removed when
+-- their row was last updated, unless the time of removal was recorded.
+INSERT INTO crate_owner_actions (crate_id, owner_id, owner_kind, action, performed_by, created_at)
+SELECT crate_id, owner_id, owner_kind, 'add', created_by, created_at
+FROM crate_owners;
+
+INSERT INTO crate_owner_actions (crate_id, owner_id, owner_kind, action, created_at)
+SELECT crate_id, owner_id, owner_kind, 'remove', COALESCE(deleted_at, updated_at)
+FROM crate_owners
+WHERE deleted;
@@ -0,0 +1,3 @@
+-- Free text set by whoever provisioned the token, such as the path it is stored at in a
+-- secret manager, so that tokens can be matched up with an external system.
+ALTER TABLE api_tokens ADD COLUMN external_ref VARCHAR(255);
@@ -0,0 +1,3 @@
+-- The kinds of request a token may be used for, such as `publish-update` or `yank`. `NULL`
+-- means the token can be used for anything its user can do.
+ALTER TABLE api_tokens ADD COLUMN endpoint_scopes TEXT[];
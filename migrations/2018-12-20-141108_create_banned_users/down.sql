@@ -0,0 +1,2 @@
+DROP TABLE user_ban_events;
+DROP TABLE banned_users;
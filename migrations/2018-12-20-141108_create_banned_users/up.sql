@@ -0,0 +1,19 @@
+-- Users listed here can't sign in or use their API tokens. Rows are added and
+-- removed by the crates.io team through the admin endpoints.
+CREATE TABLE banned_users (
+    user_id INTEGER PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    reason VARCHAR NOT NULL,
+    banned_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+-- Every ban and unban, kept after the ban is lifted.
+CREATE TABLE user_ban_events (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    admin_id INTEGER NOT NULL REFERENCES users (id),
+    kind VARCHAR NOT NULL,
+    reason VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX user_ban_events_user_id ON user_ban_events (user_id);
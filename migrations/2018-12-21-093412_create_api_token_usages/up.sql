@@ -0,0 +1,8 @@
+-- How many authenticated requests each API token made, counted per hour so
+-- that a busy token only ever updates one row at a time.
+CREATE TABLE api_token_usages (
+    api_token_id INTEGER NOT NULL REFERENCES api_tokens (id) ON DELETE CASCADE,
+    hour TIMESTAMP NOT NULL DEFAULT date_trunc('hour', now()),
+    uses INTEGER NOT NULL DEFAULT 1,
+    PRIMARY KEY (api_token_id, hour)
+);
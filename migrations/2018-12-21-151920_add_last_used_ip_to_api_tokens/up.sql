@@ -0,0 +1,3 @@
+-- The address of the client that last authenticated with the token, so that
+-- users can spot it being used from somewhere unexpected.
+ALTER TABLE api_tokens ADD COLUMN last_used_ip VARCHAR;
@@ -0,0 +1,3 @@
+-- How many times the token has been used to authenticate, so that users can
+-- spot scripts using it far more often than expected.
+ALTER TABLE api_tokens ADD COLUMN use_count BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,5 @@
+-- The original tokens can't be recovered from their hashes, so every token
+-- is given a new value, which its user will have to retrieve again.
+ALTER TABLE api_tokens ADD COLUMN token VARCHAR NOT NULL UNIQUE DEFAULT random_string(32);
+CREATE INDEX ON api_tokens (token);
+ALTER TABLE api_tokens DROP COLUMN token_hash;
@@ -0,0 +1,11 @@
+-- Only a SHA-256 hash of each token is kept, so that the tokens can't be
+-- recovered from the database. Tokens are looked up by hashing the value a
+-- request authenticates with.
+CREATE EXTENSION IF NOT EXISTS pgcrypto;
+
+ALTER TABLE api_tokens ADD COLUMN token_hash BYTEA;
+UPDATE api_tokens SET token_hash = digest(token, 'sha256');
+ALTER TABLE api_tokens ALTER COLUMN token_hash SET NOT NULL;
+CREATE UNIQUE INDEX api_tokens_token_hash ON api_tokens (token_hash);
+
+ALTER TABLE api_tokens DROP COLUMN token;
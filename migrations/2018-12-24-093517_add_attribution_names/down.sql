@@ -0,0 +1,3 @@
+ALTER TABLE versions DROP COLUMN published_as;
+ALTER TABLE versions DROP COLUMN published_by;
+ALTER TABLE api_tokens DROP COLUMN attribution_name;
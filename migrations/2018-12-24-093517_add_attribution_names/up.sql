@@ -0,0 +1,8 @@
+-- A name such as "release-bot" that publishes made with the token are
+-- attributed to, for tokens used by automation on behalf of an organization.
+ALTER TABLE api_tokens ADD COLUMN attribution_name VARCHAR(255);
+
+-- Who published each version, and the attribution name of the token they
+-- used, if it had one. Versions published before this was tracked have neither.
+ALTER TABLE versions ADD COLUMN published_by INTEGER REFERENCES users (id);
+ALTER TABLE versions ADD COLUMN published_as VARCHAR(255);
@@ -0,0 +1,2 @@
+DELETE FROM emails WHERE replaces_primary;
+ALTER TABLE emails DROP COLUMN replaces_primary;
@@ -0,0 +1,4 @@
+-- Set on an address a user asked to change their primary email to. It takes
+-- the primary address's place once it is confirmed, until then the old
+-- address stays in use.
+ALTER TABLE emails ADD COLUMN replaces_primary BOOLEAN NOT NULL DEFAULT 'f';
@@ -0,0 +1,3 @@
+-- Existing accounts get the time of this migration, their real creation
+-- time isn't recorded anywhere.
+ALTER TABLE users ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT now();
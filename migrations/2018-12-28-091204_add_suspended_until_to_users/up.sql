@@ -0,0 +1,3 @@
+-- Until this time the account can't sign in or use its API tokens. Unlike a
+-- ban it lifts on its own.
+ALTER TABLE users ADD COLUMN suspended_until TIMESTAMP;
@@ -0,0 +1,2 @@
+-- A name chosen on crates.io, shown instead of the GitHub name when set.
+ALTER TABLE users ADD COLUMN display_name VARCHAR;
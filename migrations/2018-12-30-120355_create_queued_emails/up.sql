@@ -0,0 +1,11 @@
+-- Emails waiting to be sent by `send-queued-emails`, so that sending them
+-- doesn't hold up the request that queued them.
+CREATE TABLE queued_emails (
+    id SERIAL PRIMARY KEY,
+    recipient VARCHAR NOT NULL,
+    subject VARCHAR NOT NULL,
+    body TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT
+);
@@ -0,0 +1,2 @@
+UPDATE users SET gh_access_token = '' WHERE gh_access_token IS NULL;
+ALTER TABLE users ALTER COLUMN gh_access_token SET NOT NULL;
@@ -0,0 +1,2 @@
+-- Cleared when the user logs out, as the token is revoked on GitHub then.
+ALTER TABLE users ALTER COLUMN gh_access_token DROP NOT NULL;
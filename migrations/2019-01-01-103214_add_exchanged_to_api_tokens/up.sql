@@ -0,0 +1,4 @@
+-- Set for the short-lived tokens handed out in exchange for a GitHub Actions
+-- OIDC token, which don't count against the limits on how many tokens a user
+-- may have or create.
+ALTER TABLE api_tokens ADD COLUMN exchanged BOOLEAN NOT NULL DEFAULT FALSE;
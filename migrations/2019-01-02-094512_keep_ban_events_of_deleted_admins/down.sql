@@ -0,0 +1,5 @@
+DELETE FROM user_ban_events WHERE admin_id IS NULL;
+ALTER TABLE user_ban_events DROP CONSTRAINT user_ban_events_admin_id_fkey;
+ALTER TABLE user_ban_events ADD CONSTRAINT user_ban_events_admin_id_fkey
+    FOREIGN KEY (admin_id) REFERENCES users (id);
+ALTER TABLE user_ban_events ALTER COLUMN admin_id SET NOT NULL;
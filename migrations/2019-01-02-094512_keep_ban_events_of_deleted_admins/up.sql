@@ -0,0 +1,6 @@
+-- Admins can delete their accounts like anyone else, which leaves the bans and
+-- unbans they made without an admin rather than blocking the deletion.
+ALTER TABLE user_ban_events ALTER COLUMN admin_id DROP NOT NULL;
+ALTER TABLE user_ban_events DROP CONSTRAINT user_ban_events_admin_id_fkey;
+ALTER TABLE user_ban_events ADD CONSTRAINT user_ban_events_admin_id_fkey
+    FOREIGN KEY (admin_id) REFERENCES users (id) ON DELETE SET NULL;
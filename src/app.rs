@@ -11,7 +11,8 @@ use oauth2;
 use reqwest;
 use scheduled_thread_pool::ScheduledThreadPool;
 
-use util::CargoResult;
+use email::{self, EmailSender};
+use util::{CargoResult, RateLimit, TtlCache};
 use {db, Config, Env};
 
 /// The `App` struct holds the main components of the application like
@@ -36,6 +37,31 @@ pub struct App {
 
     /// The server configuration
     pub config: Config,
+
+    /// Invalid API tokens seen recently, keyed by the IP address that sent them
+    pub token_lockout: RateLimit,
+
+    /// Recent ownership changes, keyed by crate id
+    pub owner_changes: RateLimit,
+
+    /// Whether users are active members of GitHub teams, keyed by the team's GitHub id and the
+    /// user's id
+    pub team_memberships: TtlCache<(i32, i32), bool>,
+
+    /// The modulus and exponent of the keys GitHub Actions signs OIDC tokens with, keyed by key id
+    pub actions_signing_keys: TtlCache<String, (String, String)>,
+
+    /// Present while the GitHub Actions signing keys were fetched recently, so that tokens naming
+    /// an unknown key id are rejected without asking GitHub again
+    pub actions_signing_keys_refreshed: TtlCache<(), ()>,
+
+    /// How many more requests GitHub's latest response said can be made before being rate
+    /// limited, keyed by the access token or client id the requests were made with. GitHub's
+    /// limits reset every hour, so counts are forgotten an hour after they were last updated.
+    pub github_rate_limit_remaining: TtlCache<String, u32>,
+
+    /// How emails that can't wait for `send-queued-emails` are sent
+    pub email_sender: Box<dyn EmailSender>,
 }
 
 impl App {
@@ -97,6 +123,16 @@ impl App {
             git_repo: Mutex::new(repo),
             git_repo_checkout: config.git_repo_checkout.clone(),
             config: config.clone(),
+            token_lockout: RateLimit::new(
+                config.token_lockout_threshold,
+                config.token_lockout_window,
+            ),
+            owner_changes: RateLimit::new(config.owner_change_limit, config.owner_change_window),
+            team_memberships: TtlCache::new(config.gh_membership_cache_ttl),
+            actions_signing_keys: TtlCache::new(Duration::from_secs(60 * 60)),
+            actions_signing_keys_refreshed: TtlCache::new(Duration::from_secs(5 * 60)),
+            github_rate_limit_remaining: TtlCache::new(Duration::from_secs(60 * 60)),
+            email_sender: email::sender(),
         }
     }
 
@@ -106,10 +142,23 @@ impl App {
     /// is only done in tests with `TestApp::with_proxy()` in order to be able to record and
     /// inspect the HTTP requests that tests make.
     pub fn http_client(&self) -> CargoResult<reqwest::Client> {
+        Ok(self.http_client_builder()?.build()?)
+    }
+
+    /// Returns a client for making requests to the GitHub API.
+    ///
+    /// This is the same as `http_client`, except that requests give up once `Config::gh_timeout`
+    /// has elapsed so that a slow GitHub can't hold up the request being served.
+    pub fn github_client(&self) -> CargoResult<reqwest::Client> {
+        let builder = self.http_client_builder()?.timeout(self.config.gh_timeout);
+        Ok(builder.build()?)
+    }
+
+    fn http_client_builder(&self) -> CargoResult<reqwest::ClientBuilder> {
         let mut builder = reqwest::Client::builder();
         if let Some(proxy) = self.config.uploader.proxy() {
             builder = builder.proxy(reqwest::Proxy::all(proxy)?);
         }
-        Ok(builder.build()?)
+        Ok(builder)
     }
 }
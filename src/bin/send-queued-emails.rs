@@ -0,0 +1,51 @@
+// Sends the emails queued in the `queued_emails` table.
+//
+// Usage:
+//      cargo run --bin send-queued-emails [daemon <seconds between runs>]
+
+#![deny(warnings)]
+#![allow(unknown_lints, proc_macro_derive_resolution_fallback)] // This can be removed after diesel-1.4
+
+extern crate cargo_registry;
+
+use std::env;
+use std::error::Error;
+use std::process;
+use std::time::Duration;
+
+use cargo_registry::email::{self, EmailSender};
+use cargo_registry::models::QueuedEmail;
+
+fn main() {
+    let daemon = env::args().nth(1).as_ref().map(|s| &s[..]) == Some("daemon");
+    let sleep = env::args().nth(2).map(|s| s.parse().unwrap());
+    let sender = email::sender();
+    loop {
+        // A failed run is retried on the next one when running as a daemon
+        if let Err(e) = send_queued(&*sender) {
+            eprintln!("Failed to send queued emails: {}", e);
+            if !daemon {
+                process::exit(1);
+            }
+        }
+        if daemon {
+            std::thread::sleep(Duration::new(sleep.unwrap(), 0));
+        } else {
+            break;
+        }
+    }
+}
+
+fn send_queued(sender: &dyn EmailSender) -> Result<(), Box<dyn Error>> {
+    let conn = cargo_registry::db::connect_now()?;
+    let sent = QueuedEmail::send_queued(&conn, sender)?;
+    println!("Sent {} queued emails", sent);
+    let given_up = QueuedEmail::given_up_count(&conn)?;
+    if given_up > 0 {
+        println!(
+            "Gave up on {} queued emails that failed to send too many times",
+            given_up
+        );
+    }
+    Ok(())
+}
@@ -13,7 +13,7 @@ use std::env;
 use std::io;
 use std::io::prelude::*;
 
-use cargo_registry::models::{Crate, OwnerKind, User};
+use cargo_registry::models::{Crate, Owner, OwnerKind, User};
 use cargo_registry::schema::*;
 
 fn main() {
@@ -76,9 +76,10 @@ fn transfer(conn: &PgConnection) {
         .load::<Crate>(conn)
         .unwrap();
 
+    let crate_ids = crates.iter().map(|krate| krate.id).collect::<Vec<_>>();
+    let owners = Owner::owning_many(&crate_ids, conn).unwrap();
     for krate in crates {
-        let owners = krate.owners(conn).unwrap();
-        if owners.len() != 1 {
+        if owners[&krate.id].len() != 1 {
             println!("warning: not exactly one owner for {}", krate.name);
         }
     }
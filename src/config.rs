@@ -2,7 +2,10 @@ use s3;
 
 use std::env;
 use std::path::PathBuf;
+use std::time::Duration;
 
+use controllers::token::parse_scopes;
+use util::CargoError;
 use {env, Env, Replica, Uploader};
 
 #[derive(Clone, Debug)]
@@ -18,6 +21,24 @@ pub struct Config {
     pub max_unpack_size: u64,
     pub mirror: Replica,
     pub api_protocol: String,
+    pub gh_timeout: Duration,
+    pub gh_webhook_secret: Option<String>,
+    pub token_lockout_threshold: u32,
+    pub token_lockout_window: Duration,
+    pub gh_membership_cache_ttl: Duration,
+    pub owner_change_limit: u32,
+    pub owner_change_window: Duration,
+    pub rights_elevation_duration: Duration,
+    pub email_confirmation_url: Option<String>,
+    pub require_verified_owner_email: bool,
+    pub require_verified_publisher_email: bool,
+    pub unverified_email_message: String,
+    pub narrow_rotated_tokens: bool,
+    pub gh_rate_limit_reserve: u32,
+    pub default_token_scopes: Option<Vec<String>>,
+    pub token_creation_limit: u32,
+    pub expired_token_grace_period: Duration,
+    pub max_tokens_per_user: usize,
 }
 
 impl Default for Config {
@@ -27,6 +48,24 @@ impl Default for Config {
     ///
     /// - `Config::max_upload_size`: 10MiB
     /// - `Config::api_protocol`: `https`
+    /// - `Config::gh_timeout`: 10 seconds
+    /// - `Config::token_lockout_threshold`: 10 invalid API tokens
+    /// - `Config::token_lockout_window`: 15 minutes
+    /// - `Config::gh_membership_cache_ttl`: 5 minutes
+    /// - `Config::owner_change_limit`: 10 ownership changes per crate
+    /// - `Config::owner_change_window`: 1 hour
+    /// - `Config::rights_elevation_duration`: 0, so team members can't elevate their rights
+    /// - `Config::require_verified_owner_email`: `true`
+    /// - `Config::require_verified_publisher_email`: `false`, so publishing only warns
+    /// - `Config::unverified_email_message`: "you must verify your email before publishing"
+    /// - `Config::narrow_rotated_tokens`: `true`
+    /// - `Config::gh_rate_limit_reserve`: 100 requests
+    /// - `Config::default_token_scopes`: `None`, so new tokens can do anything unless scoped
+    /// - `Config::token_creation_limit`: 50 API tokens created per user per hour, not counting
+    ///   those handed out by `POST /tokens/exchange`
+    /// - `Config::expired_token_grace_period`: none, so expired API tokens stop working at once
+    /// - `Config::max_tokens_per_user`: 500 API tokens that can still be used, not counting those
+    ///   handed out by `POST /tokens/exchange`
     ///
     /// Pulls values from the following environment variables:
     ///
@@ -42,6 +81,40 @@ impl Default for Config {
     /// - `GH_CLIENT_ID`: The client ID of the associated GitHub application.
     /// - `GH_CLIENT_SECRET`: The client secret of the associated GitHub application.
     /// - `DATABASE_URL`: The URL of the postgres database to use.
+    /// - `GH_TIMEOUT`: The number of seconds to wait for a response from the GitHub API.
+    /// - `GH_WEBHOOK_SECRET`: The secret used to sign webhook deliveries from GitHub. Webhooks are
+    /// rejected if this is not set.
+    /// - `TOKEN_LOCKOUT_THRESHOLD`: The number of invalid API tokens an IP address can send before
+    /// its requests authenticated by API token are rejected. Counted separately by each server
+    /// process, so the limit applies per process rather than across all of them.
+    /// - `TOKEN_LOCKOUT_WINDOW`: The number of seconds over which invalid API tokens are counted.
+    /// - `GH_MEMBERSHIP_CACHE_TTL`: The number of seconds GitHub team memberships are remembered
+    /// for. Set to 0 to ask GitHub every time.
+    /// - `OWNER_CHANGE_LIMIT`: The number of owners that can be added to or removed from a crate
+    /// within the window. Like `TOKEN_LOCKOUT_THRESHOLD`, this is counted per server process.
+    /// - `OWNER_CHANGE_WINDOW`: The number of seconds over which ownership changes are counted.
+    /// - `RIGHTS_ELEVATION_DURATION`: The number of seconds a team member's temporary full rights
+    /// over a crate last for. Elevated members can change the crate's owners, so only allow this
+    /// where every member of an owning team may be trusted with the whole crate. Set to 0 to not
+    /// allow elevation at all.
+    /// - `EMAIL_CONFIRMATION_URL`: Where to send users after they follow the link confirming
+    /// their email address, with a `status` query parameter of `success` or `failure`. If this is
+    /// not set, the confirmation endpoint responds with JSON instead.
+    /// - `REQUIRE_VERIFIED_OWNER_EMAIL`: Whether users must have a verified email address before
+    /// they can be invited to own a crate, `true` or `false`.
+    /// - `NARROW_ROTATED_TOKENS`: Whether the replacement for a rotated API token can only have
+    /// narrower crate scopes and an earlier expiry than the token it replaces, `true` or `false`.
+    /// - `GH_RATE_LIMIT_RESERVE`: How many GitHub API requests to keep in hand. Once GitHub says
+    /// fewer than this are left for the signed in user's token, requests made with it that aren't
+    /// essential, such as expanding teams into their members, are skipped.
+    /// - `DEFAULT_TOKEN_SCOPES`: A comma separated list of the scopes, such as `publish-update`,
+    /// given to new API tokens whose creator doesn't pick any. Checked at startup; scopes that
+    /// name crates, such as `publish-update:serde`, aren't allowed.
+    /// - `TOKEN_CREATION_LIMIT`: The number of API tokens a user can create in an hour.
+    /// - `EXPIRED_TOKEN_GRACE_PERIOD`: The number of seconds after an API token expires during
+    /// which it can still be used for reads, though not for changes such as publishing.
+    /// - `MAX_TOKENS_PER_USER`: The number of API tokens a user can have, not counting revoked or
+    /// expired ones, or those handed out by `POST /tokens/exchange`.
     fn default() -> Config {
         let checkout = PathBuf::from(env("GIT_REPO_CHECKOUT"));
         let api_protocol = String::from("https");
@@ -50,6 +123,91 @@ impl Default for Config {
         } else {
             Replica::Primary
         };
+        let gh_timeout = match env::var("GH_TIMEOUT") {
+            Ok(secs) => secs.parse().expect("couldn't parse GH_TIMEOUT"),
+            Err(_) => 10,
+        };
+        let token_lockout_threshold = match env::var("TOKEN_LOCKOUT_THRESHOLD") {
+            Ok(n) => n.parse().expect("couldn't parse TOKEN_LOCKOUT_THRESHOLD"),
+            Err(_) => 10,
+        };
+        let token_lockout_window = match env::var("TOKEN_LOCKOUT_WINDOW") {
+            Ok(secs) => secs.parse().expect("couldn't parse TOKEN_LOCKOUT_WINDOW"),
+            Err(_) => 15 * 60,
+        };
+        let gh_membership_cache_ttl = match env::var("GH_MEMBERSHIP_CACHE_TTL") {
+            Ok(secs) => secs
+                .parse()
+                .expect("couldn't parse GH_MEMBERSHIP_CACHE_TTL"),
+            Err(_) => 5 * 60,
+        };
+        let owner_change_limit = match env::var("OWNER_CHANGE_LIMIT") {
+            Ok(n) => n.parse().expect("couldn't parse OWNER_CHANGE_LIMIT"),
+            Err(_) => 10,
+        };
+        let owner_change_window = match env::var("OWNER_CHANGE_WINDOW") {
+            Ok(secs) => secs.parse().expect("couldn't parse OWNER_CHANGE_WINDOW"),
+            Err(_) => 60 * 60,
+        };
+        let rights_elevation_duration = match env::var("RIGHTS_ELEVATION_DURATION") {
+            Ok(secs) => secs
+                .parse()
+                .expect("couldn't parse RIGHTS_ELEVATION_DURATION"),
+            Err(_) => 0,
+        };
+        let require_verified_owner_email = match env::var("REQUIRE_VERIFIED_OWNER_EMAIL") {
+            Ok(flag) => flag
+                .parse()
+                .expect("couldn't parse REQUIRE_VERIFIED_OWNER_EMAIL"),
+            Err(_) => true,
+        };
+        let require_verified_publisher_email = match env::var("REQUIRE_VERIFIED_PUBLISHER_EMAIL") {
+            Ok(flag) => flag
+                .parse()
+                .expect("couldn't parse REQUIRE_VERIFIED_PUBLISHER_EMAIL"),
+            Err(_) => false,
+        };
+        let unverified_email_message = env::var("UNVERIFIED_EMAIL_MESSAGE")
+            .unwrap_or_else(|_| String::from("you must verify your email before publishing"));
+        let narrow_rotated_tokens = match env::var("NARROW_ROTATED_TOKENS") {
+            Ok(flag) => flag.parse().expect("couldn't parse NARROW_ROTATED_TOKENS"),
+            Err(_) => true,
+        };
+        let gh_rate_limit_reserve = match env::var("GH_RATE_LIMIT_RESERVE") {
+            Ok(n) => n.parse().expect("couldn't parse GH_RATE_LIMIT_RESERVE"),
+            Err(_) => 100,
+        };
+        let token_creation_limit = match env::var("TOKEN_CREATION_LIMIT") {
+            Ok(n) => n.parse().expect("couldn't parse TOKEN_CREATION_LIMIT"),
+            Err(_) => 50,
+        };
+        let expired_token_grace_period = match env::var("EXPIRED_TOKEN_GRACE_PERIOD") {
+            Ok(secs) => secs
+                .parse()
+                .expect("couldn't parse EXPIRED_TOKEN_GRACE_PERIOD"),
+            Err(_) => 0,
+        };
+        let max_tokens_per_user = match env::var("MAX_TOKENS_PER_USER") {
+            Ok(n) => n.parse().expect("couldn't parse MAX_TOKENS_PER_USER"),
+            Err(_) => 500,
+        };
+        let default_token_scopes = env::var("DEFAULT_TOKEN_SCOPES").ok().map(|scopes| {
+            let scopes = scopes
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .collect::<Vec<_>>();
+            if let Some(scope) = scopes.iter().find(|s| s.contains(':')) {
+                panic!(
+                    "couldn't parse DEFAULT_TOKEN_SCOPES: `{}` names crates, but default scopes \
+                     apply to every crate",
+                    scope
+                );
+            }
+            if let Err(e) = parse_scopes(&scopes) {
+                panic!("couldn't parse DEFAULT_TOKEN_SCOPES: {}", e.description());
+            }
+            scopes
+        });
         let heroku = env::var("HEROKU").is_ok();
         let cargo_env = if heroku {
             Env::Production
@@ -136,6 +294,24 @@ impl Default for Config {
             max_unpack_size: 512 * 1024 * 1024, // 512 MB max when decompressed
             mirror,
             api_protocol,
+            gh_timeout: Duration::from_secs(gh_timeout),
+            gh_webhook_secret: env::var("GH_WEBHOOK_SECRET").ok(),
+            token_lockout_threshold,
+            token_lockout_window: Duration::from_secs(token_lockout_window),
+            gh_membership_cache_ttl: Duration::from_secs(gh_membership_cache_ttl),
+            owner_change_limit,
+            owner_change_window: Duration::from_secs(owner_change_window),
+            rights_elevation_duration: Duration::from_secs(rights_elevation_duration),
+            email_confirmation_url: env::var("EMAIL_CONFIRMATION_URL").ok(),
+            require_verified_owner_email,
+            require_verified_publisher_email,
+            unverified_email_message,
+            narrow_rotated_tokens,
+            gh_rate_limit_reserve,
+            default_token_scopes,
+            token_creation_limit,
+            expired_token_grace_period: Duration::from_secs(expired_token_grace_period),
+            max_tokens_per_user,
         }
     }
 }
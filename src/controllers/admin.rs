@@ -0,0 +1,248 @@
+//! Endpoints used by the crates.io team for moderation and incident response.
+//!
+//! Every handler here requires the signed in user to be listed in the
+//! `admin_users` table, which `RequestUser::admin` checks.
+
+use super::prelude::*;
+
+use serde_json;
+
+use util::errors::bad_request;
+
+use models::{Email, User, UserBan};
+use views::EncodablePublicUser;
+
+/// Handles the `GET /admin/tokens/:id/owner` route.
+///
+/// Resolves a token id, for example one quoted in a report, to the account
+/// that owns it. The token itself is never part of the response.
+pub fn token_owner(req: &mut dyn Request) -> CargoResult<Response> {
+    use schema::{api_tokens, users};
+
+    req.admin()?;
+    let id = req.params()["id"]
+        .parse::<i32>()
+        .map_err(|e| bad_request(&format!("invalid token id: {:?}", e)))?;
+
+    let conn = req.db_conn()?;
+    let owner = api_tokens::table
+        .find(id)
+        .inner_join(users::table)
+        .select(users::all_columns)
+        .first::<User>(&*conn)?;
+
+    #[derive(Serialize)]
+    struct R {
+        user: EncodablePublicUser,
+    }
+    Ok(req.json(&R {
+        user: owner.encodable_public(),
+    }))
+}
+
+/// What stands in for the confirmation token in previewed emails.
+const REDACTED_TOKEN: &str = "[redacted]";
+
+/// Handles the `GET /admin/users/:id/confirm_email/preview` route.
+///
+/// Renders the email that would ask the user to confirm their primary
+/// email address, without sending it. The confirmation token is redacted.
+pub fn preview_confirm_email(req: &mut dyn Request) -> CargoResult<Response> {
+    use schema::{emails, users};
+
+    req.admin()?;
+    let id = req.params()["id"]
+        .parse::<i32>()
+        .map_err(|e| bad_request(&format!("invalid user id: {:?}", e)))?;
+
+    let conn = req.db_conn()?;
+    let user = users::table.find(id).first::<User>(&*conn)?;
+    let email = Email::belonging_to(&user)
+        .order((emails::is_primary.desc(), emails::id))
+        .first::<Email>(&*conn)
+        .optional()?
+        .ok_or_else(|| human("this user has no email address to confirm"))?;
+    let rendered = ::email::user_confirm_email(&user.gh_login, REDACTED_TOKEN);
+
+    #[derive(Serialize)]
+    struct R {
+        to: String,
+        subject: String,
+        body: String,
+    }
+    Ok(req.json(&R {
+        to: email.email,
+        subject: rendered.subject,
+        body: rendered.body,
+    }))
+}
+
+/// Handles the `GET /admin/emails/pending_count` route.
+///
+/// Counts the email addresses that were sent a confirmation link which hasn't
+/// been followed yet. With `older_than_hours`, only addresses whose link was
+/// sent at least that long ago are counted.
+pub fn pending_email_count(req: &mut dyn Request) -> CargoResult<Response> {
+    use diesel::dsl::{count_star, now, IntervalDsl};
+    use schema::emails;
+
+    req.admin()?;
+    let older_than_hours = match req.query().get("older_than_hours") {
+        Some(hours) => Some(
+            hours
+                .parse::<i32>()
+                .map_err(|e| bad_request(&format!("invalid older_than_hours: {:?}", e)))?,
+        ),
+        None => None,
+    };
+
+    let mut query = emails::table
+        .filter(emails::verified.eq(false))
+        .select(count_star())
+        .into_boxed();
+    if let Some(hours) = older_than_hours {
+        query = query.filter(emails::token_generated_at.le((now - hours.hours()).nullable()));
+    }
+    let count = query.get_result::<i64>(&*req.db_conn()?)?;
+
+    #[derive(Serialize)]
+    struct R {
+        count: i64,
+    }
+    Ok(req.json(&R { count }))
+}
+
+/// Handles the `PUT /admin/users/:id/ban` route.
+///
+/// Bans a user, which stops them from signing in and revokes all of their API
+/// tokens. The body must give a `reason`, which is kept with the ban and in
+/// its audit log.
+pub fn ban_user(req: &mut dyn Request) -> CargoResult<Response> {
+    use schema::users;
+
+    #[derive(Deserialize)]
+    struct BanRequest {
+        reason: String,
+    }
+
+    let admin_id = req.admin()?.id;
+    let id = req.params()["id"]
+        .parse::<i32>()
+        .map_err(|e| bad_request(&format!("invalid user id: {:?}", e)))?;
+
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    let ban: BanRequest = serde_json::from_str(&body)
+        .map_err(|e| bad_request(&format!("invalid ban request: {}", e)))?;
+    if ban.reason.trim().is_empty() {
+        return Err(bad_request("a reason must be given for the ban"));
+    }
+
+    let conn = req.db_conn()?;
+    let user = users::table.find(id).first::<User>(&*conn)?;
+    let (ban, revoked_tokens) = UserBan::create(&conn, user.id, admin_id, &ban.reason)?
+        .ok_or_else(|| bad_request(&format_args!("`{}` is already banned", user.gh_login)))?;
+
+    #[derive(Serialize)]
+    struct R {
+        ban: UserBan,
+        revoked_tokens: usize,
+    }
+    Ok(req.json(&R {
+        ban,
+        revoked_tokens,
+    }))
+}
+
+/// Handles the `DELETE /admin/users/:id/ban` route.
+///
+/// Lifts a user's ban so that they can sign in again. API tokens revoked by
+/// the ban stay revoked.
+pub fn unban_user(req: &mut dyn Request) -> CargoResult<Response> {
+    use schema::users;
+
+    let admin_id = req.admin()?.id;
+    let id = req.params()["id"]
+        .parse::<i32>()
+        .map_err(|e| bad_request(&format!("invalid user id: {:?}", e)))?;
+
+    let conn = req.db_conn()?;
+    let user = users::table.find(id).first::<User>(&*conn)?;
+    if !UserBan::lift(&conn, user.id, admin_id)? {
+        return Err(bad_request(&format_args!(
+            "`{}` is not banned",
+            user.gh_login
+        )));
+    }
+    ok_true()
+}
+
+/// Handles the `POST /users/:id/suspend` route.
+///
+/// Suspends a user until the RFC 3339 time given as `until` in the body. Until
+/// then they can't sign in or use their API tokens, which are kept. Suspending
+/// an already suspended user moves the end of the suspension.
+pub fn suspend_user(req: &mut dyn Request) -> CargoResult<Response> {
+    use chrono::{NaiveDateTime, Utc};
+    use schema::users;
+    use util::rfc3339;
+
+    #[derive(Deserialize)]
+    struct SuspendRequest {
+        #[serde(with = "rfc3339")]
+        until: NaiveDateTime,
+    }
+
+    req.admin()?;
+    let id = req.params()["id"]
+        .parse::<i32>()
+        .map_err(|e| bad_request(&format!("invalid user id: {:?}", e)))?;
+
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    let suspension: SuspendRequest = serde_json::from_str(&body)
+        .map_err(|e| bad_request(&format!("invalid suspend request: {}", e)))?;
+    if suspension.until <= Utc::now().naive_utc() {
+        return Err(bad_request("a suspension must end in the future"));
+    }
+
+    let conn = req.db_conn()?;
+    let user = diesel::update(users::table.find(id))
+        .set(users::suspended_until.eq(suspension.until))
+        .get_result::<User>(&*conn)?;
+
+    #[derive(Serialize)]
+    struct R {
+        #[serde(with = "rfc3339::option")]
+        suspended_until: Option<NaiveDateTime>,
+    }
+    Ok(req.json(&R {
+        suspended_until: user.suspended_until,
+    }))
+}
+
+/// Handles the `DELETE /users/:id/suspend` route.
+///
+/// Ends a user's suspension early so that they can sign in and use their API
+/// tokens again.
+pub fn unsuspend_user(req: &mut dyn Request) -> CargoResult<Response> {
+    use schema::users;
+
+    req.admin()?;
+    let id = req.params()["id"]
+        .parse::<i32>()
+        .map_err(|e| bad_request(&format!("invalid user id: {:?}", e)))?;
+
+    let conn = req.db_conn()?;
+    let user = users::table.find(id).first::<User>(&*conn)?;
+    if user.suspension_end().is_none() {
+        return Err(bad_request(&format_args!(
+            "`{}` is not suspended",
+            user.gh_login
+        )));
+    }
+    diesel::update(&user)
+        .set(users::suspended_until.eq(None::<::chrono::NaiveDateTime>))
+        .execute(&*conn)?;
+    ok_true()
+}
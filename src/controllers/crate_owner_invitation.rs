@@ -3,7 +3,8 @@ use super::prelude::*;
 use serde_json;
 
 use models::{CrateOwner, CrateOwnerInvitation, OwnerKind};
-use schema::{crate_owner_invitations, crate_owners};
+use schema::crate_owner_invitations;
+use util::errors::gone;
 use views::{EncodableCrateOwnerInvitation, InvitationResponse};
 
 /// Handles the `GET /me/crate_owner_invitations` route.
@@ -13,6 +14,7 @@ pub fn list(req: &mut dyn Request) -> CargoResult<Response> {
 
     let crate_owner_invitations = crate_owner_invitations::table
         .filter(crate_owner_invitations::invited_user_id.eq(user_id))
+        .filter(crate_owner_invitations::created_at.gt(CrateOwnerInvitation::expiry_cutoff()))
         .load::<CrateOwnerInvitation>(&*conn)?
         .into_iter()
         .map(|i| i.encodable(conn))
@@ -56,7 +58,7 @@ fn accept_invite(
     conn: &PgConnection,
     crate_invite: InvitationResponse,
 ) -> CargoResult<Response> {
-    use diesel::{delete, insert_into};
+    use diesel::delete;
 
     let user_id = req.user()?.id;
 
@@ -65,17 +67,21 @@ fn accept_invite(
             .find((user_id, crate_invite.crate_id))
             .first::<CrateOwnerInvitation>(&*conn)?;
 
-        insert_into(crate_owners::table)
-            .values(&CrateOwner {
-                crate_id: crate_invite.crate_id,
-                owner_id: user_id,
-                created_by: pending_crate_owner.invited_by_user_id,
-                owner_kind: OwnerKind::User as i32,
-            })
-            .on_conflict(crate_owners::table.primary_key())
-            .do_update()
-            .set(crate_owners::deleted.eq(false))
-            .execute(conn)?;
+        if pending_crate_owner.is_expired() {
+            return Err(gone(&format_args!(
+                "the invitation to become an owner of `{}` has expired, \
+                 ask an owner of the crate to invite you again",
+                pending_crate_owner.crate_name(conn)
+            )));
+        }
+
+        CrateOwner {
+            crate_id: crate_invite.crate_id,
+            owner_id: user_id,
+            created_by: pending_crate_owner.invited_by_user_id,
+            owner_kind: OwnerKind::User as i32,
+        }
+        .add(conn)?;
         delete(crate_owner_invitations::table.find((user_id, crate_invite.crate_id)))
             .execute(conn)?;
 
@@ -6,7 +6,8 @@
 
 use controllers::prelude::*;
 use models::{
-    Category, Crate, CrateCategory, CrateDownload, CrateKeyword, CrateVersions, Keyword, Version,
+    Category, Crate, CrateCategory, CrateDownload, CrateKeyword, CrateVersions, Keyword, Rights,
+    Version,
 };
 use schema::*;
 use views::{
@@ -99,6 +100,9 @@ pub fn summary(req: &mut dyn Request) -> CargoResult<Response> {
 }
 
 /// Handles the `GET /crates/:crate_id` route.
+///
+/// `can_publish` tells whether the signed in user may publish new versions of
+/// the crate, and is always false for anonymous requests.
 pub fn show(req: &mut dyn Request) -> CargoResult<Response> {
     use diesel::dsl::*;
 
@@ -128,6 +132,14 @@ pub fn show(req: &mut dyn Request) -> CargoResult<Response> {
         .load(&*conn)?;
     let max_version = krate.max_version(&conn)?;
 
+    let can_publish = match req.user() {
+        Ok(user) => {
+            let owners = krate.owners(&conn)?;
+            user.rights_over(req.app(), &conn, &krate, &owners)? >= Rights::Publish
+        }
+        Err(_) => false,
+    };
+
     #[derive(Serialize)]
     struct R {
         #[serde(rename = "crate")]
@@ -135,6 +147,7 @@ pub fn show(req: &mut dyn Request) -> CargoResult<Response> {
         versions: Vec<EncodableVersion>,
         keywords: Vec<EncodableKeyword>,
         categories: Vec<EncodableCategory>,
+        can_publish: bool,
     }
     Ok(req.json(&R {
         krate: krate.clone().encodable(
@@ -152,6 +165,7 @@ pub fn show(req: &mut dyn Request) -> CargoResult<Response> {
             .collect(),
         keywords: kws.into_iter().map(|k| k.encodable()).collect(),
         categories: cats.into_iter().map(|k| k.encodable()).collect(),
+        can_publish,
     }))
 }
 
@@ -1,18 +1,99 @@
 //! All routes related to managing owners of a crate
 
+use github;
 use serde_json;
 
 use controllers::prelude::*;
-use models::{Crate, Owner, Rights, Team, User};
-use views::EncodableOwner;
+use models::{ApiToken, Crate, CrateOwnerInvitation, Owner, Rights, Team, User};
+use util::{bad_request, too_many_requests, CargoError};
+use views::{EncodableOwner, EncodablePendingOwner};
+use App;
 
 /// Handles the `GET /crates/:crate_id/owners` route.
+///
+/// Users are marked with whether they have a verified email address. When
+/// called with `include=invitations` by a user with full rights over the
+/// crate, pending owner invitations are listed as well.
 pub fn owners(req: &mut dyn Request) -> CargoResult<Response> {
+    let include_invitations = req.query().get("include").map_or(false, |include| {
+        include.split(',').any(|i| i == "invitations")
+    });
+
     let crate_name = &req.params()["crate_id"];
     let conn = req.db_conn()?;
     let krate = Crate::by_name(crate_name).first::<Crate>(&*conn)?;
-    let owners = krate
-        .owners(&conn)?
+    let owners = krate.owners(&conn)?;
+
+    let mut pending_invitations = None;
+    if include_invitations {
+        if let Ok(user) = req.user() {
+            if user.rights_over(req.app(), &conn, &krate, &owners)? == Rights::Full {
+                let invitations = CrateOwnerInvitation::pending_for_crate(krate.id, &conn)?
+                    .into_iter()
+                    .map(|(invitation, login)| invitation.encodable_pending(login, &conn))
+                    .collect();
+                pending_invitations = Some(invitations);
+            }
+        }
+    }
+
+    let user_ids = owners
+        .iter()
+        .filter_map(|owner| match *owner {
+            Owner::User(ref user) => Some(user.id),
+            Owner::Team(_) => None,
+        })
+        .collect::<Vec<_>>();
+    let verified = User::verified_emails_for(&conn, &user_ids)?;
+    let owners = owners
+        .into_iter()
+        .map(|owner| {
+            let verified_email = match owner {
+                Owner::User(ref user) => Some(verified.contains(&user.id)),
+                Owner::Team(_) => None,
+            };
+            EncodableOwner {
+                verified_email,
+                ..owner.encodable()
+            }
+        })
+        .collect();
+
+    #[derive(Serialize)]
+    struct R {
+        users: Vec<EncodableOwner>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pending_invitations: Option<Vec<EncodablePendingOwner>>,
+    }
+    Ok(req.json(&R {
+        users: owners,
+        pending_invitations,
+    }))
+}
+
+/// Handles the `GET /crates/:crate_id/owners/at` route.
+///
+/// Lists the owners the crate had at the time given by the `timestamp` query
+/// parameter, in RFC 3339 format, as recorded by the log of ownership changes.
+pub fn owners_at(req: &mut dyn Request) -> CargoResult<Response> {
+    use chrono::DateTime;
+
+    let timestamp = req
+        .query()
+        .remove("timestamp")
+        .ok_or_else(|| bad_request("missing query parameter: timestamp"))?;
+    let at = DateTime::parse_from_rfc3339(&timestamp)
+        .map_err(|_| {
+            bad_request(&format_args!(
+                "invalid timestamp `{}`, expected an RFC 3339 date and time",
+                timestamp
+            ))
+        })?
+        .naive_utc();
+
+    let conn = req.db_conn()?;
+    let krate = Crate::by_name(&req.params()["crate_id"]).first::<Crate>(&*conn)?;
+    let owners = Owner::owning_at(&conn, krate.id, at)?
         .into_iter()
         .map(Owner::encodable)
         .collect();
@@ -24,6 +105,54 @@ pub fn owners(req: &mut dyn Request) -> CargoResult<Response> {
     Ok(req.json(&R { users: owners }))
 }
 
+/// Handles the `GET /crates/:crate_id/owner_changes` route.
+///
+/// Lists who was added to or removed from the owners of the crate, and by
+/// whom, newest first.
+pub fn owner_changes(req: &mut dyn Request) -> CargoResult<Response> {
+    use controllers::helpers::Paginate;
+    use models::CrateOwnerAction;
+    use schema::crate_owner_actions;
+    use views::EncodableOwnerAction;
+
+    let (offset, limit) = req.pagination(20, 100)?;
+    let conn = req.db_conn()?;
+    let krate = Crate::by_name(&req.params()["crate_id"]).first::<Crate>(&*conn)?;
+
+    let data = crate_owner_actions::table
+        .filter(crate_owner_actions::crate_id.eq(krate.id))
+        .order((
+            crate_owner_actions::created_at.desc(),
+            crate_owner_actions::id.desc(),
+        ))
+        .paginate(limit, offset)
+        .load::<(CrateOwnerAction, i64)>(&*conn)?;
+    let more = data
+        .get(0)
+        .map(|&(_, count)| count > offset + limit)
+        .unwrap_or(false);
+
+    let actions = data
+        .into_iter()
+        .map(|(action, _)| (action, krate.name.clone()))
+        .collect();
+    let owner_changes = CrateOwnerAction::encodable_many(&conn, actions)?;
+
+    #[derive(Serialize)]
+    struct R {
+        owner_changes: Vec<EncodableOwnerAction>,
+        meta: Meta,
+    }
+    #[derive(Serialize)]
+    struct Meta {
+        more: bool,
+    }
+    Ok(req.json(&R {
+        owner_changes,
+        meta: Meta { more },
+    }))
+}
+
 /// Handles the `GET /crates/:crate_id/owner_team` route.
 pub fn owner_team(req: &mut dyn Request) -> CargoResult<Response> {
     let crate_name = &req.params()["crate_id"];
@@ -58,11 +187,705 @@ pub fn owner_user(req: &mut dyn Request) -> CargoResult<Response> {
     Ok(req.json(&R { users: owners }))
 }
 
+/// Handles the `GET /crates/:crate_id/why` route.
+///
+/// Explains to the current user which rights they have over the crate and
+/// why. The rights themselves come from `User::rights_over`, the same check
+/// that publishing, yanking and changing owners go through.
+pub fn why(req: &mut dyn Request) -> CargoResult<Response> {
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let krate = Crate::by_name(&req.params()["crate_id"]).first::<Crate>(&*conn)?;
+    let owners = krate.owners(&conn)?;
+
+    #[derive(Serialize)]
+    struct ExplainedOwner {
+        login: String,
+        kind: &'static str,
+    }
+
+    #[derive(Serialize)]
+    struct TeamMembership {
+        login: String,
+        member: bool,
+    }
+
+    let mut direct_owner = false;
+    let mut teams = Vec::new();
+    for owner in &owners {
+        match *owner {
+            Owner::User(ref other_user) => direct_owner |= other_user.id == user.id,
+            Owner::Team(ref team) => teams.push(TeamMembership {
+                login: team.login.clone(),
+                member: team.contains_user(req.app(), user)?,
+            }),
+        }
+    }
+
+    let rights = user.rights_over(req.app(), &conn, &krate, &owners)?;
+    let blocked = user.is_blocked(&conn)?;
+    let email_verified = user.has_verified_email(&conn)?;
+    let email_required = req.app().config.require_verified_publisher_email;
+
+    let mut reasons = Vec::new();
+    if blocked {
+        reasons.push(
+            "your account is blocked, so you have no rights over any crate \
+             until an administrator unblocks it"
+                .to_string(),
+        );
+    } else {
+        match rights {
+            Rights::Full if direct_owner => {
+                reasons.push("you are an owner of this crate".to_string())
+            }
+            Rights::Full => reasons.push(
+                "you have temporarily elevated your rights through a team that owns \
+                 this crate, so you may also change owners until the elevation ends"
+                    .to_string(),
+            ),
+            Rights::Publish => reasons.push(
+                "you are a member of a team that owns this crate, so you may publish \
+                 but not change owners"
+                    .to_string(),
+            ),
+            Rights::None => reasons.push(
+                "you are not an owner of this crate, nor a member of any team that owns it. \
+                 If you have been invited, accept the invitation first."
+                    .to_string(),
+            ),
+        }
+    }
+    if !email_verified {
+        let reason = if email_required {
+            "you do not have a verified email address, which is required to publish"
+        } else {
+            "you do not have a verified email address, which may become required to publish"
+        };
+        reasons.push(reason.to_string());
+    }
+
+    let owners = owners
+        .iter()
+        .map(|owner| ExplainedOwner {
+            login: owner.login().to_string(),
+            kind: match *owner {
+                Owner::User(_) => "user",
+                Owner::Team(_) => "team",
+            },
+        })
+        .collect();
+
+    #[derive(Serialize)]
+    struct Explanation {
+        login: String,
+        owners: Vec<ExplainedOwner>,
+        direct_owner: bool,
+        teams: Vec<TeamMembership>,
+        rights: Rights,
+        blocked: bool,
+        email_verified: bool,
+        can_publish: bool,
+        can_change_owners: bool,
+        reasons: Vec<String>,
+    }
+
+    #[derive(Serialize)]
+    struct R {
+        explanation: Explanation,
+    }
+    Ok(req.json(&R {
+        explanation: Explanation {
+            login: user.gh_login.clone(),
+            owners,
+            direct_owner,
+            teams,
+            rights,
+            blocked,
+            email_verified,
+            can_publish: rights >= Rights::Publish && (email_verified || !email_required),
+            can_change_owners: rights == Rights::Full,
+            reasons,
+        },
+    }))
+}
+
+/// Handles the `GET /crates/:crate_id/my_access_via` route.
+///
+/// Lists the crate's owning teams that the current user is a member of, and
+/// so that give them publish rights over it. Whether the user also owns the
+/// crate directly is given separately, along with the rights `User::rights_over`
+/// works out for them. No teams are listed for a user without any rights, such
+/// as a blocked one.
+pub fn my_access_via(req: &mut dyn Request) -> CargoResult<Response> {
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let krate = Crate::by_name(&req.params()["crate_id"]).first::<Crate>(&*conn)?;
+    let owners = krate.owners(&conn)?;
+    let rights = user.rights_over(req.app(), &conn, &krate, &owners)?;
+
+    let mut direct_owner = false;
+    let mut teams = Vec::new();
+    for owner in owners {
+        let grants_publish = match owner {
+            Owner::User(ref other_user) => {
+                direct_owner |= other_user.id == user.id;
+                false
+            }
+            Owner::Team(ref team) => {
+                rights >= Rights::Publish && team.contains_user(req.app(), user)?
+            }
+        };
+        if grants_publish {
+            teams.push(owner.encodable());
+        }
+    }
+
+    #[derive(Serialize)]
+    struct R {
+        direct_owner: bool,
+        rights: Rights,
+        teams: Vec<EncodableOwner>,
+    }
+    Ok(req.json(&R {
+        direct_owner,
+        rights,
+        teams,
+    }))
+}
+
+/// Handles the `GET /crates/:crate_id/can_publish` route.
+///
+/// Lets owners check which rights the user given by the `login` query
+/// parameter would have over the crate, for example before inviting someone
+/// who may already be able to publish through a team. A blocked user who owns
+/// the crate is reported as an owner, but as `restricted` with no rights.
+pub fn can_publish(req: &mut dyn Request) -> CargoResult<Response> {
+    let login = req
+        .query()
+        .remove("login")
+        .ok_or_else(|| bad_request("missing query parameter: login"))?;
+
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let krate = Crate::by_name(&req.params()["crate_id"]).first::<Crate>(&*conn)?;
+    let owners = krate.owners(&conn)?;
+
+    if user.rights_over(req.app(), &conn, &krate, &owners)? != Rights::Full {
+        return Err(human(
+            "only owners have permission to check the rights of other users",
+        ));
+    }
+
+    let other_user = User::find_by_login(&conn, &login)
+        .optional()?
+        .ok_or_else(|| bad_request(&format_args!("could not find user with login `{}`", login)))?;
+    let rights = other_user.rights_over(req.app(), &conn, &krate, &owners)?;
+    let owner = owners.iter().any(|owner| match *owner {
+        Owner::User(ref user) => user.id == other_user.id,
+        Owner::Team(_) => false,
+    });
+    let restricted = other_user.is_blocked(&conn)?;
+
+    #[derive(Serialize)]
+    struct R {
+        login: String,
+        owner: bool,
+        restricted: bool,
+        rights: Rights,
+        can_publish: bool,
+    }
+    Ok(req.json(&R {
+        login: other_user.gh_login,
+        owner,
+        restricted,
+        rights,
+        can_publish: rights >= Rights::Publish,
+    }))
+}
+
+/// Handles the `GET /crates/:crate_id/owners/effective` route.
+///
+/// Lists everyone with rights over the crate: its user owners, and the
+/// members of its owning teams who have a crates.io account. Only users with
+/// full rights over the crate may see the list.
+///
+/// Blocked users are still listed, marked as `restricted` and with no rights.
+/// Teams aren't expanded while GitHub's rate limit is nearly used up. They're
+/// listed in `skipped_teams` instead and the result is marked `incomplete`.
+pub fn effective_owners(req: &mut dyn Request) -> CargoResult<Response> {
+    use diesel::dsl::any;
+    use schema::blocked_users;
+
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let krate = Crate::by_name(&req.params()["crate_id"]).first::<Crate>(&*conn)?;
+    let owners = krate.owners(&conn)?;
+
+    if user.rights_over(req.app(), &conn, &krate, &owners)? != Rights::Full {
+        return Err(human(
+            "only owners have permission to list the rights of other users",
+        ));
+    }
+
+    #[derive(Serialize)]
+    struct EffectiveOwner {
+        id: i32,
+        login: String,
+        rights: Rights,
+        restricted: bool,
+        teams: Vec<String>,
+    }
+
+    // Teams are expanded with the signed in user's GitHub token, so it's their limit that matters
+    let near_rate_limit = || {
+        user.gh_access_token
+            .as_ref()
+            .map_or(false, |token| github::near_rate_limit(req.app(), token))
+    };
+    let mut effective = Vec::<EffectiveOwner>::new();
+    let mut skipped_teams = Vec::new();
+    for owner in &owners {
+        let (members, rights, team) = match *owner {
+            Owner::User(ref owner) => (vec![owner.clone()], Rights::Full, None),
+            Owner::Team(ref team) if near_rate_limit() => {
+                skipped_teams.push(team.login.clone());
+                continue;
+            }
+            Owner::Team(ref team) => {
+                let members = team_members(req.app(), &conn, team, user)?;
+                (members, Rights::Publish, Some(&team.login))
+            }
+        };
+
+        for member in members {
+            let position = effective.iter().position(|e| e.id == member.id);
+            let entry = match position {
+                Some(i) => &mut effective[i],
+                None => {
+                    effective.push(EffectiveOwner {
+                        id: member.id,
+                        login: member.gh_login,
+                        rights: Rights::None,
+                        restricted: false,
+                        teams: Vec::new(),
+                    });
+                    effective.last_mut().unwrap()
+                }
+            };
+            entry.rights = entry.rights.max(rights);
+            entry.teams.extend(team.cloned());
+        }
+    }
+    effective.sort_by(|a, b| a.login.cmp(&b.login).then(a.id.cmp(&b.id)));
+
+    let ids = effective.iter().map(|e| e.id).collect::<Vec<_>>();
+    let blocked = blocked_users::table
+        .filter(blocked_users::user_id.eq(any(ids)))
+        .select(blocked_users::user_id)
+        .load::<i32>(&*conn)?;
+    for entry in effective.iter_mut().filter(|e| blocked.contains(&e.id)) {
+        entry.rights = Rights::None;
+        entry.restricted = true;
+    }
+
+    #[derive(Serialize)]
+    struct R {
+        users: Vec<EffectiveOwner>,
+        incomplete: bool,
+        skipped_teams: Vec<String>,
+    }
+    Ok(req.json(&R {
+        users: effective,
+        incomplete: !skipped_teams.is_empty(),
+        skipped_teams,
+    }))
+}
+
+/// Handles the `GET /crates/:crate_id/publishing_tokens` route.
+///
+/// Lists the API tokens that could publish the crate right now: tokens that
+/// are neither revoked nor expired, are scoped to the crate, and belong to
+/// someone with publish rights over it. Tokens of blocked, suspended or banned
+/// accounts can't publish, so they aren't listed. Only users with full rights over the
+/// crate may see the list, and the tokens themselves are never included.
+pub fn publishing_tokens(req: &mut dyn Request) -> CargoResult<Response> {
+    use chrono::NaiveDateTime;
+    use diesel::dsl::{any, now};
+    use schema::{api_tokens, banned_users, blocked_users};
+    use util::rfc3339;
+
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let krate = Crate::by_name(&req.params()["crate_id"]).first::<Crate>(&*conn)?;
+    let owners = krate.owners(&conn)?;
+
+    if user.rights_over(req.app(), &conn, &krate, &owners)? != Rights::Full {
+        return Err(human(
+            "only owners have permission to list the tokens that can publish this crate",
+        ));
+    }
+
+    let mut publishers = Vec::new();
+    for owner in &owners {
+        match *owner {
+            Owner::User(ref owner) => publishers.push(owner.clone()),
+            Owner::Team(ref team) => publishers.extend(team_members(req.app(), &conn, team, user)?),
+        }
+    }
+    let ids = publishers.iter().map(|p| p.id).collect::<Vec<_>>();
+    let blocked = blocked_users::table
+        .filter(blocked_users::user_id.eq(any(&ids)))
+        .select(blocked_users::user_id)
+        .load::<i32>(&*conn)?;
+    let banned = banned_users::table
+        .filter(banned_users::user_id.eq(any(&ids)))
+        .select(banned_users::user_id)
+        .load::<i32>(&*conn)?;
+    publishers.retain(|p| {
+        !blocked.contains(&p.id) && !banned.contains(&p.id) && p.suspension_end().is_none()
+    });
+    let publisher_ids = publishers.iter().map(|p| p.id).collect::<Vec<_>>();
+
+    let tokens = api_tokens::table
+        .filter(api_tokens::user_id.eq(any(publisher_ids)))
+        .filter(api_tokens::revoked.eq(false))
+        .filter(
+            api_tokens::expires_at
+                .is_null()
+                .or(api_tokens::expires_at.gt(now.nullable())),
+        )
+        .order((api_tokens::user_id, api_tokens::id))
+        .load::<ApiToken>(&*conn)?;
+
+    #[derive(Serialize)]
+    struct PublishingToken {
+        id: i32,
+        owner: String,
+        name: String,
+        #[serde(with = "rfc3339::option")]
+        last_used_at: Option<NaiveDateTime>,
+    }
+
+    let tokens = tokens
+        .into_iter()
+        .filter(|token| token.has_scope("publish-new") || token.has_scope("publish-update"))
+        .filter(|token| token.allows_crate(&krate.name))
+        .filter_map(|token| {
+            let owner = publishers.iter().find(|p| p.id == token.user_id)?;
+            Some(PublishingToken {
+                id: token.id,
+                owner: owner.gh_login.clone(),
+                name: token.name,
+                last_used_at: token.last_used_at,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    #[derive(Serialize)]
+    struct R {
+        count: usize,
+        tokens: Vec<PublishingToken>,
+    }
+    Ok(req.json(&R {
+        count: tokens.len(),
+        tokens,
+    }))
+}
+
+/// The crates.io accounts of the members of a team, as GitHub lists them to
+/// `user`.
+fn team_members(
+    app: &App,
+    conn: &PgConnection,
+    team: &Team,
+    user: &User,
+) -> CargoResult<Vec<User>> {
+    use diesel::dsl::any;
+    use schema::users;
+
+    let gh_ids = team.member_gh_ids(app, user)?;
+    let members = users::table
+        .filter(users::gh_id.eq(any(gh_ids)))
+        .load::<User>(conn)?;
+    Ok(members)
+}
+
+/// Handles the `POST /crates/:crate_id/refresh_access` route.
+///
+/// Forgets what GitHub last said about the user's membership of the teams
+/// owning the crate and works out their rights again, so that someone who
+/// was just added to a team doesn't have to wait for the cache to expire.
+pub fn refresh_access(req: &mut dyn Request) -> CargoResult<Response> {
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let krate = Crate::by_name(&req.params()["crate_id"]).first::<Crate>(&*conn)?;
+    let owners = krate.owners(&conn)?;
+
+    for owner in &owners {
+        if let Owner::Team(ref team) = *owner {
+            team.forget_membership(req.app(), user);
+        }
+    }
+    let rights = user.rights_over(req.app(), &conn, &krate, &owners)?;
+
+    #[derive(Serialize)]
+    struct R {
+        rights: Rights,
+        can_publish: bool,
+    }
+    Ok(req.json(&R {
+        rights,
+        can_publish: rights >= Rights::Publish,
+    }))
+}
+
+/// Handles the `POST /crates/:crate_id/elevate` route.
+///
+/// Gives a member of one of the crate's owning teams full rights over it for
+/// `Config::rights_elevation_duration`, for example to repair its ownership
+/// when none of its user owners are around. The body must give a
+/// justification, which is kept with the elevation.
+pub fn elevate(req: &mut dyn Request) -> CargoResult<Response> {
+    use chrono::{Duration, Utc};
+    use models::{NewRightsElevation, RightsElevation};
+
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+
+    #[derive(Deserialize)]
+    struct Request {
+        justification: String,
+    }
+
+    let request: Request =
+        serde_json::from_str(&body).map_err(|_| bad_request("invalid json request"))?;
+    let justification = request.justification.trim();
+    if justification.is_empty() {
+        return Err(bad_request("a justification is required to elevate rights"));
+    }
+
+    let duration = req.app().config.rights_elevation_duration;
+    if duration.as_secs() == 0 {
+        return Err(bad_request(
+            "rights elevation is not allowed on this registry",
+        ));
+    }
+
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let krate = Crate::by_name(&req.params()["crate_id"]).first::<Crate>(&*conn)?;
+    req.check_crate_scope(&krate.name)?;
+    req.check_endpoint_scope("change-owners")?;
+    let owners = krate.owners(&conn)?;
+
+    match user.rights_over(req.app(), &conn, &krate, &owners)? {
+        Rights::Publish => {}
+        Rights::Full => return Err(human("you already have full rights over this crate")),
+        Rights::None => {
+            return Err(human(
+                "only members of a team that owns this crate can elevate their rights",
+            ));
+        }
+    }
+
+    let expires_at = Utc::now().naive_utc() + Duration::seconds(duration.as_secs() as i64);
+    let elevation = NewRightsElevation {
+        crate_id: krate.id,
+        user_id: user.id,
+        justification,
+        expires_at,
+    }
+    .create(&conn)?;
+
+    #[derive(Serialize)]
+    struct R {
+        rights: Rights,
+        elevation: RightsElevation,
+    }
+    Ok(req.json(&R {
+        rights: Rights::Full,
+        elevation,
+    }))
+}
+
 /// Handles the `PUT /crates/:crate_id/owners` route.
 pub fn add_owners(req: &mut dyn Request) -> CargoResult<Response> {
     modify_owners(req, true)
 }
 
+/// Handles the `POST /crates/:crate_id/owners/import` route.
+///
+/// The body is a newline-separated list of user logins. Each login is
+/// checked individually and the outcome is reported per login; every login
+/// that passes the checks is invited, even if others in the list fail.
+pub fn import_owners(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let krate = Crate::by_name(&req.params()["crate_id"]).first::<Crate>(&*conn)?;
+    req.check_crate_scope(&krate.name)?;
+    req.check_endpoint_scope("change-owners")?;
+    let owners = krate.owners(&conn)?;
+
+    match user.rights_over(req.app(), &conn, &krate, &owners)? {
+        Rights::Full => {}
+        Rights::Publish => {
+            return Err(human("team members don't have permission to modify owners"));
+        }
+        Rights::None => {
+            return Err(human("only owners have permission to modify owners"));
+        }
+    }
+
+    #[derive(Serialize)]
+    struct ImportResult {
+        login: String,
+        ok: bool,
+        msg: String,
+    }
+
+    let mut results = Vec::new();
+    let mut to_invite: Vec<(usize, User)> = Vec::new();
+
+    for login in body.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let outcome = if login.contains(':') {
+            Err("teams cannot be imported, add them individually".to_string())
+        } else if let Some(invitee) = User::find_by_login(&conn, login).optional()? {
+            let is_owner = owners
+                .iter()
+                .any(|owner| owner.login().to_lowercase() == invitee.gh_login.to_lowercase());
+            if is_owner {
+                Err(format!("`{}` is already an owner", invitee.gh_login))
+            } else if to_invite.iter().any(|&(_, ref u)| u.id == invitee.id) {
+                Err(format!("`{}` is listed more than once", invitee.gh_login))
+            } else if !invitee.has_verified_email(&conn)? {
+                Err(format!(
+                    "`{}` does not have a verified email address",
+                    invitee.gh_login
+                ))
+            } else {
+                to_invite.push((results.len(), invitee));
+                Ok(())
+            }
+        } else {
+            Err(format!("could not find user with login `{}`", login))
+        };
+
+        results.push(match outcome {
+            Ok(()) => ImportResult {
+                login: login.to_string(),
+                ok: true,
+                msg: String::new(),
+            },
+            Err(msg) => ImportResult {
+                login: login.to_string(),
+                ok: false,
+                msg,
+            },
+        });
+    }
+
+    record_owner_changes(req.app(), &krate, to_invite.len(), || {
+        conn.transaction::<_, Box<dyn CargoError>, _>(|| {
+            for &(index, ref invitee) in &to_invite {
+                results[index].msg = krate.owner_add(req.app(), &conn, user, &invitee.gh_login)?;
+            }
+            Ok(((), to_invite.len()))
+        })
+    })?;
+
+    #[derive(Serialize)]
+    struct R {
+        ok: bool,
+        results: Vec<ImportResult>,
+    }
+    Ok(req.json(&R { ok: true, results }))
+}
+
+/// Makes up to `changes` ownership changes with `f`, which returns how many it
+/// actually made. Fails without calling `f` if that many changes would go over
+/// `Config::owner_change_limit`, and only the changes that were made count
+/// against the crate's rate limit. Checking and counting happen atomically, so
+/// concurrent requests can't both slip under the limit.
+fn record_owner_changes<T, F>(app: &App, krate: &Crate, changes: usize, f: F) -> CargoResult<T>
+where
+    F: FnOnce() -> CargoResult<(T, usize)>,
+{
+    let key = krate.id.to_string();
+    let changes = changes as u32;
+    if !app.owner_changes.try_record(&key, changes) {
+        return Err(too_many_requests(&format_args!(
+            "too many ownership changes to `{}` recently, please try again later",
+            krate.name
+        )));
+    }
+    match f() {
+        Ok((value, made)) => {
+            app.owner_changes
+                .release(&key, changes.saturating_sub(made as u32));
+            Ok(value)
+        }
+        Err(e) => {
+            app.owner_changes.release(&key, changes);
+            Err(e)
+        }
+    }
+}
+
+/// Handles the `POST /crates/:crate_id/owners/transfer` route.
+///
+/// Makes the user named in the body an owner of the crate in place of the
+/// signed in user, who must own it directly. Like adding and removing owners,
+/// it counts against the crate's rate limit for ownership changes.
+pub fn transfer_ownership(req: &mut dyn Request) -> CargoResult<Response> {
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+
+    #[derive(Deserialize)]
+    struct Request {
+        user: String,
+    }
+
+    let request: Request =
+        serde_json::from_str(&body).map_err(|_| bad_request("invalid json request"))?;
+
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let krate = Crate::by_name(&req.params()["crate_id"]).first::<Crate>(&*conn)?;
+    req.check_crate_scope(&krate.name)?;
+    req.check_endpoint_scope("change-owners")?;
+
+    let to = User::find_by_login(&conn, &request.user)
+        .optional()?
+        .ok_or_else(|| {
+            human(&format_args!(
+                "could not find user with login `{}`",
+                request.user
+            ))
+        })?;
+    // Adding the new owner and removing the old one
+    record_owner_changes(req.app(), &krate, 2, || {
+        krate.transfer_ownership(req.app(), &conn, user, to.id)?;
+        Ok(((), if to.id == user.id { 0 } else { 2 }))
+    })?;
+
+    #[derive(Serialize)]
+    struct R {
+        ok: bool,
+        msg: String,
+    }
+    Ok(req.json(&R {
+        ok: true,
+        msg: format!(
+            "crate {} has been transferred to {}",
+            krate.name, to.gh_login
+        ),
+    }))
+}
+
 /// Handles the `DELETE /crates/:crate_id/owners` route.
 pub fn remove_owners(req: &mut dyn Request) -> CargoResult<Response> {
     modify_owners(req, false)
@@ -75,9 +898,11 @@ fn modify_owners(req: &mut dyn Request, add: bool) -> CargoResult<Response> {
     let user = req.user()?;
     let conn = req.db_conn()?;
     let krate = Crate::by_name(&req.params()["crate_id"]).first::<Crate>(&*conn)?;
+    req.check_crate_scope(&krate.name)?;
+    req.check_endpoint_scope("change-owners")?;
     let owners = krate.owners(&conn)?;
 
-    match user.rights(req.app(), &owners)? {
+    match user.rights_over(req.app(), &conn, &krate, &owners)? {
         Rights::Full => {}
         // Yes!
         Rights::Publish => {
@@ -103,25 +928,35 @@ fn modify_owners(req: &mut dyn Request, add: bool) -> CargoResult<Response> {
         .or(request.users)
         .ok_or_else(|| human("invalid json request"))?;
 
-    let mut msgs = Vec::new();
+    let msgs = record_owner_changes(req.app(), &krate, logins.len(), || {
+        conn.transaction::<_, Box<dyn CargoError>, _>(|| {
+            let mut msgs = Vec::new();
+            let mut made = 0;
 
-    for login in &logins {
-        if add {
-            let login_test = |owner: &Owner| owner.login().to_lowercase() == *login.to_lowercase();
-            if owners.iter().any(login_test) {
-                return Err(human(&format_args!("`{}` is already an owner", login)));
+            for login in &logins {
+                if add {
+                    let login_test =
+                        |owner: &Owner| owner.login().to_lowercase() == *login.to_lowercase();
+                    if owners.iter().any(login_test) {
+                        return Err(human(&format_args!("`{}` is already an owner", login)));
+                    }
+                    let msg = krate.owner_add(req.app(), &conn, user, login)?;
+                    msgs.push(msg);
+                    made += 1;
+                } else {
+                    // Removing the team that gives you rights is prevented because
+                    // team members only have Rights::Publish
+                    if owners.len() == 1 {
+                        return Err(human("cannot remove the sole owner of a crate"));
+                    }
+                    if krate.owner_remove(req.app(), &conn, user, login)? {
+                        made += 1;
+                    }
+                }
             }
-            let msg = krate.owner_add(req.app(), &conn, user, login)?;
-            msgs.push(msg);
-        } else {
-            // Removing the team that gives you rights is prevented because
-            // team members only have Rights::Publish
-            if owners.len() == 1 {
-                return Err(human("cannot remove the sole owner of a crate"));
-            }
-            krate.owner_remove(req.app(), &conn, user, login)?;
-        }
-    }
+            Ok((msgs, made))
+        })
+    })?;
 
     let comma_sep_msg = msgs.join(",");
 
@@ -13,7 +13,7 @@ use util::{read_fill, read_le_u32};
 
 use controllers::prelude::*;
 use models::dependency;
-use models::{Badge, Category, Keyword, NewCrate, NewVersion, Rights, User};
+use models::{ApiToken, Badge, Category, Crate, Keyword, NewCrate, NewVersion, Rights, User};
 use views::{EncodableCrateUpload, GoodCrate, PublishWarnings};
 
 /// Handles the `PUT /crates/new` route.
@@ -42,6 +42,7 @@ pub fn publish(req: &mut dyn Request) -> CargoResult<Response> {
     let (new_crate, user) = parse_new_headers(req)?;
 
     let name = &*new_crate.name;
+    req.check_crate_scope(name)?;
     let vers = &*new_crate.vers;
     let links = new_crate.links.clone();
     let repo = new_crate.repository.as_ref().map(|s| &**s);
@@ -64,8 +65,35 @@ pub fn publish(req: &mut dyn Request) -> CargoResult<Response> {
     let categories = new_crate.categories.as_ref().map(|s| &s[..]).unwrap_or(&[]);
     let categories: Vec<_> = categories.iter().map(|k| &***k).collect();
 
+    // Versions published with a token that has an attribution name are attributed to it.
+    let attribution_name = req
+        .extensions()
+        .find::<ApiToken>()
+        .and_then(|token| token.attribution_name.clone());
+
+    if req.app().config.require_verified_publisher_email {
+        req.verified_user()?;
+    }
+
     let conn = req.db_conn()?;
 
+    let exists = Crate::by_name(name)
+        .first::<Crate>(&*conn)
+        .optional()?
+        .is_some();
+    req.check_endpoint_scope(if exists {
+        "publish-update"
+    } else {
+        "publish-new"
+    })?;
+
+    if user.is_blocked(&conn)? {
+        return Err(human(
+            "this account has been restricted from publishing crates. \
+             Please contact help@crates.io for more information.",
+        ));
+    }
+
     let mut other_warnings = vec![];
     if !user.has_verified_email(&conn)? {
         other_warnings.push(String::from(
@@ -95,7 +123,7 @@ pub fn publish(req: &mut dyn Request) -> CargoResult<Response> {
         let krate = persist.create_or_update(&conn, license_file, user.id)?;
 
         let owners = krate.owners(&conn)?;
-        if user.rights(req.app(), &owners)? < Rights::Publish {
+        if user.rights_over(req.app(), &conn, &krate, &owners)? < Rights::Publish {
             return Err(human(
                 "this crate exists but you don't seem to be an owner. \
                  If you believe this is a mistake, perhaps you need \
@@ -148,6 +176,7 @@ pub fn publish(req: &mut dyn Request) -> CargoResult<Response> {
             // to get here, and max upload sizes are way less than i32 max
             file_length as i32,
         )?
+        .published_by(user.id, attribution_name)
         .save(&conn, &new_crate.authors)?;
 
         // Link this new version to all dependencies
@@ -80,6 +80,7 @@ mod prelude {
 
 pub mod helpers;
 
+pub mod admin;
 pub mod category;
 pub mod crate_owner_invitation;
 pub mod keyword;
@@ -89,3 +90,4 @@ pub mod team;
 pub mod token;
 pub mod user;
 pub mod version;
+pub mod webhook;
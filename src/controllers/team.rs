@@ -1,8 +1,11 @@
 use controllers::prelude::*;
 
-use models::Team;
-use schema::teams;
-use views::EncodableTeam;
+use util::errors::Unauthorized;
+
+use models::krate::ALL_COLUMNS;
+use models::{Crate, CrateVersions, OwnerKind, Team, Version};
+use schema::{crate_owners, crates, teams};
+use views::{EncodableCrate, EncodableTeam};
 
 /// Handles the `GET /teams/:team_id` route.
 pub fn show_team(req: &mut dyn Request) -> CargoResult<Response> {
@@ -20,3 +23,41 @@ pub fn show_team(req: &mut dyn Request) -> CargoResult<Response> {
         team: team.encodable(),
     }))
 }
+
+/// Handles the `GET /teams/:team_id/crates` route.
+///
+/// Lists every crate the team currently owns. Only members of the team can
+/// see the list.
+pub fn team_crates(req: &mut dyn Request) -> CargoResult<Response> {
+    let name = &req.params()["team_id"];
+    let conn = req.db_conn()?;
+    let team = teams::table
+        .filter(teams::login.eq(name))
+        .first::<Team>(&*conn)?;
+    if !team.contains_user(req.app(), req.user()?)? {
+        return Err(Box::new(Unauthorized));
+    }
+
+    let krates = crates::table
+        .inner_join(crate_owners::table)
+        .filter(crate_owners::owner_id.eq(team.id))
+        .filter(crate_owners::owner_kind.eq(OwnerKind::Team as i32))
+        .filter(crate_owners::deleted.eq(false))
+        .order(crates::name)
+        .select(ALL_COLUMNS)
+        .load::<Crate>(&*conn)?;
+    let versions = krates.versions().load::<Version>(&*conn)?;
+    let krates = versions
+        .grouped_by(&krates)
+        .into_iter()
+        .map(|versions| Version::max(versions.into_iter().map(|v| v.num)))
+        .zip(krates)
+        .map(|(max_version, krate)| krate.minimal_encodable(&max_version, None, false, None))
+        .collect();
+
+    #[derive(Serialize)]
+    struct R {
+        crates: Vec<EncodableCrate>,
+    }
+    Ok(req.json(&R { crates: krates }))
+}
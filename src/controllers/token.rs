@@ -1,33 +1,243 @@
 use super::prelude::*;
 
-use diesel;
+use std::cmp;
+
+use app::App;
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+use github;
 use middleware::current_user::AuthenticationSource;
 use serde_json as json;
-use util::{bad_request, read_fill, ChainError};
+use util::{bad_request, gone, read_fill, too_many_requests, CargoError, ChainError};
 
-use models::ApiToken;
+use models::{ApiToken, ApiTokenEvent, Crate, GitHubActionsBinding, Rights, User, ENDPOINT_SCOPES};
 use schema::api_tokens;
 use views::EncodableApiTokenWithToken;
 
+/// The values accepted by the `filter` query parameter of `GET /me/tokens`.
+const LIST_FILTERS: &[&str] = &["revoked", "active", "expired", "never_used"];
+
+/// Pairs of `filter` values that can never both hold for the same token.
+const CONFLICTING_LIST_FILTERS: &[(&str, &str)] = &[("revoked", "active"), ("active", "expired")];
+
 /// Handles the `GET /me/tokens` route.
+///
+/// Accepts an optional `expiry` query parameter: `expired` lists tokens past
+/// their expiry date, `active` those that can still be used (including tokens
+/// that never expire) and `never` only the tokens without an expiry date. An
+/// `external_ref` query parameter only lists tokens with that reference, and
+/// `unused_since`, a date such as `2018-06-01`, only those that haven't been
+/// used since then, if ever.
+///
+/// Alternatively, `filter` takes a comma separated list of `revoked`,
+/// `active`, `expired` and `never_used`, all of which a token must match.
+/// Revoked tokens are only listed when asked for. `sort` orders the tokens by
+/// `created` (the default, newest first), `last_used` (most recent first) or
+/// `name`. Filters that no token could match at once, or that make the sort
+/// meaningless, are rejected.
+///
+/// `page` and `per_page` page through the tokens, 100 at a time by default
+/// and at most `Config::max_tokens_per_user`. `meta.total` counts all of the tokens that matched.
 pub fn list(req: &mut dyn Request) -> CargoResult<Response> {
-    let tokens = ApiToken::belonging_to(req.user()?)
-        .filter(api_tokens::revoked.eq(false))
-        .order(api_tokens::created_at.desc())
-        .load(&*req.db_conn()?)?;
+    use diesel::dsl::now;
+
+    req.check_endpoint_scope("manage-tokens")?;
+
+    let query_params = req.query();
+    let filters = match query_params.get("filter") {
+        Some(filter) => filter.split(',').collect::<Vec<_>>(),
+        None => Vec::new(),
+    };
+    if let Some(filter) = filters.iter().find(|f| !LIST_FILTERS.contains(f)) {
+        return Err(bad_request(&format_args!(
+            "invalid filter `{}`, expected one of `{}`",
+            filter,
+            LIST_FILTERS.join("`, `")
+        )));
+    }
+    if let Some(&(a, b)) = CONFLICTING_LIST_FILTERS
+        .iter()
+        .find(|&&(a, b)| filters.contains(&a) && filters.contains(&b))
+    {
+        return Err(bad_request(&format_args!(
+            "the `{}` and `{}` filters cannot be combined, no token is both",
+            a, b
+        )));
+    }
+    if !filters.is_empty() && query_params.contains_key("expiry") {
+        return Err(bad_request(
+            "the `expiry` parameter cannot be combined with `filter`",
+        ));
+    }
+    let sort = query_params.get("sort").map_or("created", |s| &**s);
+    if sort == "last_used" && filters.contains(&"never_used") {
+        return Err(bad_request(
+            "tokens that were never used cannot be sorted by when they were last used",
+        ));
+    }
+
+    let unused_since = match query_params.get("unused_since") {
+        Some(unused_since) => Some(
+            NaiveDate::parse_from_str(unused_since, "%F")
+                .map_err(|_| {
+                    bad_request(&format_args!(
+                        "invalid unused_since date `{}`, expected YYYY-MM-DD",
+                        unused_since
+                    ))
+                })?
+                .and_hms(0, 0, 0),
+        ),
+        None => None,
+    };
+
+    let expiry = query_params.get("expiry").map(|s| &**s);
+    match expiry {
+        None | Some("expired") | Some("active") | Some("never") => {}
+        Some(other) => {
+            return Err(bad_request(&format!(
+                "invalid expiry filter `{}`, expected one of `expired`, `active` or `never`",
+                other
+            )));
+        }
+    }
+
+    // The same filters are applied once to count all the matching tokens and once to load
+    // the requested page of them.
+    let user = req.user()?;
+    let filtered = || {
+        let mut query = ApiToken::belonging_to(user)
+            .filter(api_tokens::revoked.eq(filters.contains(&"revoked")))
+            .into_boxed();
+
+        for filter in &filters {
+            query = match *filter {
+                "active" => query.filter(
+                    api_tokens::expires_at
+                        .is_null()
+                        .or(api_tokens::expires_at.gt(now.nullable())),
+                ),
+                "expired" => query.filter(api_tokens::expires_at.le(now.nullable())),
+                "never_used" => query.filter(api_tokens::last_used_at.is_null()),
+                _ => query,
+            };
+        }
+
+        if let Some(external_ref) = query_params.get("external_ref") {
+            query = query.filter(api_tokens::external_ref.eq(external_ref.clone()));
+        }
+
+        if let Some(unused_since) = unused_since {
+            query = query.filter(
+                api_tokens::last_used_at
+                    .is_null()
+                    .or(api_tokens::last_used_at.lt(unused_since)),
+            );
+        }
+
+        match expiry {
+            Some("expired") => query.filter(api_tokens::expires_at.le(now.nullable())),
+            Some("active") => query.filter(
+                api_tokens::expires_at
+                    .is_null()
+                    .or(api_tokens::expires_at.gt(now.nullable())),
+            ),
+            Some("never") => query.filter(api_tokens::expires_at.is_null()),
+            _ => query,
+        }
+    };
+
+    let query = match sort {
+        "created" => filtered().order((api_tokens::created_at.desc(), api_tokens::id.desc())),
+        "last_used" => filtered().order((
+            api_tokens::last_used_at.is_null(),
+            api_tokens::last_used_at.desc(),
+            api_tokens::id.desc(),
+        )),
+        "name" => filtered().order((api_tokens::name, api_tokens::id)),
+        other => {
+            return Err(bad_request(&format_args!(
+                "invalid sort `{}`, expected one of `created`, `last_used` or `name`",
+                other
+            )));
+        }
+    };
+
+    // Revoked, expired and exchanged tokens don't count towards `max_tokens_per_user`, so a
+    // user's tokens may still need more than one page even at the largest page size.
+    let max_per_page = cmp::max(
+        req.app().config.max_tokens_per_user,
+        DEFAULT_TOKENS_PER_PAGE,
+    );
+    let (offset, limit) = req.pagination(DEFAULT_TOKENS_PER_PAGE, max_per_page)?;
+    let conn = req.db_conn()?;
+    let total = filtered().count().get_result::<i64>(&*conn)?;
+    let tokens = query.offset(offset).limit(limit).load::<ApiToken>(&*conn)?;
+    let more = total > offset + limit;
+
     #[derive(Serialize)]
     struct R {
         api_tokens: Vec<ApiToken>,
+        meta: Meta,
+    }
+    #[derive(Serialize)]
+    struct Meta {
+        total: i64,
+        more: bool,
+    }
+    Ok(req.json(&R {
+        api_tokens: tokens,
+        meta: Meta { total, more },
+    }))
+}
+
+/// Handles the `GET /me/tokens/:id` route.
+///
+/// Revoked tokens are hidden from the listing, but asking for one by id
+/// returns a 410 rather than a 404 so clients can tell it once existed.
+pub fn show(req: &mut dyn Request) -> CargoResult<Response> {
+    req.check_endpoint_scope("manage-tokens")?;
+
+    let id = req.params()["id"]
+        .parse::<i32>()
+        .map_err(|e| bad_request(&format!("invalid token id: {:?}", e)))?;
+
+    let token = ApiToken::belonging_to(req.user()?)
+        .find(id)
+        .first::<ApiToken>(&*req.db_conn()?)?;
+    if token.revoked {
+        return Err(gone("this token has been revoked"));
     }
-    Ok(req.json(&R { api_tokens: tokens }))
+
+    #[derive(Serialize)]
+    struct R {
+        api_token: ApiToken,
+    }
+    Ok(req.json(&R { api_token: token }))
 }
 
+/// How many tokens `GET /me/tokens` lists per page unless `per_page` says otherwise.
+const DEFAULT_TOKENS_PER_PAGE: usize = 100;
+
+/// The longest `external_ref` a token can be given, matching the column's length.
+const MAX_EXTERNAL_REF_LENGTH: usize = 255;
+
+/// The longest `attribution_name` a token can be given, matching the column's length.
+const MAX_ATTRIBUTION_NAME_LENGTH: usize = 255;
+
 /// Handles the `PUT /me/tokens` route.
 pub fn new(req: &mut dyn Request) -> CargoResult<Response> {
     /// The incoming serialization format for the `ApiToken` model.
     #[derive(Deserialize, Serialize)]
     struct NewApiToken {
         name: String,
+        crate_scopes: Option<Vec<String>>,
+        /// Scopes such as `publish`, `yank` or the cargo-style `publish-update:serde`, as an
+        /// alternative to `crate_scopes` that can also limit what the token may be used for.
+        scopes: Option<Vec<String>>,
+        external_ref: Option<String>,
+        #[serde(default, with = "::util::rfc3339::option")]
+        expires_at: Option<NaiveDateTime>,
+        /// A service account name that versions published with the token are attributed to.
+        attribution_name: Option<String>,
     }
 
     /// The incoming serialization format for the `ApiToken` model.
@@ -65,12 +275,98 @@ pub fn new(req: &mut dyn Request) -> CargoResult<Response> {
         return Err(bad_request("name must have a value"));
     }
 
+    if let Some(ref external_ref) = new.api_token.external_ref {
+        if external_ref.is_empty() || external_ref.chars().count() > MAX_EXTERNAL_REF_LENGTH {
+            return Err(bad_request(&format_args!(
+                "external_ref must be between 1 and {} characters long",
+                MAX_EXTERNAL_REF_LENGTH
+            )));
+        }
+    }
+
+    if let Some(ref attribution_name) = new.api_token.attribution_name {
+        let length = attribution_name.trim().chars().count();
+        if length == 0 || length > MAX_ATTRIBUTION_NAME_LENGTH {
+            return Err(bad_request(&format_args!(
+                "attribution_name must be between 1 and {} characters long",
+                MAX_ATTRIBUTION_NAME_LENGTH
+            )));
+        }
+    }
+
+    if let Some(expires_at) = new.api_token.expires_at {
+        if expires_at <= Utc::now().naive_utc() {
+            return Err(bad_request("expires_at must be in the future"));
+        }
+    }
+
     let user = req.user()?;
+    check_token_limits(req.app(), &*req.db_conn()?, user)?;
 
-    let max_token_per_user = 500;
-    let count = ApiToken::belonging_to(user)
-        .count()
-        .get_result::<i64>(&*req.db_conn()?)?;
+    let (crate_scopes, endpoint_scopes) = match new.api_token.scopes {
+        Some(ref scopes) => {
+            if new.api_token.crate_scopes.is_some() {
+                return Err(bad_request(
+                    "`scopes` and `crate_scopes` cannot be given together",
+                ));
+            }
+            let (crate_scopes, endpoint_scopes) = parse_scopes(scopes)?;
+            (crate_scopes, Some(endpoint_scopes))
+        }
+        // Tokens created without any scopes get the configured defaults, which never name
+        // crates, so crate scopes given on their own still apply.
+        None => match req.app().config.default_token_scopes {
+            Some(ref defaults) => {
+                let (_, endpoint_scopes) = parse_scopes(defaults)?;
+                (new.api_token.crate_scopes.clone(), Some(endpoint_scopes))
+            }
+            None => (new.api_token.crate_scopes.clone(), None),
+        },
+    };
+
+    if let Some(ref crate_scopes) = crate_scopes {
+        validate_crate_scopes(req.app(), &*req.db_conn()?, user, crate_scopes)?;
+    }
+
+    let api_token = ApiToken::insert_full(
+        &*req.db_conn()?,
+        user.id,
+        &::models::NewApiToken {
+            name,
+            crate_scopes,
+            expires_at: new.api_token.expires_at,
+            external_ref: new.api_token.external_ref.clone(),
+            endpoint_scopes,
+            attribution_name: new
+                .api_token
+                .attribution_name
+                .as_ref()
+                .map(|n| n.trim().to_string()),
+            exchanged: false,
+        },
+    )?;
+
+    // Let the user know about the new token, so that one created by someone else gets noticed.
+    // The token already exists by now, so failing to send the email doesn't fail the request.
+    if let Some(email) = user.notification_email(&*req.db_conn()?)? {
+        let sender = &*req.app().email_sender;
+        let _ = ::email::send_token_created_email(sender, &email, &user.gh_login, name);
+    }
+
+    #[derive(Serialize)]
+    struct R {
+        api_token: EncodableApiTokenWithToken,
+    }
+    Ok(req.json(&R {
+        api_token: api_token.encodable_with_token(),
+    }))
+}
+
+/// Fails unless `user` can have another API token, given `Config::max_tokens_per_user` and
+/// `Config::token_creation_limit`. Neither counts tokens handed out by `POST /tokens/exchange`.
+fn check_token_limits(app: &App, conn: &PgConnection, user: &User) -> CargoResult<()> {
+    let max_token_per_user = app.config.max_tokens_per_user as i64;
+    let count = ApiToken::count_towards_max(conn, user)?;
     if count >= max_token_per_user {
         return Err(bad_request(&format!(
             "maximum tokens per user is: {}",
@@ -78,7 +374,211 @@ pub fn new(req: &mut dyn Request) -> CargoResult<Response> {
         )));
     }
 
-    let api_token = ApiToken::insert(&*req.db_conn()?, user.id, name)?;
+    let limit = app.config.token_creation_limit;
+    let created_recently = ApiToken::belonging_to(user)
+        .filter(api_tokens::exchanged.eq(false))
+        .filter(api_tokens::created_at.gt(Utc::now().naive_utc() - Duration::hours(1)))
+        .count()
+        .get_result::<i64>(conn)?;
+    if created_recently >= i64::from(limit) {
+        return Err(too_many_requests(&format_args!(
+            "too many API tokens created recently, only {} can be created per hour",
+            limit
+        )));
+    }
+    Ok(())
+}
+
+/// Splits scopes such as `publish-update:serde` or `yank` into the crate scopes and endpoint
+/// scopes they stand for. A crate scope of `*`, or none at all, covers every crate, leaving the
+/// token unscoped by crate. A token's crate scopes apply to all of its endpoint scopes, so every
+/// endpoint has to be given for the same crates. Scopes that don't act on crates, such as
+/// `manage-tokens`, leave the crate scopes alone.
+pub fn parse_scopes(scopes: &[String]) -> CargoResult<(Option<Vec<String>>, Vec<String>)> {
+    if scopes.is_empty() {
+        return Err(bad_request("scopes must list at least one scope"));
+    }
+
+    let mut endpoint_scopes = Vec::new();
+    // The crates each endpoint scope was given for, with `None` standing for every crate
+    let mut crates_by_endpoint: Vec<(&str, Option<Vec<&str>>)> = Vec::new();
+    for scope in scopes {
+        let (endpoints, crate_scope) = ApiToken::parse_scope(scope).ok_or_else(|| {
+            bad_request(&format_args!(
+                "invalid scope `{}`: expected `<endpoint>` or `<endpoint>:<crate>` where the \
+                 endpoint is `publish` or one of {}",
+                scope,
+                ENDPOINT_SCOPES.join(", ")
+            ))
+        })?;
+        for &endpoint in endpoints {
+            if !endpoint_scopes.iter().any(|s| s == endpoint) {
+                endpoint_scopes.push(endpoint.to_string());
+            }
+            let crate_scope = match crate_scope {
+                Some(crate_scope) => crate_scope,
+                None => continue,
+            };
+            let i = match crates_by_endpoint.iter().position(|&(e, _)| e == endpoint) {
+                Some(i) => i,
+                None => {
+                    crates_by_endpoint.push((endpoint, Some(Vec::new())));
+                    crates_by_endpoint.len() - 1
+                }
+            };
+            let crates = &mut crates_by_endpoint[i].1;
+            if crate_scope == "*" {
+                *crates = None;
+            } else if let Some(ref mut crates) = *crates {
+                if !crates.contains(&crate_scope) {
+                    crates.push(crate_scope);
+                }
+            }
+        }
+    }
+
+    let sorted = |crates: &Option<Vec<&str>>| {
+        crates.as_ref().map(|crates| {
+            let mut crates = crates.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+            crates.sort();
+            crates
+        })
+    };
+    let (first_endpoint, first_crates) = match crates_by_endpoint.first() {
+        Some(&(endpoint, ref crates)) => (endpoint, crates),
+        None => return Ok((None, endpoint_scopes)),
+    };
+    if let Some(&(endpoint, _)) = crates_by_endpoint
+        .iter()
+        .find(|&&(_, ref crates)| sorted(crates) != sorted(first_crates))
+    {
+        return Err(bad_request(&format_args!(
+            "scopes `{}` and `{}` are given for different crates, but a token's crates apply \
+             to all of its scopes; create a separate token for each set of crates",
+            first_endpoint, endpoint
+        )));
+    }
+
+    let crate_scopes = first_crates
+        .as_ref()
+        .map(|crates| crates.iter().map(|c| c.to_string()).collect());
+    Ok((crate_scopes, endpoint_scopes))
+}
+
+/// Checks that crate scopes requested for a new token are well formed and only name crates the
+/// user can publish.
+fn validate_crate_scopes(
+    app: &App,
+    conn: &PgConnection,
+    user: &User,
+    crate_scopes: &[String],
+) -> CargoResult<()> {
+    if crate_scopes.is_empty() {
+        return Err(bad_request("crate_scopes must list at least one crate"));
+    }
+
+    if let Some(scope) = crate_scopes
+        .iter()
+        .find(|s| !ApiToken::valid_crate_scope(s))
+    {
+        return Err(bad_request(&format_args!(
+            "invalid crate scope `{}`: expected a crate name, optionally ending in `*`",
+            scope
+        )));
+    }
+
+    // Wildcard scopes can match crates that don't exist yet, so whether the user may
+    // publish those is only checked when the token is used.
+    let mut crates = Vec::new();
+    for crate_name in crate_scopes.iter().filter(|s| !s.ends_with('*')) {
+        let krate = Crate::by_name(crate_name).first::<Crate>(conn).optional()?;
+        crates.push((crate_name, krate));
+    }
+    let crate_ids = crates
+        .iter()
+        .filter_map(|&(_, ref krate)| krate.as_ref().map(|k| k.id))
+        .collect::<Vec<_>>();
+    let rights = user.rights_for_crates(app, conn, &crate_ids)?;
+
+    let mut unauthorized = Vec::new();
+    for (crate_name, krate) in crates {
+        let can_publish = match krate {
+            Some(krate) => rights[&krate.id] >= Rights::Publish,
+            None => false,
+        };
+        if !can_publish {
+            unauthorized.push(&**crate_name);
+        }
+    }
+    if !unauthorized.is_empty() {
+        return Err(bad_request(&format!(
+            "you do not have permission to publish the following crates: {}",
+            unauthorized.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// How long a token minted for a GitHub Actions workflow can be used for.
+const ACTIONS_TOKEN_LIFETIME_MINUTES: i64 = 15;
+
+/// Handles the `POST /tokens/exchange` route.
+///
+/// Trades the OIDC token GitHub Actions gives a workflow for a short-lived
+/// API token, so that crates can be published from CI without storing a
+/// token there. The new token belongs to the user who bound the workflow's
+/// repository to crates, and can only be used for those crates.
+pub fn exchange(req: &mut dyn Request) -> CargoResult<Response> {
+    #[derive(Deserialize)]
+    struct ExchangeRequest {
+        token: String,
+    }
+
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    let exchange: ExchangeRequest = json::from_str(&body)
+        .map_err(|e| bad_request(&format!("invalid token exchange request: {:?}", e)))?;
+
+    let claims = github::verify_actions_token(req.app(), &exchange.token)?;
+
+    let conn = req.db_conn()?;
+    let bindings = GitHubActionsBinding::for_repository(
+        &conn,
+        &claims.repository_id,
+        &claims.repository_owner_id,
+    )?;
+    let user_id = match bindings.first() {
+        Some(&(ref binding, _)) => binding.user_id,
+        None => {
+            return Err(bad_request(&format_args!(
+                "no crates can be published from the repository `{}`",
+                claims.repository
+            )))
+        }
+    };
+    if bindings.iter().any(|&(ref b, _)| b.user_id != user_id) {
+        return Err(bad_request(&format_args!(
+            "the repository `{}` is bound to crates by more than one user",
+            claims.repository
+        )));
+    }
+    let crate_scopes = bindings.into_iter().map(|(_, name)| name).collect();
+
+    let name = format!("GitHub Actions: {}", claims.repository);
+    let expires_at = Utc::now().naive_utc() + Duration::minutes(ACTIONS_TOKEN_LIFETIME_MINUTES);
+    let api_token = ApiToken::insert_full(
+        &conn,
+        user_id,
+        &::models::NewApiToken {
+            name: &name,
+            crate_scopes: Some(crate_scopes),
+            expires_at: Some(expires_at),
+            external_ref: None,
+            endpoint_scopes: None,
+            attribution_name: None,
+            exchanged: true,
+        },
+    )?;
 
     #[derive(Serialize)]
     struct R {
@@ -89,17 +589,526 @@ pub fn new(req: &mut dyn Request) -> CargoResult<Response> {
     }))
 }
 
+/// Handles the `PUT /me/tokens/:id` and `PATCH /me/tokens/:id` routes.
+///
+/// Renames one of the user's tokens. The token's value is never included in the response.
+pub fn update(req: &mut dyn Request) -> CargoResult<Response> {
+    #[derive(Deserialize)]
+    struct UpdatedApiToken {
+        name: String,
+    }
+
+    #[derive(Deserialize)]
+    struct UpdateApiTokenRequest {
+        api_token: UpdatedApiToken,
+    }
+
+    req.check_endpoint_scope("manage-tokens")?;
+
+    let id = req.params()["id"]
+        .parse::<i32>()
+        .map_err(|e| bad_request(&format!("invalid token id: {:?}", e)))?;
+
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    let update: UpdateApiTokenRequest = json::from_str(&body)
+        .map_err(|e| bad_request(&format!("invalid token update request: {:?}", e)))?;
+    let name = &update.api_token.name;
+    if name.is_empty() {
+        return Err(bad_request("name must have a value"));
+    }
+
+    let conn = req.db_conn()?;
+    let token = ApiToken::belonging_to(req.user()?)
+        .find(id)
+        .first::<ApiToken>(&*conn)?;
+    if token.revoked {
+        return Err(gone("this token has been revoked"));
+    }
+    let token = token.rename(&conn, name)?;
+
+    #[derive(Serialize)]
+    struct R {
+        api_token: ApiToken,
+    }
+    Ok(req.json(&R { api_token: token }))
+}
+
+/// Handles the `PATCH /me/tokens/rename` route.
+///
+/// Renames all of the user's tokens whose names contain `find`, replacing its first occurrence
+/// with `replace`, such as `oldci-` with `newci-`. Tokens whose new name would be empty, or the
+/// same as that of another of the user's tokens, are skipped and listed in the response. Tokens
+/// whose name wouldn't change are left alone.
+pub fn rename_all(req: &mut dyn Request) -> CargoResult<Response> {
+    #[derive(Deserialize)]
+    struct RenameApiTokensRequest {
+        find: String,
+        replace: String,
+    }
+
+    #[derive(Serialize)]
+    struct SkippedToken {
+        id: i32,
+        name: String,
+        reason: String,
+    }
+
+    req.check_endpoint_scope("manage-tokens")?;
+
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    let rename: RenameApiTokensRequest = json::from_str(&body)
+        .map_err(|e| bad_request(&format!("invalid token rename request: {:?}", e)))?;
+    if rename.find.is_empty() {
+        return Err(bad_request("find must have a value"));
+    }
+
+    let conn = req.db_conn()?;
+    let tokens = ApiToken::belonging_to(req.user()?)
+        .filter(api_tokens::revoked.eq(false))
+        .order(api_tokens::id)
+        .load::<ApiToken>(&*conn)?;
+    let mut names = tokens.iter().map(|t| t.name.clone()).collect::<Vec<_>>();
+
+    let mut renamed = 0;
+    let mut skipped = Vec::new();
+    conn.transaction::<_, Box<dyn CargoError>, _>(|| {
+        for (i, token) in tokens.iter().enumerate() {
+            if !token.name.contains(&rename.find) {
+                continue;
+            }
+            let name = token.name.replacen(&rename.find, &rename.replace, 1);
+            // Replacing the text with itself leaves nothing to rename
+            if name == token.name {
+                continue;
+            }
+            let reason = if name.is_empty() {
+                Some("name must have a value".to_string())
+            } else if names.contains(&name) {
+                Some(format!("a token named `{}` already exists", name))
+            } else {
+                None
+            };
+            match reason {
+                Some(reason) => skipped.push(SkippedToken {
+                    id: token.id,
+                    name: token.name.clone(),
+                    reason,
+                }),
+                None => {
+                    token.rename(&conn, &name)?;
+                    names[i] = name;
+                    renamed += 1;
+                }
+            }
+        }
+        Ok(())
+    })?;
+
+    #[derive(Serialize)]
+    struct R {
+        renamed: usize,
+        skipped: Vec<SkippedToken>,
+    }
+    Ok(req.json(&R { renamed, skipped }))
+}
+
+/// Handles the `POST /me/tokens/:id/rotate` route.
+///
+/// Revokes the token and creates a replacement for it. The replacement keeps the old token's
+/// external reference and attribution name, and its name, crate scopes and expiry unless the
+/// request overrides them. When `Config::narrow_rotated_tokens` is set, overrides can only narrow
+/// what the token allows. Expired tokens can't be rotated. Replacements are never treated as
+/// exchanged, so rotating a token handed out by `POST /tokens/exchange` counts against the same
+/// limits as creating one.
+pub fn rotate(req: &mut dyn Request) -> CargoResult<Response> {
+    #[derive(Deserialize, Default)]
+    struct RotatedApiToken {
+        name: Option<String>,
+        crate_scopes: Option<Vec<String>>,
+        #[serde(default, with = "::util::rfc3339::option")]
+        expires_at: Option<NaiveDateTime>,
+    }
+
+    #[derive(Deserialize, Default)]
+    struct RotateApiTokenRequest {
+        #[serde(default)]
+        api_token: RotatedApiToken,
+    }
+
+    if req.authentication_source()? != AuthenticationSource::SessionCookie {
+        return Err(bad_request(
+            "cannot use an API token to rotate an API token",
+        ));
+    }
+
+    let id = req.params()["id"]
+        .parse::<i32>()
+        .map_err(|e| bad_request(&format!("invalid token id: {:?}", e)))?;
+
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    let overrides = if body.trim().is_empty() {
+        RotatedApiToken::default()
+    } else {
+        json::from_str::<RotateApiTokenRequest>(&body)
+            .map_err(|e| bad_request(&format!("invalid token rotation request: {:?}", e)))?
+            .api_token
+    };
+
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let token = ApiToken::belonging_to(user)
+        .find(id)
+        .first::<ApiToken>(&*conn)?;
+    if token.revoked {
+        return Err(gone("this token has been revoked"));
+    }
+    // A replacement would inherit the expiry, or be given a new one that brings the token back
+    if token.is_expired() {
+        return Err(gone(
+            "this token has expired and cannot be rotated, create a new token instead",
+        ));
+    }
+    // The replacement is an ordinary token, so one replacing an exchanged token counts as new
+    if token.exchanged {
+        check_token_limits(req.app(), &conn, user)?;
+    }
+
+    let narrow_only = req.app().config.narrow_rotated_tokens;
+    let name = overrides.name.unwrap_or_else(|| token.name.clone());
+    if name.is_empty() {
+        return Err(bad_request("name must have a value"));
+    }
+
+    if let Some(ref crate_scopes) = overrides.crate_scopes {
+        validate_crate_scopes(req.app(), &conn, user, crate_scopes)?;
+        if narrow_only {
+            if let Some(scope) = crate_scopes.iter().find(|s| !token.covers_scope(s)) {
+                return Err(bad_request(&format_args!(
+                    "rotating a token cannot broaden its crate scopes, \
+                     `{}` is not covered by the current scopes",
+                    scope
+                )));
+            }
+        }
+    }
+
+    if let Some(expires_at) = overrides.expires_at {
+        if expires_at <= Utc::now().naive_utc() {
+            return Err(bad_request("expires_at must be in the future"));
+        }
+        if narrow_only
+            && token
+                .expires_at
+                .map_or(false, |current| expires_at > current)
+        {
+            return Err(bad_request(
+                "rotating a token cannot extend when it expires",
+            ));
+        }
+    }
+
+    let rotated = token.rotate(
+        &conn,
+        &::models::NewApiToken {
+            name: &name,
+            crate_scopes: overrides
+                .crate_scopes
+                .or_else(|| token.crate_scopes.clone()),
+            expires_at: overrides.expires_at.or(token.expires_at),
+            external_ref: token.external_ref.clone(),
+            endpoint_scopes: token.endpoint_scopes.clone(),
+            attribution_name: token.attribution_name.clone(),
+            exchanged: false,
+        },
+    )?;
+
+    #[derive(Serialize)]
+    struct R {
+        api_token: EncodableApiTokenWithToken,
+    }
+    Ok(req.json(&R {
+        api_token: rotated.encodable_with_token(),
+    }))
+}
+
+/// Handles the `POST /me/tokens/merge` route.
+///
+/// Creates a token whose crate and endpoint scopes are the union of those of two of the user's
+/// tokens, for example to replace two CI secrets with one. A token's crate scopes apply to all of
+/// its endpoint scopes, so the two tokens have to be scoped to the same crates or to the same
+/// endpoints; otherwise the union would let each token's endpoints act on the other's crates.
+/// The union has to be something the user could create a token for today, so crates they can no
+/// longer publish are refused. The new token expires when the first of the two would have, and
+/// the originals are revoked when `revoke_originals` is set.
+pub fn merge(req: &mut dyn Request) -> CargoResult<Response> {
+    #[derive(Deserialize)]
+    struct MergeApiTokensRequest {
+        ids: Vec<i32>,
+        name: String,
+        #[serde(default)]
+        revoke_originals: bool,
+    }
+
+    if req.authentication_source()? != AuthenticationSource::SessionCookie {
+        return Err(bad_request("cannot use an API token to merge API tokens"));
+    }
+
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    let merge: MergeApiTokensRequest = json::from_str(&body)
+        .map_err(|e| bad_request(&format!("invalid token merge request: {:?}", e)))?;
+    if merge.ids.len() != 2 || merge.ids[0] == merge.ids[1] {
+        return Err(bad_request("exactly two different tokens must be merged"));
+    }
+    if merge.name.is_empty() {
+        return Err(bad_request("name must have a value"));
+    }
+
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let mut originals = Vec::new();
+    for &id in &merge.ids {
+        let token = ApiToken::belonging_to(user)
+            .find(id)
+            .first::<ApiToken>(&*conn)?;
+        if token.revoked {
+            return Err(gone(&format!("token {} has been revoked", id)));
+        }
+        originals.push(token);
+    }
+    let (first, second) = (&originals[0], &originals[1]);
+
+    let count = ApiToken::count_towards_max(&conn, user)?;
+    let max_token_per_user = req.app().config.max_tokens_per_user as i64;
+    if count >= max_token_per_user {
+        return Err(bad_request(&format!(
+            "maximum tokens per user is: {}",
+            max_token_per_user
+        )));
+    }
+
+    // A token without scopes of a kind isn't limited by them, so neither is the union.
+    let union = |a: &Option<Vec<String>>, b: &Option<Vec<String>>| match (a, b) {
+        (&Some(ref a), &Some(ref b)) => {
+            let mut scopes = a.clone();
+            scopes.extend(b.iter().filter(|s| !a.contains(s)).cloned());
+            Some(scopes)
+        }
+        _ => None,
+    };
+    let sorted = |scopes: &Option<Vec<String>>| {
+        scopes.as_ref().map(|scopes| {
+            let mut scopes = scopes.clone();
+            scopes.sort();
+            scopes.dedup();
+            scopes
+        })
+    };
+    if sorted(&first.crate_scopes) != sorted(&second.crate_scopes)
+        && sorted(&first.endpoint_scopes) != sorted(&second.endpoint_scopes)
+    {
+        return Err(bad_request(
+            "tokens can only be merged if they are scoped to the same crates or to the same \
+             endpoints, as a token's crates apply to all of its endpoints",
+        ));
+    }
+    let crate_scopes = union(&first.crate_scopes, &second.crate_scopes);
+    let endpoint_scopes = union(&first.endpoint_scopes, &second.endpoint_scopes);
+    if let Some(ref crate_scopes) = crate_scopes {
+        validate_crate_scopes(req.app(), &conn, user, crate_scopes)?;
+    }
+
+    let expires_at = match (first.expires_at, second.expires_at) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+    if expires_at.map_or(false, |e| e <= Utc::now().naive_utc()) {
+        return Err(bad_request("cannot merge an expired token"));
+    }
+
+    // Publishes are only attributed to a service account both tokens agree on.
+    let attribution_name = if first.attribution_name == second.attribution_name {
+        first.attribution_name.clone()
+    } else {
+        None
+    };
+
+    let merged = ApiToken::merge(
+        &conn,
+        user.id,
+        &[first, second],
+        &::models::NewApiToken {
+            name: &merge.name,
+            crate_scopes,
+            expires_at,
+            external_ref: None,
+            endpoint_scopes,
+            attribution_name,
+            exchanged: false,
+        },
+        merge.revoke_originals,
+    )?;
+
+    #[derive(Serialize)]
+    struct R {
+        api_token: EncodableApiTokenWithToken,
+    }
+    Ok(req.json(&R {
+        api_token: merged.encodable_with_token(),
+    }))
+}
+
+/// Handles the `GET /me/tokens/:id/history` route.
+///
+/// Lists everything that has happened to the token, oldest first.
+pub fn history(req: &mut dyn Request) -> CargoResult<Response> {
+    req.check_endpoint_scope("manage-tokens")?;
+
+    let id = req.params()["id"]
+        .parse::<i32>()
+        .map_err(|e| bad_request(&format!("invalid token id: {:?}", e)))?;
+
+    let conn = req.db_conn()?;
+    let token = ApiToken::belonging_to(req.user()?)
+        .find(id)
+        .first::<ApiToken>(&*conn)?;
+    let events = ApiTokenEvent::history(&conn, &token)?;
+
+    #[derive(Serialize)]
+    struct R {
+        events: Vec<ApiTokenEvent>,
+    }
+    Ok(req.json(&R { events }))
+}
+
+/// How many buckets of token usage are returned for each bucket size.
+const USAGE_HOUR_BUCKETS: i64 = 48;
+const USAGE_DAY_BUCKETS: i64 = 30;
+
+/// Handles the `GET /me/tokens/:id/usage` route.
+///
+/// Counts the authenticated requests made with the token in each of the last
+/// 48 hours with `bucket=hour`, or each of the last 30 days with `bucket=day`
+/// (the default), oldest first. Buckets without any requests are left out.
+pub fn usage(req: &mut dyn Request) -> CargoResult<Response> {
+    use chrono::Timelike;
+    use util::rfc3339;
+
+    req.check_endpoint_scope("manage-tokens")?;
+
+    let id = req.params()["id"]
+        .parse::<i32>()
+        .map_err(|e| bad_request(&format!("invalid token id: {:?}", e)))?;
+    let bucket = req.query().remove("bucket");
+    let by_day = match bucket.as_ref().map(|s| &**s) {
+        None | Some("day") => true,
+        Some("hour") => false,
+        Some(other) => {
+            return Err(bad_request(&format_args!(
+                "invalid bucket `{}`, expected `day` or `hour`",
+                other
+            )));
+        }
+    };
+
+    let now = Utc::now().naive_utc();
+    let this_hour = now.date().and_hms(now.hour(), 0, 0);
+    let since = if by_day {
+        this_hour.date().and_hms(0, 0, 0) - Duration::days(USAGE_DAY_BUCKETS - 1)
+    } else {
+        this_hour - Duration::hours(USAGE_HOUR_BUCKETS - 1)
+    };
+
+    let conn = req.db_conn()?;
+    let token = ApiToken::belonging_to(req.user()?)
+        .find(id)
+        .first::<ApiToken>(&*conn)?;
+
+    #[derive(Serialize)]
+    struct Bucket {
+        #[serde(with = "rfc3339")]
+        time: NaiveDateTime,
+        count: i64,
+    }
+
+    let mut usage: Vec<Bucket> = Vec::new();
+    for (hour, uses) in token.hourly_uses(&conn, since)? {
+        let time = if by_day {
+            hour.date().and_hms(0, 0, 0)
+        } else {
+            hour
+        };
+        if let Some(last) = usage.last_mut() {
+            if last.time == time {
+                last.count += i64::from(uses);
+                continue;
+            }
+        }
+        usage.push(Bucket {
+            time,
+            count: i64::from(uses),
+        });
+    }
+
+    #[derive(Serialize)]
+    struct R {
+        usage: Vec<Bucket>,
+    }
+    Ok(req.json(&R { usage }))
+}
+
 /// Handles the `DELETE /me/tokens/:id` route.
 pub fn revoke(req: &mut dyn Request) -> CargoResult<Response> {
+    req.check_endpoint_scope("manage-tokens")?;
+
     let id = req.params()["id"]
         .parse::<i32>()
         .map_err(|e| bad_request(&format!("invalid token id: {:?}", e)))?;
 
-    diesel::update(ApiToken::belonging_to(req.user()?).find(id))
-        .set(api_tokens::revoked.eq(true))
-        .execute(&*req.db_conn()?)?;
+    ApiToken::revoke(&*req.db_conn()?, req.user()?.id, id, "revoked by its owner")?;
 
     #[derive(Serialize)]
     struct R {}
     Ok(req.json(&R {}))
 }
+
+/// Handles the `DELETE /me/tokens` route.
+///
+/// Revokes every one of the user's tokens. Since that can't be undone, the
+/// request has to confirm it with `confirm=true`, either as a query parameter
+/// or as `{ "confirm": true }` in the body.
+pub fn revoke_all(req: &mut dyn Request) -> CargoResult<Response> {
+    #[derive(Deserialize)]
+    struct RevokeAllRequest {
+        #[serde(default)]
+        confirm: bool,
+    }
+
+    req.check_endpoint_scope("manage-tokens")?;
+
+    let user_id = req.user()?.id;
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    let confirmed = req.query().get("confirm").map(|s| &**s) == Some("true") || {
+        !body.trim().is_empty()
+            && json::from_str::<RevokeAllRequest>(&body)
+                .map_err(|e| bad_request(&format!("invalid revoke request: {:?}", e)))?
+                .confirm
+    };
+    if !confirmed {
+        return Err(bad_request(
+            "confirmation required: pass `confirm=true` to revoke all of your tokens",
+        ));
+    }
+
+    let revoked = ApiToken::revoke_all(&*req.db_conn()?, user_id, "revoked by its owner")?;
+
+    #[derive(Serialize)]
+    struct R {
+        revoked: usize,
+    }
+    Ok(req.json(&R { revoked }))
+}
@@ -1,16 +1,23 @@
 use controllers::prelude::*;
 
+use conduit_cookie::RequestSession;
 use serde_json;
 
 use controllers::helpers::Paginate;
 use email;
-use util::bad_request;
+use middleware::current_user::AuthenticationSource;
+use util::{bad_request, conflict, too_many_requests};
 
-use models::{Email, Follow, NewEmail, User, Version};
-use schema::{crates, emails, follows, users, versions};
-use views::{EncodableMe, EncodableVersion};
+use models::{
+    CrateOwnerAction, Email, Follow, NewEmail, OwnerAction, OwnerKind, Team, User, Version,
+};
+use schema::{crate_owner_actions, crate_owners, crates, emails, follows, teams, users, versions};
+use views::{EncodableMe, EncodableOwnerAction, EncodableOwnerRef, EncodableVersion};
 
 /// Handles the `GET /me` route.
+///
+/// With `include=rights`, the response also says whether the user can publish at all and to
+/// how many crates.
 pub fn me(req: &mut dyn Request) -> CargoResult<Response> {
     // Changed to getting User information from database because in
     // src/tests/user.rs, when testing put and get on updating email,
@@ -26,24 +33,45 @@ pub fn me(req: &mut dyn Request) -> CargoResult<Response> {
     let id = req.user()?.id;
     let conn = req.db_conn()?;
 
-    let (user, verified, email, verification_sent) = users::table
+    let (user, email) = users::table
         .find(id)
-        .left_join(emails::table)
-        .select((
-            users::all_columns,
-            emails::verified.nullable(),
-            emails::email.nullable(),
-            emails::token_generated_at.nullable().is_not_null(),
-        ))
-        .first::<(User, Option<bool>, Option<String>, bool)>(&*conn)?;
+        .left_join(
+            emails::table.on(emails::user_id
+                .eq(users::id)
+                .and(emails::is_primary.eq(true))),
+        )
+        .select((users::all_columns, emails::email.nullable()))
+        .first::<(User, Option<String>)>(&*conn)?;
 
-    let verified = verified.unwrap_or(false);
-    let verification_sent = verified || verification_sent;
     let user = User { email, ..user };
+    let (verified, verification_sent) = user.email_status(&conn)?;
+
+    let include_rights = req
+        .query()
+        .get("include")
+        .map_or(false, |include| include.split(',').any(|i| i == "rights"));
+    let rights = if include_rights {
+        // Only crates owned directly are counted, since counting those owned through teams
+        // would mean asking GitHub about every team.
+        let crate_count = crate_owners::table
+            .filter(crate_owners::owner_id.eq(id))
+            .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+            .filter(crate_owners::deleted.eq(false))
+            .count()
+            .get_result::<i64>(&*conn)?;
+        Some((user.has_verified_email(&conn)?, crate_count))
+    } else {
+        None
+    };
+
+    let is_admin = user.is_admin(&conn)?;
+    let mut encodable = user.encodable_private(verified, verification_sent, is_admin);
+    if let Some((can_publish, crate_count)) = rights {
+        encodable.can_publish = Some(can_publish);
+        encodable.publishable_crate_count = Some(crate_count);
+    }
 
-    Ok(req.json(&EncodableMe {
-        user: user.encodable_private(verified, verification_sent),
-    }))
+    Ok(req.json(&EncodableMe { user: encodable }))
 }
 
 /// Handles the `GET /me/updates` route.
@@ -88,10 +116,140 @@ pub fn updates(req: &mut dyn Request) -> CargoResult<Response> {
     }))
 }
 
+/// Handles the `GET /me/owner_activity` route.
+///
+/// Lists who was added to or removed from the owners of the crates the user
+/// owns directly, newest first. `crate` only lists the changes to one crate
+/// and `action` only those that are an `add` or a `remove`.
+pub fn owner_activity(req: &mut dyn Request) -> CargoResult<Response> {
+    use diesel::dsl::any;
+
+    let user = req.user()?;
+    let query_params = req.query();
+    let (offset, limit) = req.pagination(20, 100)?;
+    let conn = req.db_conn()?;
+
+    let owned_crates = crate_owners::table
+        .filter(crate_owners::owner_id.eq(user.id))
+        .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+        .filter(crate_owners::deleted.eq(false))
+        .select(crate_owners::crate_id);
+    let mut query = crate_owner_actions::table
+        .inner_join(crates::table)
+        .filter(crate_owner_actions::crate_id.eq(any(owned_crates)))
+        .select((crate_owner_actions::all_columns, crates::name))
+        .order((
+            crate_owner_actions::created_at.desc(),
+            crate_owner_actions::id.desc(),
+        ))
+        .into_boxed();
+    if let Some(crate_name) = query_params.get("crate") {
+        query = query.filter(crates::name.eq(crate_name.clone()));
+    }
+    if let Some(action) = query_params.get("action") {
+        let action = OwnerAction::parse(action).ok_or_else(|| {
+            bad_request(&format_args!(
+                "invalid action `{}`, expected `add` or `remove`",
+                action
+            ))
+        })?;
+        query = query.filter(crate_owner_actions::action.eq(action));
+    }
+
+    let data = query
+        .paginate(limit, offset)
+        .load::<((CrateOwnerAction, String), i64)>(&*conn)?;
+    let more = data
+        .get(0)
+        .map(|&(_, count)| count > offset + limit)
+        .unwrap_or(false);
+
+    let actions = data.into_iter().map(|(action, _)| action).collect();
+    let activity = CrateOwnerAction::encodable_many(&conn, actions)?;
+
+    #[derive(Serialize)]
+    struct R {
+        activity: Vec<EncodableOwnerAction>,
+        meta: Meta,
+    }
+    #[derive(Serialize)]
+    struct Meta {
+        more: bool,
+    }
+    Ok(req.json(&R {
+        activity,
+        meta: Meta { more },
+    }))
+}
+
+/// Handles the `GET /me/collaborators` route.
+///
+/// Lists everyone who can publish at least one of the crates the current
+/// user owns, either as a fellow owner or as a member of an owning team.
+pub fn collaborators(req: &mut dyn Request) -> CargoResult<Response> {
+    use diesel::dsl::any;
+
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+
+    let owned_crates = crate_owners::table
+        .filter(crate_owners::owner_id.eq(user.id))
+        .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+        .filter(crate_owners::deleted.eq(false))
+        .select(crate_owners::crate_id)
+        .load::<i32>(&*conn)?;
+    let owners = crate_owners::table
+        .filter(crate_owners::crate_id.eq(any(owned_crates)))
+        .filter(crate_owners::deleted.eq(false));
+
+    let mut collaborators = owners
+        .clone()
+        .inner_join(users::table)
+        .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+        .select(users::all_columns)
+        .distinct()
+        .load::<User>(&*conn)?;
+    let teams = owners
+        .inner_join(teams::table)
+        .filter(crate_owners::owner_kind.eq(OwnerKind::Team as i32))
+        .select(teams::all_columns)
+        .distinct()
+        .order(teams::id)
+        .load::<Team>(&*conn)?;
+
+    let mut gh_ids = Vec::new();
+    for team in &teams {
+        gh_ids.extend(team.member_gh_ids(req.app(), user)?);
+    }
+    if !gh_ids.is_empty() {
+        collaborators.extend(
+            users::table
+                .filter(users::gh_id.eq(any(gh_ids)))
+                .load::<User>(&*conn)?,
+        );
+    }
+
+    collaborators.retain(|c| c.id != user.id);
+    collaborators.sort_by(|a, b| a.gh_login.cmp(&b.gh_login).then(a.id.cmp(&b.id)));
+    collaborators.dedup_by_key(|c| c.id);
+
+    #[derive(Serialize)]
+    struct R {
+        users: Vec<EncodableOwnerRef>,
+    }
+    Ok(req.json(&R {
+        users: collaborators
+            .into_iter()
+            .map(User::encodable_owner_ref)
+            .collect(),
+    }))
+}
+
 /// Handles the `PUT /user/:user_id` route.
 pub fn update_user(req: &mut dyn Request) -> CargoResult<Response> {
-    use self::emails::user_id;
     use self::users::dsl::{email, gh_login, users};
+    use diesel::dsl::sql;
+    use diesel::sql_types::Integer;
     use diesel::{insert_into, update};
 
     let mut body = String::new();
@@ -99,6 +257,7 @@ pub fn update_user(req: &mut dyn Request) -> CargoResult<Response> {
     let user = req.user()?;
     let name = &req.params()["user_id"];
     let conn = req.db_conn()?;
+    let sender = &*req.app().email_sender;
 
     // need to check if current user matches user to be updated
     if &user.id.to_string() != name {
@@ -141,15 +300,14 @@ pub fn update_user(req: &mut dyn Request) -> CargoResult<Response> {
 
         let token = insert_into(emails::table)
             .values(&new_email)
-            .on_conflict(user_id)
+            .on_conflict(sql::<Integer>("(user_id) WHERE is_primary"))
             .do_update()
             .set(&new_email)
             .returning(emails::token)
             .get_result::<String>(&*conn)
             .map_err(|_| human("Error in creating token"))?;
 
-        ::email::send_user_confirm_email(user_email, &user.gh_login, &token)
-            .map_err(|_| bad_request("Email could not be sent"))
+        ::email::send_user_confirm_email(sender, user_email, &user.gh_login, &token)
     })?;
 
     #[derive(Serialize)]
@@ -159,18 +317,62 @@ pub fn update_user(req: &mut dyn Request) -> CargoResult<Response> {
     Ok(req.json(&R { ok: true }))
 }
 
-/// Handles the `PUT /confirm/:email_token` route
-pub fn confirm_user_email(req: &mut dyn Request) -> CargoResult<Response> {
-    use diesel::update;
+/// Handles the `PUT /me/email` route.
+///
+/// Sends a confirmation link to the new address, which replaces the user's primary address once
+/// it's confirmed. Until then the current address keeps being used. This can't be done with an
+/// API token.
+pub fn update_email(req: &mut dyn Request) -> CargoResult<Response> {
+    if req.authentication_source()? != AuthenticationSource::SessionCookie {
+        return Err(bad_request(
+            "cannot use an API token to change an email address",
+        ));
+    }
 
+    #[derive(Deserialize)]
+    struct EmailUpdate {
+        email: String,
+    }
+
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    let update: EmailUpdate =
+        serde_json::from_str(&body).map_err(|_| bad_request("invalid json request"))?;
+    let new_email = update.email.trim();
+    if new_email.is_empty() {
+        return Err(bad_request("empty email rejected"));
+    }
+
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let sender = &*req.app().email_sender;
+    conn.transaction(|| {
+        let token = user.update_email(&conn, new_email)?;
+        ::email::send_user_confirm_email(sender, new_email, &user.gh_login, &token)
+    })?;
+
+    #[derive(Serialize)]
+    struct R {
+        ok: bool,
+    }
+    Ok(req.json(&R { ok: true }))
+}
+
+/// Handles the `GET /confirm/:email_token` and `PUT /confirm/:email_token` routes
+pub fn confirm_user_email(req: &mut dyn Request) -> CargoResult<Response> {
     let conn = req.db_conn()?;
     let req_token = &req.params()["email_token"];
 
-    let updated_rows = update(emails::table.filter(emails::token.eq(req_token)))
-        .set(emails::verified.eq(true))
-        .execute(&*conn)?;
+    let confirmed = Email::confirm(&conn, req_token)?;
+
+    // When someone follows the link in their browser, send them back to the
+    // frontend rather than showing them JSON
+    if let Some(ref url) = req.app().config.email_confirmation_url {
+        let status = if confirmed { "success" } else { "failure" };
+        return Ok(req.redirect(format!("{}?status={}", url, status)));
+    }
 
-    if updated_rows == 0 {
+    if !confirmed {
         return Err(bad_request("Email belonging to token not found."));
     }
 
@@ -189,6 +391,7 @@ pub fn regenerate_token_and_send(req: &mut dyn Request) -> CargoResult<Response>
     let user = req.user()?;
     let name = &req.params()["user_id"].parse::<i32>().ok().unwrap();
     let conn = req.db_conn()?;
+    let sender = &*req.app().email_sender;
 
     // need to check if current user matches user to be updated
     if &user.id != name {
@@ -196,13 +399,12 @@ pub fn regenerate_token_and_send(req: &mut dyn Request) -> CargoResult<Response>
     }
 
     conn.transaction(|| {
-        let email = update(Email::belonging_to(user))
+        let email = update(Email::belonging_to(user).filter(emails::is_primary.eq(true)))
             .set(emails::token.eq(sql("DEFAULT")))
             .get_result::<Email>(&*conn)
             .map_err(|_| bad_request("Email could not be found"))?;
 
-        email::send_user_confirm_email(&email.email, &user.gh_login, &email.token)
-            .map_err(|_| bad_request("Error in sending email"))
+        email::send_user_confirm_email(sender, &email.email, &user.gh_login, &email.token)
     })?;
 
     #[derive(Serialize)]
@@ -211,3 +413,175 @@ pub fn regenerate_token_and_send(req: &mut dyn Request) -> CargoResult<Response>
     }
     Ok(req.json(&R { ok: true }))
 }
+
+/// Handles the `POST /me/email_verification/regenerate` route.
+///
+/// Replaces the token used to confirm the user's primary email address without sending it,
+/// for clients that send the confirmation email themselves. Links sent before stop working.
+pub fn regenerate_email_verification(req: &mut dyn Request) -> CargoResult<Response> {
+    use diesel::dsl::sql;
+    use diesel::update;
+
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+
+    let email = Email::belonging_to(user)
+        .filter(emails::is_primary.eq(true))
+        .first::<Email>(&*conn)
+        .optional()?
+        .ok_or_else(|| bad_request("Email could not be found"))?;
+    if email.verified {
+        return Err(conflict("Email has already been verified"));
+    }
+
+    update(&email)
+        .set(emails::token.eq(sql("DEFAULT")))
+        .execute(&*conn)?;
+
+    #[derive(Serialize)]
+    struct R {
+        ok: bool,
+    }
+    Ok(req.json(&R { ok: true }))
+}
+
+/// Handles the `DELETE /me` route.
+///
+/// Deletes the signed in user's account, see `User::delete_account`, and signs them out. This
+/// can't be done with an API token.
+pub fn delete_account(req: &mut dyn Request) -> CargoResult<Response> {
+    if req.authentication_source()? != AuthenticationSource::SessionCookie {
+        return Err(bad_request("cannot use an API token to delete an account"));
+    }
+
+    req.user()?.delete_account(&*req.db_conn()?)?;
+    req.session().remove(&"user_id".to_string());
+
+    #[derive(Serialize)]
+    struct R {
+        ok: bool,
+    }
+    Ok(req.json(&R { ok: true }))
+}
+
+/// Handles the `PUT /me/email/resend` route.
+///
+/// Sends the confirmation link for the user's unverified address again with a new token,
+/// preferring an address they're changing their primary address to. Links can be resent once
+/// a minute.
+pub fn resend_email_verification(req: &mut dyn Request) -> CargoResult<Response> {
+    use diesel::dsl::{exists, now, sql, IntervalDsl};
+    use diesel::{select, update};
+
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+    let sender = &*req.app().email_sender;
+
+    let email = Email::belonging_to(user)
+        .filter(emails::is_primary.or(emails::replaces_primary))
+        .order(emails::replaces_primary.desc())
+        .first::<Email>(&*conn)
+        .optional()?
+        .ok_or_else(|| bad_request("Email could not be found"))?;
+    if email.verified {
+        return Err(conflict("Email has already been verified"));
+    }
+
+    let recently_sent =
+        select(exists(emails::table.find(email.id).filter(
+            emails::token_generated_at.gt((now - 1.minute()).nullable()),
+        )))
+        .get_result::<bool>(&*conn)?;
+    if recently_sent {
+        return Err(too_many_requests(
+            "a confirmation email was sent less than a minute ago, please try again later",
+        ));
+    }
+
+    conn.transaction(|| {
+        let email = update(&email)
+            .set(emails::token.eq(sql("DEFAULT")))
+            .get_result::<Email>(&*conn)?;
+        email::send_user_confirm_email(sender, &email.email, &user.gh_login, &email.token)
+    })?;
+
+    #[derive(Serialize)]
+    struct R {
+        ok: bool,
+    }
+    Ok(req.json(&R { ok: true }))
+}
+
+/// The longest display name a user can choose, in characters.
+const MAX_DISPLAY_NAME_LENGTH: usize = 100;
+
+/// Handles the `PUT /me/profile` route.
+///
+/// Sets the `display_name` shown instead of the name from the user's GitHub profile. An empty or
+/// `null` name goes back to showing the GitHub one.
+pub fn update_profile(req: &mut dyn Request) -> CargoResult<Response> {
+    #[derive(Deserialize)]
+    struct Profile {
+        display_name: Option<String>,
+    }
+
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    let profile: Profile =
+        serde_json::from_str(&body).map_err(|_| bad_request("invalid json request"))?;
+    let display_name = profile
+        .display_name
+        .as_ref()
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty());
+    if let Some(name) = display_name {
+        if name.chars().count() > MAX_DISPLAY_NAME_LENGTH {
+            return Err(bad_request(&format_args!(
+                "display names can be at most {} characters long",
+                MAX_DISPLAY_NAME_LENGTH
+            )));
+        }
+    }
+
+    let user = req.user()?;
+    user.set_display_name(&*req.db_conn()?, display_name)?;
+    ok_true()
+}
+
+/// Handles the `PUT /me/notification_email` route.
+pub fn update_notification_email(req: &mut dyn Request) -> CargoResult<Response> {
+    use diesel::update;
+
+    let mut body = String::new();
+    req.body().read_to_string(&mut body)?;
+    let user = req.user()?;
+    let conn = req.db_conn()?;
+
+    #[derive(Deserialize)]
+    struct NotificationEmail {
+        email: String,
+    }
+
+    let notification_email: NotificationEmail =
+        serde_json::from_str(&body).map_err(|_| bad_request("invalid json request"))?;
+
+    let email = Email::belonging_to(user)
+        .filter(emails::email.eq(notification_email.email.trim()))
+        .filter(emails::verified.eq(true))
+        .first::<Email>(&*conn)
+        .optional()?
+        .ok_or_else(|| {
+            bad_request("notifications can only be sent to one of your verified email addresses")
+        })?;
+
+    conn.transaction(|| {
+        update(Email::belonging_to(user))
+            .set(emails::notifications.eq(false))
+            .execute(&*conn)?;
+        update(&email)
+            .set(emails::notifications.eq(true))
+            .execute(&*conn)
+    })?;
+
+    ok_true()
+}
@@ -1,26 +1,59 @@
 use controllers::prelude::*;
 
-use models::{OwnerKind, User};
+use controllers::helpers::Paginate;
+use util::bad_request;
+
+use models::krate::ALL_COLUMNS;
+use models::{Crate, CrateVersions, OwnerKind, User, Version};
 use schema::{crate_owners, crates, users};
-use views::EncodablePublicUser;
+use views::{EncodableCrate, EncodablePublicUser};
 
 /// Handles the `GET /users/:user_id` route.
+///
+/// With `include=verified`, the response also says whether the user has a verified email address.
 pub fn show(req: &mut dyn Request) -> CargoResult<Response> {
-    use self::users::dsl::{gh_login, id, users};
-
-    let name = &req.params()["user_id"].to_lowercase();
     let conn = req.db_conn()?;
-    let user = users
-        .filter(::lower(gh_login).eq(name))
-        .order(id.desc())
-        .first::<User>(&*conn)?;
+    let user = User::find_by_login(&conn, &req.params()["user_id"])?;
+
+    let include_verified = req
+        .query()
+        .get("include")
+        .map_or(false, |include| include.split(',').any(|i| i == "verified"));
+    let verified = if include_verified {
+        Some(user.has_verified_email(&conn)?)
+    } else {
+        None
+    };
+
+    let mut user = user.encodable_public();
+    user.verified = verified;
 
     #[derive(Serialize)]
     struct R {
         user: EncodablePublicUser,
     }
+    Ok(req.json(&R { user }))
+}
+
+/// Handles the `GET /users/resolve` route.
+///
+/// Looks up the crates.io user with the GitHub login given as `gh_login`, ignoring case.
+pub fn resolve(req: &mut dyn Request) -> CargoResult<Response> {
+    let gh_login = req
+        .query()
+        .remove("gh_login")
+        .ok_or_else(|| bad_request("missing query parameter `gh_login`"))?;
+    let conn = req.db_conn()?;
+    let user = User::find_by_login(&conn, &gh_login)?;
+
+    #[derive(Serialize)]
+    struct R {
+        id: i32,
+        login: String,
+    }
     Ok(req.json(&R {
-        user: user.encodable_public(),
+        id: user.id,
+        login: user.gh_login,
     }))
 }
 
@@ -50,3 +83,50 @@ pub fn stats(req: &mut dyn Request) -> CargoResult<Response> {
         total_downloads: data,
     }))
 }
+
+/// Handles the `GET /users/:user_id/crates` route.
+///
+/// Lists the crates the user currently owns directly, by name. Crates they only own through a
+/// team are left out.
+pub fn crates(req: &mut dyn Request) -> CargoResult<Response> {
+    let user_id = req.params()["user_id"]
+        .parse::<i32>()
+        .map_err(|e| bad_request(&format!("invalid user id: {:?}", e)))?;
+    let (offset, limit) = req.pagination(10, 100)?;
+    let conn = req.db_conn()?;
+    let user = users::table.find(user_id).first::<User>(&*conn)?;
+
+    let data = crates::table
+        .inner_join(crate_owners::table)
+        .filter(crate_owners::owner_id.eq(user.id))
+        .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+        .filter(crate_owners::deleted.eq(false))
+        .order(crates::name)
+        .select(ALL_COLUMNS)
+        .paginate(limit, offset)
+        .load::<(Crate, i64)>(&*conn)?;
+    let total = data.get(0).map(|&(_, t)| t).unwrap_or(0);
+    let krates = data.into_iter().map(|(c, _)| c).collect::<Vec<_>>();
+    let versions = krates.versions().load::<Version>(&*conn)?;
+    let krates = versions
+        .grouped_by(&krates)
+        .into_iter()
+        .map(|versions| Version::max(versions.into_iter().map(|v| v.num)))
+        .zip(krates)
+        .map(|(max_version, krate)| krate.minimal_encodable(&max_version, None, false, None))
+        .collect();
+
+    #[derive(Serialize)]
+    struct R {
+        crates: Vec<EncodableCrate>,
+        meta: Meta,
+    }
+    #[derive(Serialize)]
+    struct Meta {
+        total: i64,
+    }
+    Ok(req.json(&R {
+        crates: krates,
+        meta: Meta { total },
+    }))
+}
@@ -1,7 +1,9 @@
 use controllers::prelude::*;
 
+use chrono::{DateTime, Utc};
 use conduit_cookie::RequestSession;
 use github;
+use middleware::current_user::AuthenticationSource;
 use rand::{thread_rng, Rng};
 
 use models::NewUser;
@@ -102,15 +104,30 @@ pub fn github_access_token(req: &mut dyn Request) -> CargoResult<Response> {
 
     let ghuser = github::github::<GithubUser>(req.app(), "/user", &token)?;
 
-    let user = NewUser::new(
-        ghuser.id,
-        &ghuser.login,
-        ghuser.email.as_ref().map(|s| &s[..]),
-        ghuser.name.as_ref().map(|s| &s[..]),
-        ghuser.avatar_url.as_ref().map(|s| &s[..]),
-        &token.access_token,
-    )
-    .create_or_update(&*req.db_conn()?)?;
+    let user = {
+        let conn = req.db_conn()?;
+        NewUser::new(
+            ghuser.id,
+            &ghuser.login,
+            ghuser.email.as_ref().map(|s| &s[..]),
+            ghuser.name.as_ref().map(|s| &s[..]),
+            ghuser.avatar_url.as_ref().map(|s| &s[..]),
+            &token.access_token,
+        )
+        .create_or_update(&conn)?
+        .record_login(&conn)?
+    };
+    if user.is_banned(&*req.db_conn()?)? {
+        return Err(human(
+            "this account has been banned. Please contact help@crates.io for more information.",
+        ));
+    }
+    if let Some(until) = user.suspension_end() {
+        return Err(human(&format_args!(
+            "this account is suspended until {}. Please contact help@crates.io for more information.",
+            DateTime::<Utc>::from_utc(until, Utc).to_rfc3339()
+        )));
+    }
     req.session()
         .insert("user_id".to_string(), user.id.to_string());
     req.mut_extensions().insert(user);
@@ -118,7 +135,16 @@ pub fn github_access_token(req: &mut dyn Request) -> CargoResult<Response> {
 }
 
 /// Handles the `GET /logout` route.
+///
+/// Users signed in with a session cookie also get their GitHub access token
+/// revoked, so crates.io can't act on their behalf until they log in again.
+/// Until then they aren't counted as members of any team that owns a crate,
+/// so their API tokens only work for crates they own directly.
 pub fn logout(req: &mut dyn Request) -> CargoResult<Response> {
+    if let Ok(AuthenticationSource::SessionCookie) = req.authentication_source() {
+        let user = req.user()?;
+        user.revoke_gh_token(req.app(), &*req.db_conn()?)?;
+    }
     req.session().remove(&"user_id".to_string());
     Ok(req.json(&true))
 }
@@ -34,9 +34,11 @@ pub fn unyank(req: &mut dyn Request) -> CargoResult<Response> {
 fn modify_yank(req: &mut dyn Request, yanked: bool) -> CargoResult<Response> {
     let (version, krate) = version_and_crate(req)?;
     let user = req.user()?;
+    req.check_crate_scope(&krate.name)?;
+    req.check_endpoint_scope("yank")?;
     let conn = req.db_conn()?;
     let owners = krate.owners(&conn)?;
-    if user.rights(req.app(), &owners)? < Rights::Publish {
+    if user.rights_over(req.app(), &conn, &krate, &owners)? < Rights::Publish {
         return Err(human("must already be an owner to yank or unyank"));
     }
 
@@ -0,0 +1,95 @@
+//! Endpoints receiving webhook deliveries from third parties
+
+use hex;
+use openssl::hash::MessageDigest;
+use openssl::memcmp;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use serde_json;
+
+use super::prelude::*;
+use util::errors::NotFound;
+use util::{bad_request, internal};
+
+use models::ApiToken;
+use schema::users;
+
+/// Handles the `POST /webhooks/github` route.
+///
+/// When a user revokes crates.io's access to their GitHub account, GitHub
+/// sends a `github_app_authorization` event. Since we can no longer check
+/// who they are, all of that user's API tokens are revoked as well. Other
+/// events are acknowledged and ignored.
+pub fn github(req: &mut dyn Request) -> CargoResult<Response> {
+    let secret = match req.app().config.gh_webhook_secret {
+        Some(ref secret) => secret.clone(),
+        None => return Err(Box::new(NotFound)),
+    };
+
+    let mut body = Vec::new();
+    req.body().read_to_end(&mut body)?;
+
+    let signature = req
+        .headers()
+        .find("X-Hub-Signature")
+        .and_then(|values| values.first().map(|s| s.to_string()))
+        .ok_or_else(|| bad_request("missing header: X-Hub-Signature"))?;
+    if !signature_matches(&secret, &body, &signature)? {
+        return Err(bad_request("invalid webhook signature"));
+    }
+
+    let event = req
+        .headers()
+        .find("X-GitHub-Event")
+        .and_then(|values| values.first().map(|s| s.to_string()))
+        .unwrap_or_default();
+
+    if event == "github_app_authorization" {
+        #[derive(Deserialize)]
+        struct Sender {
+            id: i32,
+        }
+
+        #[derive(Deserialize)]
+        struct Payload {
+            action: String,
+            sender: Sender,
+        }
+
+        let payload: Payload =
+            serde_json::from_slice(&body).map_err(|_| bad_request("invalid json request"))?;
+
+        if payload.action == "revoked" {
+            let conn = req.db_conn()?;
+            let user_ids = users::table
+                .filter(users::gh_id.eq(payload.sender.id))
+                .select(users::id)
+                .load::<i32>(&*conn)?;
+            for user_id in user_ids {
+                ApiToken::revoke_all(&conn, user_id, "GitHub access to the account was revoked")?;
+            }
+        }
+    }
+
+    ok_true()
+}
+
+/// Checks an `X-Hub-Signature` header value (`sha1=<hex digest>`) against
+/// the HMAC of the body.
+fn signature_matches(secret: &str, body: &[u8], signature: &str) -> CargoResult<bool> {
+    let signature = signature.trim();
+    if !signature.starts_with("sha1=") {
+        return Ok(false);
+    }
+    let expected = match hex::decode(&signature["sha1=".len()..]) {
+        Ok(digest) => digest,
+        Err(_) => return Ok(false),
+    };
+
+    let key = PKey::hmac(secret.as_bytes()).map_err(|e| internal(&e))?;
+    let mut signer = Signer::new(MessageDigest::sha1(), &key).map_err(|e| internal(&e))?;
+    signer.update(body).map_err(|e| internal(&e))?;
+    let actual = signer.sign_to_vec().map_err(|e| internal(&e))?;
+
+    Ok(actual.len() == expected.len() && memcmp::eq(&actual, &expected))
+}
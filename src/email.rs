@@ -1,7 +1,10 @@
 use std::env;
-use std::path::Path;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
+use chrono::{NaiveDateTime, Utc};
 use dotenv::dotenv;
+use util::errors::email_delivery_failed;
 use util::{bad_request, CargoResult};
 
 use lettre::file::FileEmailTransport;
@@ -35,33 +38,150 @@ pub fn init_config_vars() -> Option<MailgunConfigVars> {
     }
 }
 
-fn build_email(
-    recipient: &str,
-    subject: &str,
-    body: &str,
-    mailgun_config: &Option<MailgunConfigVars>,
-) -> CargoResult<Email> {
-    let sender = mailgun_config
-        .as_ref()
-        .map(|s| s.smtp_login.as_str())
-        .unwrap_or("Development Mode");
-
+fn build_email(message: &Message, sender: &str) -> CargoResult<Email> {
     let email = EmailBuilder::new()
-        .to(recipient)
+        .to(message.recipient.as_str())
         .from(sender)
-        .subject(subject)
-        .body(body)
+        .subject(message.subject.as_str())
+        .body(message.body.as_str())
         .build()?;
 
     Ok(email)
 }
 
-pub fn send_user_confirm_email(email: &str, user_name: &str, token: &str) -> CargoResult<()> {
+/// An email to a single recipient, ready to be sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+}
+
+impl Message {
+    pub fn new(recipient: &str, rendered: RenderedEmail) -> Message {
+        Message {
+            recipient: recipient.to_string(),
+            subject: rendered.subject,
+            body: rendered.body,
+        }
+    }
+}
+
+/// A way of delivering emails
+pub trait EmailSender: Send + Sync {
+    fn send(&self, message: &Message) -> CargoResult<()>;
+}
+
+/// Sends emails through Mailgun's SMTP server
+#[derive(Debug)]
+pub struct SmtpSender {
+    config: MailgunConfigVars,
+}
+
+impl SmtpSender {
+    pub fn new(config: MailgunConfigVars) -> SmtpSender {
+        SmtpSender { config }
+    }
+}
+
+impl EmailSender for SmtpSender {
+    fn send(&self, message: &Message) -> CargoResult<()> {
+        let email = build_email(message, &self.config.smtp_login)?;
+        let mut transport = SmtpTransport::simple_builder(&self.config.smtp_server)?
+            .credentials(Credentials::new(
+                self.config.smtp_login.clone(),
+                self.config.smtp_password.clone(),
+            ))
+            .smtp_utf8(true)
+            .authentication_mechanism(Mechanism::Plain)
+            .build();
+
+        transport
+            .send(&email)
+            .map_err(|_| bad_request("Error in sending email"))?;
+        Ok(())
+    }
+}
+
+/// Writes emails to files in a directory instead of sending them, for development
+#[derive(Debug)]
+pub struct FileSender {
+    dir: PathBuf,
+}
+
+impl FileSender {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> FileSender {
+        FileSender { dir: dir.into() }
+    }
+}
+
+impl EmailSender for FileSender {
+    fn send(&self, message: &Message) -> CargoResult<()> {
+        let email = build_email(message, "Development Mode")?;
+        FileEmailTransport::new(&self.dir)
+            .send(&email)
+            .map_err(|_| bad_request("Email file could not be generated"))?;
+        Ok(())
+    }
+}
+
+/// Keeps the emails it is asked to send so that tests can look at them. With `failing`, every
+/// send fails instead. Clones share the emails sent, so a test can keep one while the app uses
+/// another.
+#[derive(Debug, Default, Clone)]
+pub struct TestEmailSender {
+    sent: Arc<Mutex<Vec<Message>>>,
+    failing: bool,
+}
+
+impl TestEmailSender {
+    pub fn failing() -> TestEmailSender {
+        TestEmailSender {
+            failing: true,
+            ..TestEmailSender::default()
+        }
+    }
+
+    /// The emails sent so far, oldest first
+    pub fn sent(&self) -> Vec<Message> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+impl EmailSender for TestEmailSender {
+    fn send(&self, message: &Message) -> CargoResult<()> {
+        if self.failing {
+            return Err(bad_request("Error in sending email"));
+        }
+        self.sent.lock().unwrap().push(message.clone());
+        Ok(())
+    }
+}
+
+/// The sender configured for this environment: Mailgun when its SMTP settings are present,
+/// otherwise files in `/tmp`.
+pub fn sender() -> Box<dyn EmailSender> {
+    match init_config_vars() {
+        Some(config) => Box::new(SmtpSender::new(config)),
+        None => Box::new(FileSender::new("/tmp")),
+    }
+}
+
+/// The subject and body of an email, ready to be sent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub body: String,
+}
+
+/// Renders the email asking a user to confirm their email address by following a link
+/// containing `token`
+pub fn user_confirm_email(user_name: &str, token: &str) -> RenderedEmail {
     // Create a URL with token string as path to send to user
     // If user clicks on path, look email/user up in database,
     // make sure tokens match
 
-    let subject = "Please confirm your email address";
+    let subject = "Please confirm your email address".to_string();
     let body = format!(
         "Hello {}! Welcome to Crates.io. Please click the
 link below to verify your email address. Thank you!\n
@@ -69,33 +189,49 @@ https://crates.io/confirm/{}",
         user_name, token
     );
 
-    send_email(email, subject, &body)
+    RenderedEmail { subject, body }
 }
 
-fn send_email(recipient: &str, subject: &str, body: &str) -> CargoResult<()> {
-    let mailgun_config = init_config_vars();
-    let email = build_email(recipient, subject, body, &mailgun_config)?;
+/// Sends the email asking a user to confirm their email address. Failures are reported as
+/// `EmailDeliveryFailed`.
+pub fn send_user_confirm_email(
+    sender: &dyn EmailSender,
+    email: &str,
+    user_name: &str,
+    token: &str,
+) -> CargoResult<()> {
+    let rendered = user_confirm_email(user_name, token);
+    sender
+        .send(&Message::new(email, rendered))
+        .map_err(email_delivery_failed)
+}
 
-    match mailgun_config {
-        Some(mailgun_config) => {
-            let mut transport = SmtpTransport::simple_builder(&mailgun_config.smtp_server)?
-                .credentials(Credentials::new(
-                    mailgun_config.smtp_login,
-                    mailgun_config.smtp_password,
-                ))
-                .smtp_utf8(true)
-                .authentication_mechanism(Mechanism::Plain)
-                .build();
+/// Renders the email telling a user that an API token was created for their account. Only the
+/// token's name is included, never its value.
+pub fn token_created_email(
+    user_name: &str,
+    token_name: &str,
+    created_at: NaiveDateTime,
+) -> RenderedEmail {
+    let subject = "A new API token was created for your account".to_string();
+    let body = format!(
+        "Hello {}! A new API token named \"{}\" was created for your crates.io account at {} UTC.\n
+If you did not create this token, please revoke it at https://crates.io/me and
+contact help@crates.io.",
+        user_name,
+        token_name,
+        created_at.format("%Y-%m-%d %H:%M:%S")
+    );
 
-            let result = transport.send(&email);
-            result.map_err(|_| bad_request("Error in sending email"))?;
-        }
-        None => {
-            let mut sender = FileEmailTransport::new(Path::new("/tmp"));
-            let result = sender.send(&email);
-            result.map_err(|_| bad_request("Email file could not be generated"))?;
-        }
-    }
+    RenderedEmail { subject, body }
+}
 
-    Ok(())
+pub fn send_token_created_email(
+    sender: &dyn EmailSender,
+    email: &str,
+    user_name: &str,
+    token_name: &str,
+) -> CargoResult<()> {
+    let rendered = token_created_email(user_name, token_name, Utc::now().naive_utc());
+    sender.send(&Message::new(email, rendered))
 }
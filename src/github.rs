@@ -1,14 +1,24 @@
 //! This module implements functionality for interacting with GitHub.
 
+use base64;
+use chrono::Utc;
 use oauth2::*;
+use openssl::bn::BigNum;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::rsa::Rsa;
+use openssl::sign::Verifier;
 use reqwest::{self, header};
+use serde_json;
 
 use serde::de::DeserializeOwned;
 
+use std::io;
 use std::str;
 
 use app::App;
-use util::{errors::NotFound, human, internal, CargoError, CargoResult};
+use util::errors::{GitHubTimeout, NotFound};
+use util::{bad_request, human, internal, CargoError, CargoResult};
 
 /// Does all the nonsense for sending a GET to Github. Doesn't handle parsing
 /// because custom error-code handling may be desirable. Use
@@ -17,22 +27,136 @@ pub fn github<T>(app: &App, url: &str, auth: &Token) -> CargoResult<T>
 where
     T: DeserializeOwned,
 {
+    get(app, url, auth)?.json().map_err(handle_request_error)
+}
+
+/// Like `github`, but for endpoints that return a list split into pages.
+/// Follows the `next` links GitHub sends in the `Link` header until the last
+/// page, and returns the items of all pages.
+pub fn github_all_pages<T>(app: &App, url: &str, auth: &Token) -> CargoResult<Vec<T>>
+where
+    T: DeserializeOwned,
+{
+    let mut items = Vec::new();
+    let mut next = Some(url.to_string());
+    while let Some(url) = next {
+        let mut response = get(app, &url, auth)?;
+        next = next_page(response.headers());
+        let page: Vec<T> = response.json().map_err(handle_request_error)?;
+        items.extend(page);
+    }
+    Ok(items)
+}
+
+fn get(app: &App, url: &str, auth: &Token) -> CargoResult<reqwest::Response> {
     let url = format!("{}://api.github.com{}", app.config.api_protocol, url);
     info!("GITHUB HTTP: {}", url);
 
-    let client = app.http_client()?;
-    client
+    let client = app.github_client()?;
+    let response = client
         .get(&url)
         .header(header::ACCEPT, "application/vnd.github.v3+json")
         .header(
             header::AUTHORIZATION,
             format!("token {}", auth.access_token),
         )
-        .send()?
+        .send()
+        .map_err(handle_request_error)?;
+    record_rate_limit(app, &auth.access_token, response.headers());
+    response
+        .error_for_status()
+        .map_err(|e| handle_error_response(&e))
+}
+
+/// Finds the path of the next page in a `Link` header such as
+/// `<https://api.github.com/teams/1/members?page=2>; rel="next", <...>; rel="last"`.
+/// Only the path is kept, so the next page is requested the same way as the first.
+fn next_page(headers: &header::HeaderMap) -> Option<String> {
+    let link = headers.get(header::LINK)?.to_str().ok()?;
+    link.split(',')
+        .find(|part| part.contains("rel=\"next\""))
+        .and_then(|part| {
+            let start = part.find('<')? + 1;
+            let end = part.find('>')?;
+            let url = part.get(start..end)?;
+            let path_start = url.find("api.github.com")? + "api.github.com".len();
+            Some(url[path_start..].to_string())
+        })
+}
+
+/// Asks GitHub to revoke an access token that was handed to crates.io, so it
+/// stops working even if a copy of it survives somewhere.
+pub fn revoke_token(app: &App, access_token: &str) -> CargoResult<()> {
+    #[derive(Serialize)]
+    struct Body<'a> {
+        access_token: &'a str,
+    }
+
+    let url = format!(
+        "{}://api.github.com/applications/{}/token",
+        app.config.api_protocol, app.github.client_id
+    );
+    info!("GITHUB HTTP: {}", url);
+
+    let client = app.github_client()?;
+    let response = client
+        .delete(&url)
+        .header(header::ACCEPT, "application/vnd.github.v3+json")
+        .basic_auth(&app.github.client_id, Some(&app.github.client_secret))
+        .json(&Body { access_token })
+        .send()
+        .map_err(handle_request_error)?;
+    // Made with the application's credentials, so it counts against their limit
+    record_rate_limit(app, &app.github.client_id, response.headers());
+    response
         .error_for_status()
-        .map_err(|e| handle_error_response(&e))?
-        .json()
-        .map_err(Into::into)
+        .map_err(|e| handle_error_response(&e))?;
+    Ok(())
+}
+
+/// Remembers how many requests GitHub says are left for `key` before it gets rate limited.
+/// GitHub limits each access token separately, so requests made with a token are keyed by it.
+fn record_rate_limit(app: &App, key: &str, headers: &header::HeaderMap) {
+    let remaining = headers
+        .get("x-ratelimit-remaining")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok());
+    if let Some(remaining) = remaining {
+        app.github_rate_limit_remaining
+            .insert(key.to_string(), remaining);
+    }
+}
+
+/// Whether the rate limit of `access_token` is close enough to being used up that requests
+/// which can be skipped should be, leaving `Config::gh_rate_limit_reserve` requests for
+/// everything else.
+pub fn near_rate_limit(app: &App, access_token: &str) -> bool {
+    app.github_rate_limit_remaining
+        .get(&access_token.to_string())
+        .map_or(false, |remaining| {
+            remaining < app.config.gh_rate_limit_reserve
+        })
+}
+
+fn handle_request_error(error: reqwest::Error) -> Box<dyn CargoError> {
+    if is_timeout(&error) {
+        Box::new(GitHubTimeout)
+    } else {
+        error.into()
+    }
+}
+
+/// Whether the request gave up because GitHub didn't respond within
+/// `Config::gh_timeout`.
+fn is_timeout(error: &reqwest::Error) -> bool {
+    error
+        .get_ref()
+        .and_then(|e| e.downcast_ref::<io::Error>())
+        .map_or(false, |e| match e.kind() {
+            // reqwest reports its own timeouts as `WouldBlock` on unix
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => true,
+            _ => false,
+        })
 }
 
 fn handle_error_response(error: &reqwest::Error) -> Box<dyn CargoError> {
@@ -74,3 +198,177 @@ pub fn team_url(login: &str) -> String {
         login_pieces.next().expect("org failed"),
     )
 }
+
+/// The issuer of the OIDC tokens that GitHub Actions hands to workflows.
+const ACTIONS_ISSUER: &str = "https://token.actions.githubusercontent.com";
+
+/// The audience workflows must request their OIDC token for, so that tokens
+/// meant for other services can't be replayed against crates.io.
+pub const ACTIONS_AUDIENCE: &str = "crates.io";
+
+/// The claims of a GitHub Actions OIDC token that crates.io cares about.
+#[derive(Debug, Deserialize)]
+pub struct ActionsClaims {
+    pub iss: String,
+    pub aud: String,
+    pub exp: i64,
+    pub nbf: Option<i64>,
+    /// The repository the workflow runs in, as `owner/name`.
+    pub repository: String,
+    /// GitHub's id for the repository, which unlike its name is never reused.
+    pub repository_id: String,
+    /// GitHub's id for the user or organization owning the repository.
+    pub repository_owner_id: String,
+}
+
+/// Checks that `token` is an OIDC token issued by GitHub Actions for crates.io
+/// that hasn't expired, and returns its claims.
+///
+/// The signature is checked against the keys GitHub publishes, see
+/// `actions_signing_key`.
+pub fn verify_actions_token(app: &App, token: &str) -> CargoResult<ActionsClaims> {
+    #[derive(Deserialize)]
+    struct Header {
+        alg: String,
+        kid: String,
+    }
+
+    let invalid =
+        |reason: &str| bad_request(&format_args!("invalid GitHub Actions token: {}", reason));
+
+    let parts = token.split('.').collect::<Vec<_>>();
+    if parts.len() != 3 {
+        return Err(invalid("not a JWT"));
+    }
+    let header = decode_jwt_part::<Header>(parts[0]).ok_or_else(|| invalid("malformed header"))?;
+    if header.alg != "RS256" {
+        return Err(invalid("unsupported signing algorithm"));
+    }
+    let signature = base64::decode_config(parts[2], base64::URL_SAFE_NO_PAD)
+        .map_err(|_| invalid("malformed signature"))?;
+
+    let key = actions_signing_key(app, &header.kid)?
+        .ok_or_else(|| invalid("signed with an unknown key"))?;
+    let mut verifier = Verifier::new(MessageDigest::sha256(), &key)?;
+    verifier.update(parts[0].as_bytes())?;
+    verifier.update(b".")?;
+    verifier.update(parts[1].as_bytes())?;
+    if !verifier.verify(&signature)? {
+        return Err(invalid("bad signature"));
+    }
+
+    let claims =
+        decode_jwt_part::<ActionsClaims>(parts[1]).ok_or_else(|| invalid("malformed claims"))?;
+    let now = Utc::now().timestamp();
+    if claims.iss != ACTIONS_ISSUER {
+        return Err(invalid("not issued by GitHub Actions"));
+    }
+    if claims.aud != ACTIONS_AUDIENCE {
+        return Err(invalid("not issued for crates.io"));
+    }
+    if claims.exp <= now || claims.nbf.map_or(false, |nbf| nbf > now) {
+        return Err(invalid("expired"));
+    }
+    Ok(claims)
+}
+
+fn decode_jwt_part<T: DeserializeOwned>(part: &str) -> Option<T> {
+    let json = base64::decode_config(part, base64::URL_SAFE_NO_PAD).ok()?;
+    serde_json::from_slice(&json).ok()
+}
+
+/// Looks up the public key GitHub Actions signs OIDC tokens with that has the
+/// given key id, if there is one. The keys GitHub publishes are remembered
+/// for an hour, and fetched again sooner only for a key id that isn't known,
+/// at most once every five minutes.
+fn actions_signing_key(app: &App, kid: &str) -> CargoResult<Option<PKey>> {
+    #[derive(Deserialize)]
+    struct Jwk {
+        kid: String,
+        kty: String,
+        n: String,
+        e: String,
+    }
+
+    #[derive(Deserialize)]
+    struct Jwks {
+        keys: Vec<Jwk>,
+    }
+
+    let (n, e) = match app.actions_signing_keys.get(&kid.to_string()) {
+        Some(components) => components,
+        // Anyone can send a token naming a made up key, so the keys are fetched at most once
+        // every few minutes
+        None if app.actions_signing_keys_refreshed.get(&()).is_some() => return Ok(None),
+        None => {
+            let url = format!(
+                "{}://token.actions.githubusercontent.com/.well-known/jwks",
+                app.config.api_protocol
+            );
+            let jwks: Jwks = app
+                .github_client()?
+                .get(&url)
+                .header(header::ACCEPT, "application/json")
+                .send()
+                .map_err(handle_request_error)?
+                .error_for_status()
+                .map_err(|e| handle_error_response(&e))?
+                .json()
+                .map_err(handle_request_error)?;
+            app.actions_signing_keys_refreshed.insert((), ());
+
+            let mut found = None;
+            for jwk in jwks.keys.into_iter().filter(|k| k.kty == "RSA") {
+                if jwk.kid == kid {
+                    found = Some((jwk.n.clone(), jwk.e.clone()));
+                }
+                app.actions_signing_keys.insert(jwk.kid, (jwk.n, jwk.e));
+            }
+            match found {
+                Some(components) => components,
+                None => return Ok(None),
+            }
+        }
+    };
+
+    let component = |value: &str| -> CargoResult<BigNum> {
+        let bytes = base64::decode_config(value, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| internal("malformed key published by GitHub Actions"))?;
+        Ok(BigNum::from_slice(&bytes)?)
+    };
+    let rsa = Rsa::from_public_components(component(&n)?, component(&e)?)?;
+    Ok(Some(PKey::from_rsa(rsa)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(value: &str) -> header::HeaderMap {
+        let mut headers = header::HeaderMap::new();
+        headers.insert(header::LINK, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn next_page_is_found_in_link_header() {
+        let headers = link(
+            "<https://api.github.com/teams/1/members?per_page=100&page=2>; rel=\"next\", \
+             <https://api.github.com/teams/1/members?per_page=100&page=3>; rel=\"last\"",
+        );
+        assert_eq!(
+            next_page(&headers),
+            Some("/teams/1/members?per_page=100&page=2".to_string())
+        );
+    }
+
+    #[test]
+    fn last_page_has_no_next_page() {
+        let headers = link(
+            "<https://api.github.com/teams/1/members?per_page=100&page=1>; rel=\"first\", \
+             <https://api.github.com/teams/1/members?per_page=100&page=2>; rel=\"prev\"",
+        );
+        assert_eq!(next_page(&headers), None);
+        assert_eq!(next_page(&header::HeaderMap::new()), None);
+    }
+}
@@ -10,6 +10,7 @@
 #![allow(unknown_lints, proc_macro_derive_resolution_fallback)] // TODO: This can be removed after diesel-1.4
 
 extern crate ammonia;
+extern crate base64;
 extern crate chrono;
 extern crate comrak;
 #[macro_use]
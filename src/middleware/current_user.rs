@@ -1,23 +1,48 @@
 use super::prelude::*;
 
+use chrono::{Duration, Utc};
+use conduit::Method;
 use conduit_cookie::RequestSession;
 use diesel::prelude::*;
 
 use db::RequestTransaction;
-use util::errors::{std_error, CargoResult, ChainError, Unauthorized};
+use middleware::app::RequestApp;
+use util::errors::{
+    bad_request, forbidden, std_error, too_many_requests, CargoResult, ChainError, Forbidden,
+    NotFound, Unauthorized,
+};
 
-use models::User;
+use models::{ApiToken, User};
 use schema::users;
 
 #[derive(Debug, Clone, Copy)]
 pub struct CurrentUser;
 
+/// API tokens expiring within this many days have a `Warning` header added to
+/// the responses they get, so the expiry shows up in CI logs before it breaks anything.
+const EXPIRY_WARNING_DAYS: i64 = 7;
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum AuthenticationSource {
     SessionCookie,
     ApiToken,
 }
 
+/// Attached to requests whose API token was not looked up because the IP
+/// address sending it has sent too many invalid tokens recently.
+#[derive(Debug, Clone, Copy)]
+struct TokenLockedOut;
+
+/// Attached to requests whose `Authorization` header uses a scheme other than `Bearer`, holding
+/// the scheme that was used.
+#[derive(Debug, Clone)]
+struct UnsupportedScheme(String);
+
+/// Attached to requests with a valid API token that its owner may not use right now, for example
+/// because their account is suspended, holding the reason.
+#[derive(Debug, Clone)]
+struct RejectedToken(String);
+
 impl Middleware for CurrentUser {
     fn before(&self, req: &mut dyn Request) -> Result<(), Box<dyn Error + Send>> {
         // Check if the request has a session cookie with a `user_id` property inside
@@ -33,6 +58,11 @@ impl Middleware for CurrentUser {
             // If it did, look for a user in the database with the given `user_id`
             let maybe_user = users::table.find(id).first::<User>(&*conn);
             if let Ok(user) = maybe_user {
+                // Banned and suspended users are treated as if they weren't signed in
+                let banned = user.is_banned(&conn).map_err(|e| std_error(e.into()))?;
+                if banned || user.suspension_end().is_some() {
+                    return Ok(());
+                }
                 // Attach the `User` model from the database to the request
                 req.mut_extensions().insert(user);
                 req.mut_extensions()
@@ -41,29 +71,181 @@ impl Middleware for CurrentUser {
         } else {
             // Otherwise, look for an `Authorization` header on the request
             // and try to find a user in the database with a matching API token
+            let mut locked_out = false;
+            let mut unsupported_scheme = None;
+            let mut rejected = None;
             let user = if let Some(headers) = req.headers().find("Authorization") {
-                User::find_by_api_token(&conn, headers[0]).ok()
+                // Tokens from an IP address that has sent too many invalid ones recently aren't
+                // looked up at all, so that they can't be guessed by brute force.
+                let ip = client_ip(req);
+                let lockout = &req.app().token_lockout;
+                locked_out = !lockout.allows(&ip, 1);
+                if locked_out {
+                    None
+                } else {
+                    match token_from_header(headers[0]) {
+                        Ok(token) => {
+                            let grace = req.app().config.expired_token_grace_period;
+                            match User::find_by_api_token(&conn, token, &ip, grace) {
+                                Ok(found) => Some(found),
+                                // The token is valid, but its owner may not use it right now
+                                Err(ref e) if e.is::<Forbidden>() => {
+                                    rejected = Some(RejectedToken(e.description().to_string()));
+                                    None
+                                }
+                                // No such token, or it belongs to a banned user
+                                Err(ref e) if e.is::<NotFound>() => {
+                                    lockout.record(&ip, 1);
+                                    None
+                                }
+                                Err(e) => return Err(std_error(e)),
+                            }
+                        }
+                        Err(scheme) => {
+                            unsupported_scheme = Some(UnsupportedScheme(scheme.to_string()));
+                            None
+                        }
+                    }
+                }
             } else {
                 None
             };
-            if let Some(user) = user {
-                // Attach the `User` model from the database to the request
+            if locked_out {
+                req.mut_extensions().insert(TokenLockedOut);
+            }
+            if let Some(scheme) = unsupported_scheme {
+                req.mut_extensions().insert(scheme);
+            }
+            if let Some(rejected) = rejected {
+                req.mut_extensions().insert(rejected);
+            }
+            if let Some((user, token)) = user {
+                // Attach the `User` and `ApiToken` models from the database to the request
                 req.mut_extensions().insert(user);
+                req.mut_extensions().insert(token);
                 req.mut_extensions().insert(AuthenticationSource::ApiToken);
             }
         }
 
         Ok(())
     }
+
+    fn after(
+        &self,
+        req: &mut dyn Request,
+        mut res: Result<Response, Box<dyn Error + Send>>,
+    ) -> Result<Response, Box<dyn Error + Send>> {
+        if let Ok(ref mut response) = res {
+            if let Some(warning) = req.extensions().find::<ApiToken>().and_then(expiry_warning) {
+                response
+                    .headers
+                    .entry("Warning".into())
+                    .or_insert_with(Vec::new)
+                    .push(warning);
+            }
+        }
+        res
+    }
+}
+
+/// The token sent in an `Authorization` header, which is either the bare token or, as most HTTP
+/// tooling sends it, `Bearer <token>`. Any other scheme is returned as the error.
+fn token_from_header(value: &str) -> Result<&str, &str> {
+    let value = value.trim();
+    match value.find(char::is_whitespace) {
+        Some(i) if value[..i].eq_ignore_ascii_case("Bearer") => Ok(value[i..].trim_start()),
+        Some(i) => Err(&value[..i]),
+        None => Ok(value),
+    }
+}
+
+/// A `Warning` header value for a token that is about to expire.
+fn expiry_warning(token: &ApiToken) -> Option<String> {
+    let remaining = token
+        .expires_at?
+        .signed_duration_since(Utc::now().naive_utc());
+    if remaining < Duration::zero() {
+        // Only found during `Config::expired_token_grace_period`
+        return Some(
+            "299 crates.io \"this API token has expired and only works for reads\"".into(),
+        );
+    }
+    if remaining > Duration::days(EXPIRY_WARNING_DAYS) {
+        return None;
+    }
+    let when = match remaining.num_days() {
+        0 => "in less than a day".to_string(),
+        1 => "in 1 day".to_string(),
+        days => format!("in {} days", days),
+    };
+    Some(format!("299 crates.io \"this API token expires {}\"", when))
+}
+
+/// The IP address of the client that sent the request. Heroku's router appends the address it
+/// received the request from to `X-Forwarded-For`, so the last entry is the one to trust.
+fn client_ip(req: &dyn Request) -> String {
+    req.headers()
+        .find("X-Forwarded-For")
+        .and_then(|values| {
+            values
+                .last()
+                .and_then(|v| v.rsplit(',').next())
+                .map(|ip| ip.trim().to_string())
+        })
+        .unwrap_or_else(|| req.remote_addr().ip().to_string())
 }
 
 pub trait RequestUser {
     fn user(&self) -> CargoResult<&User>;
     fn authentication_source(&self) -> CargoResult<AuthenticationSource>;
+
+    /// Like `user`, but fails with a 403 unless the user is an admin. Admin-only endpoints call
+    /// this before doing anything else.
+    fn admin(&self) -> CargoResult<&User>;
+
+    /// Like `user`, but fails with a 403 and `Config::unverified_email_message` unless the user
+    /// has a verified email address. Meant for publishing and similar endpoints.
+    fn verified_user(&self) -> CargoResult<&User>;
+
+    /// Fails with a 403 unless the request may act on the named crate. Requests authenticated by
+    /// an API token that is scoped to some crates may only act on those.
+    fn check_crate_scope(&self, crate_name: &str) -> CargoResult<()>;
+
+    /// Fails with a 403 unless the request may make the given kind of change, one of
+    /// `ENDPOINT_SCOPES`. Requests authenticated by an API token that is scoped to some endpoints
+    /// may only make those changes.
+    fn check_endpoint_scope(&self, endpoint: &str) -> CargoResult<()>;
 }
 
 impl<'a> RequestUser for dyn Request + 'a {
     fn user(&self) -> CargoResult<&User> {
+        if self.extensions().find::<TokenLockedOut>().is_some() {
+            return Err(too_many_requests(
+                "too many requests with an invalid API token, please try again later",
+            ));
+        }
+        if let Some(scheme) = self.extensions().find::<UnsupportedScheme>() {
+            return Err(bad_request(&format_args!(
+                "unsupported authorization scheme `{}`, \
+                 send the API token on its own or as `Bearer <token>`",
+                scheme.0
+            )));
+        }
+        if let Some(rejected) = self.extensions().find::<RejectedToken>() {
+            return Err(forbidden(&rejected.0));
+        }
+        // Tokens in their grace period after expiring can only be used for reads
+        if let Some(token) = self.extensions().find::<ApiToken>() {
+            let is_read = match self.method() {
+                Method::Get | Method::Head => true,
+                _ => false,
+            };
+            if token.is_expired() && !is_read {
+                return Err(forbidden(
+                    "this API token has expired and can only be used for reads",
+                ));
+            }
+        }
         self.extensions()
             .find::<User>()
             .chain_error(|| Unauthorized)
@@ -75,4 +257,42 @@ impl<'a> RequestUser for dyn Request + 'a {
             .cloned()
             .chain_error(|| Unauthorized)
     }
+
+    fn admin(&self) -> CargoResult<&User> {
+        let user = self.user()?;
+        if user.is_admin(&*self.db_conn()?)? {
+            Ok(user)
+        } else {
+            Err(forbidden("must be an admin to perform that action"))
+        }
+    }
+
+    fn verified_user(&self) -> CargoResult<&User> {
+        let user = self.user()?;
+        if user.has_verified_email(&*self.db_conn()?)? {
+            Ok(user)
+        } else {
+            Err(forbidden(&self.app().config.unverified_email_message))
+        }
+    }
+
+    fn check_crate_scope(&self, crate_name: &str) -> CargoResult<()> {
+        match self.extensions().find::<ApiToken>() {
+            Some(token) if !token.allows_crate(crate_name) => Err(forbidden(&format_args!(
+                "this API token is not scoped to the crate `{}`",
+                crate_name
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_endpoint_scope(&self, endpoint: &str) -> CargoResult<()> {
+        match self.extensions().find::<ApiToken>() {
+            Some(token) if !token.has_scope(endpoint) => Err(forbidden(&format_args!(
+                "this API token does not have the `{}` scope",
+                endpoint
+            ))),
+            _ => Ok(()),
+        }
+    }
 }
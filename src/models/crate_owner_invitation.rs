@@ -1,8 +1,11 @@
-use chrono::NaiveDateTime;
+use chrono::{Duration, NaiveDateTime, Utc};
 use diesel::prelude::*;
 
 use schema::{crate_owner_invitations, crates, users};
-use views::EncodableCrateOwnerInvitation;
+use views::{EncodableCrateOwnerInvitation, EncodablePendingOwner};
+
+/// The number of days a crate owner invitation remains valid for.
+pub const INVITATION_EXPIRATION_DAYS: i64 = 30;
 
 /// The model representing a row in the `crate_owner_invitations` database table.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Identifiable, Queryable)]
@@ -23,6 +26,36 @@ pub struct NewCrateOwnerInvitation {
 }
 
 impl CrateOwnerInvitation {
+    /// Invitations created before this time have expired.
+    pub fn expiry_cutoff() -> NaiveDateTime {
+        Utc::now().naive_utc() - Duration::days(INVITATION_EXPIRATION_DAYS)
+    }
+
+    /// Returns the pending invitations to become an owner of the given crate,
+    /// along with the login of each invited user.
+    pub fn pending_for_crate(
+        crate_id: i32,
+        conn: &PgConnection,
+    ) -> QueryResult<Vec<(CrateOwnerInvitation, String)>> {
+        crate_owner_invitations::table
+            .inner_join(users::table.on(users::id.eq(crate_owner_invitations::invited_user_id)))
+            .filter(crate_owner_invitations::crate_id.eq(crate_id))
+            .filter(crate_owner_invitations::created_at.gt(Self::expiry_cutoff()))
+            .select((crate_owner_invitations::all_columns, users::gh_login))
+            .order(crate_owner_invitations::created_at)
+            .load(conn)
+    }
+
+    /// The time at which this invitation expires.
+    pub fn expires_at(&self) -> NaiveDateTime {
+        self.created_at + Duration::days(INVITATION_EXPIRATION_DAYS)
+    }
+
+    /// Whether this invitation can no longer be accepted.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at() <= Utc::now().naive_utc()
+    }
+
     pub fn invited_by_username(&self, conn: &PgConnection) -> String {
         users::table
             .find(self.invited_by_user_id)
@@ -47,4 +80,13 @@ impl CrateOwnerInvitation {
             created_at: self.created_at,
         }
     }
+
+    pub fn encodable_pending(self, login: String, conn: &PgConnection) -> EncodablePendingOwner {
+        EncodablePendingOwner {
+            login,
+            invited_by: self.invited_by_username(conn),
+            created_at: self.created_at,
+            expires_at: self.expires_at(),
+        }
+    }
 }
@@ -1,7 +1,10 @@
 use chrono::NaiveDateTime;
+use diesel;
+use diesel::prelude::*;
 
-use models::User;
-use schema::emails;
+use email::Message;
+use models::{QueuedEmail, User};
+use schema::{emails, users};
 
 #[derive(Debug, Queryable, AsChangeset, Identifiable, Associations)]
 #[belongs_to(User)]
@@ -12,6 +15,77 @@ pub struct Email {
     pub verified: bool,
     pub token: String,
     pub token_generated_at: Option<NaiveDateTime>,
+    pub is_primary: bool,
+    pub notifications: bool,
+    pub replaces_primary: bool,
+}
+
+impl Email {
+    /// Records the user's email address with a new confirmation token, returning the token.
+    /// Nothing is recorded if the address, or another primary address, already is.
+    pub fn insert_for_user(conn: &PgConnection, user: &User) -> QueryResult<Option<String>> {
+        let address = match user.email {
+            Some(ref address) => address,
+            None => return Ok(None),
+        };
+        diesel::insert_into(emails::table)
+            .values(&NewEmail {
+                user_id: user.id,
+                email: address,
+            })
+            .on_conflict_do_nothing()
+            .returning(emails::token)
+            .get_result::<String>(conn)
+            .optional()
+    }
+
+    /// Like `insert_for_user`, but also queues an email with the new token so that the user can
+    /// confirm the address. Nothing is queued when nothing was recorded, so signing in repeatedly
+    /// can't flood their inbox: only their first sign in with an address records it.
+    pub fn generate_and_send_token(conn: &PgConnection, user: &User) -> QueryResult<()> {
+        conn.transaction(|| {
+            let token = Self::insert_for_user(conn, user)?;
+            match (token, user.email.as_ref()) {
+                (Some(ref token), Some(address)) => {
+                    let rendered = ::email::user_confirm_email(&user.gh_login, token);
+                    QueuedEmail::enqueue(conn, &Message::new(address, rendered))
+                }
+                _ => Ok(()),
+            }
+        })
+    }
+
+    /// Marks the address with the given confirmation token as verified, returning whether there
+    /// was one. An address that replaces its user's primary address becomes the primary address
+    /// in its place.
+    pub fn confirm(conn: &PgConnection, token: &str) -> QueryResult<bool> {
+        conn.transaction(|| {
+            let email = diesel::update(emails::table.filter(emails::token.eq(token)))
+                .set(emails::verified.eq(true))
+                .get_result::<Email>(conn)
+                .optional()?;
+            if let Some(ref email) = email {
+                if email.replaces_primary {
+                    diesel::delete(
+                        emails::table
+                            .filter(emails::user_id.eq(email.user_id))
+                            .filter(emails::is_primary.eq(true)),
+                    )
+                    .execute(conn)?;
+                    diesel::update(email)
+                        .set((
+                            emails::is_primary.eq(true),
+                            emails::replaces_primary.eq(false),
+                        ))
+                        .execute(conn)?;
+                    diesel::update(users::table.find(email.user_id))
+                        .set(users::email.eq(&email.email))
+                        .execute(conn)?;
+                }
+            }
+            Ok(email.is_some())
+        })
+    }
 }
 
 #[derive(Debug, Insertable, AsChangeset)]
@@ -0,0 +1,63 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use models::{Crate, User};
+use schema::{crates, github_actions_bindings};
+
+/// The model representing a row in the `github_actions_bindings` database table.
+#[derive(Clone, Debug, PartialEq, Eq, Identifiable, Queryable, Associations)]
+#[belongs_to(Crate)]
+#[belongs_to(User)]
+#[table_name = "github_actions_bindings"]
+pub struct GitHubActionsBinding {
+    pub id: i32,
+    pub crate_id: i32,
+    pub user_id: i32,
+    pub repository: String,
+    pub created_at: NaiveDateTime,
+    pub repository_id: String,
+    pub repository_owner_id: String,
+}
+
+#[derive(Insertable, Clone, Copy, Debug)]
+#[table_name = "github_actions_bindings"]
+pub struct NewGitHubActionsBinding<'a> {
+    pub crate_id: i32,
+    pub user_id: i32,
+    pub repository: &'a str,
+    pub repository_id: &'a str,
+    pub repository_owner_id: &'a str,
+}
+
+impl<'a> NewGitHubActionsBinding<'a> {
+    pub fn create(&self, conn: &PgConnection) -> QueryResult<GitHubActionsBinding> {
+        use diesel::insert_into;
+
+        insert_into(github_actions_bindings::table)
+            .values(&NewGitHubActionsBinding {
+                repository: &self.repository.to_lowercase(),
+                ..*self
+            })
+            .get_result(conn)
+    }
+}
+
+impl GitHubActionsBinding {
+    /// Returns the bindings for the repository and owner with the given
+    /// GitHub ids, along with the name of each bound crate. The ids are
+    /// matched rather than the `owner/name`, so a different repository that
+    /// later takes the same name doesn't match.
+    pub fn for_repository(
+        conn: &PgConnection,
+        repository_id: &str,
+        repository_owner_id: &str,
+    ) -> QueryResult<Vec<(GitHubActionsBinding, String)>> {
+        github_actions_bindings::table
+            .inner_join(crates::table)
+            .filter(github_actions_bindings::repository_id.eq(repository_id))
+            .filter(github_actions_bindings::repository_owner_id.eq(repository_owner_id))
+            .select((github_actions_bindings::all_columns, crates::name))
+            .order(crates::name)
+            .load(conn)
+    }
+}
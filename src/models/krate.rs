@@ -1,6 +1,5 @@
 use chrono::{NaiveDate, NaiveDateTime};
 use diesel;
-use diesel::associations::Identifiable;
 use diesel::pg::Pg;
 use diesel::prelude::*;
 use license_exprs;
@@ -8,11 +7,11 @@ use semver;
 use url::Url;
 
 use app::App;
-use util::{human, CargoResult};
+use util::{forbidden, human, CargoResult};
 
 use models::{
     Badge, Category, CrateOwner, Keyword, NewCrateOwnerInvitation, Owner, OwnerKind,
-    ReverseDependency, User, Version,
+    ReverseDependency, Rights, User, Version,
 };
 use views::{EncodableCrate, EncodableCrateLinks};
 
@@ -227,9 +226,7 @@ impl<'a> NewCrate<'a> {
                     created_by: user_id,
                     owner_kind: OwnerKind::User as i32,
                 };
-                diesel::insert_into(crate_owners::table)
-                    .values(&owner)
-                    .execute(conn)?;
+                owner.add(conn)?;
             }
 
             Ok(maybe_inserted)
@@ -408,23 +405,7 @@ impl Crate {
     }
 
     pub fn owners(&self, conn: &PgConnection) -> CargoResult<Vec<Owner>> {
-        let base_query = CrateOwner::belonging_to(self).filter(crate_owners::deleted.eq(false));
-        let users = base_query
-            .inner_join(users::table)
-            .select(users::all_columns)
-            .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
-            .load(conn)?
-            .into_iter()
-            .map(Owner::User);
-        let teams = base_query
-            .inner_join(teams::table)
-            .select(teams::all_columns)
-            .filter(crate_owners::owner_kind.eq(OwnerKind::Team as i32))
-            .load(conn)?
-            .into_iter()
-            .map(Owner::Team);
-
-        Ok(users.chain(teams).collect())
+        Owner::all_owning(self, conn)
     }
 
     pub fn owner_add(
@@ -438,6 +419,15 @@ impl Crate {
 
         let owner = Owner::find_or_create_by_login(app, conn, req_user, login)?;
 
+        if let Owner::User(ref user) = owner {
+            if app.config.require_verified_owner_email && !user.has_verified_email(conn)? {
+                return Err(human(&format_args!(
+                    "`{}` does not have a verified email address and cannot be added as an owner",
+                    user.gh_login
+                )));
+            }
+        }
+
         match owner {
             // Users are invited and must accept before being added
             owner @ Owner::User(_) => {
@@ -447,7 +437,16 @@ impl Crate {
                         invited_by_user_id: req_user.id,
                         crate_id: self.id,
                     })
-                    .on_conflict_do_nothing()
+                    // Inviting someone again restarts the expiry clock
+                    .on_conflict((
+                        crate_owner_invitations::invited_user_id,
+                        crate_owner_invitations::crate_id,
+                    ))
+                    .do_update()
+                    .set((
+                        crate_owner_invitations::invited_by_user_id.eq(req_user.id),
+                        crate_owner_invitations::created_at.eq(diesel::dsl::now),
+                    ))
                     .execute(conn)?;
                 Ok(format!(
                     "user {} has been invited to be an owner of crate {}",
@@ -456,42 +455,107 @@ impl Crate {
                 ))
             }
             // Teams are added as owners immediately
-            owner @ Owner::Team(_) => {
-                insert_into(crate_owners::table)
-                    .values(&CrateOwner {
-                        crate_id: self.id,
-                        owner_id: owner.id(),
-                        created_by: req_user.id,
-                        owner_kind: OwnerKind::Team as i32,
-                    })
-                    .on_conflict(crate_owners::table.primary_key())
-                    .do_update()
-                    .set(crate_owners::deleted.eq(false))
-                    .execute(conn)?;
+            Owner::Team(ref team) => {
+                CrateOwner {
+                    crate_id: self.id,
+                    owner_id: team.id,
+                    created_by: req_user.id,
+                    owner_kind: OwnerKind::Team as i32,
+                }
+                .add(conn)?;
+                team.forget_memberships(app);
 
                 Ok(format!(
                     "team {} has been added as an owner of crate {}",
-                    owner.login(),
-                    self.name
+                    team.login, self.name
                 ))
             }
         }
     }
 
+    /// Removes `login` from the crate's owners, returning whether they were an owner.
     pub fn owner_remove(
         &self,
         app: &App,
         conn: &PgConnection,
         req_user: &User,
         login: &str,
-    ) -> CargoResult<()> {
+    ) -> CargoResult<bool> {
         let owner = Owner::find_or_create_by_login(app, conn, req_user, login)?;
 
-        let target = crate_owners::table.find((self.id(), owner.id(), owner.kind() as i32));
-        diesel::update(target)
-            .set(crate_owners::deleted.eq(true))
-            .execute(conn)?;
-        Ok(())
+        let removed = CrateOwner::remove(conn, self.id, &owner, req_user.id)?;
+        if let Owner::Team(ref team) = owner {
+            team.forget_memberships(app);
+        }
+        Ok(removed)
+    }
+
+    /// Hands the crate over from one user to another: the new owner is added directly, without
+    /// an invitation, and the old owner is removed, all in one transaction. Only a user who owns
+    /// the crate themselves can hand it over, not a team member with elevated rights.
+    /// Transferring the crate to yourself does nothing.
+    pub fn transfer_ownership(
+        &self,
+        app: &App,
+        conn: &PgConnection,
+        from: &User,
+        to_user_id: i32,
+    ) -> CargoResult<()> {
+        use diesel::delete;
+
+        conn.transaction(|| {
+            let owners = self.owners(conn)?;
+            let owns_directly = owners.iter().any(|owner| match *owner {
+                Owner::User(ref user) => user.id == from.id,
+                Owner::Team(_) => false,
+            });
+            if !owns_directly || from.rights_over(app, conn, self, &owners)? != Rights::Full {
+                return Err(forbidden(
+                    "only owners with full rights can transfer ownership of a crate",
+                ));
+            }
+            if from.id == to_user_id {
+                return Ok(());
+            }
+
+            let to = users::table
+                .find(to_user_id)
+                .first::<User>(conn)
+                .optional()?
+                .ok_or_else(|| human("the user to transfer the crate to could not be found"))?;
+            let already_owner = owners.iter().any(|owner| match *owner {
+                Owner::User(ref user) => user.id == to.id,
+                Owner::Team(_) => false,
+            });
+            if already_owner {
+                return Err(human(&format_args!(
+                    "`{}` is already an owner",
+                    to.gh_login
+                )));
+            }
+            if app.config.require_verified_owner_email && !to.has_verified_email(conn)? {
+                return Err(human(&format_args!(
+                    "`{}` does not have a verified email address and cannot be added as an owner",
+                    to.gh_login
+                )));
+            }
+
+            CrateOwner {
+                crate_id: self.id,
+                owner_id: to.id,
+                created_by: from.id,
+                owner_kind: OwnerKind::User as i32,
+            }
+            .add(conn)?;
+            delete(crate_owner_invitations::table.find((to.id, self.id))).execute(conn)?;
+            if !CrateOwner::remove(conn, self.id, &Owner::User(from.clone()), from.id)? {
+                return Err(human(&format_args!(
+                    "`{}` is no longer an owner of this crate",
+                    from.gh_login
+                )));
+            }
+            Ok(())
+        })
     }
 
     pub fn badges(&self, conn: &PgConnection) -> QueryResult<Vec<Badge>> {
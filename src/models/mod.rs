@@ -5,13 +5,17 @@ pub use self::dependency::{Dependency, DependencyKind, ReverseDependency};
 pub use self::download::VersionDownload;
 pub use self::email::{Email, NewEmail};
 pub use self::follow::Follow;
+pub use self::github_actions_binding::{GitHubActionsBinding, NewGitHubActionsBinding};
 pub use self::keyword::{CrateKeyword, Keyword};
 pub use self::krate::{Crate, CrateDownload, CrateVersions, NewCrate};
-pub use self::owner::{CrateOwner, Owner, OwnerKind};
-pub use self::rights::Rights;
+pub use self::owner::{CrateOwner, CrateOwnerAction, Owner, OwnerAction, OwnerKind};
+pub use self::queued_email::{QueuedEmail, MAX_SEND_ATTEMPTS};
+pub use self::rights::{Rights, RightsResolver};
+pub use self::rights_elevation::{NewRightsElevation, RightsElevation};
 pub use self::team::{NewTeam, Team};
-pub use self::token::ApiToken;
-pub use self::user::{NewUser, User};
+pub use self::token::{ApiToken, ApiTokenEvent, CreatedApiToken, NewApiToken, ENDPOINT_SCOPES};
+pub use self::user::{CreateUserOptions, NewUser, User};
+pub use self::user_ban::{UserBan, UserBanEvent};
 pub use self::version::{NewVersion, Version};
 
 pub mod helpers;
@@ -23,11 +27,15 @@ pub mod dependency;
 mod download;
 mod email;
 mod follow;
+mod github_actions_binding;
 mod keyword;
 pub mod krate;
 mod owner;
+mod queued_email;
 mod rights;
+mod rights_elevation;
 mod team;
 mod token;
 mod user;
+mod user_ban;
 mod version;
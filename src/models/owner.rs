@@ -1,12 +1,20 @@
+use chrono::NaiveDateTime;
+use diesel;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
 use diesel::prelude::*;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Text;
+use std::collections::HashMap;
+use std::io::Write;
 
 use app::App;
 use github;
 use util::{human, CargoResult};
 
 use models::{Crate, Team, User};
-use schema::{crate_owners, users};
-use views::EncodableOwner;
+use schema::{crate_owner_actions, crate_owners, teams, users};
+use views::{EncodableOwner, EncodableOwnerAction};
 
 #[derive(Insertable, Associations, Identifiable, Debug, Clone, Copy)]
 #[belongs_to(Crate)]
@@ -21,6 +29,195 @@ pub struct CrateOwner {
     pub owner_kind: i32,
 }
 
+impl CrateOwner {
+    /// Makes the owner an owner of the crate. Owners who were removed before get their old row
+    /// back rather than a new one. The change is logged in `crate_owner_actions`.
+    pub fn add(&self, conn: &PgConnection) -> QueryResult<()> {
+        conn.transaction(|| {
+            diesel::insert_into(crate_owners::table)
+                .values(self)
+                .on_conflict(crate_owners::table.primary_key())
+                .do_update()
+                .set((
+                    crate_owners::deleted.eq(false),
+                    crate_owners::deleted_at.eq(None::<NaiveDateTime>),
+                ))
+                .execute(conn)?;
+            CrateOwnerAction::record(
+                conn,
+                self.crate_id,
+                self.owner_id,
+                self.owner_kind,
+                OwnerAction::Add,
+                Some(self.created_by),
+            )
+        })
+    }
+
+    /// Removes the owner from the crate, returning whether they were an owner. The row is kept,
+    /// marked as deleted along with the time of the removal, and the change is logged in
+    /// `crate_owner_actions`.
+    pub fn remove(
+        conn: &PgConnection,
+        crate_id: i32,
+        owner: &Owner,
+        removed_by: i32,
+    ) -> QueryResult<bool> {
+        use diesel::dsl::now;
+
+        conn.transaction(|| {
+            let target = crate_owners::table
+                .find((crate_id, owner.id(), owner.kind() as i32))
+                .filter(crate_owners::deleted.eq(false));
+            let removed = diesel::update(target)
+                .set((
+                    crate_owners::deleted.eq(true),
+                    crate_owners::deleted_at.eq(now.nullable()),
+                ))
+                .execute(conn)?;
+            if removed > 0 {
+                CrateOwnerAction::record(
+                    conn,
+                    crate_id,
+                    owner.id(),
+                    owner.kind(),
+                    OwnerAction::Remove,
+                    Some(removed_by),
+                )?;
+            }
+            Ok(removed > 0)
+        })
+    }
+}
+
+/// What happened to the owner in a `CrateOwnerAction`. Stored as `add` or `remove`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, FromSqlRow, AsExpression)]
+#[serde(rename_all = "lowercase")]
+#[sql_type = "Text"]
+pub enum OwnerAction {
+    Add,
+    Remove,
+}
+
+impl OwnerAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OwnerAction::Add => "add",
+            OwnerAction::Remove => "remove",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<OwnerAction> {
+        match s {
+            "add" => Some(OwnerAction::Add),
+            "remove" => Some(OwnerAction::Remove),
+            _ => None,
+        }
+    }
+}
+
+impl FromSql<Text, Pg> for OwnerAction {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let action = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        OwnerAction::parse(&action)
+            .ok_or_else(|| format!("unknown owner action: {}", action).into())
+    }
+}
+
+impl ToSql<Text, Pg> for OwnerAction {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        <str as ToSql<Text, Pg>>::to_sql(self.as_str(), out)
+    }
+}
+
+/// The model representing a row in the `crate_owner_actions` database table.
+#[derive(Clone, Debug, PartialEq, Eq, Identifiable, Queryable)]
+pub struct CrateOwnerAction {
+    pub id: i32,
+    pub crate_id: i32,
+    pub owner_id: i32,
+    pub owner_kind: i32,
+    pub action: OwnerAction,
+    pub performed_by: Option<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+impl CrateOwnerAction {
+    /// Encodes the actions, each paired with the name of its crate, looking up
+    /// the logins of the owners and of who made the changes all at once.
+    pub fn encodable_many(
+        conn: &PgConnection,
+        actions: Vec<(CrateOwnerAction, String)>,
+    ) -> QueryResult<Vec<EncodableOwnerAction>> {
+        use diesel::dsl::any;
+        use std::collections::HashMap;
+
+        let mut user_ids = Vec::new();
+        let mut team_ids = Vec::new();
+        for &(ref action, _) in &actions {
+            if action.owner_kind == OwnerKind::Team as i32 {
+                team_ids.push(action.owner_id);
+            } else {
+                user_ids.push(action.owner_id);
+            }
+            user_ids.extend(action.performed_by);
+        }
+        let user_logins = users::table
+            .filter(users::id.eq(any(user_ids)))
+            .select((users::id, users::gh_login))
+            .load::<(i32, String)>(conn)?
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let team_logins = teams::table
+            .filter(teams::id.eq(any(team_ids)))
+            .select((teams::id, teams::login))
+            .load::<(i32, String)>(conn)?
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+
+        Ok(actions
+            .into_iter()
+            .map(|(action, crate_name)| {
+                let (owner, owner_kind) = if action.owner_kind == OwnerKind::Team as i32 {
+                    (team_logins.get(&action.owner_id), "team")
+                } else {
+                    (user_logins.get(&action.owner_id), "user")
+                };
+                EncodableOwnerAction {
+                    crate_name,
+                    owner: owner.cloned(),
+                    owner_kind: owner_kind.to_string(),
+                    performed_by: action
+                        .performed_by
+                        .and_then(|id| user_logins.get(&id).cloned()),
+                    action: action.action,
+                    created_at: action.created_at,
+                }
+            })
+            .collect())
+    }
+
+    fn record(
+        conn: &PgConnection,
+        crate_id: i32,
+        owner_id: i32,
+        owner_kind: i32,
+        action: OwnerAction,
+        performed_by: Option<i32>,
+    ) -> QueryResult<()> {
+        diesel::insert_into(crate_owner_actions::table)
+            .values((
+                crate_owner_actions::crate_id.eq(crate_id),
+                crate_owner_actions::owner_id.eq(owner_id),
+                crate_owner_actions::owner_kind.eq(owner_kind),
+                crate_owner_actions::action.eq(action),
+                crate_owner_actions::performed_by.eq(performed_by),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 #[repr(u32)]
 pub enum OwnerKind {
@@ -40,8 +237,8 @@ impl Owner {
     /// up-to-date GitHub ID. Fails out if the user isn't found in the
     /// database, the team isn't found on GitHub, or if the user isn't a member
     /// of the team on GitHub.
-    /// May be a user's GH login or a full team name. This is case
-    /// sensitive.
+    /// May be a user's GH login, matched case-insensitively like GitHub does,
+    /// or a full team name, which is case sensitive.
     pub fn find_or_create_by_login(
         app: &App,
         conn: &PgConnection,
@@ -53,14 +250,120 @@ impl Owner {
                 app, conn, name, req_user,
             )?))
         } else {
-            users::table
-                .filter(users::gh_login.eq(name))
-                .first(conn)
+            User::find_by_login(conn, name)
+                .optional()?
                 .map(Owner::User)
-                .map_err(|_| human(&format_args!("could not find user with login `{}`", name)))
+                .ok_or_else(|| human(&format_args!("could not find user with login `{}`", name)))
         }
     }
 
+    /// Loads the current owners of the crate, both users and teams, leaving
+    /// out removed ones. Users come before teams.
+    pub fn all_owning(krate: &Crate, conn: &PgConnection) -> CargoResult<Vec<Owner>> {
+        let base_query = CrateOwner::belonging_to(krate).filter(crate_owners::deleted.eq(false));
+        let users = base_query
+            .inner_join(users::table)
+            .select(users::all_columns)
+            .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+            .load(conn)?
+            .into_iter()
+            .map(Owner::User);
+        let teams = base_query
+            .inner_join(teams::table)
+            .select(teams::all_columns)
+            .filter(crate_owners::owner_kind.eq(OwnerKind::Team as i32))
+            .load(conn)?
+            .into_iter()
+            .map(Owner::Team);
+
+        Ok(users.chain(teams).collect())
+    }
+
+    /// Loads the owners of all the given crates at once, grouped by the id of
+    /// the crate they own. Every given crate gets an entry, even one without
+    /// owners, and like `Crate::owners`, users come before teams.
+    pub fn owning_many(
+        crate_ids: &[i32],
+        conn: &PgConnection,
+    ) -> CargoResult<HashMap<i32, Vec<Owner>>> {
+        use diesel::dsl::any;
+
+        let base_query = crate_owners::table
+            .filter(crate_owners::crate_id.eq(any(crate_ids)))
+            .filter(crate_owners::deleted.eq(false));
+        let users = base_query
+            .inner_join(users::table)
+            .select((crate_owners::crate_id, users::all_columns))
+            .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+            .load::<(i32, User)>(conn)?
+            .into_iter()
+            .map(|(crate_id, user)| (crate_id, Owner::User(user)));
+        let teams = base_query
+            .inner_join(teams::table)
+            .select((crate_owners::crate_id, teams::all_columns))
+            .filter(crate_owners::owner_kind.eq(OwnerKind::Team as i32))
+            .load::<(i32, Team)>(conn)?
+            .into_iter()
+            .map(|(crate_id, team)| (crate_id, Owner::Team(team)));
+
+        let mut owners = crate_ids
+            .iter()
+            .map(|&id| (id, Vec::new()))
+            .collect::<HashMap<_, _>>();
+        for (crate_id, owner) in users.chain(teams) {
+            owners.entry(crate_id).or_insert_with(Vec::new).push(owner);
+        }
+        Ok(owners)
+    }
+
+    /// Works out who owned the crate at the given time by replaying the log
+    /// of ownership changes up to then. Crates whose log doesn't go back that
+    /// far had no owners. Like `Crate::owners`, users come before teams.
+    pub fn owning_at(
+        conn: &PgConnection,
+        crate_id: i32,
+        at: NaiveDateTime,
+    ) -> CargoResult<Vec<Owner>> {
+        use diesel::dsl::any;
+
+        let actions = crate_owner_actions::table
+            .filter(crate_owner_actions::crate_id.eq(crate_id))
+            .filter(crate_owner_actions::created_at.le(at))
+            .order((crate_owner_actions::created_at, crate_owner_actions::id))
+            .load::<CrateOwnerAction>(conn)?;
+
+        let mut owners = Vec::new();
+        for action in actions {
+            let owner = (action.owner_id, action.owner_kind);
+            owners.retain(|&o| o != owner);
+            if action.action == OwnerAction::Add {
+                owners.push(owner);
+            }
+        }
+
+        let ids_of_kind = |kind: OwnerKind| {
+            owners
+                .iter()
+                .filter(|&&(_, k)| k == kind as i32)
+                .map(|&(id, _)| id)
+                .collect::<Vec<_>>()
+        };
+        let users = users::table
+            .filter(users::id.eq(any(ids_of_kind(OwnerKind::User))))
+            .order(users::id)
+            .load::<User>(conn)?
+            .into_iter()
+            .map(Owner::User);
+        let teams = teams::table
+            .filter(teams::id.eq(any(ids_of_kind(OwnerKind::Team))))
+            .order(teams::id)
+            .load::<Team>(conn)?
+            .into_iter()
+            .map(Owner::Team);
+
+        Ok(users.chain(teams).collect())
+    }
+
     pub fn kind(&self) -> i32 {
         match *self {
             Owner::User(_) => OwnerKind::User as i32,
@@ -99,6 +402,7 @@ impl Owner {
                     url: Some(url),
                     name,
                     kind: String::from("user"),
+                    verified_email: None,
                 }
             }
             Owner::Team(Team {
@@ -116,6 +420,7 @@ impl Owner {
                     avatar,
                     name,
                     kind: String::from("team"),
+                    verified_email: None,
                 }
             }
         }
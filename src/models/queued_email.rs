@@ -0,0 +1,92 @@
+use chrono::NaiveDateTime;
+use diesel;
+use diesel::prelude::*;
+
+use email::{EmailSender, Message};
+use schema::queued_emails;
+
+/// How many queued emails `QueuedEmail::send_queued` sends at a time
+const BATCH_SIZE: i64 = 100;
+
+/// How many times sending a queued email is tried before giving up on it. Emails that were given
+/// up on stay in the table, so that they can be looked into.
+pub const MAX_SEND_ATTEMPTS: i32 = 10;
+
+/// An email waiting in the `queued_emails` table to be sent by `send-queued-emails`.
+#[derive(Debug, Clone, Queryable, QueryableByName, Identifiable)]
+#[table_name = "queued_emails"]
+pub struct QueuedEmail {
+    pub id: i32,
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+    pub created_at: NaiveDateTime,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+}
+
+impl QueuedEmail {
+    /// Queues an email to be sent. It can't be sent before the transaction queueing it
+    /// commits, so emails about changes that get rolled back are never sent.
+    pub fn enqueue(conn: &PgConnection, message: &Message) -> QueryResult<()> {
+        diesel::insert_into(queued_emails::table)
+            .values((
+                queued_emails::recipient.eq(&message.recipient),
+                queued_emails::subject.eq(&message.subject),
+                queued_emails::body.eq(&message.body),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// Sends a batch of queued emails with `sender`, removing the ones that were sent and
+    /// returning how many that was. Emails that couldn't be sent stay queued with the error, to be
+    /// tried again after the ones that haven't failed yet, until they have been tried
+    /// `MAX_SEND_ATTEMPTS` times. Emails another worker is sending are skipped.
+    pub fn send_queued(conn: &PgConnection, sender: &dyn EmailSender) -> QueryResult<usize> {
+        use diesel::sql_query;
+        use diesel::sql_types::{BigInt, Integer};
+
+        conn.transaction(|| {
+            let queued = sql_query(include_str!("queued_email_batch.sql"))
+                .bind::<Integer, _>(MAX_SEND_ATTEMPTS)
+                .bind::<BigInt, _>(BATCH_SIZE)
+                .load::<QueuedEmail>(conn)?;
+
+            let mut sent = 0;
+            for email in queued {
+                match sender.send(&email.message()) {
+                    Ok(()) => {
+                        diesel::delete(&email).execute(conn)?;
+                        sent += 1;
+                    }
+                    Err(e) => {
+                        diesel::update(&email)
+                            .set((
+                                queued_emails::attempts.eq(queued_emails::attempts + 1),
+                                queued_emails::last_error.eq(e.to_string()),
+                            ))
+                            .execute(conn)?;
+                    }
+                }
+            }
+            Ok(sent)
+        })
+    }
+
+    /// Counts the queued emails that were given up on after `MAX_SEND_ATTEMPTS` tries.
+    pub fn given_up_count(conn: &PgConnection) -> QueryResult<i64> {
+        queued_emails::table
+            .filter(queued_emails::attempts.ge(MAX_SEND_ATTEMPTS))
+            .count()
+            .get_result(conn)
+    }
+
+    pub fn message(&self) -> Message {
+        Message {
+            recipient: self.recipient.clone(),
+            subject: self.subject.clone(),
+            body: self.body.clone(),
+        }
+    }
+}
@@ -0,0 +1,8 @@
+-- Diesel 1.3 can't express SKIP LOCKED, which lets several workers send
+-- queued emails at once without sending any of them twice.
+SELECT *
+FROM queued_emails
+WHERE attempts < $1
+ORDER BY attempts, id
+LIMIT $2
+FOR UPDATE SKIP LOCKED
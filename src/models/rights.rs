@@ -1,8 +1,190 @@
+use std::collections::HashMap;
+
+use app::App;
+use models::{Owner, Team, User};
+use util::CargoResult;
+
 /// Access rights to the crate (publishing and ownership management)
 /// NOTE: The order of these variants matters!
-#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Rights {
     None,
     Publish,
     Full,
 }
+
+/// Works out users' rights over crates for the duration of a request.
+///
+/// Whether a user is a member of a team is only asked once per resolver, so
+/// a team owning several of the crates a request deals with costs a single
+/// lookup.
+#[allow(missing_debug_implementations)]
+pub struct RightsResolver<'a> {
+    check_membership: Box<dyn FnMut(&Team, &User) -> CargoResult<bool> + 'a>,
+    /// Keyed by team id and user id
+    memberships: HashMap<(i32, i32), bool>,
+}
+
+impl<'a> RightsResolver<'a> {
+    pub fn new(app: &'a App) -> Self {
+        Self::with_membership_check(move |team, user| team.contains_user(app, user))
+    }
+
+    fn with_membership_check<F>(check_membership: F) -> Self
+    where
+        F: FnMut(&Team, &User) -> CargoResult<bool> + 'a,
+    {
+        RightsResolver {
+            check_membership: Box::new(check_membership),
+            memberships: HashMap::new(),
+        }
+    }
+
+    /// Whether `user` is a member of `team`, asking GitHub only the first time.
+    pub fn team_contains_user(&mut self, team: &Team, user: &User) -> CargoResult<bool> {
+        let key = (team.id, user.id);
+        if let Some(&is_member) = self.memberships.get(&key) {
+            return Ok(is_member);
+        }
+        let is_member = (self.check_membership)(team, user)?;
+        self.memberships.insert(key, is_member);
+        Ok(is_member)
+    }
+
+    /// Like `User::rights`.
+    pub fn rights(&mut self, user: &User, owners: &[Owner]) -> CargoResult<Rights> {
+        self.rights_up_to(user, owners, Rights::Full)
+    }
+
+    /// Like `User::rights_up_to`.
+    pub fn rights_up_to(
+        &mut self,
+        user: &User,
+        owners: &[Owner],
+        required: Rights,
+    ) -> CargoResult<Rights> {
+        let mut best = Rights::None;
+        for owner in owners {
+            if best >= required {
+                break;
+            }
+            match *owner {
+                Owner::User(ref other_user) => {
+                    if other_user.id == user.id {
+                        return Ok(Rights::Full);
+                    }
+                }
+                // Being a member of another team can't give more than `Publish`
+                Owner::Team(ref team) => {
+                    if best < Rights::Publish && self.team_contains_user(team, user)? {
+                        best = Rights::Publish;
+                    }
+                }
+            }
+        }
+        Ok(best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use std::cell::RefCell;
+
+    fn user(id: i32) -> User {
+        User {
+            id,
+            email: None,
+            gh_access_token: None,
+            gh_login: format!("user{}", id),
+            name: None,
+            gh_avatar: None,
+            gh_id: id,
+            last_login_at: None,
+            created_at: NaiveDate::from_ymd(2018, 12, 12).and_hms(0, 0, 0),
+            suspended_until: None,
+            display_name: None,
+        }
+    }
+
+    fn team(id: i32) -> Owner {
+        Owner::Team(Team {
+            id,
+            login: format!("github:org:team{}", id),
+            github_id: id,
+            name: None,
+            avatar: None,
+        })
+    }
+
+    #[test]
+    fn rights_up_to_stops_checking_teams_once_the_required_rights_are_met() {
+        let me = user(1);
+        let owners = [team(1), team(2), team(3)];
+
+        let checked = RefCell::new(Vec::new());
+        let mut resolver = RightsResolver::with_membership_check(|team, _| {
+            assert!(
+                team.id < 3,
+                "team {} checked after rights were met",
+                team.id
+            );
+            checked.borrow_mut().push(team.id);
+            Ok(team.id == 2)
+        });
+        let rights = resolver
+            .rights_up_to(&me, &owners, Rights::Publish)
+            .unwrap();
+        assert_eq!(rights, Rights::Publish);
+        assert_eq!(*checked.borrow(), vec![1, 2]);
+    }
+
+    #[test]
+    fn rights_still_finds_full_rights_after_a_team() {
+        let me = user(1);
+        let owners = [team(1), team(2), Owner::User(user(2)), Owner::User(user(1))];
+
+        let mut resolver = RightsResolver::with_membership_check(|team, _| {
+            assert_eq!(team.id, 1, "team {} checked after publish rights", team.id);
+            Ok(true)
+        });
+        assert_eq!(resolver.rights(&me, &owners).unwrap(), Rights::Full);
+    }
+
+    #[test]
+    fn rights_checks_every_team_of_a_non_member() {
+        let me = user(1);
+        let owners = [Owner::User(user(2)), team(1), team(2)];
+
+        let checked = RefCell::new(0);
+        let mut resolver = RightsResolver::with_membership_check(|_, _| {
+            *checked.borrow_mut() += 1;
+            Ok(false)
+        });
+        let rights = resolver
+            .rights_up_to(&me, &owners, Rights::Publish)
+            .unwrap();
+        assert_eq!(rights, Rights::None);
+        assert_eq!(*checked.borrow(), 2);
+    }
+
+    #[test]
+    fn repeated_team_checks_are_answered_once_per_user() {
+        let (me, other) = (user(1), user(2));
+        let first_crate = [team(1), team(2)];
+        let second_crate = [team(2), team(1)];
+
+        let checked = RefCell::new(Vec::new());
+        let mut resolver = RightsResolver::with_membership_check(|team, user| {
+            checked.borrow_mut().push((team.id, user.id));
+            Ok(false)
+        });
+        for owners in &[&first_crate[..], &second_crate[..]] {
+            assert_eq!(resolver.rights(&me, owners).unwrap(), Rights::None);
+            assert_eq!(resolver.rights(&other, owners).unwrap(), Rights::None);
+        }
+        assert_eq!(*checked.borrow(), vec![(1, 1), (2, 1), (1, 2), (2, 2)]);
+    }
+}
@@ -0,0 +1,76 @@
+use chrono::NaiveDateTime;
+use diesel;
+use diesel::prelude::*;
+
+use models::{Crate, User};
+use schema::rights_elevations;
+use util::rfc3339;
+
+/// The model representing a row in the `rights_elevations` database table.
+///
+/// While it hasn't expired, an elevation gives a member of one of the crate's
+/// owning teams full rights over it.
+#[derive(Clone, Debug, PartialEq, Eq, Identifiable, Queryable, Associations, Serialize)]
+#[belongs_to(Crate)]
+#[belongs_to(User)]
+pub struct RightsElevation {
+    pub id: i32,
+    #[serde(skip)]
+    pub crate_id: i32,
+    #[serde(skip)]
+    pub user_id: i32,
+    pub justification: String,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "rfc3339")]
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Clone, Copy, Debug)]
+#[table_name = "rights_elevations"]
+pub struct NewRightsElevation<'a> {
+    pub crate_id: i32,
+    pub user_id: i32,
+    pub justification: &'a str,
+    pub expires_at: NaiveDateTime,
+}
+
+impl<'a> NewRightsElevation<'a> {
+    pub fn create(&self, conn: &PgConnection) -> QueryResult<RightsElevation> {
+        diesel::insert_into(rights_elevations::table)
+            .values(self)
+            .get_result(conn)
+    }
+}
+
+impl RightsElevation {
+    /// Whether the user currently has an elevation over the crate that hasn't expired.
+    pub fn is_active(conn: &PgConnection, crate_id: i32, user_id: i32) -> QueryResult<bool> {
+        use diesel::dsl::{exists, now};
+
+        diesel::select(exists(
+            rights_elevations::table
+                .filter(rights_elevations::crate_id.eq(crate_id))
+                .filter(rights_elevations::user_id.eq(user_id))
+                .filter(rights_elevations::expires_at.gt(now)),
+        ))
+        .get_result(conn)
+    }
+
+    /// Which of the crates the user currently has an unexpired elevation over.
+    pub fn active_crate_ids(
+        conn: &PgConnection,
+        user_id: i32,
+        crate_ids: &[i32],
+    ) -> QueryResult<Vec<i32>> {
+        use diesel::dsl::{any, now};
+
+        rights_elevations::table
+            .filter(rights_elevations::user_id.eq(user_id))
+            .filter(rights_elevations::crate_id.eq(any(crate_ids)))
+            .filter(rights_elevations::expires_at.gt(now))
+            .select(rights_elevations::crate_id)
+            .distinct()
+            .load(conn)
+    }
+}
@@ -141,7 +141,7 @@ impl Team {
         // FIXME: we just set per_page=100 and don't bother chasing pagination
         // links. A hundred teams should be enough for any org, right?
         let url = format!("/orgs/{}/teams?per_page=100", org_name);
-        let token = github::token(req_user.gh_access_token.clone());
+        let token = req_user.github_token()?;
         let teams = github::github::<Vec<GithubTeam>>(app, &url, &token)?;
 
         let team = teams
@@ -175,10 +175,54 @@ impl Team {
     /// Note that we're assuming that the given user is the one interested in
     /// the answer. If this is not the case, then we could accidentally leak
     /// private membership information here.
+    ///
+    /// Answers are remembered for `Config::gh_membership_cache_ttl`.
+    ///
+    /// A user who logged out has no GitHub token to ask with, so they aren't
+    /// counted as a member of any team until they log in again. API tokens
+    /// keep working for the crates they own directly.
     pub fn contains_user(&self, app: &App, user: &User) -> CargoResult<bool> {
         team_with_gh_id_contains_user(app, self.github_id, user)
     }
 
+    /// Forgets any remembered answer to whether `user` is a member of this
+    /// team, so that the next check asks GitHub again.
+    pub fn forget_membership(&self, app: &App, user: &User) {
+        app.team_memberships.remove(&(self.github_id, user.id));
+    }
+
+    /// Forgets every remembered answer about this team's members. Called when
+    /// the team gains or loses ownership of a crate, so that those changes are
+    /// always checked against GitHub's current view of the team.
+    pub fn forget_memberships(&self, app: &App) {
+        let github_id = self.github_id;
+        app.team_memberships.retain(|&(id, _)| id != github_id);
+    }
+
+    /// Phones home to Github to list the GitHub ids of this team's members,
+    /// following GitHub's pagination for teams of more than 100 members. Ids
+    /// are used rather than logins, which can be changed and then taken by
+    /// someone else. The request is made on behalf of `user`, so the list is
+    /// only as complete as their view of the team; a team they can't see has
+    /// no members.
+    pub fn member_gh_ids(&self, app: &App, user: &User) -> CargoResult<Vec<i32>> {
+        // GET teams/:team_id/members
+
+        #[derive(Deserialize)]
+        struct Member {
+            id: i32,
+        }
+
+        let url = format!("/teams/{}/members?per_page=100", self.github_id);
+        let token = user.github_token()?;
+        let members = match github::github_all_pages::<Member>(app, &url, &token) {
+            Err(ref e) if e.is::<NotFound>() => return Ok(Vec::new()),
+            x => x?,
+        };
+
+        Ok(members.into_iter().map(|m| m.id).collect())
+    }
+
     pub fn owning(krate: &Crate, conn: &PgConnection) -> CargoResult<Vec<Owner>> {
         let base_query = CrateOwner::belonging_to(krate).filter(crate_owners::deleted.eq(false));
         let teams = base_query
@@ -221,15 +265,27 @@ fn team_with_gh_id_contains_user(app: &App, github_id: i32, user: &User) -> Carg
         state: String,
     }
 
+    // Checked before any remembered answer, which may predate logging out
+    if user.gh_access_token.is_none() {
+        return Ok(false);
+    }
+
+    let cache_key = (github_id, user.id);
+    if let Some(is_member) = app.team_memberships.get(&cache_key) {
+        return Ok(is_member);
+    }
+
     let url = format!("/teams/{}/memberships/{}", &github_id, &user.gh_login);
-    let token = github::token(user.gh_access_token.clone());
-    let membership = match github::github::<Membership>(app, &url, &token) {
+    let token = user.github_token()?;
+    let is_member = match github::github::<Membership>(app, &url, &token) {
         // Officially how `false` is returned
-        Err(ref e) if e.is::<NotFound>() => return Ok(false),
-        x => x?,
+        Err(ref e) if e.is::<NotFound>() => false,
+        // There is also `state: pending` for which we could possibly give
+        // some feedback, but it's not obvious how that should work.
+        Ok(membership) => membership.state == "active",
+        Err(e) => return Err(e),
     };
 
-    // There is also `state: pending` for which we could possibly give
-    // some feedback, but it's not obvious how that should work.
-    Ok(membership.state == "active")
+    app.team_memberships.insert(cache_key, is_member);
+    Ok(is_member)
 }
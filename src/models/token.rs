@@ -1,12 +1,33 @@
-use chrono::NaiveDateTime;
+use std::time::Duration;
+
+use chrono::{NaiveDateTime, Utc};
 use diesel;
 use diesel::prelude::*;
+use openssl::sha::sha256;
+use rand::{thread_rng, Rng};
 
-use models::User;
-use schema::api_tokens;
+use models::{Crate, User};
+use schema::{api_token_events, api_token_usages, api_tokens, users};
+use util::errors::is_read_only;
 use util::rfc3339;
 use views::EncodableApiTokenWithToken;
 
+/// The kinds of request a token can be limited to. Apart from `manage-tokens`, these use the same
+/// names as cargo's token scopes.
+pub const ENDPOINT_SCOPES: &[&str] = &[
+    "publish-new",
+    "publish-update",
+    "yank",
+    "change-owners",
+    "manage-tokens",
+];
+
+/// What the `publish` scope stands for when a token is created.
+const PUBLISH_SCOPES: &[&str] = &["publish-new", "publish-update"];
+
+/// The endpoint scopes that don't act on any crate, so take no crate scope.
+const CRATELESS_SCOPES: &[&str] = &["manage-tokens"];
+
 /// The model representing a row in the `api_tokens` database table.
 #[derive(Clone, Debug, PartialEq, Eq, Identifiable, Queryable, Associations, Serialize)]
 #[belongs_to(User)]
@@ -14,8 +35,6 @@ pub struct ApiToken {
     pub id: i32,
     #[serde(skip)]
     pub user_id: i32,
-    #[serde(skip)]
-    pub token: String,
     pub name: String,
     #[serde(with = "rfc3339")]
     pub created_at: NaiveDateTime,
@@ -23,29 +42,465 @@ pub struct ApiToken {
     pub last_used_at: Option<NaiveDateTime>,
     #[serde(skip)]
     pub revoked: bool,
+    pub crate_scopes: Option<Vec<String>>,
+    #[serde(with = "rfc3339::option")]
+    pub expires_at: Option<NaiveDateTime>,
+    pub external_ref: Option<String>,
+    pub endpoint_scopes: Option<Vec<String>>,
+    pub last_used_ip: Option<String>,
+    pub use_count: i64,
+    #[serde(skip)]
+    pub token_hash: Vec<u8>,
+    pub attribution_name: Option<String>,
+    #[serde(skip)]
+    pub exchanged: bool,
+}
+
+/// A token that was just created, along with its value. Only a hash of the value is stored, so
+/// this is the only time the value is known.
+#[derive(Debug)]
+pub struct CreatedApiToken {
+    pub model: ApiToken,
+    pub plaintext: String,
+}
+
+impl CreatedApiToken {
+    /// Converts this token into an `EncodableApiTokenWithToken` including the token's value for
+    /// JSON serialization. This should only be used when responding to the request that created
+    /// the token.
+    pub fn encodable_with_token(self) -> EncodableApiTokenWithToken {
+        let token = self.model;
+        EncodableApiTokenWithToken {
+            id: token.id,
+            name: token.name,
+            token: self.plaintext,
+            revoked: token.revoked,
+            created_at: token.created_at,
+            last_used_at: token.last_used_at,
+            crate_scopes: token.crate_scopes,
+            expires_at: token.expires_at,
+            external_ref: token.external_ref,
+            endpoint_scopes: token.endpoint_scopes,
+            attribution_name: token.attribution_name,
+        }
+    }
+}
+
+/// The properties of a token that is about to be created.
+///
+/// Anything left as `None` gets the same defaults as `ApiToken::insert`.
+#[derive(Debug, Default, Clone)]
+pub struct NewApiToken<'a> {
+    pub name: &'a str,
+    /// The crates this token may be used for, or `None` for all of the user's crates.
+    pub crate_scopes: Option<Vec<String>>,
+    pub expires_at: Option<NaiveDateTime>,
+    /// A reference to where the token is kept in some external system, such as a secret manager.
+    pub external_ref: Option<String>,
+    /// The kinds of request this token may be used for, or `None` for all of them.
+    pub endpoint_scopes: Option<Vec<String>>,
+    /// The name that versions published with this token are attributed to, such as a service
+    /// account's, or `None` to attribute them to the user.
+    pub attribution_name: Option<String>,
+    /// Whether the token is handed out by `POST /tokens/exchange`, so doesn't count against the
+    /// user's token limits.
+    pub exchanged: bool,
 }
 
 impl ApiToken {
     /// Generates a new named API token for a user
-    pub fn insert(conn: &PgConnection, user_id: i32, name: &str) -> QueryResult<ApiToken> {
-        diesel::insert_into(api_tokens::table)
-            .values((api_tokens::user_id.eq(user_id), api_tokens::name.eq(name)))
-            .get_result::<ApiToken>(conn)
+    pub fn insert(conn: &PgConnection, user_id: i32, name: &str) -> QueryResult<CreatedApiToken> {
+        Self::insert_full(
+            conn,
+            user_id,
+            &NewApiToken {
+                name,
+                ..NewApiToken::default()
+            },
+        )
     }
 
-    /// Converts this `ApiToken` model into an `EncodableApiToken` including
-    /// the actual token value for JSON serialization.  This should only be
-    /// used when initially creating a new token to minimize the chance of
-    /// token leaks.
-    pub fn encodable_with_token(self) -> EncodableApiTokenWithToken {
-        EncodableApiTokenWithToken {
-            id: self.id,
-            name: self.name,
-            token: self.token,
-            revoked: self.revoked,
-            created_at: self.created_at,
-            last_used_at: self.last_used_at,
+    /// Generates a new API token for a user with the given name, scopes and expiry
+    pub fn insert_full(
+        conn: &PgConnection,
+        user_id: i32,
+        new_token: &NewApiToken<'_>,
+    ) -> QueryResult<CreatedApiToken> {
+        let plaintext = thread_rng().gen_ascii_chars().take(32).collect::<String>();
+        conn.transaction(|| {
+            let token = diesel::insert_into(api_tokens::table)
+                .values((
+                    api_tokens::user_id.eq(user_id),
+                    api_tokens::token_hash.eq(Self::hash(&plaintext)),
+                    api_tokens::name.eq(new_token.name),
+                    api_tokens::crate_scopes.eq(&new_token.crate_scopes),
+                    api_tokens::expires_at.eq(new_token.expires_at),
+                    api_tokens::external_ref.eq(&new_token.external_ref),
+                    api_tokens::endpoint_scopes.eq(&new_token.endpoint_scopes),
+                    api_tokens::attribution_name.eq(&new_token.attribution_name),
+                    api_tokens::exchanged.eq(new_token.exchanged),
+                ))
+                .get_result::<ApiToken>(conn)?;
+            ApiTokenEvent::record(conn, &[token.id], "created", None)?;
+            Ok(CreatedApiToken {
+                model: token,
+                plaintext: plaintext.clone(),
+            })
+        })
+    }
+
+    /// Whether this token is past its expiry date, and so may only be used for reads during
+    /// `Config::expired_token_grace_period`.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .map_or(false, |expires_at| expires_at <= Utc::now().naive_utc())
+    }
+
+    /// Counts the user's tokens that take up one of the `Config::max_tokens_per_user` they may
+    /// have. Revoked and expired tokens don't, and neither do exchanged ones.
+    pub fn count_towards_max(conn: &PgConnection, user: &User) -> QueryResult<i64> {
+        use diesel::dsl::now;
+
+        ApiToken::belonging_to(user)
+            .filter(api_tokens::revoked.eq(false))
+            .filter(api_tokens::exchanged.eq(false))
+            .filter(
+                api_tokens::expires_at
+                    .is_null()
+                    .or(api_tokens::expires_at.gt(now.nullable())),
+            )
+            .count()
+            .get_result(conn)
+    }
+
+    /// The hash of a token's value, which is what's stored in place of the value itself
+    pub fn hash(plaintext: &str) -> Vec<u8> {
+        sha256(plaintext.as_bytes()).to_vec()
+    }
+
+    /// Looks up a token that hasn't been revoked or expired by its value. Tokens that expired
+    /// less than `expiry_grace` ago are still found, it's up to the caller to limit what they can
+    /// do. Tokens whose user no longer exists are never found. Finding a token doesn't count as
+    /// using it, see `record_used`.
+    pub fn find_active(
+        conn: &PgConnection,
+        value: &str,
+        expiry_grace: Duration,
+    ) -> QueryResult<ApiToken> {
+        use diesel::dsl::{now, IntervalDsl};
+
+        let hash = Self::hash(value);
+        let expired_before = now - (expiry_grace.as_secs() as i64).seconds();
+        api_tokens::table
+            .filter(api_tokens::token_hash.eq(&hash[..]))
+            .filter(api_tokens::revoked.eq(false))
+            .filter(
+                api_tokens::expires_at
+                    .is_null()
+                    .or(api_tokens::expires_at.gt(expired_before.nullable())),
+            )
+            .filter(api_tokens::user_id.eq_any(users::table.select(users::id)))
+            .first(conn)
+    }
+
+    /// Records that this token was just used, and from which address, and counts the use both
+    /// in total and per hour. The token is checked again as the use is recorded, with the same
+    /// `expiry_grace` as `find_active`, so a token revoked or expired since it was found fails
+    /// with `NotFound`. While the database is read-only the use goes unrecorded, so that tokens
+    /// keep working for reads during maintenance.
+    pub fn record_used(
+        &self,
+        conn: &PgConnection,
+        client_ip: &str,
+        expiry_grace: Duration,
+    ) -> QueryResult<ApiToken> {
+        use diesel::dsl::{now, IntervalDsl};
+
+        let expired_before = now - (expiry_grace.as_secs() as i64).seconds();
+        let updated = conn.transaction(|| {
+            let token = diesel::update(
+                api_tokens::table
+                    .find(self.id)
+                    .filter(api_tokens::revoked.eq(false))
+                    .filter(
+                        api_tokens::expires_at
+                            .is_null()
+                            .or(api_tokens::expires_at.gt(expired_before.nullable())),
+                    ),
+            )
+            .set((
+                api_tokens::last_used_at.eq(now.nullable()),
+                api_tokens::last_used_ip.eq(client_ip),
+                api_tokens::use_count.eq(api_tokens::use_count + 1),
+            ))
+            .get_result::<ApiToken>(conn)?;
+            token.record_use(conn)?;
+            Ok(token)
+        });
+        match updated {
+            Err(ref e) if is_read_only(e) => Ok(self.clone()),
+            updated => updated,
+        }
+    }
+
+    /// Lists the tokens that haven't been revoked but whose user no longer exists, so that they
+    /// can be cleaned up.
+    pub fn find_orphaned(conn: &PgConnection) -> QueryResult<Vec<ApiToken>> {
+        api_tokens::table
+            .left_join(users::table)
+            .filter(users::id.is_null())
+            .filter(api_tokens::revoked.eq(false))
+            .select(api_tokens::all_columns)
+            .order(api_tokens::id)
+            .load(conn)
+    }
+
+    /// Whether a crate scope is either a crate name or a crate name prefix followed by a single
+    /// trailing `*`, such as `acme-*`.
+    pub fn valid_crate_scope(scope: &str) -> bool {
+        let name = if scope.ends_with('*') {
+            &scope[..scope.len() - 1]
+        } else {
+            scope
+        };
+        Crate::valid_name(name)
+    }
+
+    /// Splits a cargo-style scope such as `publish-update:serde` or `yank:acme-*` into the endpoint
+    /// scopes and the crate scope it stands for. The crate scope may also be `*` for every crate,
+    /// which is what a scope without a crate, such as `yank`, means too. `publish` stands for
+    /// both `publish-new` and `publish-update`. Scopes that don't act on crates, such as
+    /// `manage-tokens`, can't be given a crate and have no crate scope. Returns `None` unless
+    /// both halves are valid.
+    pub fn parse_scope(scope: &str) -> Option<(&'static [&'static str], Option<&str>)> {
+        let mut parts = scope.splitn(2, ':');
+        let endpoint = parts.next()?;
+        let crate_scope = parts.next();
+        let endpoints = if endpoint == "publish" {
+            PUBLISH_SCOPES
+        } else {
+            let i = ENDPOINT_SCOPES.iter().position(|&s| s == endpoint)?;
+            &ENDPOINT_SCOPES[i..=i]
+        };
+        if CRATELESS_SCOPES.contains(&endpoint) {
+            return match crate_scope {
+                Some(_) => None,
+                None => Some((endpoints, None)),
+            };
+        }
+        let crate_scope = crate_scope.unwrap_or("*");
+        if crate_scope != "*" && !Self::valid_crate_scope(crate_scope) {
+            return None;
+        }
+        Some((endpoints, Some(crate_scope)))
+    }
+
+    /// Whether this token may be used for the given kind of request, one of `ENDPOINT_SCOPES`.
+    /// Tokens created before endpoint scopes existed have none and may be used for anything.
+    pub fn has_scope(&self, scope: &str) -> bool {
+        match self.endpoint_scopes {
+            Some(ref scopes) => scopes.iter().any(|s| s == scope),
+            None => true,
+        }
+    }
+
+    /// Whether this token may be used for the named crate. Scopes ending in `*` match every
+    /// crate whose name starts with what comes before it. Like crate names themselves, scopes
+    /// ignore case and treat `-` and `_` as the same.
+    pub fn allows_crate(&self, crate_name: &str) -> bool {
+        let scopes = match self.crate_scopes {
+            Some(ref scopes) => scopes,
+            None => return true,
+        };
+        let crate_name = canonical(crate_name);
+        scopes.iter().any(|scope| {
+            if scope.ends_with('*') {
+                crate_name.starts_with(&canonical(&scope[..scope.len() - 1]))
+            } else {
+                crate_name == canonical(scope)
+            }
+        })
+    }
+
+    /// Whether everything the given crate scope allows is already allowed by this token, so
+    /// that a token scoped to it would be no broader than this one.
+    pub fn covers_scope(&self, scope: &str) -> bool {
+        let scopes = match self.crate_scopes {
+            Some(ref scopes) => scopes,
+            None => return true,
+        };
+        if !scope.ends_with('*') {
+            return self.allows_crate(scope);
         }
+        let prefix = canonical(&scope[..scope.len() - 1]);
+        scopes
+            .iter()
+            .filter(|s| s.ends_with('*'))
+            .any(|s| prefix.starts_with(&canonical(&s[..s.len() - 1])))
+    }
+
+    /// Counts one more use of this token in the current hour
+    fn record_use(&self, conn: &PgConnection) -> QueryResult<()> {
+        diesel::insert_into(api_token_usages::table)
+            .values(api_token_usages::api_token_id.eq(self.id))
+            .on_conflict((api_token_usages::api_token_id, api_token_usages::hour))
+            .do_update()
+            .set(api_token_usages::uses.eq(api_token_usages::uses + 1))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// How many times this token was used in each hour since the given time, oldest first.
+    /// Hours in which it wasn't used are left out.
+    pub fn hourly_uses(
+        &self,
+        conn: &PgConnection,
+        since: NaiveDateTime,
+    ) -> QueryResult<Vec<(NaiveDateTime, i32)>> {
+        api_token_usages::table
+            .filter(api_token_usages::api_token_id.eq(self.id))
+            .filter(api_token_usages::hour.ge(since))
+            .select((api_token_usages::hour, api_token_usages::uses))
+            .order(api_token_usages::hour)
+            .load(conn)
+    }
+
+    /// Gives this token a new name
+    pub fn rename(&self, conn: &PgConnection, name: &str) -> QueryResult<ApiToken> {
+        conn.transaction(|| {
+            let token = diesel::update(self)
+                .set(api_tokens::name.eq(name))
+                .get_result::<ApiToken>(conn)?;
+            let detail = format!("renamed from `{}` to `{}`", self.name, name);
+            ApiTokenEvent::record(conn, &[self.id], "renamed", Some(&detail))?;
+            Ok(token)
+        })
+    }
+
+    /// Replaces this token with a new one, revoking this token and recording which token took
+    /// its place
+    pub fn rotate(
+        &self,
+        conn: &PgConnection,
+        new_token: &NewApiToken<'_>,
+    ) -> QueryResult<CreatedApiToken> {
+        conn.transaction(|| {
+            let rotated = Self::insert_full(conn, self.user_id, new_token)?;
+            diesel::update(self)
+                .set(api_tokens::revoked.eq(true))
+                .execute(conn)?;
+            let detail = format!("replaced by token {}", rotated.model.id);
+            ApiTokenEvent::record(conn, &[self.id], "rotated", Some(&detail))?;
+            Ok(rotated)
+        })
+    }
+
+    /// Creates a token combining what the given tokens allow, recording on each of them which
+    /// token they were merged into, and revoking them if asked to
+    pub fn merge(
+        conn: &PgConnection,
+        user_id: i32,
+        originals: &[&ApiToken],
+        new_token: &NewApiToken<'_>,
+        revoke_originals: bool,
+    ) -> QueryResult<CreatedApiToken> {
+        conn.transaction(|| {
+            let merged = Self::insert_full(conn, user_id, new_token)?;
+            let ids = originals.iter().map(|t| t.id).collect::<Vec<_>>();
+            let detail = format!("merged into token {}", merged.model.id);
+            ApiTokenEvent::record(conn, &ids, "merged", Some(&detail))?;
+            if revoke_originals {
+                diesel::update(api_tokens::table.filter(api_tokens::id.eq_any(&ids)))
+                    .set(api_tokens::revoked.eq(true))
+                    .execute(conn)?;
+                let reason = format!("revoked when merged into token {}", merged.model.id);
+                ApiTokenEvent::record(conn, &ids, "revoked", Some(&reason))?;
+            }
+            Ok(merged)
+        })
+    }
+
+    /// Revokes one of a user's tokens, returning whether it was still active
+    pub fn revoke(conn: &PgConnection, user_id: i32, id: i32, reason: &str) -> QueryResult<bool> {
+        conn.transaction(|| {
+            let revoked = diesel::update(api_tokens::table.find(id))
+                .filter(api_tokens::user_id.eq(user_id))
+                .filter(api_tokens::revoked.eq(false))
+                .set(api_tokens::revoked.eq(true))
+                .returning(api_tokens::id)
+                .get_results::<i32>(conn)?;
+            ApiTokenEvent::record(conn, &revoked, "revoked", Some(reason))?;
+            Ok(!revoked.is_empty())
+        })
+    }
+
+    /// Revokes every token belonging to a user, returning how many were revoked
+    pub fn revoke_all(conn: &PgConnection, user_id: i32, reason: &str) -> QueryResult<usize> {
+        conn.transaction(|| {
+            let revoked = diesel::update(api_tokens::table)
+                .filter(api_tokens::user_id.eq(user_id))
+                .filter(api_tokens::revoked.eq(false))
+                .set(api_tokens::revoked.eq(true))
+                .returning(api_tokens::id)
+                .get_results::<i32>(conn)?;
+            ApiTokenEvent::record(conn, &revoked, "revoked", Some(reason))?;
+            Ok(revoked.len())
+        })
+    }
+}
+
+/// Crate names and scopes ignore case and treat `-` and `_` as the same
+fn canonical(name: &str) -> String {
+    name.to_lowercase().replace('-', "_")
+}
+
+/// Something that happened to an API token, such as it being created, renamed or revoked.
+#[derive(Clone, Debug, PartialEq, Eq, Identifiable, Queryable, Associations, Serialize)]
+#[belongs_to(ApiToken)]
+#[table_name = "api_token_events"]
+pub struct ApiTokenEvent {
+    #[serde(skip)]
+    pub id: i32,
+    #[serde(skip)]
+    pub api_token_id: i32,
+    pub kind: String,
+    pub detail: Option<String>,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+}
+
+impl ApiTokenEvent {
+    /// Records that the same thing happened to each of the given tokens
+    pub fn record(
+        conn: &PgConnection,
+        api_token_ids: &[i32],
+        kind: &str,
+        detail: Option<&str>,
+    ) -> QueryResult<()> {
+        if api_token_ids.is_empty() {
+            return Ok(());
+        }
+        let events = api_token_ids
+            .iter()
+            .map(|&id| {
+                (
+                    api_token_events::api_token_id.eq(id),
+                    api_token_events::kind.eq(kind),
+                    api_token_events::detail.eq(detail),
+                )
+            })
+            .collect::<Vec<_>>();
+        diesel::insert_into(api_token_events::table)
+            .values(&events)
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// Lists everything that happened to a token, oldest first
+    pub fn history(conn: &PgConnection, token: &ApiToken) -> QueryResult<Vec<ApiTokenEvent>> {
+        ApiTokenEvent::belonging_to(token)
+            .order((api_token_events::created_at, api_token_events::id))
+            .load(conn)
     }
 }
 
@@ -55,16 +510,112 @@ mod tests {
     use chrono::NaiveDate;
     use serde_json;
 
+    fn token_with_scopes(scopes: &[&str]) -> ApiToken {
+        ApiToken {
+            id: 1,
+            user_id: 1,
+            revoked: false,
+            name: "".to_string(),
+            created_at: NaiveDate::from_ymd(2018, 12, 12).and_hms(0, 0, 0),
+            last_used_at: None,
+            crate_scopes: Some(scopes.iter().map(|s| s.to_string()).collect()),
+            expires_at: None,
+            external_ref: None,
+            endpoint_scopes: None,
+            last_used_ip: None,
+            use_count: 0,
+            token_hash: vec![],
+            attribution_name: None,
+            exchanged: false,
+        }
+    }
+
+    #[test]
+    fn crate_scopes_match_exact_names_and_prefixes() {
+        let token = token_with_scopes(&["acme-*", "serde"]);
+        assert!(token.allows_crate("acme-utils"));
+        assert!(token.allows_crate("Acme_Utils"));
+        assert!(token.allows_crate("serde"));
+        assert!(!token.allows_crate("serde_json"));
+        assert!(!token.allows_crate("other"));
+    }
+
+    #[test]
+    fn narrower_scopes_are_covered() {
+        let token = token_with_scopes(&["acme-*", "serde"]);
+        assert!(token.covers_scope("acme-utils"));
+        assert!(token.covers_scope("acme_utils-*"));
+        assert!(token.covers_scope("Serde"));
+        assert!(!token.covers_scope("ac*"));
+        assert!(!token.covers_scope("serde*"));
+        assert!(!token.covers_scope("other"));
+    }
+
+    #[test]
+    fn cargo_style_scopes_are_split_into_endpoint_and_crate() {
+        let parse = |scope| ApiToken::parse_scope(scope).map(|(e, c)| (e.to_vec(), c));
+        assert_eq!(
+            parse("publish-update:serde"),
+            Some((vec!["publish-update"], Some("serde")))
+        );
+        assert_eq!(parse("yank:acme-*"), Some((vec!["yank"], Some("acme-*"))));
+        assert_eq!(parse("yank"), Some((vec!["yank"], Some("*"))));
+        assert_eq!(
+            parse("change-owners:*"),
+            Some((vec!["change-owners"], Some("*")))
+        );
+        assert_eq!(parse("manage-tokens"), Some((vec!["manage-tokens"], None)));
+        assert_eq!(parse("manage-tokens:serde"), None);
+        assert_eq!(parse("manage-tokens:*"), None);
+        assert_eq!(
+            parse("publish:serde"),
+            Some((vec!["publish-new", "publish-update"], Some("serde")))
+        );
+        assert_eq!(parse("publish-all:serde"), None);
+        assert_eq!(parse("yank:ser*de"), None);
+        assert_eq!(parse("yank:"), None);
+    }
+
+    #[test]
+    fn tokens_without_endpoint_scopes_have_every_scope() {
+        let mut token = token_with_scopes(&[]);
+        assert!(token.has_scope("yank"));
+        assert!(token.has_scope("manage-tokens"));
+
+        token.endpoint_scopes = Some(vec!["publish-update".into(), "yank".into()]);
+        assert!(token.has_scope("yank"));
+        assert!(!token.has_scope("publish-new"));
+        assert!(!token.has_scope("manage-tokens"));
+    }
+
+    #[test]
+    fn crate_scopes_allow_only_a_trailing_wildcard() {
+        assert!(ApiToken::valid_crate_scope("acme"));
+        assert!(ApiToken::valid_crate_scope("acme-*"));
+        assert!(!ApiToken::valid_crate_scope("*"));
+        assert!(!ApiToken::valid_crate_scope("acme-**"));
+        assert!(!ApiToken::valid_crate_scope("*-utils"));
+        assert!(!ApiToken::valid_crate_scope("ac*me"));
+    }
+
     #[test]
     fn api_token_serializes_to_rfc3339() {
         let tok = ApiToken {
             id: 12345,
             user_id: 23456,
-            token: "".to_string(),
             revoked: false,
             name: "".to_string(),
             created_at: NaiveDate::from_ymd(2017, 1, 6).and_hms(14, 23, 11),
             last_used_at: Some(NaiveDate::from_ymd(2017, 1, 6).and_hms(14, 23, 12)),
+            crate_scopes: None,
+            expires_at: None,
+            external_ref: None,
+            endpoint_scopes: None,
+            last_used_ip: None,
+            use_count: 0,
+            token_hash: vec![],
+            attribution_name: None,
+            exchanged: false,
         };
         let json = serde_json::to_string(&tok).unwrap();
         assert!(json
@@ -86,6 +637,11 @@ mod tests {
             revoked: false,
             created_at: NaiveDate::from_ymd(2017, 1, 6).and_hms(14, 23, 11),
             last_used_at: Some(NaiveDate::from_ymd(2017, 1, 6).and_hms(14, 23, 12)),
+            crate_scopes: None,
+            expires_at: None,
+            external_ref: None,
+            endpoint_scopes: None,
+            attribution_name: None,
         };
         let json = serde_json::to_string(&tok).unwrap();
         assert!(json
@@ -1,24 +1,35 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
 use diesel::dsl::now;
 use diesel::prelude::*;
+use oauth2::Token;
 use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 
 use app::App;
-use util::CargoResult;
+use github;
+use util::{forbidden, human, CargoResult};
 
-use models::{Crate, CrateOwner, NewEmail, Owner, OwnerKind, Rights};
-use schema::{crate_owners, emails, users};
-use views::{EncodablePrivateUser, EncodablePublicUser};
+use models::{
+    ApiToken, Crate, CrateOwner, Email, Owner, OwnerKind, Rights, RightsElevation, RightsResolver,
+};
+use schema::{admin_users, banned_users, blocked_users, crate_owners, emails, users};
+use views::{EncodableOwnerRef, EncodablePrivateUser, EncodablePublicUser};
 
 /// The model representing a row in the `users` database table.
 #[derive(Clone, Debug, PartialEq, Eq, Queryable, Identifiable, AsChangeset, Associations)]
 pub struct User {
     pub id: i32,
     pub email: Option<String>,
-    pub gh_access_token: String,
+    pub gh_access_token: Option<String>,
     pub gh_login: String,
     pub name: Option<String>,
     pub gh_avatar: Option<String>,
     pub gh_id: i32,
+    pub last_login_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub suspended_until: Option<NaiveDateTime>,
+    pub display_name: Option<String>,
 }
 
 #[derive(Insertable, Debug)]
@@ -32,6 +43,22 @@ pub struct NewUser<'a> {
     pub gh_access_token: Cow<'a, str>,
 }
 
+/// Options controlling the side effects of `NewUser::create_or_update_with`.
+#[derive(Clone, Copy, Debug)]
+pub struct CreateUserOptions {
+    /// Whether a newly recorded email address is sent a confirmation link.
+    /// The address is stored with a confirmation token either way.
+    pub send_confirmation_email: bool,
+}
+
+impl Default for CreateUserOptions {
+    fn default() -> Self {
+        CreateUserOptions {
+            send_confirmation_email: true,
+        }
+    }
+}
+
 impl<'a> NewUser<'a> {
     pub fn new(
         gh_id: i32,
@@ -53,11 +80,20 @@ impl<'a> NewUser<'a> {
 
     /// Inserts the user into the database, or updates an existing one.
     pub fn create_or_update(&self, conn: &PgConnection) -> QueryResult<User> {
+        self.create_or_update_with(conn, CreateUserOptions::default())
+    }
+
+    /// Like `create_or_update`, but with control over whether a confirmation
+    /// email is sent for a newly recorded email address.
+    pub fn create_or_update_with(
+        &self,
+        conn: &PgConnection,
+        options: CreateUserOptions,
+    ) -> QueryResult<User> {
         use diesel::dsl::sql;
         use diesel::insert_into;
         use diesel::pg::upsert::excluded;
         use diesel::sql_types::Integer;
-        use diesel::NotFound;
         use schema::users::dsl::*;
 
         conn.transaction(|| {
@@ -82,23 +118,10 @@ impl<'a> NewUser<'a> {
                 .get_result::<User>(conn)?;
 
             // To send the user an account verification email...
-            if let Some(user_email) = user.email.as_ref() {
-                let new_email = NewEmail {
-                    user_id: user.id,
-                    email: user_email,
-                };
-
-                let token = insert_into(emails::table)
-                    .values(&new_email)
-                    .on_conflict_do_nothing()
-                    .returning(emails::token)
-                    .get_result::<String>(conn)
-                    .optional()?;
-
-                if let Some(token) = token {
-                    ::email::send_user_confirm_email(user_email, &user.gh_login, &token)
-                        .map_err(|_| NotFound)?;
-                }
+            if options.send_confirmation_email {
+                Email::generate_and_send_token(conn, &user)?;
+            } else {
+                Email::insert_for_user(conn, &user)?;
             }
 
             Ok(user)
@@ -107,19 +130,62 @@ impl<'a> NewUser<'a> {
 }
 
 impl User {
-    /// Queries the database for a user with a certain `api_token` value.
-    pub fn find_by_api_token(conn: &PgConnection, token_: &str) -> CargoResult<User> {
-        use diesel::update;
-        use schema::api_tokens::dsl::{api_tokens, last_used_at, revoked, token, user_id};
-        use schema::users::dsl::{id, users};
-        let tokens = api_tokens
-            .filter(token.eq(token_))
-            .filter(revoked.eq(false));
-        let user_id_ = update(tokens)
-            .set(last_used_at.eq(now.nullable()))
-            .returning(user_id)
-            .get_result::<i32>(conn)?;
-        Ok(users.filter(id.eq(user_id_)).get_result(conn)?)
+    /// Queries the database for a user with a certain `api_token` value, returning the token
+    /// along with the user so that its scopes can be checked. The token records `client_ip` as
+    /// the address it was last used from, and is found for `expiry_grace` after it expires.
+    /// Banned users are never found, and the tokens of banned or suspended users aren't recorded
+    /// as used.
+    pub fn find_by_api_token(
+        conn: &PgConnection,
+        token: &str,
+        client_ip: &str,
+        expiry_grace: Duration,
+    ) -> CargoResult<(User, ApiToken)> {
+        let token = ApiToken::find_active(conn, token, expiry_grace)?;
+        let user = users::table.find(token.user_id).get_result::<User>(conn)?;
+        if user.is_banned(conn)? {
+            return Err(diesel::result::Error::NotFound.into());
+        }
+        if let Some(until) = user.suspension_end() {
+            return Err(forbidden(&format_args!(
+                "this account is suspended until {}",
+                DateTime::<Utc>::from_utc(until, Utc).to_rfc3339()
+            )));
+        }
+        let token = token.record_used(conn, client_ip, expiry_grace)?;
+        Ok((user, token))
+    }
+
+    /// Queries the database for a user by GitHub login, ignoring case.
+    ///
+    /// If several accounts have used the same login over time, the most recently created one
+    /// is returned.
+    pub fn find_by_login(conn: &PgConnection, login: &str) -> QueryResult<User> {
+        users::table
+            .filter(::lower(users::gh_login).eq(login.to_lowercase()))
+            .order(users::id.desc())
+            .first(conn)
+    }
+
+    /// Sets the name shown for this user instead of the one from their GitHub profile, or goes
+    /// back to the GitHub one with `None`. Unlike the GitHub name, it isn't changed by signing in.
+    pub fn set_display_name(
+        &self,
+        conn: &PgConnection,
+        display_name: Option<&str>,
+    ) -> QueryResult<User> {
+        diesel::update(self)
+            .set(users::display_name.eq(display_name))
+            .get_result(conn)
+    }
+
+    /// Records that this user has just logged in through GitHub, returning the updated user.
+    ///
+    /// This is only for session logins; API token use is tracked by `ApiToken::last_used_at`.
+    pub fn record_login(&self, conn: &PgConnection) -> QueryResult<User> {
+        diesel::update(self)
+            .set(users::last_login_at.eq(now.nullable()))
+            .get_result(conn)
     }
 
     pub fn owning(krate: &Crate, conn: &PgConnection) -> CargoResult<Vec<Owner>> {
@@ -138,28 +204,125 @@ impl User {
     /// Given this set of owners, determines the strongest rights the
     /// user has.
     ///
-    /// Shortcircuits on `Full` because you can't beat it. In practice we'll always
-    /// see `[user, user, user, ..., team, team, team]`, so we could shortcircuit on
-    /// `Publish` as well, but this is a non-obvious invariant so we don't bother.
-    /// Sweet free optimization if teams are proving burdensome to check.
-    /// More than one team isn't really expected, though.
+    /// Shortcircuits on `Full` because you can't beat it. When only a minimum
+    /// level of rights matters, `rights_up_to` checks fewer teams.
     pub fn rights(&self, app: &App, owners: &[Owner]) -> CargoResult<Rights> {
-        let mut best = Rights::None;
-        for owner in owners {
-            match *owner {
-                Owner::User(ref other_user) => {
-                    if other_user.id == self.id {
-                        return Ok(Rights::Full);
+        self.rights_up_to(app, owners, Rights::Full)
+    }
+
+    /// Like `rights`, but stops looking as soon as the user is known to have
+    /// at least `required` rights, so that crates owned by many teams don't
+    /// cost a GitHub membership lookup per team. The result is only the
+    /// strongest rights the user has when it is below `required`.
+    ///
+    /// Use a `RightsResolver` to check rights over several crates.
+    pub fn rights_up_to(
+        &self,
+        app: &App,
+        owners: &[Owner],
+        required: Rights,
+    ) -> CargoResult<Rights> {
+        RightsResolver::new(app).rights_up_to(self, owners, required)
+    }
+
+    /// Like `rights`, but for what the user can currently do with the crate:
+    /// a blocked user keeps whatever ownership they're listed with, but has no
+    /// rights through it, and a temporary elevation gives full rights. Only
+    /// users who have publish rights through a team can be elevated.
+    pub fn rights_over(
+        &self,
+        app: &App,
+        conn: &PgConnection,
+        krate: &Crate,
+        owners: &[Owner],
+    ) -> CargoResult<Rights> {
+        if self.is_blocked(conn)? {
+            return Ok(Rights::None);
+        }
+        let rights = self.rights(app, owners)?;
+        if rights == Rights::Publish && RightsElevation::is_active(conn, krate.id, self.id)? {
+            return Ok(Rights::Full);
+        }
+        Ok(rights)
+    }
+
+    /// Works out the user's rights over each of the given crates, keyed by
+    /// crate id, as `rights_over` would. The owners of every crate are loaded
+    /// together, and GitHub is asked about each team at most once, and only
+    /// for crates the user doesn't already own directly.
+    pub fn rights_for_crates(
+        &self,
+        app: &App,
+        conn: &PgConnection,
+        crate_ids: &[i32],
+    ) -> CargoResult<HashMap<i32, Rights>> {
+        let mut rights = crate_ids
+            .iter()
+            .map(|&id| (id, Rights::None))
+            .collect::<HashMap<_, _>>();
+        if self.is_blocked(conn)? {
+            return Ok(rights);
+        }
+        let owners = Owner::owning_many(crate_ids, conn)?;
+
+        for (&crate_id, crate_owners) in &owners {
+            let owns_directly = crate_owners.iter().any(|owner| match *owner {
+                Owner::User(ref user) => user.id == self.id,
+                Owner::Team(_) => false,
+            });
+            if owns_directly {
+                rights.insert(crate_id, Rights::Full);
+            }
+        }
+
+        let mut resolver = RightsResolver::new(app);
+        for (&crate_id, crate_owners) in &owners {
+            for owner in crate_owners {
+                if let Owner::Team(ref team) = *owner {
+                    if rights[&crate_id] != Rights::None {
+                        break;
                     }
-                }
-                Owner::Team(ref team) => {
-                    if team.contains_user(app, self)? {
-                        best = Rights::Publish;
+                    if resolver.team_contains_user(team, self)? {
+                        rights.insert(crate_id, Rights::Publish);
                     }
                 }
             }
         }
-        Ok(best)
+
+        let elevated = RightsElevation::active_crate_ids(conn, self.id, crate_ids)?;
+        for crate_id in elevated {
+            if rights.get(&crate_id) == Some(&Rights::Publish) {
+                rights.insert(crate_id, Rights::Full);
+            }
+        }
+
+        Ok(rights)
+    }
+
+    /// Whether the crates.io team has restricted this account from publishing.
+    pub fn is_blocked(&self, conn: &PgConnection) -> QueryResult<bool> {
+        use diesel::dsl::exists;
+        diesel::select(exists(blocked_users::table.find(self.id))).get_result(conn)
+    }
+
+    /// Whether an admin has banned this account, which stops it from signing in or using its
+    /// API tokens.
+    pub fn is_banned(&self, conn: &PgConnection) -> QueryResult<bool> {
+        use diesel::dsl::exists;
+        diesel::select(exists(banned_users::table.find(self.id))).get_result(conn)
+    }
+
+    /// When the suspension of this account ends, if it is currently suspended. A suspended
+    /// account can't sign in or use its API tokens.
+    pub fn suspension_end(&self) -> Option<NaiveDateTime> {
+        self.suspended_until
+            .filter(|until| *until > Utc::now().naive_utc())
+    }
+
+    /// Whether this account may use the admin endpoints.
+    pub fn is_admin(&self, conn: &PgConnection) -> QueryResult<bool> {
+        use diesel::dsl::exists;
+        diesel::select(exists(admin_users::table.find(self.id))).get_result(conn)
     }
 
     pub fn has_verified_email(&self, conn: &PgConnection) -> CargoResult<bool> {
@@ -173,11 +336,297 @@ impl User {
         Ok(email_exists)
     }
 
+    /// The verification status of the user's primary email address, as shown
+    /// on their own profile: whether it has been verified, and whether a
+    /// confirmation email has been sent to it. Both are false when the user has
+    /// no email address, and a verified address counts as sent.
+    pub fn email_status(&self, conn: &PgConnection) -> CargoResult<(bool, bool)> {
+        let status = emails::table
+            .filter(emails::user_id.eq(self.id))
+            .filter(emails::is_primary.eq(true))
+            .select((emails::verified, emails::token_generated_at.is_not_null()))
+            .first::<(bool, bool)>(conn)
+            .optional()?;
+        Ok(status.map_or((false, false), |(verified, sent)| {
+            (verified, verified || sent)
+        }))
+    }
+
+    /// The token to make requests to GitHub on the user's behalf with. Users
+    /// who logged out have none until they log in again.
+    pub fn github_token(&self) -> CargoResult<Token> {
+        self.gh_access_token
+            .clone()
+            .map(github::token)
+            .ok_or_else(|| {
+                human(
+                    "crates.io can no longer check your GitHub account since you logged out. \
+                 Log in again at https://crates.io/login to fix this.",
+                )
+            })
+    }
+
+    /// Revokes the user's GitHub access token, as done when they log out, and
+    /// forgets it. It is forgotten even if GitHub fails to revoke it.
+    pub fn revoke_gh_token(&self, app: &App, conn: &PgConnection) -> CargoResult<()> {
+        if let Some(ref token) = self.gh_access_token {
+            if let Err(e) = github::revoke_token(app, token) {
+                warn!(
+                    "failed to revoke the GitHub token of user {}: {}",
+                    self.id, e
+                );
+            }
+        }
+        diesel::update(self)
+            .set(users::gh_access_token.eq(None::<String>))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// Like `has_verified_email`, but for many users at once: returns the ids of those of the
+    /// given users who have a verified email address.
+    pub fn verified_emails_for(conn: &PgConnection, user_ids: &[i32]) -> CargoResult<HashSet<i32>> {
+        let ids = emails::table
+            .filter(emails::user_id.eq_any(user_ids))
+            .filter(emails::verified.eq(true))
+            .select(emails::user_id)
+            .distinct()
+            .load::<i32>(conn)?;
+        Ok(ids.into_iter().collect())
+    }
+
+    /// Returns the address that notifications for this user should be sent to.
+    ///
+    /// This is the verified email the user picked for notifications, falling back to their
+    /// primary email as long as it is verified.
+    pub fn notification_email(&self, conn: &PgConnection) -> CargoResult<Option<String>> {
+        let email = emails::table
+            .filter(emails::user_id.eq(self.id))
+            .filter(emails::verified.eq(true))
+            .filter(emails::notifications.or(emails::is_primary))
+            .order(emails::notifications.desc())
+            .select(emails::email)
+            .first(conn)
+            .optional()?;
+        Ok(email)
+    }
+
+    /// Starts changing this user's primary email address to `new_email`, returning the token
+    /// that confirms the new address. The current address stays in use until then, see
+    /// `Email::confirm`. Asking for another change replaces one that is still pending.
+    pub fn update_email(&self, conn: &PgConnection, new_email: &str) -> QueryResult<String> {
+        use diesel::dsl::exists;
+
+        conn.transaction(|| {
+            diesel::delete(
+                emails::table
+                    .filter(emails::user_id.eq(self.id))
+                    .filter(emails::replaces_primary.eq(true)),
+            )
+            .execute(conn)?;
+            // Users without a primary address yet don't have anything to keep using meanwhile
+            let has_primary = diesel::select(exists(
+                emails::table
+                    .filter(emails::user_id.eq(self.id))
+                    .filter(emails::is_primary.eq(true)),
+            ))
+            .get_result::<bool>(conn)?;
+            diesel::insert_into(emails::table)
+                .values((
+                    emails::user_id.eq(self.id),
+                    emails::email.eq(new_email),
+                    emails::is_primary.eq(!has_primary),
+                    emails::replaces_primary.eq(has_primary),
+                ))
+                .returning(emails::token)
+                .get_result(conn)
+        })
+    }
+
+    /// Deletes this user's account along with their API tokens, email addresses and follows. They
+    /// are removed from the owners of their crates, which is logged like any other removal.
+    /// Versions they published or authored are kept, but no longer point at them. Fails without
+    /// changing anything if they are the only user owning a crate, since owning teams can't
+    /// change owners, so the crate would be left without anyone able to manage it.
+    pub fn delete_account(&self, conn: &PgConnection) -> CargoResult<()> {
+        use diesel::dsl::not;
+        use schema::{api_tokens, crates, follows, version_authors, versions};
+
+        conn.transaction(|| {
+            let is_self = crate_owners::owner_id
+                .eq(self.id)
+                .and(crate_owners::owner_kind.eq(OwnerKind::User as i32));
+            let owned = crate_owners::table
+                .filter(is_self)
+                .filter(crate_owners::deleted.eq(false))
+                .select(crate_owners::crate_id)
+                .load::<i32>(conn)?;
+            let co_owned = crate_owners::table
+                .filter(crate_owners::crate_id.eq_any(&owned))
+                .filter(crate_owners::deleted.eq(false))
+                .filter(crate_owners::owner_kind.eq(OwnerKind::User as i32))
+                .filter(crate_owners::owner_id.ne(self.id))
+                .select(crate_owners::crate_id)
+                .load::<i32>(conn)?;
+            let solely_owned = crates::table
+                .filter(crates::id.eq_any(&owned))
+                .filter(not(crates::id.eq_any(&co_owned)))
+                .select(crates::name)
+                .order(crates::name)
+                .load::<String>(conn)?;
+            if !solely_owned.is_empty() {
+                return Err(human(&format_args!(
+                    "cannot delete an account that is the only user owning a crate, \
+                     add another user as an owner of `{}` first",
+                    solely_owned.join("`, `")
+                )));
+            }
+
+            let owner = Owner::User(self.clone());
+            for &crate_id in &owned {
+                CrateOwner::remove(conn, crate_id, &owner, self.id)?;
+            }
+            diesel::update(crate_owners::table.filter(crate_owners::created_by.eq(self.id)))
+                .set(crate_owners::created_by.eq(None::<i32>))
+                .execute(conn)?;
+            diesel::update(version_authors::table.filter(version_authors::user_id.eq(self.id)))
+                .set(version_authors::user_id.eq(None::<i32>))
+                .execute(conn)?;
+            diesel::update(versions::table.filter(versions::published_by.eq(self.id)))
+                .set(versions::published_by.eq(None::<i32>))
+                .execute(conn)?;
+            diesel::delete(api_tokens::table.filter(api_tokens::user_id.eq(self.id)))
+                .execute(conn)?;
+            diesel::delete(emails::table.filter(emails::user_id.eq(self.id))).execute(conn)?;
+            diesel::delete(follows::table.filter(follows::user_id.eq(self.id))).execute(conn)?;
+            diesel::delete(self).execute(conn)?;
+            Ok(())
+        })
+    }
+
+    /// Records the real GitHub ID of the account with the given login that was backfilled with
+    /// a `gh_id` of `-1`, because GitHub didn't know the login at the time. It's an error for
+    /// more than one such account to have the login.
+    ///
+    /// If no account has that GitHub ID yet, the placeholder account is simply updated. If one
+    /// does, the placeholder is merged into it: its crate ownerships, API tokens and the versions
+    /// it published or authored are moved over, and the placeholder is then deleted. Moving the
+    /// ownerships is logged as the account with the GitHub ID adding itself and removing the
+    /// placeholder. The account that ends up with the GitHub ID is returned.
+    pub fn backfill_gh_id(
+        conn: &PgConnection,
+        gh_login: &str,
+        real_gh_id: i32,
+    ) -> CargoResult<User> {
+        use diesel::result::{DatabaseErrorKind, Error};
+        use schema::{api_tokens, version_authors, versions};
+
+        if real_gh_id <= 0 {
+            return Err(human(&format_args!("invalid GitHub ID {}", real_gh_id)));
+        }
+
+        conn.transaction(|| {
+            // There is no telling which of several placeholders for the login the GitHub ID
+            // belongs to, so those have to be sorted out by hand
+            let mut placeholders = users::table
+                .filter(users::gh_id.eq(-1))
+                .filter(::lower(users::gh_login).eq(gh_login.to_lowercase()))
+                .order(users::id)
+                .limit(2)
+                .load::<User>(conn)?;
+            if placeholders.len() > 1 {
+                return Err(human(&format_args!(
+                    "several accounts for `{}` are missing their GitHub ID",
+                    gh_login
+                )));
+            }
+            let placeholder = placeholders.pop().ok_or_else(|| {
+                human(&format_args!(
+                    "no account for `{}` is missing its GitHub ID",
+                    gh_login
+                ))
+            })?;
+            let existing = users::table
+                .filter(users::gh_id.eq(real_gh_id))
+                .first::<User>(conn)
+                .optional()?;
+
+            let existing = match existing {
+                Some(existing) => existing,
+                None => {
+                    return diesel::update(&placeholder)
+                        .set(users::gh_id.eq(real_gh_id))
+                        .get_result(conn)
+                        .map_err(|e| match e {
+                            Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                                human(&format_args!(
+                                    "another account was given the GitHub ID {} while `{}` was \
+                                     being updated",
+                                    real_gh_id, gh_login
+                                ))
+                            }
+                            e => e.into(),
+                        });
+                }
+            };
+
+            // The existing account takes over the placeholder's ownerships, getting back any it
+            // was removed from, and both changes are logged like any other
+            let placeholder_owner = crate_owners::owner_id
+                .eq(placeholder.id)
+                .and(crate_owners::owner_kind.eq(OwnerKind::User as i32));
+            let existing_owner = crate_owners::owner_id
+                .eq(existing.id)
+                .and(crate_owners::owner_kind.eq(OwnerKind::User as i32));
+            let owned_by_placeholder = crate_owners::table
+                .filter(placeholder_owner)
+                .filter(crate_owners::deleted.eq(false))
+                .select((crate_owners::crate_id, crate_owners::created_by))
+                .load::<(i32, Option<i32>)>(conn)?;
+            let owned_by_existing = crate_owners::table
+                .filter(existing_owner)
+                .filter(crate_owners::deleted.eq(false))
+                .select(crate_owners::crate_id)
+                .load::<i32>(conn)?;
+            let placeholder_as_owner = Owner::User(placeholder.clone());
+            for (crate_id, created_by) in owned_by_placeholder {
+                if !owned_by_existing.contains(&crate_id) {
+                    CrateOwner {
+                        crate_id,
+                        owner_id: existing.id,
+                        created_by: created_by.unwrap_or(existing.id),
+                        owner_kind: OwnerKind::User as i32,
+                    }
+                    .add(conn)?;
+                }
+                CrateOwner::remove(conn, crate_id, &placeholder_as_owner, existing.id)?;
+            }
+            diesel::update(crate_owners::table.filter(crate_owners::created_by.eq(placeholder.id)))
+                .set(crate_owners::created_by.eq(existing.id))
+                .execute(conn)?;
+            diesel::update(api_tokens::table.filter(api_tokens::user_id.eq(placeholder.id)))
+                .set(api_tokens::user_id.eq(existing.id))
+                .execute(conn)?;
+            diesel::update(
+                version_authors::table.filter(version_authors::user_id.eq(placeholder.id)),
+            )
+            .set(version_authors::user_id.eq(existing.id))
+            .execute(conn)?;
+            diesel::update(versions::table.filter(versions::published_by.eq(placeholder.id)))
+                .set(versions::published_by.eq(existing.id))
+                .execute(conn)?;
+            placeholder.delete_account(conn)?;
+
+            Ok(existing)
+        })
+    }
+
     /// Converts this `User` model into an `EncodablePrivateUser` for JSON serialization.
     pub fn encodable_private(
         self,
         email_verified: bool,
         email_verification_sent: bool,
+        is_admin: bool,
     ) -> EncodablePrivateUser {
         let User {
             id,
@@ -185,6 +634,9 @@ impl User {
             name,
             gh_login,
             gh_avatar,
+            last_login_at,
+            created_at,
+            display_name,
             ..
         } = self;
         let url = format!("https://github.com/{}", gh_login);
@@ -195,8 +647,13 @@ impl User {
             email_verification_sent,
             avatar: gh_avatar,
             login: gh_login,
-            name,
+            name: display_name.or(name),
             url: Some(url),
+            last_login_at,
+            created_at,
+            is_admin,
+            can_publish: None,
+            publishable_crate_count: None,
         }
     }
 
@@ -207,6 +664,7 @@ impl User {
             name,
             gh_login,
             gh_avatar,
+            display_name,
             ..
         } = self;
         let url = format!("https://github.com/{}", gh_login);
@@ -214,7 +672,20 @@ impl User {
             id,
             avatar: gh_avatar,
             login: gh_login,
-            name,
+            name: display_name.or(name),
+            url: Some(url),
+            verified: None,
+        }
+    }
+
+    /// Converts this `User` model into an `EncodableOwnerRef`, for listing
+    /// owners without their whole public profile.
+    pub fn encodable_owner_ref(self) -> EncodableOwnerRef {
+        let url = format!("https://github.com/{}", self.gh_login);
+        EncodableOwnerRef {
+            id: self.id,
+            login: self.gh_login,
+            avatar: self.gh_avatar,
             url: Some(url),
         }
     }
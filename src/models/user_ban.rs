@@ -0,0 +1,99 @@
+use chrono::NaiveDateTime;
+use diesel;
+use diesel::prelude::*;
+
+use models::{ApiToken, User};
+use schema::{banned_users, user_ban_events};
+use util::rfc3339;
+
+/// The model representing a row in the `banned_users` database table.
+#[derive(Clone, Debug, PartialEq, Eq, Queryable, Identifiable, Associations, Serialize)]
+#[belongs_to(User)]
+#[primary_key(user_id)]
+#[table_name = "banned_users"]
+pub struct UserBan {
+    #[serde(skip)]
+    pub user_id: i32,
+    pub reason: String,
+    #[serde(with = "rfc3339")]
+    pub banned_at: NaiveDateTime,
+}
+
+impl UserBan {
+    /// Bans a user, revoking all of their API tokens. Returns the ban and how many tokens were
+    /// revoked, or `None` if the user was already banned.
+    pub fn create(
+        conn: &PgConnection,
+        user_id: i32,
+        admin_id: i32,
+        reason: &str,
+    ) -> QueryResult<Option<(UserBan, usize)>> {
+        conn.transaction(|| {
+            let ban = diesel::insert_into(banned_users::table)
+                .values((
+                    banned_users::user_id.eq(user_id),
+                    banned_users::reason.eq(reason),
+                ))
+                .on_conflict_do_nothing()
+                .get_result::<UserBan>(conn)
+                .optional()?;
+            let ban = match ban {
+                Some(ban) => ban,
+                None => return Ok(None),
+            };
+            let revoked = ApiToken::revoke_all(conn, user_id, "revoked when the user was banned")?;
+            UserBanEvent::record(conn, user_id, admin_id, "banned", Some(reason))?;
+            Ok(Some((ban, revoked)))
+        })
+    }
+
+    /// Lifts a user's ban, returning whether they were banned. Tokens revoked by the ban stay
+    /// revoked.
+    pub fn lift(conn: &PgConnection, user_id: i32, admin_id: i32) -> QueryResult<bool> {
+        conn.transaction(|| {
+            let lifted = diesel::delete(banned_users::table.find(user_id)).execute(conn)? > 0;
+            if lifted {
+                UserBanEvent::record(conn, user_id, admin_id, "unbanned", None)?;
+            }
+            Ok(lifted)
+        })
+    }
+}
+
+/// A ban or unban of a user by an admin.
+#[derive(Clone, Debug, PartialEq, Eq, Queryable, Identifiable, Associations, Serialize)]
+#[belongs_to(User)]
+#[table_name = "user_ban_events"]
+pub struct UserBanEvent {
+    #[serde(skip)]
+    pub id: i32,
+    #[serde(skip)]
+    pub user_id: i32,
+    /// The admin who made the change, unless they have since deleted their account
+    pub admin_id: Option<i32>,
+    pub kind: String,
+    pub reason: Option<String>,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+}
+
+impl UserBanEvent {
+    /// Records that an admin banned or unbanned a user
+    pub fn record(
+        conn: &PgConnection,
+        user_id: i32,
+        admin_id: i32,
+        kind: &str,
+        reason: Option<&str>,
+    ) -> QueryResult<()> {
+        diesel::insert_into(user_ban_events::table)
+            .values((
+                user_ban_events::user_id.eq(user_id),
+                user_ban_events::admin_id.eq(admin_id),
+                user_ban_events::kind.eq(kind),
+                user_ban_events::reason.eq(reason),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+}
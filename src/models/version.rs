@@ -27,6 +27,8 @@ pub struct Version {
     pub yanked: bool,
     pub license: Option<String>,
     pub crate_size: Option<i32>,
+    pub published_by: Option<i32>,
+    pub published_as: Option<String>,
 }
 
 #[derive(Insertable, Debug)]
@@ -37,6 +39,8 @@ pub struct NewVersion {
     features: serde_json::Value,
     license: Option<String>,
     crate_size: Option<i32>,
+    published_by: Option<i32>,
+    published_as: Option<String>,
 }
 
 impl Version {
@@ -51,6 +55,7 @@ impl Version {
             yanked,
             license,
             crate_size,
+            published_as,
             ..
         } = self;
         let num = num.to_string();
@@ -72,6 +77,7 @@ impl Version {
                 authors: format!("/api/v1/crates/{}/{}/authors", crate_name, num),
             },
             crate_size,
+            published_as,
         }
     }
 
@@ -131,6 +137,8 @@ impl NewVersion {
             features,
             license,
             crate_size: Some(crate_size),
+            published_by: None,
+            published_as: None,
         };
 
         new_version.validate_license(license_file)?;
@@ -138,6 +146,16 @@ impl NewVersion {
         Ok(new_version)
     }
 
+    /// Records the user publishing this version, along with the attribution name of the API
+    /// token they published it with, if it has one.
+    pub fn published_by(self, user_id: i32, attribution_name: Option<String>) -> Self {
+        NewVersion {
+            published_by: Some(user_id),
+            published_as: attribution_name,
+            ..self
+        }
+    }
+
     pub fn save(&self, conn: &PgConnection, authors: &[String]) -> CargoResult<Version> {
         use diesel::dsl::exists;
         use diesel::{insert_into, select};
@@ -1,7 +1,7 @@
 use std::error::Error;
 use std::sync::Arc;
 
-use conduit::{Handler, Request, Response};
+use conduit::{Handler, Method, Request, Response};
 use conduit_git_http_backend;
 use conduit_router::{RequestParams, RouteBuilder};
 
@@ -21,6 +21,27 @@ pub fn build_router(app: &App) -> R404 {
     api_router.get("/crates/:crate_id/owners", C(krate::owners::owners));
     api_router.put("/crates/:crate_id/owners", C(krate::owners::add_owners));
     api_router.delete("/crates/:crate_id/owners", C(krate::owners::remove_owners));
+    api_router.get("/crates/:crate_id/owners/at", C(krate::owners::owners_at));
+    api_router.get(
+        "/crates/:crate_id/owner_changes",
+        C(krate::owners::owner_changes),
+    );
+    api_router.get(
+        "/crates/:crate_id/owners/effective",
+        C(krate::owners::effective_owners),
+    );
+    api_router.get(
+        "/crates/:crate_id/publishing_tokens",
+        C(krate::owners::publishing_tokens),
+    );
+    api_router.post(
+        "/crates/:crate_id/owners/import",
+        C(krate::owners::import_owners),
+    );
+    api_router.post(
+        "/crates/:crate_id/owners/transfer",
+        C(krate::owners::transfer_ownership),
+    );
     api_router.delete("/crates/:crate_id/:version/yank", C(version::yank::yank));
     api_router.put(
         "/crates/:crate_id/:version/unyank",
@@ -62,6 +83,20 @@ pub fn build_router(app: &App) -> R404 {
     api_router.put("/crates/:crate_id/follow", C(krate::follow::follow));
     api_router.delete("/crates/:crate_id/follow", C(krate::follow::unfollow));
     api_router.get("/crates/:crate_id/following", C(krate::follow::following));
+    api_router.get("/crates/:crate_id/why", C(krate::owners::why));
+    api_router.get(
+        "/crates/:crate_id/my_access_via",
+        C(krate::owners::my_access_via),
+    );
+    api_router.get(
+        "/crates/:crate_id/can_publish",
+        C(krate::owners::can_publish),
+    );
+    api_router.post(
+        "/crates/:crate_id/refresh_access",
+        C(krate::owners::refresh_access),
+    );
+    api_router.post("/crates/:crate_id/elevate", C(krate::owners::elevate));
     api_router.get("/crates/:crate_id/owner_team", C(krate::owners::owner_team));
     api_router.get("/crates/:crate_id/owner_user", C(krate::owners::owner_user));
     api_router.get(
@@ -73,15 +108,52 @@ pub fn build_router(app: &App) -> R404 {
     api_router.get("/categories", C(category::index));
     api_router.get("/categories/:category_id", C(category::show));
     api_router.get("/category_slugs", C(category::slugs));
+    api_router.get("/users/resolve", C(user::other::resolve));
     api_router.get("/users/:user_id", C(user::other::show));
     api_router.put("/users/:user_id", C(user::me::update_user));
     api_router.get("/users/:user_id/stats", C(user::other::stats));
+    api_router.get("/users/:user_id/crates", C(user::other::crates));
     api_router.get("/teams/:team_id", C(team::show_team));
+    api_router.get("/teams/:team_id/crates", C(team::team_crates));
     api_router.get("/me", C(user::me::me));
+    api_router.delete("/me", C(user::me::delete_account));
     api_router.get("/me/updates", C(user::me::updates));
+    api_router.get("/me/collaborators", C(user::me::collaborators));
+    api_router.get("/me/owner_activity", C(user::me::owner_activity));
+    api_router.put("/me/email", C(user::me::update_email));
+    api_router.put("/me/email/resend", C(user::me::resend_email_verification));
+    api_router.put("/me/profile", C(user::me::update_profile));
+    api_router.put(
+        "/me/notification_email",
+        C(user::me::update_notification_email),
+    );
+    api_router.post(
+        "/me/email_verification/regenerate",
+        C(user::me::regenerate_email_verification),
+    );
     api_router.get("/me/tokens", C(token::list));
     api_router.put("/me/tokens", C(token::new));
+    api_router.delete("/me/tokens", C(token::revoke_all));
+    api_router.post("/me/tokens/merge", C(token::merge));
+    api_router.map(Method::Patch, "/me/tokens/rename", C(token::rename_all));
+    api_router.get("/me/tokens/:id", C(token::show));
+    api_router.put("/me/tokens/:id", C(token::update));
+    api_router.map(Method::Patch, "/me/tokens/:id", C(token::update));
+    api_router.post("/me/tokens/:id/rotate", C(token::rotate));
+    api_router.get("/me/tokens/:id/history", C(token::history));
+    api_router.get("/me/tokens/:id/usage", C(token::usage));
     api_router.delete("/me/tokens/:id", C(token::revoke));
+    api_router.post("/tokens/exchange", C(token::exchange));
+    api_router.get("/admin/tokens/:id/owner", C(admin::token_owner));
+    api_router.get("/admin/emails/pending_count", C(admin::pending_email_count));
+    api_router.get(
+        "/admin/users/:id/confirm_email/preview",
+        C(admin::preview_confirm_email),
+    );
+    api_router.put("/admin/users/:id/ban", C(admin::ban_user));
+    api_router.delete("/admin/users/:id/ban", C(admin::unban_user));
+    api_router.post("/users/:id/suspend", C(admin::suspend_user));
+    api_router.delete("/users/:id/suspend", C(admin::unsuspend_user));
     api_router.get(
         "/me/crate_owner_invitations",
         C(crate_owner_invitation::list),
@@ -91,12 +163,14 @@ pub fn build_router(app: &App) -> R404 {
         C(crate_owner_invitation::handle_invite),
     );
     api_router.get("/summary", C(krate::metadata::summary));
+    api_router.get("/confirm/:email_token", C(user::me::confirm_user_email));
     api_router.put("/confirm/:email_token", C(user::me::confirm_user_email));
     api_router.put(
         "/users/:user_id/resend",
         C(user::me::regenerate_token_and_send),
     );
     api_router.get("/site_metadata", C(site_metadata::show_deployed_sha));
+    api_router.post("/webhooks/github", C(webhook::github));
     let api_router = Arc::new(R404(api_router));
 
     let mut router = RouteBuilder::new();
@@ -107,6 +181,7 @@ pub fn build_router(app: &App) -> R404 {
     router.put("/api/v1/*path", R(Arc::clone(&api_router)));
     router.post("/api/v1/*path", R(Arc::clone(&api_router)));
     router.head("/api/v1/*path", R(Arc::clone(&api_router)));
+    router.map(Method::Patch, "/api/v1/*path", R(Arc::clone(&api_router)));
     router.delete("/api/v1/*path", R(api_router));
 
     router.get("/authorize_url", C(user::session::github_authorize));
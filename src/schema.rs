@@ -1,5 +1,101 @@
 #![allow(unused_imports)]
 
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+    use diesel_ltree::Ltree;
+
+    /// Representation of the `admin_users` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    admin_users (user_id) {
+        /// The `user_id` column of the `admin_users` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `created_at` column of the `admin_users` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+    use diesel_ltree::Ltree;
+
+    /// Representation of the `api_token_events` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    api_token_events (id) {
+        /// The `id` column of the `api_token_events` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `api_token_id` column of the `api_token_events` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        api_token_id -> Int4,
+        /// The `kind` column of the `api_token_events` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        kind -> Varchar,
+        /// The `detail` column of the `api_token_events` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        detail -> Nullable<Varchar>,
+        /// The `created_at` column of the `api_token_events` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+    use diesel_ltree::Ltree;
+
+    /// Representation of the `api_token_usages` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    api_token_usages (api_token_id, hour) {
+        /// The `api_token_id` column of the `api_token_usages` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        api_token_id -> Int4,
+        /// The `hour` column of the `api_token_usages` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        hour -> Timestamp,
+        /// The `uses` column of the `api_token_usages` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        uses -> Int4,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
@@ -21,12 +117,6 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         user_id -> Int4,
-        /// The `token` column of the `api_tokens` table.
-        ///
-        /// Its SQL type is `Varchar`.
-        ///
-        /// (Automatically generated by Diesel.)
-        token -> Varchar,
         /// The `name` column of the `api_tokens` table.
         ///
         /// Its SQL type is `Varchar`.
@@ -51,6 +141,60 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         revoked -> Bool,
+        /// The `crate_scopes` column of the `api_tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Array<Text>>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_scopes -> Nullable<Array<Text>>,
+        /// The `expires_at` column of the `api_tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expires_at -> Nullable<Timestamp>,
+        /// The `external_ref` column of the `api_tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        external_ref -> Nullable<Varchar>,
+        /// The `endpoint_scopes` column of the `api_tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Array<Text>>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        endpoint_scopes -> Nullable<Array<Text>>,
+        /// The `last_used_ip` column of the `api_tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_used_ip -> Nullable<Varchar>,
+        /// The `use_count` column of the `api_tokens` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        use_count -> Int8,
+        /// The `token_hash` column of the `api_tokens` table.
+        ///
+        /// Its SQL type is `Bytea`.
+        ///
+        /// (Automatically generated by Diesel.)
+        token_hash -> Bytea,
+        /// The `attribution_name` column of the `api_tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        attribution_name -> Nullable<Varchar>,
+        /// The `exchanged` column of the `api_tokens` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        exchanged -> Bool,
     }
 }
 
@@ -84,6 +228,66 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+    use diesel_ltree::Ltree;
+
+    /// Representation of the `banned_users` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    banned_users (user_id) {
+        /// The `user_id` column of the `banned_users` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `reason` column of the `banned_users` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        reason -> Varchar,
+        /// The `banned_at` column of the `banned_users` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        banned_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+    use diesel_ltree::Ltree;
+
+    /// Representation of the `blocked_users` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    blocked_users (user_id) {
+        /// The `user_id` column of the `blocked_users` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `reason` column of the `blocked_users` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        reason -> Nullable<Varchar>,
+        /// The `created_at` column of the `blocked_users` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
@@ -168,6 +372,60 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+    use diesel_ltree::Ltree;
+
+    /// Representation of the `crate_owner_actions` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    crate_owner_actions (id) {
+        /// The `id` column of the `crate_owner_actions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `crate_id` column of the `crate_owner_actions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `owner_id` column of the `crate_owner_actions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        owner_id -> Int4,
+        /// The `owner_kind` column of the `crate_owner_actions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        owner_kind -> Int4,
+        /// The `action` column of the `crate_owner_actions` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        action -> Varchar,
+        /// The `performed_by` column of the `crate_owner_actions` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        performed_by -> Nullable<Int4>,
+        /// The `created_at` column of the `crate_owner_actions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
@@ -255,6 +513,12 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         owner_kind -> Int4,
+        /// The `deleted_at` column of the `crate_owners` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        deleted_at -> Nullable<Timestamp>,
     }
 }
 
@@ -513,6 +777,24 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         token_generated_at -> Nullable<Timestamp>,
+        /// The `is_primary` column of the `emails` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        is_primary -> Bool,
+        /// The `notifications` column of the `emails` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        notifications -> Bool,
+        /// The `replaces_primary` column of the `emails` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        replaces_primary -> Bool,
     }
 }
 
@@ -540,6 +822,60 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+    use diesel_ltree::Ltree;
+
+    /// Representation of the `github_actions_bindings` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    github_actions_bindings (id) {
+        /// The `id` column of the `github_actions_bindings` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `crate_id` column of the `github_actions_bindings` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `user_id` column of the `github_actions_bindings` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `repository` column of the `github_actions_bindings` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        repository -> Varchar,
+        /// The `created_at` column of the `github_actions_bindings` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `repository_id` column of the `github_actions_bindings` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        repository_id -> Varchar,
+        /// The `repository_owner_id` column of the `github_actions_bindings` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        repository_owner_id -> Varchar,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
@@ -594,6 +930,60 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+    use diesel_ltree::Ltree;
+
+    /// Representation of the `queued_emails` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    queued_emails (id) {
+        /// The `id` column of the `queued_emails` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `recipient` column of the `queued_emails` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        recipient -> Varchar,
+        /// The `subject` column of the `queued_emails` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        subject -> Varchar,
+        /// The `body` column of the `queued_emails` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        body -> Text,
+        /// The `created_at` column of the `queued_emails` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `attempts` column of the `queued_emails` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        attempts -> Int4,
+        /// The `last_error` column of the `queued_emails` table.
+        ///
+        /// Its SQL type is `Nullable<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_error -> Nullable<Text>,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
@@ -654,6 +1044,54 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+    use diesel_ltree::Ltree;
+
+    /// Representation of the `rights_elevations` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    rights_elevations (id) {
+        /// The `id` column of the `rights_elevations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `crate_id` column of the `rights_elevations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        crate_id -> Int4,
+        /// The `user_id` column of the `rights_elevations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `justification` column of the `rights_elevations` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        justification -> Varchar,
+        /// The `created_at` column of the `rights_elevations` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `expires_at` column of the `rights_elevations` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expires_at -> Timestamp,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
@@ -696,6 +1134,54 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::{TsVector as Tsvector};
+    use diesel_ltree::Ltree;
+
+    /// Representation of the `user_ban_events` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    user_ban_events (id) {
+        /// The `id` column of the `user_ban_events` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `user_id` column of the `user_ban_events` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `admin_id` column of the `user_ban_events` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        admin_id -> Nullable<Int4>,
+        /// The `kind` column of the `user_ban_events` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        kind -> Varchar,
+        /// The `reason` column of the `user_ban_events` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        reason -> Nullable<Varchar>,
+        /// The `created_at` column of the `user_ban_events` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use diesel_full_text_search::{TsVector as Tsvector};
@@ -719,10 +1205,10 @@ table! {
         email -> Nullable<Varchar>,
         /// The `gh_access_token` column of the `users` table.
         ///
-        /// Its SQL type is `Varchar`.
+        /// Its SQL type is `Nullable<Varchar>`.
         ///
         /// (Automatically generated by Diesel.)
-        gh_access_token -> Varchar,
+        gh_access_token -> Nullable<Varchar>,
         /// The `gh_login` column of the `users` table.
         ///
         /// Its SQL type is `Varchar`.
@@ -747,6 +1233,30 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         gh_id -> Int4,
+        /// The `last_login_at` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_login_at -> Nullable<Timestamp>,
+        /// The `created_at` column of the `users` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `suspended_until` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        suspended_until -> Nullable<Timestamp>,
+        /// The `display_name` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        display_name -> Nullable<Varchar>,
     }
 }
 
@@ -903,11 +1413,30 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         crate_size -> Nullable<Int4>,
+        /// The `published_by` column of the `versions` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        published_by -> Nullable<Int4>,
+        /// The `published_as` column of the `versions` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        published_as -> Nullable<Varchar>,
     }
 }
 
+joinable!(admin_users -> users (user_id));
+joinable!(api_token_events -> api_tokens (api_token_id));
+joinable!(api_token_usages -> api_tokens (api_token_id));
 joinable!(api_tokens -> users (user_id));
+joinable!(banned_users -> users (user_id));
+joinable!(blocked_users -> users (user_id));
 joinable!(crate_downloads -> crates (crate_id));
+joinable!(crate_owner_actions -> crates (crate_id));
+joinable!(crate_owner_actions -> users (performed_by));
 joinable!(crate_owner_invitations -> crates (crate_id));
 joinable!(crate_owners -> crates (crate_id));
 joinable!(crate_owners -> teams (owner_id));
@@ -921,18 +1450,30 @@ joinable!(dependencies -> versions (version_id));
 joinable!(emails -> users (user_id));
 joinable!(follows -> crates (crate_id));
 joinable!(follows -> users (user_id));
+joinable!(github_actions_bindings -> crates (crate_id));
+joinable!(github_actions_bindings -> users (user_id));
 joinable!(readme_renderings -> versions (version_id));
 joinable!(recent_crate_downloads -> crates (crate_id));
+joinable!(rights_elevations -> crates (crate_id));
+joinable!(rights_elevations -> users (user_id));
+joinable!(user_ban_events -> users (user_id));
 joinable!(version_authors -> users (user_id));
 joinable!(version_authors -> versions (version_id));
 joinable!(version_downloads -> versions (version_id));
 joinable!(versions -> crates (crate_id));
+joinable!(versions -> users (published_by));
 
 allow_tables_to_appear_in_same_query!(
+    admin_users,
+    api_token_events,
+    api_token_usages,
     api_tokens,
     badges,
+    banned_users,
+    blocked_users,
     categories,
     crate_downloads,
+    crate_owner_actions,
     crate_owner_invitations,
     crate_owners,
     crates,
@@ -941,12 +1482,16 @@ allow_tables_to_appear_in_same_query!(
     dependencies,
     emails,
     follows,
+    github_actions_bindings,
     keywords,
     metadata,
+    queued_emails,
     readme_renderings,
     recent_crate_downloads,
     reserved_crate_names,
+    rights_elevations,
     teams,
+    user_ban_events,
     users,
     version_authors,
     version_downloads,
@@ -1,6 +1,7 @@
 #![deny(warnings)]
 #![allow(unknown_lints, proc_macro_derive_resolution_fallback)] // TODO: This can be removed after diesel-1.4
 
+extern crate base64;
 extern crate cargo_registry;
 extern crate chrono;
 extern crate conduit;
@@ -11,8 +12,10 @@ extern crate diesel;
 extern crate dotenv;
 extern crate flate2;
 extern crate git2;
+extern crate hex;
 #[macro_use]
 extern crate lazy_static;
+extern crate openssl;
 extern crate s3;
 extern crate semver;
 extern crate serde;
@@ -27,6 +30,7 @@ use std::borrow::Cow;
 use std::env;
 use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
 use std::sync::Arc;
+use std::time::Duration;
 
 use cargo_registry::app::App;
 use cargo_registry::middleware::current_user::AuthenticationSource;
@@ -104,6 +108,7 @@ pub struct CrateResponse {
     krate: EncodableCrate,
     versions: Vec<EncodableVersion>,
     keywords: Vec<EncodableKeyword>,
+    can_publish: bool,
 }
 #[derive(Deserialize)]
 pub struct VersionResponse {
@@ -122,6 +127,17 @@ fn app() -> (
     record::Bomb,
     Arc<App>,
     conduit_middleware::MiddlewareBuilder,
+) {
+    app_with_config(|_| ())
+}
+
+/// Like `app`, after letting the caller adjust the configuration
+fn app_with_config<F: FnOnce(&mut cargo_registry::Config)>(
+    f: F,
+) -> (
+    record::Bomb,
+    Arc<App>,
+    conduit_middleware::MiddlewareBuilder,
 ) {
     dotenv::dotenv().ok();
 
@@ -140,15 +156,20 @@ fn app() -> (
         cdn: None,
     };
 
-    let (app, handler) = simple_app(uploader);
+    let mut config = simple_config(uploader);
+    f(&mut config);
+    let (app, handler) = build_app(&config);
     (bomb, app, handler)
 }
 
 fn simple_app(
     uploader: cargo_registry::Uploader,
 ) -> (Arc<App>, conduit_middleware::MiddlewareBuilder) {
-    git::init();
-    let config = cargo_registry::Config {
+    build_app(&simple_config(uploader))
+}
+
+fn simple_config(uploader: cargo_registry::Uploader) -> cargo_registry::Config {
+    cargo_registry::Config {
         uploader,
         session_key: "test this has to be over 32 bytes long".to_string(),
         git_repo_checkout: git::checkout(),
@@ -162,8 +183,38 @@ fn simple_app(
         // When testing we route all API traffic over HTTP so we can
         // sniff/record it, but everywhere else we use https
         api_protocol: String::from("http"),
-    };
-    let app = App::new(&config);
+        gh_timeout: Duration::from_secs(10),
+        gh_webhook_secret: Some(String::from("test webhook secret")),
+        token_lockout_threshold: 10,
+        token_lockout_window: Duration::from_secs(15 * 60),
+        gh_membership_cache_ttl: Duration::from_secs(5 * 60),
+        owner_change_limit: 10,
+        owner_change_window: Duration::from_secs(60 * 60),
+        rights_elevation_duration: Duration::from_secs(0),
+        email_confirmation_url: None,
+        require_verified_owner_email: false,
+        require_verified_publisher_email: false,
+        unverified_email_message: String::from("you must verify your email before publishing"),
+        narrow_rotated_tokens: true,
+        gh_rate_limit_reserve: 100,
+        default_token_scopes: None,
+        token_creation_limit: 50,
+        expired_token_grace_period: Duration::from_secs(0),
+        max_tokens_per_user: 500,
+    }
+}
+
+fn build_app(config: &cargo_registry::Config) -> (Arc<App>, conduit_middleware::MiddlewareBuilder) {
+    build_app_with(config, |_| ())
+}
+
+fn build_app_with<F: FnOnce(&mut App)>(
+    config: &cargo_registry::Config,
+    f: F,
+) -> (Arc<App>, conduit_middleware::MiddlewareBuilder) {
+    git::init();
+    let mut app = App::new(config);
+    f(&mut app);
     t!(t!(app.diesel_database.get()).begin_test_transaction());
     let app = Arc::new(app);
     let handler = cargo_registry::build_handler(Arc::clone(&app));
@@ -21,7 +21,7 @@ use cargo_registry::git;
 use cargo_registry::models::krate::MAX_NAME_LENGTH;
 
 use builders::{CrateBuilder, DependencyBuilder, PublishBuilder, VersionBuilder};
-use models::{Category, Crate};
+use models::{ApiToken, Category, Crate};
 use schema::{api_tokens, crates, emails, metadata, versions};
 use views::{
     EncodableCategory, EncodableCrate, EncodableDependency, EncodableKeyword, EncodableVersion,
@@ -470,6 +470,17 @@ fn show() {
     assert_eq!(json.versions[2].num, "0.5.0");
 }
 
+#[test]
+fn show_tells_whether_the_user_can_publish() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let stranger = app.db_new_user("stranger");
+    app.db(|conn| CrateBuilder::new("foo_can_publish", user.as_model().id).expect_build(conn));
+
+    assert!(user.show_crate("foo_can_publish").can_publish);
+    assert!(!stranger.show_crate("foo_can_publish").can_publish);
+    assert!(!anon.show_crate("foo_can_publish").can_publish);
+}
+
 #[test]
 fn yanked_versions_are_not_considered_for_max_version() {
     let (app, anon, user) = TestApp::init().with_user();
@@ -553,7 +564,7 @@ fn new_wrong_token() {
     // Try to publish with the wrong token (by changing the token in the database)
     app.db(|conn| {
         diesel::update(api_tokens::table)
-            .set(api_tokens::token.eq("bad"))
+            .set(api_tokens::token_hash.eq(ApiToken::hash("bad")))
             .execute(conn)
             .unwrap();
     });
@@ -619,6 +630,30 @@ fn new_krate_with_token() {
     assert_eq!(json.krate.max_version, "1.0.0");
 }
 
+#[test]
+fn new_krate_with_attributed_token() {
+    let (app, anon, user) = TestApp::with_proxy().with_user();
+    let token = user.db_new_attributed_token("ci", "release-bot");
+
+    let crate_to_publish = PublishBuilder::new("foo_new").version("1.0.0");
+    token.publish(crate_to_publish).good();
+
+    let (published_by, published_as) = app.db(|conn| {
+        versions::table
+            .select((versions::published_by, versions::published_as))
+            .first::<(Option<i32>, Option<String>)>(conn)
+            .unwrap()
+    });
+    assert_eq!(published_by, Some(user.as_model().id));
+    assert_eq!(published_as, Some("release-bot".to_string()));
+
+    let json: VersionsList = anon.get("/api/v1/crates/foo_new/versions").good();
+    assert_eq!(
+        json.versions[0].published_as,
+        Some("release-bot".to_string())
+    );
+}
+
 #[test]
 fn new_krate_weird_version() {
     let (_, _, _, token) = TestApp::with_proxy().with_token();
@@ -763,6 +798,117 @@ fn new_krate_wrong_user() {
     );
 }
 
+#[test]
+fn blocked_owner_cannot_publish() {
+    use cargo_registry::schema::blocked_users;
+
+    let (app, _, user, token) = TestApp::init().with_token();
+    app.db(|conn| {
+        CrateBuilder::new("foo_blocked", user.as_model().id).expect_build(&conn);
+        insert_into(blocked_users::table)
+            .values(blocked_users::user_id.eq(user.as_model().id))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let crate_to_publish = PublishBuilder::new("foo_blocked").version("2.0.0");
+    let json = token.publish(crate_to_publish).bad_with_status(200);
+    assert!(
+        json.errors[0]
+            .detail
+            .contains("this account has been restricted from publishing crates"),
+        "{:?}",
+        json.errors
+    );
+
+    // Reads still work, and say the owner can't publish
+    let json: CrateResponse = token.show_crate("foo_blocked");
+    assert_eq!(json.krate.name, "foo_blocked");
+    assert!(!json.can_publish);
+}
+
+#[test]
+fn blocked_owner_cannot_yank_or_change_owners() {
+    use cargo_registry::schema::blocked_users;
+
+    let (app, _, user, token) = TestApp::init().with_token();
+    app.db_new_user("bar");
+    app.db(|conn| {
+        CrateBuilder::new("foo_blocked", user.as_model().id)
+            .version("1.0.0")
+            .expect_build(&conn);
+        insert_into(blocked_users::table)
+            .values(blocked_users::user_id.eq(user.as_model().id))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let json = token.yank("foo_blocked", "1.0.0").bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "must already be an owner to yank or unyank"
+    );
+
+    let json = token
+        .add_named_owner("foo_blocked", "bar")
+        .bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "only owners have permission to modify owners"
+    );
+}
+
+#[test]
+fn logged_out_team_member_is_not_counted_as_a_member() {
+    use cargo_registry::schema::users;
+    use {add_team_to_crate, new_team};
+
+    let (app, _, owner) = TestApp::init().with_user();
+    let member = app.db_new_user("member");
+    let token = member.db_new_token("bar");
+    app.db(|conn| {
+        let team = new_team("github:org:core").create_or_update(conn).unwrap();
+        let krate = CrateBuilder::new("foo_team_yank", owner.as_model().id)
+            .version("1.0.0")
+            .expect_build(&conn);
+        add_team_to_crate(&team, &krate, owner.as_model(), conn).unwrap();
+        app.as_inner()
+            .team_memberships
+            .insert((team.github_id, member.as_model().id), true);
+        update(users::table.find(member.as_model().id))
+            .set(users::gh_access_token.eq(None::<String>))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let json = token.yank("foo_team_yank", "1.0.0").bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "must already be an owner to yank or unyank"
+    );
+}
+
+#[test]
+fn unblocked_owner_can_publish() {
+    use cargo_registry::schema::blocked_users;
+
+    let (app, _, user, token) = TestApp::with_proxy().with_token();
+    let user_id = user.as_model().id;
+    app.db(|conn| {
+        insert_into(blocked_users::table)
+            .values(blocked_users::user_id.eq(user_id))
+            .execute(conn)
+            .unwrap();
+        delete(blocked_users::table.find(user_id))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let crate_to_publish = PublishBuilder::new("foo_new").version("1.0.0");
+    let json: GoodCrate = token.publish(crate_to_publish).good();
+    assert_eq!(json.krate.name, "foo_new");
+}
+
 // TODO: Move this test to the main crate
 #[test]
 fn valid_feature_names() {
@@ -1088,6 +1234,35 @@ fn new_krate_with_verified_email_doesnt_warn() {
     assert_eq!(json.warnings.other.len(), 0);
 }
 
+#[test]
+fn publishing_requires_a_verified_email_when_configured() {
+    let (app, _, user, token) = TestApp::with_proxy_and_config(|config| {
+        config.require_verified_publisher_email = true;
+        config.unverified_email_message = String::from("verify your email first");
+    })
+    .with_token();
+
+    let crate_to_publish = PublishBuilder::new("foo_required_email");
+    let json = token.publish(crate_to_publish).bad_with_status(403);
+    assert_eq!(json.errors[0].detail, "verify your email first");
+
+    app.db(|conn| {
+        insert_into(emails::table)
+            .values((
+                emails::user_id.eq(user.as_model().id),
+                emails::email.eq("something@example.com"),
+                emails::verified.eq(true),
+            ))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let crate_to_publish = PublishBuilder::new("foo_required_email");
+    let json = token.publish(crate_to_publish).good();
+    assert_eq!(json.krate.name, "foo_required_email");
+    assert_eq!(json.warnings.other.len(), 0);
+}
+
 #[test]
 fn summary_doesnt_die() {
     let (_, anon) = TestApp::init().empty();
@@ -7,9 +7,10 @@ use models::{Crate, NewCrateOwnerInvitation};
 use schema::crate_owner_invitations;
 use util::RequestHelper;
 use views::{
-    EncodableCrateOwnerInvitation, EncodableOwner, EncodablePublicUser, InvitationResponse,
+    EncodableCrateOwnerInvitation, EncodableOwner, EncodableOwnerAction, EncodablePendingOwner,
+    EncodablePublicUser, InvitationResponse,
 };
-use {add_team_to_crate, app, new_team, new_user, req, sign_in_as, TestApp};
+use {add_team_to_crate, app, new_team, new_user, req, sign_in_as, OkBool, TestApp};
 
 #[derive(Deserialize)]
 struct TeamResponse {
@@ -20,6 +21,46 @@ struct UserResponse {
     users: Vec<EncodableOwner>,
 }
 #[derive(Deserialize)]
+struct OwnersWithInvitationsResponse {
+    users: Vec<EncodableOwner>,
+    pending_invitations: Option<Vec<EncodablePendingOwner>>,
+}
+#[derive(Deserialize)]
+struct ImportResult {
+    login: String,
+    ok: bool,
+    msg: String,
+}
+#[derive(Deserialize)]
+struct ImportResponse {
+    ok: bool,
+    results: Vec<ImportResult>,
+}
+#[derive(Deserialize)]
+struct ExplainedOwner {
+    login: String,
+    kind: String,
+}
+#[derive(Deserialize)]
+struct TeamMembership {}
+#[derive(Deserialize)]
+struct Explanation {
+    login: String,
+    owners: Vec<ExplainedOwner>,
+    direct_owner: bool,
+    teams: Vec<TeamMembership>,
+    rights: String,
+    blocked: bool,
+    email_verified: bool,
+    can_publish: bool,
+    can_change_owners: bool,
+    reasons: Vec<String>,
+}
+#[derive(Deserialize)]
+struct WhyResponse {
+    explanation: Explanation,
+}
+#[derive(Deserialize)]
 struct InvitationListResponse {
     crate_owner_invitations: Vec<EncodableCrateOwnerInvitation>,
 }
@@ -119,6 +160,93 @@ fn owners_can_remove_self() {
         .contains("only owners have permission to modify owners",));
 }
 
+#[test]
+fn owner_changes_are_rate_limited_per_crate() {
+    let (app, _, user, token) = TestApp::init_with_config(|config| {
+        config.owner_change_limit = 3;
+    })
+    .with_token();
+
+    app.db(|conn| {
+        CrateBuilder::new("foo_owner_churn", user.as_model().id).expect_build(conn);
+        CrateBuilder::new("bar_owner_churn", user.as_model().id).expect_build(conn);
+    });
+    for login in &["churn1", "churn2", "churn3", "churn4"] {
+        app.db_new_user(login);
+    }
+
+    token.add_named_owner("foo_owner_churn", "churn1").good();
+    token.add_named_owner("foo_owner_churn", "churn2").good();
+    token.add_named_owner("foo_owner_churn", "churn3").good();
+
+    let json = token
+        .add_named_owner("foo_owner_churn", "churn4")
+        .bad_with_status(429);
+    assert!(json.errors[0]
+        .detail
+        .contains("too many ownership changes to `foo_owner_churn` recently"));
+
+    // Other crates have their own limit
+    token.add_named_owner("bar_owner_churn", "churn4").good();
+}
+
+#[test]
+fn failed_owner_changes_are_not_rate_limited() {
+    let (app, _, user, token) = TestApp::init_with_config(|config| {
+        config.owner_change_limit = 2;
+    })
+    .with_token();
+
+    app.db(|conn| CrateBuilder::new("foo_failed_churn", user.as_model().id).expect_build(conn));
+    app.db_new_user("churn1");
+    app.db_new_user("churn2");
+
+    for _ in 0..3 {
+        token
+            .add_named_owner("foo_failed_churn", "not_a_user")
+            .bad_with_status(200);
+    }
+
+    token.add_named_owner("foo_failed_churn", "churn1").good();
+    token.add_named_owner("foo_failed_churn", "churn2").good();
+}
+
+#[test]
+fn new_owners_need_a_verified_email_when_required() {
+    use cargo_registry::schema::emails;
+
+    let (app, _, user, token) = TestApp::init_with_config(|config| {
+        config.require_verified_owner_email = true;
+    })
+    .with_token();
+    app.db(|conn| CrateBuilder::new("verified_owners", user.as_model().id).expect_build(conn));
+
+    let verified = app.db_new_user("verified_user");
+    app.db_new_user("unverified_user");
+    app.db(|conn| {
+        diesel::insert_into(emails::table)
+            .values((
+                emails::user_id.eq(verified.as_model().id),
+                emails::email.eq("verified@example.com"),
+                emails::verified.eq(true),
+            ))
+            .execute(conn)
+            .unwrap();
+    });
+
+    token
+        .add_named_owner("verified_owners", "verified_user")
+        .good();
+
+    let json = token
+        .add_named_owner("verified_owners", "unverified_user")
+        .bad_with_status(200);
+    assert_eq!(
+        json.errors[0].detail,
+        "`unverified_user` does not have a verified email address and cannot be added as an owner"
+    );
+}
+
 /*  Testing the crate ownership between two crates and one team.
     Given two crates, one crate owned by both a team and a user,
     one only owned by a user, check that the CrateList returned
@@ -184,6 +312,140 @@ fn check_ownership_one_crate() {
     assert_eq!(json.users[0].name, user.name);
 }
 
+#[test]
+fn owners_lists_users_and_teams_with_their_kind() {
+    use models::{CrateOwner, Owner, OwnerKind};
+
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+    let former = app.db_new_user("former_owner");
+    let former = former.as_model();
+
+    app.db(|conn| {
+        let team = new_team("github:org:mixed").create_or_update(conn).unwrap();
+        let krate = CrateBuilder::new("mixed", user.id).expect_build(conn);
+        add_team_to_crate(&team, &krate, user, conn).unwrap();
+        CrateOwner {
+            crate_id: krate.id,
+            owner_id: former.id,
+            created_by: user.id,
+            owner_kind: OwnerKind::User as i32,
+        }
+        .add(conn)
+        .unwrap();
+        CrateOwner::remove(conn, krate.id, &Owner::User(former.clone()), user.id).unwrap();
+    });
+
+    let json: UserResponse = anon.get("/api/v1/crates/mixed/owners").good();
+    let owners = json
+        .users
+        .iter()
+        .map(|o| (&*o.login, &*o.kind))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        owners,
+        vec![(&*user.gh_login, "user"), ("github:org:mixed", "team")]
+    );
+}
+
+#[test]
+fn owners_are_marked_with_whether_their_email_is_verified() {
+    use cargo_registry::schema::emails;
+    use models::{CrateOwner, OwnerKind};
+
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+    let verified = app.db_new_user("verified_owner");
+    let verified = verified.as_model();
+
+    app.db(|conn| {
+        let team = new_team("github:org:badges")
+            .create_or_update(conn)
+            .unwrap();
+        let krate = CrateBuilder::new("badges", user.id).expect_build(conn);
+        add_team_to_crate(&team, &krate, user, conn).unwrap();
+        CrateOwner {
+            crate_id: krate.id,
+            owner_id: verified.id,
+            created_by: user.id,
+            owner_kind: OwnerKind::User as i32,
+        }
+        .add(conn)
+        .unwrap();
+        diesel::insert_into(emails::table)
+            .values((
+                emails::user_id.eq(verified.id),
+                emails::email.eq("verified_owner@example.com"),
+                emails::verified.eq(true),
+            ))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let json: UserResponse = anon.get("/api/v1/crates/badges/owners").good();
+    let verified_email = |login: &str| {
+        json.users
+            .iter()
+            .find(|o| o.login == login)
+            .unwrap()
+            .verified_email
+    };
+    assert_eq!(verified_email(&user.gh_login), Some(false));
+    assert_eq!(verified_email("verified_owner"), Some(true));
+    assert_eq!(verified_email("github:org:badges"), None);
+}
+
+#[test]
+fn owning_many_groups_owners_like_loading_each_crate() {
+    use models::{CrateOwner, Owner, OwnerKind};
+
+    let (app, _, user) = TestApp::init().with_user();
+    let user = user.as_model();
+    let other = app.db_new_user("other_owner");
+    let other = other.as_model();
+
+    app.db(|conn| {
+        let team = new_team("github:org:batch").create_or_update(conn).unwrap();
+        let solo = CrateBuilder::new("batch_solo", user.id).expect_build(conn);
+        let shared = CrateBuilder::new("batch_shared", user.id).expect_build(conn);
+        let with_team = CrateBuilder::new("batch_team", other.id).expect_build(conn);
+        let orphan = CrateBuilder::new("batch_orphan", user.id).expect_build(conn);
+        add_team_to_crate(&team, &with_team, other, conn).unwrap();
+        for krate in &[&shared, &orphan] {
+            CrateOwner {
+                crate_id: krate.id,
+                owner_id: other.id,
+                created_by: user.id,
+                owner_kind: OwnerKind::User as i32,
+            }
+            .add(conn)
+            .unwrap();
+        }
+        CrateOwner::remove(conn, orphan.id, &Owner::User(user.clone()), user.id).unwrap();
+        CrateOwner::remove(conn, orphan.id, &Owner::User(other.clone()), user.id).unwrap();
+
+        let crates = vec![solo, shared, with_team, orphan];
+        let ids = crates.iter().map(|c| c.id).collect::<Vec<_>>();
+        let owners = Owner::owning_many(&ids, conn).unwrap();
+        let describe = |owners: &[Owner]| {
+            owners
+                .iter()
+                .map(|o| (o.kind(), o.id()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(owners.len(), crates.len());
+        for krate in &crates {
+            assert_eq!(
+                describe(&owners[&krate.id]),
+                describe(&krate.owners(conn).unwrap())
+            );
+        }
+        assert!(owners[&crates[3].id].is_empty());
+        assert_eq!(owners[&crates[2].id].len(), 2);
+    });
+}
+
 #[test]
 fn invitations_are_empty_by_default() {
     let (_, _, user) = TestApp::init().with_user();
@@ -212,6 +474,415 @@ fn invitations_list() {
     assert_eq!(json.crate_owner_invitations[0].crate_id, krate.id);
 }
 
+#[test]
+fn expired_invitations_cannot_be_accepted_until_renewed() {
+    use chrono::{Duration, Utc};
+
+    let (app, _, owner, token) = TestApp::init().with_token();
+    let krate =
+        app.db(|conn| CrateBuilder::new("invited_crate", owner.as_model().id).expect_build(conn));
+
+    let user = app.db_new_user("invited_user");
+    token.add_user_owner("invited_crate", user.as_model());
+
+    app.db(|conn| {
+        diesel::update(crate_owner_invitations::table)
+            .set(
+                crate_owner_invitations::created_at.eq(Utc::now().naive_utc() - Duration::days(31)),
+            )
+            .execute(conn)
+            .unwrap();
+    });
+
+    let json: InvitationListResponse = user.get("/api/v1/me/crate_owner_invitations").good();
+    assert_eq!(json.crate_owner_invitations.len(), 0);
+    let json: OwnersWithInvitationsResponse = token
+        .get_with_query("/api/v1/crates/invited_crate/owners", "include=invitations")
+        .good();
+    assert_eq!(json.pending_invitations.unwrap().len(), 0);
+
+    let body = json!({
+        "crate_owner_invite": {
+            "invited_by_username": "",
+            "crate_name": "invited_crate",
+            "crate_id": krate.id,
+            "created_at": "",
+            "accepted": true
+        }
+    });
+    let url = format!("/api/v1/me/crate_owner_invitations/{}", krate.id);
+    let json = user
+        .put::<()>(&url, body.to_string().as_bytes())
+        .bad_with_status(410);
+    assert!(json.errors[0].detail.contains("has expired"));
+    let json: UserResponse = token.get("/api/v1/crates/invited_crate/owners").good();
+    assert_eq!(json.users.len(), 1);
+
+    // Inviting the user again restarts the clock
+    token.add_user_owner("invited_crate", user.as_model());
+    let json: InvitationListResponse = user.get("/api/v1/me/crate_owner_invitations").good();
+    assert_eq!(json.crate_owner_invitations.len(), 1);
+    user.accept_ownership_invitation("invited_crate", krate.id);
+
+    let json: UserResponse = token.get("/api/v1/crates/invited_crate/owners").good();
+    assert_eq!(json.users.len(), 2);
+}
+
+#[test]
+fn owners_are_added_and_removed_case_insensitively() {
+    let (app, _, owner, token) = TestApp::init().with_token();
+    let krate =
+        app.db(|conn| CrateBuilder::new("cased_crate", owner.as_model().id).expect_build(conn));
+    let user = app.db_new_user("Cased_User");
+
+    token.add_named_owner("cased_crate", "cased_user").good();
+    let json: InvitationListResponse = user.get("/api/v1/me/crate_owner_invitations").good();
+    assert_eq!(json.crate_owner_invitations.len(), 1);
+    user.accept_ownership_invitation("cased_crate", krate.id);
+
+    let json = token
+        .add_named_owner("cased_crate", "CASED_USER")
+        .bad_with_status(200);
+    assert!(json.errors[0].detail.contains("is already an owner"));
+
+    token.remove_named_owner("cased_crate", "cASED_uSER").good();
+    let json: UserResponse = token.get("/api/v1/crates/cased_crate/owners").good();
+    assert_eq!(json.users.len(), 1);
+}
+
+#[test]
+fn owners_include_pending_invitations_for_full_owners() {
+    let (app, _, owner, token) = TestApp::init().with_token();
+    let owner = owner.as_model();
+    app.db(|conn| CrateBuilder::new("invited_crate", owner.id).expect_build(conn));
+
+    let user = app.db_new_user("invited_user");
+    token.add_user_owner("invited_crate", user.as_model());
+
+    let json: OwnersWithInvitationsResponse = token
+        .get_with_query("/api/v1/crates/invited_crate/owners", "include=invitations")
+        .good();
+    assert_eq!(json.users.len(), 1);
+    assert_eq!(json.users[0].login, owner.gh_login);
+
+    let pending = json.pending_invitations.unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].login, "invited_user");
+    assert_eq!(pending[0].invited_by, owner.gh_login);
+    assert_eq!(
+        pending[0]
+            .expires_at
+            .signed_duration_since(pending[0].created_at),
+        ::chrono::Duration::days(30)
+    );
+
+    // Without asking for them, invitations are left out
+    let json: OwnersWithInvitationsResponse =
+        token.get("/api/v1/crates/invited_crate/owners").good();
+    assert!(json.pending_invitations.is_none());
+}
+
+#[test]
+fn owners_hide_pending_invitations_from_others() {
+    let (app, anon, owner, token) = TestApp::init().with_token();
+    app.db(|conn| CrateBuilder::new("invited_crate", owner.as_model().id).expect_build(conn));
+
+    let user = app.db_new_user("invited_user");
+    token.add_user_owner("invited_crate", user.as_model());
+
+    let json: OwnersWithInvitationsResponse = user
+        .get_with_query("/api/v1/crates/invited_crate/owners", "include=invitations")
+        .good();
+    assert_eq!(json.users.len(), 1);
+    assert!(json.pending_invitations.is_none());
+
+    let json: OwnersWithInvitationsResponse = anon
+        .get_with_query("/api/v1/crates/invited_crate/owners", "include=invitations")
+        .good();
+    assert!(json.pending_invitations.is_none());
+}
+
+#[test]
+fn import_owners_reports_per_login_results() {
+    use cargo_registry::schema::emails;
+    use models::CrateOwnerInvitation;
+
+    let (app, _, owner, token) = TestApp::init().with_token();
+    let krate =
+        app.db(|conn| CrateBuilder::new("imported_crate", owner.as_model().id).expect_build(conn));
+
+    let verified = app.db_new_user("verified_user");
+    let unverified = app.db_new_user("unverified_user");
+    app.db(|conn| {
+        diesel::insert_into(emails::table)
+            .values((
+                emails::user_id.eq(verified.as_model().id),
+                emails::email.eq("verified@example.com"),
+                emails::verified.eq(true),
+            ))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let body = format!(
+        "{}\n\n  Verified_User  \n{}\nno_such_user\nfoo\n",
+        owner.as_model().gh_login,
+        unverified.as_model().gh_login
+    );
+    let json: ImportResponse = token
+        .post(
+            "/api/v1/crates/imported_crate/owners/import",
+            body.as_bytes(),
+        )
+        .good();
+    assert!(json.ok);
+
+    let results = json
+        .results
+        .iter()
+        .map(|r| (&*r.login, r.ok))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        results,
+        vec![
+            ("foo", false),
+            ("Verified_User", true),
+            ("unverified_user", false),
+            ("no_such_user", false),
+            ("foo", false),
+        ]
+    );
+    assert!(json.results[0].msg.contains("already an owner"));
+    assert!(json.results[1].msg.contains("has been invited"));
+    assert!(json.results[2].msg.contains("verified email"));
+    assert!(json.results[3].msg.contains("could not find user"));
+
+    let invited = app.db(|conn| {
+        CrateOwnerInvitation::pending_for_crate(krate.id, conn)
+            .unwrap()
+            .into_iter()
+            .map(|(_, login)| login)
+            .collect::<Vec<_>>()
+    });
+    assert_eq!(invited, vec!["verified_user"]);
+}
+
+#[test]
+fn import_owners_requires_full_rights() {
+    let (app, _, owner) = TestApp::init().with_user();
+    app.db(|conn| CrateBuilder::new("imported_crate", owner.as_model().id).expect_build(conn));
+
+    let other = app.db_new_user("other");
+    let json = other
+        .post::<()>("/api/v1/crates/imported_crate/owners/import", b"foo")
+        .bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("only owners have permission to modify owners"));
+}
+
+#[test]
+fn why_explains_missing_rights_and_unverified_email() {
+    let (app, _, owner) = TestApp::init().with_user();
+    app.db(|conn| CrateBuilder::new("explained_crate", owner.as_model().id).expect_build(conn));
+
+    let other = app.db_new_user("other");
+    let json: WhyResponse = other.get("/api/v1/crates/explained_crate/why").good();
+    let explanation = json.explanation;
+    assert_eq!(explanation.login, "other");
+    assert_eq!(explanation.owners.len(), 1);
+    assert_eq!(explanation.owners[0].login, owner.as_model().gh_login);
+    assert_eq!(explanation.owners[0].kind, "user");
+    assert!(!explanation.direct_owner);
+    assert!(explanation.teams.is_empty());
+    assert_eq!(explanation.rights, "none");
+    assert!(!explanation.email_verified);
+    assert!(!explanation.can_publish);
+    assert!(!explanation.can_change_owners);
+    assert!(explanation.reasons[0].contains("not an owner"));
+    assert!(explanation.reasons[1].contains("verified email"));
+}
+
+#[test]
+fn why_explains_full_rights() {
+    use cargo_registry::schema::emails;
+
+    let (app, _, owner) = TestApp::init().with_user();
+    app.db(|conn| {
+        CrateBuilder::new("explained_crate", owner.as_model().id).expect_build(conn);
+        diesel::insert_into(emails::table)
+            .values((
+                emails::user_id.eq(owner.as_model().id),
+                emails::email.eq("owner@example.com"),
+                emails::verified.eq(true),
+            ))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let json: WhyResponse = owner.get("/api/v1/crates/explained_crate/why").good();
+    let explanation = json.explanation;
+    assert!(explanation.direct_owner);
+    assert_eq!(explanation.rights, "full");
+    assert!(explanation.email_verified);
+    assert!(explanation.can_publish);
+    assert!(explanation.can_change_owners);
+    assert_eq!(explanation.reasons.len(), 1);
+}
+
+#[test]
+fn why_explains_blocked_owner() {
+    use cargo_registry::schema::blocked_users;
+
+    let (app, _, owner) = TestApp::init().with_user();
+    app.db(|conn| {
+        CrateBuilder::new("explained_crate", owner.as_model().id).expect_build(conn);
+        diesel::insert_into(blocked_users::table)
+            .values(blocked_users::user_id.eq(owner.as_model().id))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let json: WhyResponse = owner.get("/api/v1/crates/explained_crate/why").good();
+    let explanation = json.explanation;
+    assert!(explanation.direct_owner);
+    assert!(explanation.blocked);
+    assert_eq!(explanation.rights, "none");
+    assert!(!explanation.can_publish);
+    assert!(!explanation.can_change_owners);
+    assert!(explanation.reasons[0].contains("blocked"));
+}
+
+#[test]
+fn why_requires_login() {
+    let (app, anon, owner) = TestApp::init().with_user();
+    app.db(|conn| CrateBuilder::new("explained_crate", owner.as_model().id).expect_build(conn));
+
+    anon.get::<()>("/api/v1/crates/explained_crate/why")
+        .assert_forbidden();
+}
+
+#[derive(Deserialize)]
+struct CanPublishResponse {
+    login: String,
+    rights: String,
+    can_publish: bool,
+}
+
+#[test]
+fn can_publish_without_any_rights() {
+    let (app, _, owner) = TestApp::init().with_user();
+    app.db(|conn| CrateBuilder::new("foo_can_publish", owner.as_model().id).expect_build(conn));
+    let other = app.db_new_user("other");
+
+    let url = "/api/v1/crates/foo_can_publish/can_publish";
+    let json: CanPublishResponse = owner.get_with_query(url, "login=other").good();
+    assert_eq!(json.login, "other");
+    assert_eq!(json.rights, "none");
+    assert!(!json.can_publish);
+
+    let json: CanPublishResponse = owner
+        .get_with_query(url, &format!("login={}", owner.as_model().gh_login))
+        .good();
+    assert_eq!(json.rights, "full");
+    assert!(json.can_publish);
+
+    owner
+        .get_with_query::<()>(url, "login=nobody")
+        .bad_with_status(400);
+
+    // Only owners may ask
+    let json = other
+        .get_with_query::<()>(url, "login=other")
+        .bad_with_status(200);
+    assert!(json.errors[0].detail.contains("only owners"));
+}
+
+#[test]
+fn blocked_owner_is_listed_without_rights() {
+    use cargo_registry::schema::blocked_users;
+    use models::CrateOwner;
+
+    #[derive(Deserialize)]
+    struct OwnerCheck {
+        owner: bool,
+        restricted: bool,
+        rights: String,
+        can_publish: bool,
+    }
+
+    #[derive(Deserialize)]
+    struct EffectiveOwner {
+        login: String,
+        rights: String,
+        restricted: bool,
+    }
+
+    #[derive(Deserialize)]
+    struct EffectiveOwnersResponse {
+        users: Vec<EffectiveOwner>,
+    }
+
+    let (app, anon, owner) = TestApp::init().with_user();
+    let blocked = app.db_new_user("blocked");
+    app.db(|conn| {
+        let krate = CrateBuilder::new("foo_blocked_owner", owner.as_model().id).expect_build(conn);
+        CrateOwner {
+            crate_id: krate.id,
+            owner_id: blocked.as_model().id,
+            created_by: owner.as_model().id,
+            owner_kind: 0,
+        }
+        .add(conn)
+        .unwrap();
+        diesel::insert_into(blocked_users::table)
+            .values(blocked_users::user_id.eq(blocked.as_model().id))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let json: UserResponse = anon
+        .get("/api/v1/crates/foo_blocked_owner/owner_user")
+        .good();
+    let mut logins = json.users.iter().map(|u| &*u.login).collect::<Vec<_>>();
+    logins.sort();
+    assert_eq!(logins, ["blocked", "foo"]);
+
+    let json: OwnerCheck = owner
+        .get_with_query(
+            "/api/v1/crates/foo_blocked_owner/can_publish",
+            "login=blocked",
+        )
+        .good();
+    assert!(json.owner);
+    assert!(json.restricted);
+    assert_eq!(json.rights, "none");
+    assert!(!json.can_publish);
+
+    let json: EffectiveOwnersResponse = owner
+        .get("/api/v1/crates/foo_blocked_owner/owners/effective")
+        .good();
+    let users = json
+        .users
+        .iter()
+        .map(|u| (&*u.login, &*u.rights, u.restricted))
+        .collect::<Vec<_>>();
+    assert_eq!(users, [("blocked", "none", true), ("foo", "full", false)]);
+
+    // The rights reported for the blocked owner are the ones they're held to
+    let json = blocked
+        .get::<()>("/api/v1/crates/foo_blocked_owner/owners/effective")
+        .bad_with_status(200);
+    assert!(json.errors[0].detail.contains("only owners"));
+    let body = br#"{"users": ["foo"]}"#;
+    let json = blocked
+        .delete_with_body::<()>("/api/v1/crates/foo_blocked_owner/owners", body)
+        .bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("only owners have permission to modify owners"));
+}
+
 /*  Given a user inviting a different user to be a crate
     owner, check that the user invited can accept their
     invitation, the invitation will be deleted from
@@ -372,3 +1043,526 @@ fn test_decline_invitation() {
     let json: Q = ::json(&mut response);
     assert_eq!(json.users.len(), 1);
 }
+
+#[test]
+fn transfer_ownership_to_another_user() {
+    use schema::crate_owners;
+
+    let (app, _, owner, token) = TestApp::init().with_token();
+    let owner = owner.as_model();
+    let krate = app.db(|conn| CrateBuilder::new("transferred", owner.id).expect_build(conn));
+    app.db_new_user("successor");
+
+    let body = json!({ "user": "successor" }).to_string();
+    let json: OkBool = token
+        .post(
+            "/api/v1/crates/transferred/owners/transfer",
+            body.as_bytes(),
+        )
+        .good();
+    assert!(json.ok);
+
+    let json: UserResponse = token.get("/api/v1/crates/transferred/owners").good();
+    assert_eq!(json.users.len(), 1);
+    assert_eq!(json.users[0].login, "successor");
+
+    let deleted = app.db(|conn| {
+        crate_owners::table
+            .find((krate.id, owner.id, 0))
+            .select(crate_owners::deleted)
+            .first::<bool>(conn)
+            .unwrap()
+    });
+    assert!(deleted);
+
+    // The old owner has no say over the crate anymore
+    let body = json!({ "user": owner.gh_login }).to_string();
+    token
+        .post::<()>(
+            "/api/v1/crates/transferred/owners/transfer",
+            body.as_bytes(),
+        )
+        .assert_forbidden();
+}
+
+#[test]
+fn transfer_ownership_to_self_or_an_existing_owner() {
+    use models::{CrateOwner, OwnerKind};
+
+    let (app, _, owner, token) = TestApp::init().with_token();
+    let owner = owner.as_model();
+    let co_owner = app.db_new_user("co_owner");
+    let stranger = app.db_new_user("stranger");
+    app.db(|conn| {
+        let krate = CrateBuilder::new("transferred", owner.id).expect_build(conn);
+        CrateOwner {
+            crate_id: krate.id,
+            owner_id: co_owner.as_model().id,
+            created_by: owner.id,
+            owner_kind: OwnerKind::User as i32,
+        }
+        .add(conn)
+        .unwrap();
+    });
+    let url = "/api/v1/crates/transferred/owners/transfer";
+
+    // Handing the crate to yourself changes nothing
+    let body = json!({ "user": owner.gh_login }).to_string();
+    let json: OkBool = token.post(url, body.as_bytes()).good();
+    assert!(json.ok);
+    let json: UserResponse = token.get("/api/v1/crates/transferred/owners").good();
+    assert_eq!(json.users.len(), 2);
+
+    let body = json!({ "user": "co_owner" }).to_string();
+    let json = token.post::<()>(url, body.as_bytes()).bad_with_status(200);
+    assert!(json.errors[0].detail.contains("is already an owner"));
+
+    let body = json!({ "user": "nobody" }).to_string();
+    let json = token.post::<()>(url, body.as_bytes()).bad_with_status(200);
+    assert!(json.errors[0].detail.contains("could not find user"));
+
+    let body = json!({ "user": "stranger" }).to_string();
+    stranger.post::<()>(url, body.as_bytes()).assert_forbidden();
+
+    let json: UserResponse = token.get("/api/v1/crates/transferred/owners").good();
+    assert_eq!(json.users.len(), 2);
+}
+
+#[test]
+fn elevated_team_members_cannot_transfer_ownership() {
+    use chrono::{Duration, Utc};
+    use models::NewRightsElevation;
+
+    let (app, _, owner) = TestApp::init_with_config(|config| {
+        config.rights_elevation_duration = std::time::Duration::from_secs(60 * 60);
+    })
+    .with_user();
+    let owner = owner.as_model();
+    let member = app.db_new_user("member");
+    app.db_new_user("successor");
+    app.db(|conn| {
+        let team = new_team("github:org:elevated")
+            .create_or_update(conn)
+            .unwrap();
+        let krate = CrateBuilder::new("transferred", owner.id).expect_build(conn);
+        add_team_to_crate(&team, &krate, owner, conn).unwrap();
+        app.as_inner()
+            .team_memberships
+            .insert((team.github_id, member.as_model().id), true);
+        NewRightsElevation {
+            crate_id: krate.id,
+            user_id: member.as_model().id,
+            justification: "the only owner lost access to their account",
+            expires_at: Utc::now().naive_utc() + Duration::hours(1),
+        }
+        .create(conn)
+        .unwrap();
+    });
+
+    let body = json!({ "user": "successor" }).to_string();
+    member
+        .post::<()>(
+            "/api/v1/crates/transferred/owners/transfer",
+            body.as_bytes(),
+        )
+        .assert_forbidden();
+
+    let json: UserResponse = member.get("/api/v1/crates/transferred/owners").good();
+    let logins = json.users.iter().map(|u| &*u.login).collect::<Vec<_>>();
+    assert_eq!(logins, [&*owner.gh_login, "github:org:elevated"]);
+}
+
+#[test]
+fn transfers_count_against_the_owner_change_limit() {
+    let (app, _, owner, token) = TestApp::init_with_config(|config| {
+        config.owner_change_limit = 2;
+    })
+    .with_token();
+    app.db(|conn| CrateBuilder::new("transferred", owner.as_model().id).expect_build(conn));
+    app.db_new_user("churn");
+    app.db_new_user("successor");
+
+    token.add_named_owner("transferred", "churn").good();
+    let body = json!({ "user": "successor" }).to_string();
+    let json = token
+        .post::<()>(
+            "/api/v1/crates/transferred/owners/transfer",
+            body.as_bytes(),
+        )
+        .bad_with_status(429);
+    assert!(json.errors[0]
+        .detail
+        .contains("too many ownership changes to `transferred` recently"));
+}
+
+#[test]
+fn readding_a_removed_owner_reuses_the_row() {
+    use cargo_registry::schema::crate_owners;
+    use chrono::NaiveDateTime;
+    use models::{CrateOwner, Owner, OwnerKind};
+
+    let (app, _, user) = TestApp::init().with_user();
+    let user = user.as_model();
+    let other = app.db_new_user("other").as_model().clone();
+
+    app.db(|conn| {
+        let krate = CrateBuilder::new("owner_cycle", user.id).expect_build(conn);
+        let crate_owner = CrateOwner {
+            crate_id: krate.id,
+            owner_id: other.id,
+            created_by: user.id,
+            owner_kind: OwnerKind::User as i32,
+        };
+        let rows = || {
+            crate_owners::table
+                .filter(crate_owners::crate_id.eq(krate.id))
+                .filter(crate_owners::owner_id.eq(other.id))
+                .select((crate_owners::deleted, crate_owners::deleted_at))
+                .load::<(bool, Option<NaiveDateTime>)>(conn)
+                .unwrap()
+        };
+
+        crate_owner.add(conn).unwrap();
+        assert_eq!(rows(), vec![(false, None)]);
+
+        CrateOwner::remove(conn, krate.id, &Owner::User(other.clone()), user.id).unwrap();
+        let removed = rows();
+        assert_eq!(removed.len(), 1);
+        assert!(removed[0].0);
+        assert!(removed[0].1.is_some());
+
+        crate_owner.add(conn).unwrap();
+        assert_eq!(rows(), vec![(false, None)]);
+    });
+}
+
+#[test]
+fn publishing_tokens_lists_in_scope_tokens_of_owners() {
+    use chrono::{Duration, Utc};
+    use models::{ApiToken, CrateOwner, OwnerKind};
+    use serde_json;
+
+    #[derive(Deserialize)]
+    struct PublishingToken {
+        owner: String,
+        name: String,
+    }
+
+    #[derive(Deserialize)]
+    struct PublishingTokensResponse {
+        count: usize,
+        tokens: Vec<PublishingToken>,
+    }
+
+    let (app, _, owner) = TestApp::init().with_user();
+    let co_owner = app.db_new_user("co_owner");
+    let stranger = app.db_new_user("stranger");
+    app.db(|conn| {
+        let krate = CrateBuilder::new("pub_tokens", owner.as_model().id).expect_build(conn);
+        CrateOwner {
+            crate_id: krate.id,
+            owner_id: co_owner.as_model().id,
+            created_by: owner.as_model().id,
+            owner_kind: OwnerKind::User as i32,
+        }
+        .add(conn)
+        .unwrap();
+    });
+
+    let unscoped = owner.db_new_token("unscoped");
+    owner.db_new_scoped_token("other crate", &["other_crate"]);
+    co_owner.db_new_scoped_token("wildcard", &["pub_*"]);
+    co_owner.db_new_endpoint_scoped_token("yank only", &["yank"]);
+    co_owner.db_new_endpoint_scoped_token("publish", &["publish-update"]);
+    let revoked = co_owner.db_new_token("revoked");
+    co_owner.db_new_expiring_token("expired", Utc::now().naive_utc() - Duration::days(1));
+    stranger.db_new_token("stranger's");
+    app.db(|conn| {
+        ApiToken::revoke(conn, co_owner.as_model().id, revoked.as_model().id, "test").unwrap();
+    });
+
+    let url = "/api/v1/crates/pub_tokens/publishing_tokens";
+    let json: PublishingTokensResponse = owner.get(url).good();
+    assert_eq!(json.count, 3);
+    let tokens = json
+        .tokens
+        .iter()
+        .map(|t| (t.owner.as_str(), t.name.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tokens,
+        vec![
+            (owner.as_model().gh_login.as_str(), "unscoped"),
+            ("co_owner", "wildcard"),
+            ("co_owner", "publish"),
+        ]
+    );
+
+    let raw: serde_json::Value = owner.get(url).good();
+    assert!(!raw.to_string().contains(unscoped.plaintext()));
+
+    let json = stranger.get::<()>(url).bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("only owners have permission"));
+}
+
+#[test]
+fn publishing_tokens_leaves_out_restricted_owners() {
+    use cargo_registry::schema::{banned_users, blocked_users, users};
+    use chrono::{Duration, Utc};
+    use models::{CrateOwner, OwnerKind};
+
+    #[derive(Deserialize)]
+    struct PublishingToken {
+        owner: String,
+    }
+
+    #[derive(Deserialize)]
+    struct PublishingTokensResponse {
+        tokens: Vec<PublishingToken>,
+    }
+
+    let (app, _, owner) = TestApp::init().with_user();
+    let blocked = app.db_new_user("blocked");
+    let suspended = app.db_new_user("suspended");
+    let banned = app.db_new_user("banned");
+    app.db(|conn| {
+        let krate = CrateBuilder::new("restricted_pub", owner.as_model().id).expect_build(conn);
+        for co_owner in &[&blocked, &suspended, &banned] {
+            CrateOwner {
+                crate_id: krate.id,
+                owner_id: co_owner.as_model().id,
+                created_by: owner.as_model().id,
+                owner_kind: OwnerKind::User as i32,
+            }
+            .add(conn)
+            .unwrap();
+        }
+        diesel::insert_into(blocked_users::table)
+            .values(blocked_users::user_id.eq(blocked.as_model().id))
+            .execute(conn)
+            .unwrap();
+        diesel::update(users::table.find(suspended.as_model().id))
+            .set(users::suspended_until.eq(Utc::now().naive_utc() + Duration::days(1)))
+            .execute(conn)
+            .unwrap();
+        diesel::insert_into(banned_users::table)
+            .values((
+                banned_users::user_id.eq(banned.as_model().id),
+                banned_users::reason.eq("spam"),
+            ))
+            .execute(conn)
+            .unwrap();
+    });
+
+    owner.db_new_token("owner's");
+    blocked.db_new_token("blocked's");
+    suspended.db_new_token("suspended's");
+    banned.db_new_token("banned's");
+
+    let url = "/api/v1/crates/restricted_pub/publishing_tokens";
+    let json: PublishingTokensResponse = owner.get(url).good();
+    let owners = json
+        .tokens
+        .iter()
+        .map(|t| t.owner.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(owners, vec![owner.as_model().gh_login.as_str()]);
+}
+
+#[test]
+fn owner_changes_lists_additions_and_removals_newest_first() {
+    #[derive(Deserialize)]
+    struct OwnerChanges {
+        owner_changes: Vec<EncodableOwnerAction>,
+    }
+
+    let (app, anon, owner, token) = TestApp::init().with_token();
+    let krate = app.db(|conn| CrateBuilder::new("audited", owner.as_model().id).expect_build(conn));
+    let user = app.db_new_user("audited_user");
+
+    token.add_user_owner("audited", user.as_model());
+    user.accept_ownership_invitation("audited", krate.id);
+    token.remove_named_owner("audited", "audited_user").good();
+
+    let json: OwnerChanges = anon.get("/api/v1/crates/audited/owner_changes").good();
+    let changes = json
+        .owner_changes
+        .into_iter()
+        .map(|c| {
+            assert_eq!(c.crate_name, "audited");
+            assert_eq!(c.owner_kind, "user");
+            (c.action, c.owner.unwrap(), c.performed_by.unwrap())
+        })
+        .collect::<Vec<_>>();
+    let entry = |action: &str, owner: &str, performed_by: &str| {
+        (
+            action.to_string(),
+            owner.to_string(),
+            performed_by.to_string(),
+        )
+    };
+    let login = &owner.as_model().gh_login;
+    assert_eq!(
+        changes,
+        vec![
+            entry("remove", "audited_user", login),
+            entry("add", "audited_user", login),
+            entry("add", login, login),
+        ]
+    );
+}
+
+#[test]
+fn owner_activity_covers_only_directly_owned_crates() {
+    use models::{CrateOwner, Owner, OwnerKind};
+
+    #[derive(Deserialize)]
+    struct Activity {
+        #[serde(rename = "crate")]
+        crate_name: String,
+        action: String,
+        owner: Option<String>,
+        performed_by: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct ActivityResponse {
+        activity: Vec<Activity>,
+    }
+
+    let (app, _, user) = TestApp::init().with_user();
+    let user_model = user.as_model();
+    let alice = app.db_new_user("alice").as_model().clone();
+    let other = app.db_new_user("other").as_model().clone();
+
+    app.db(|conn| {
+        let add_alice = |crate_id, added_by| {
+            CrateOwner {
+                crate_id,
+                owner_id: alice.id,
+                created_by: added_by,
+                owner_kind: OwnerKind::User as i32,
+            }
+            .add(conn)
+            .unwrap();
+        };
+        let first = CrateBuilder::new("activity_first", user_model.id).expect_build(conn);
+        let second = CrateBuilder::new("activity_second", user_model.id).expect_build(conn);
+        let theirs = CrateBuilder::new("activity_theirs", other.id).expect_build(conn);
+        let left = CrateBuilder::new("activity_left", user_model.id).expect_build(conn);
+
+        add_alice(first.id, user_model.id);
+        add_alice(second.id, user_model.id);
+        CrateOwner::remove(conn, second.id, &Owner::User(alice.clone()), user_model.id).unwrap();
+        add_alice(theirs.id, other.id);
+        add_alice(left.id, user_model.id);
+        CrateOwner::remove(conn, left.id, &Owner::User(user_model.clone()), alice.id).unwrap();
+    });
+
+    let activity = |query: &str| {
+        let json: ActivityResponse = user
+            .get_with_query("/api/v1/me/owner_activity", query)
+            .good();
+        json.activity
+            .into_iter()
+            .map(|a| {
+                let owner = a.owner.unwrap();
+                assert_eq!(a.performed_by.as_ref().map(|s| &**s), Some("foo"));
+                (a.crate_name, a.action, owner)
+            })
+            .collect::<Vec<_>>()
+    };
+    let entry = |krate: &str, action: &str, owner: &str| {
+        (krate.to_string(), action.to_string(), owner.to_string())
+    };
+
+    assert_eq!(
+        activity(""),
+        vec![
+            entry("activity_second", "remove", "alice"),
+            entry("activity_second", "add", "alice"),
+            entry("activity_first", "add", "alice"),
+            entry("activity_second", "add", "foo"),
+            entry("activity_first", "add", "foo"),
+        ]
+    );
+    assert_eq!(
+        activity("crate=activity_first"),
+        vec![
+            entry("activity_first", "add", "alice"),
+            entry("activity_first", "add", "foo"),
+        ]
+    );
+    assert_eq!(
+        activity("action=remove&per_page=1"),
+        vec![entry("activity_second", "remove", "alice")]
+    );
+
+    let json = user
+        .get_with_query::<()>("/api/v1/me/owner_activity", "action=transfer")
+        .bad_with_status(400);
+    assert!(json.errors[0].detail.contains("invalid action `transfer`"));
+}
+
+#[test]
+fn owners_at_a_past_time() {
+    use chrono::{Duration, NaiveDateTime, Utc};
+    use diesel::update;
+    use models::{CrateOwner, Owner, OwnerKind};
+    use schema::crate_owner_actions;
+
+    let (app, anon, user) = TestApp::init().with_user();
+    let user = user.as_model();
+    let co_owner = app.db_new_user("co_owner").as_model().clone();
+    let now = Utc::now().naive_utc();
+
+    app.db(|conn| {
+        let krate = CrateBuilder::new("owners_at", user.id).expect_build(conn);
+        CrateOwner {
+            crate_id: krate.id,
+            owner_id: co_owner.id,
+            created_by: user.id,
+            owner_kind: OwnerKind::User as i32,
+        }
+        .add(conn)
+        .unwrap();
+        CrateOwner::remove(conn, krate.id, &Owner::User(user.clone()), co_owner.id).unwrap();
+
+        // Everything above happened within the test's transaction, so the
+        // changes are spread out over the last few days by hand
+        let actions = crate_owner_actions::table
+            .filter(crate_owner_actions::crate_id.eq(krate.id))
+            .order(crate_owner_actions::id)
+            .select(crate_owner_actions::id)
+            .load::<i32>(conn)
+            .unwrap();
+        assert_eq!(actions.len(), 3);
+        for (&id, &days_ago) in actions.iter().zip(&[3, 2, 1]) {
+            update(crate_owner_actions::table.find(id))
+                .set(crate_owner_actions::created_at.eq(now - Duration::days(days_ago)))
+                .execute(conn)
+                .unwrap();
+        }
+    });
+
+    let owners_at = |at: NaiveDateTime| {
+        let query = format!("timestamp={}", at.format("%Y-%m-%dT%H:%M:%SZ"));
+        let json: UserResponse = anon
+            .get_with_query("/api/v1/crates/owners_at/owners/at", &query)
+            .good();
+        json.users.into_iter().map(|o| o.login).collect::<Vec<_>>()
+    };
+    let login = user.gh_login.clone();
+    assert_eq!(owners_at(now - Duration::days(4)), Vec::<String>::new());
+    assert_eq!(owners_at(now - Duration::hours(60)), vec![login.clone()]);
+    assert_eq!(
+        owners_at(now - Duration::hours(36)),
+        vec![login, "co_owner".to_string()]
+    );
+    assert_eq!(owners_at(now), vec!["co_owner".to_string()]);
+
+    anon.get::<()>("/api/v1/crates/owners_at/owners/at")
+        .bad_with_status(400);
+}
@@ -1,11 +1,14 @@
 use diesel::*;
+use std::net::TcpListener;
 use std::sync::ONCE_INIT;
+use std::time::Duration;
 
 use super::OwnerTeamsResponse;
 use builders::{CrateBuilder, PublishBuilder};
+use cargo_registry::Uploader;
 use models::{Crate, NewUser};
 use record::GhUser;
-use {add_team_to_crate, new_team, RequestHelper, TestApp};
+use {add_team_to_crate, new_team, OkBool, RequestHelper, TestApp};
 
 impl ::util::MockAnonymousUser {
     /// List the team owners of the specified crate.
@@ -366,3 +369,598 @@ fn crates_by_team_id_not_including_deleted_owners() {
     let json = anon.search(&format!("team_id={}", team.id));
     assert_eq!(json.crates.len(), 0);
 }
+
+// Test that a GitHub API that never responds fails the request instead of hanging it
+#[test]
+fn rights_time_out_when_github_does_not_respond() {
+    // Connections to this listener are queued by the OS but never answered
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy = format!("http://{}", listener.local_addr().unwrap());
+
+    let (app, _, user) = TestApp::init_with_config(|config| {
+        config.uploader = Uploader::S3 {
+            bucket: ::s3::Bucket::new(
+                String::from("alexcrichton-test"),
+                None,
+                String::new(),
+                String::new(),
+                "http",
+            ),
+            proxy: Some(proxy),
+            cdn: None,
+        };
+        config.gh_timeout = Duration::from_millis(100);
+    })
+    .with_user();
+    let user = user.as_model();
+
+    app.db(|conn| {
+        let team = new_team("github:crates-test-org:core")
+            .create_or_update(conn)
+            .unwrap();
+        let krate = CrateBuilder::new("foo_slow_github", user.id).expect_build(conn);
+        add_team_to_crate(&team, &krate, user, conn).unwrap();
+    });
+
+    let token = app.db_new_user("user_bar").db_new_token("bar");
+    let json = token
+        .add_named_owner("foo_slow_github", "user_baz")
+        .bad_with_status(503);
+
+    assert!(
+        json.errors[0]
+            .detail
+            .contains("timed out waiting for a response from GitHub"),
+        "{:?}",
+        json.errors
+    );
+}
+
+/// Teams owning any of the user's crates are expanded into their members,
+/// and people who can publish several of those crates are only listed once.
+#[test]
+fn collaborators_across_owned_crates() {
+    use models::{CrateOwner, NewTeam};
+    use schema::crate_owners;
+    use serde_json;
+    use views::EncodableOwnerRef;
+
+    #[derive(Deserialize)]
+    struct CollaboratorsResponse {
+        users: Vec<EncodableOwnerRef>,
+    }
+
+    // The recorded team members are matched to accounts by their GitHub ids
+    let (app, _) = TestApp::with_proxy().empty();
+    let owner = app.db_new_user_with_gh_id("owner", 9_000_005);
+    let co_owner = app.db_new_user("co_owner");
+    let stranger = app.db_new_user("stranger");
+    app.db_new_user_with_gh_id("alice", 9_000_001);
+    app.db_new_user_with_gh_id("bob", 9_000_002);
+    app.db_new_user_with_gh_id("carol", 9_000_003);
+    // Not on either team
+    app.db_new_user("dave");
+
+    app.db(|conn| {
+        let owner = owner.as_model();
+        let core = NewTeam::new("github:crates-test-org:core", 1_699_377, None, None)
+            .create_or_update(conn)
+            .unwrap();
+        let just_for_crates_2 = NewTeam::new(
+            "github:crates-test-org:just-for-crates-2",
+            1_699_379,
+            None,
+            None,
+        )
+        .create_or_update(conn)
+        .unwrap();
+        let unrelated = new_team("github:crates-test-org:unrelated")
+            .create_or_update(conn)
+            .unwrap();
+
+        let krate_a = CrateBuilder::new("collab_a", owner.id).expect_build(conn);
+        add_team_to_crate(&core, &krate_a, owner, conn).unwrap();
+        insert_into(crate_owners::table)
+            .values(&CrateOwner {
+                crate_id: krate_a.id,
+                owner_id: co_owner.as_model().id,
+                created_by: owner.id,
+                owner_kind: 0,
+            })
+            .execute(conn)
+            .unwrap();
+
+        let krate_b = CrateBuilder::new("collab_b", owner.id).expect_build(conn);
+        add_team_to_crate(&just_for_crates_2, &krate_b, owner, conn).unwrap();
+
+        // Not owned by `owner`, so neither its team nor its owner are included
+        let stranger = stranger.as_model();
+        let krate_c = CrateBuilder::new("collab_c", stranger.id).expect_build(conn);
+        add_team_to_crate(&unrelated, &krate_c, stranger, conn).unwrap();
+    });
+
+    let json: serde_json::Value = owner.get("/api/v1/me/collaborators").good();
+    {
+        let mut fields = json["users"][0]
+            .as_object()
+            .unwrap()
+            .keys()
+            .collect::<Vec<_>>();
+        fields.sort();
+        assert_eq!(fields, ["avatar", "id", "login", "url"]);
+    }
+
+    let json: CollaboratorsResponse = serde_json::from_value(json).unwrap();
+    let logins = json.users.iter().map(|u| &*u.login).collect::<Vec<_>>();
+    assert_eq!(logins, vec!["alice", "bob", "carol", "co_owner"]);
+}
+
+#[derive(Deserialize)]
+struct CanPublishResponse {
+    login: String,
+    rights: String,
+    can_publish: bool,
+}
+
+#[test]
+fn can_publish_through_team() {
+    use models::NewTeam;
+
+    let (app, _) = TestApp::with_proxy().empty();
+    let owner = app.db_new_user("owner");
+    app.db_new_user(&mock_user_on_only_one_team().gh_login);
+
+    app.db(|conn| {
+        let owner = owner.as_model();
+        let core = NewTeam::new("github:crates-test-org:core", 1_699_377, None, None)
+            .create_or_update(conn)
+            .unwrap();
+        let krate = CrateBuilder::new("foo_can_publish", owner.id).expect_build(conn);
+        add_team_to_crate(&core, &krate, owner, conn).unwrap();
+    });
+
+    let json: CanPublishResponse = owner
+        .get_with_query(
+            "/api/v1/crates/foo_can_publish/can_publish",
+            "login=Crates-Tester-1",
+        )
+        .good();
+    assert_eq!(json.login, "crates-tester-1");
+    assert_eq!(json.rights, "publish");
+    assert!(json.can_publish);
+}
+
+#[test]
+fn refresh_access_picks_up_new_membership() {
+    use models::NewTeam;
+
+    #[derive(Deserialize)]
+    struct Explanation {
+        rights: String,
+    }
+
+    #[derive(Deserialize)]
+    struct WhyResponse {
+        explanation: Explanation,
+    }
+
+    #[derive(Deserialize)]
+    struct RefreshResponse {
+        rights: String,
+        can_publish: bool,
+    }
+
+    let (app, _) = TestApp::with_proxy().empty();
+    let owner = app.db_new_user("owner");
+    let user = app.db_new_user(&mock_user_on_only_one_team().gh_login);
+
+    app.db(|conn| {
+        let owner = owner.as_model();
+        let core = NewTeam::new("github:crates-test-org:core", 1_699_377, None, None)
+            .create_or_update(conn)
+            .unwrap();
+        let krate = CrateBuilder::new("foo_refresh_access", owner.id).expect_build(conn);
+        add_team_to_crate(&core, &krate, owner, conn).unwrap();
+    });
+
+    // GitHub says the user isn't on the team yet, and that answer is remembered
+    let json: WhyResponse = user.get("/api/v1/crates/foo_refresh_access/why").good();
+    assert_eq!(json.explanation.rights, "none");
+    let json: WhyResponse = user.get("/api/v1/crates/foo_refresh_access/why").good();
+    assert_eq!(json.explanation.rights, "none");
+
+    // They've since been added, which a refresh asks GitHub about again
+    let json: RefreshResponse = user
+        .post("/api/v1/crates/foo_refresh_access/refresh_access", b"")
+        .good();
+    assert_eq!(json.rights, "publish");
+    assert!(json.can_publish);
+}
+
+#[test]
+fn effective_owners_expand_teams() {
+    use models::NewTeam;
+
+    #[derive(Deserialize)]
+    struct EffectiveOwner {
+        login: String,
+        rights: String,
+        teams: Vec<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct EffectiveOwnersResponse {
+        users: Vec<EffectiveOwner>,
+    }
+
+    let (app, _) = TestApp::with_proxy().empty();
+    let owner = app.db_new_user_with_gh_id("owner", 9_000_005);
+    let member = app.db_new_user_with_gh_id(&mock_user_on_only_one_team().gh_login, 9_000_007);
+    // Took the login of a team member, but isn't the same GitHub account
+    app.db_new_user("ghost");
+
+    app.db(|conn| {
+        let owner = owner.as_model();
+        let core = NewTeam::new("github:crates-test-org:core", 1_699_377, None, None)
+            .create_or_update(conn)
+            .unwrap();
+        let krate = CrateBuilder::new("foo_effective_owners", owner.id).expect_build(conn);
+        add_team_to_crate(&core, &krate, owner, conn).unwrap();
+    });
+
+    let json: EffectiveOwnersResponse = owner
+        .get("/api/v1/crates/foo_effective_owners/owners/effective")
+        .good();
+    let users = json
+        .users
+        .iter()
+        .map(|u| (u.login.as_str(), u.rights.as_str(), u.teams.clone()))
+        .collect::<Vec<_>>();
+    let core = vec!["github:crates-test-org:core".to_string()];
+    assert_eq!(
+        users,
+        vec![
+            ("crates-tester-1", "publish", core.clone()),
+            ("owner", "full", core),
+        ]
+    );
+
+    // Team members only have publish rights, which isn't enough to see the list
+    let json = member
+        .get::<()>("/api/v1/crates/foo_effective_owners/owners/effective")
+        .bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("only owners have permission"));
+}
+
+/// Once GitHub says the rate limit is nearly used up, the remaining teams
+/// aren't expanded and the result says which were skipped.
+#[test]
+fn effective_owners_skip_teams_near_the_rate_limit() {
+    use models::NewTeam;
+
+    #[derive(Deserialize)]
+    struct EffectiveOwner {
+        login: String,
+    }
+
+    #[derive(Deserialize)]
+    struct EffectiveOwnersResponse {
+        users: Vec<EffectiveOwner>,
+        incomplete: bool,
+        skipped_teams: Vec<String>,
+    }
+
+    let (app, _) = TestApp::init().empty();
+    let owner = app.db_new_user("owner");
+    let owner_token = owner.as_model().gh_access_token.clone().unwrap();
+    app.as_inner()
+        .github_rate_limit_remaining
+        .insert(owner_token, 5);
+
+    app.db(|conn| {
+        let owner = owner.as_model();
+        let krate = CrateBuilder::new("foo_near_rate_limit", owner.id).expect_build(conn);
+        let core = NewTeam::new("github:crates-test-org:core", 1_699_377, None, None)
+            .create_or_update(conn)
+            .unwrap();
+        add_team_to_crate(&core, &krate, owner, conn).unwrap();
+        let just_for_crates_2 = NewTeam::new(
+            "github:crates-test-org:just-for-crates-2",
+            1_699_379,
+            None,
+            None,
+        )
+        .create_or_update(conn)
+        .unwrap();
+        add_team_to_crate(&just_for_crates_2, &krate, owner, conn).unwrap();
+    });
+
+    let json: EffectiveOwnersResponse = owner
+        .get("/api/v1/crates/foo_near_rate_limit/owners/effective")
+        .good();
+    let logins = json.users.iter().map(|u| &*u.login).collect::<Vec<_>>();
+    assert_eq!(logins, ["owner"]);
+    assert!(json.incomplete);
+    assert_eq!(
+        json.skipped_teams,
+        [
+            "github:crates-test-org:core",
+            "github:crates-test-org:just-for-crates-2"
+        ]
+    );
+}
+
+#[test]
+fn rights_for_crates_asks_about_each_team_once() {
+    use models::{NewTeam, Rights};
+    use std::collections::HashMap;
+
+    let (app, _) = TestApp::with_proxy().empty();
+    let member = app.db_new_user(&mock_user_on_only_one_team().gh_login);
+    let other = app.db_new_user("other");
+
+    let (crates, rights) = app.db(|conn| {
+        let member = member.as_model();
+        let other = other.as_model();
+        let core = NewTeam::new("github:crates-test-org:core", 1_699_377, None, None)
+            .create_or_update(conn)
+            .unwrap();
+
+        let owned = CrateBuilder::new("rights_owned", member.id).expect_build(conn);
+        let unrelated = CrateBuilder::new("rights_unrelated", other.id).expect_build(conn);
+        let team_a = CrateBuilder::new("rights_team_a", other.id).expect_build(conn);
+        let team_b = CrateBuilder::new("rights_team_b", other.id).expect_build(conn);
+        let owned_and_team =
+            CrateBuilder::new("rights_owned_and_team", member.id).expect_build(conn);
+        for krate in &[&team_a, &team_b, &owned_and_team] {
+            add_team_to_crate(&core, krate, other, conn).unwrap();
+        }
+
+        let crates = vec![owned, unrelated, team_a, team_b, owned_and_team];
+        let ids = crates.iter().map(|c| c.id).collect::<Vec<_>>();
+        // The recorded responses only cover a single membership check
+        let rights = member
+            .rights_for_crates(app.as_inner(), conn, &ids)
+            .unwrap();
+        (crates, rights)
+    });
+
+    let expected = crates
+        .iter()
+        .map(|c| c.id)
+        .zip(vec![
+            Rights::Full,
+            Rights::None,
+            Rights::Publish,
+            Rights::Publish,
+            Rights::Full,
+        ])
+        .collect::<HashMap<_, _>>();
+    assert_eq!(rights, expected);
+}
+
+#[test]
+fn my_access_via_lists_only_granting_teams() {
+    use models::NewTeam;
+    use views::EncodableOwner;
+
+    #[derive(Deserialize)]
+    struct AccessResponse {
+        direct_owner: bool,
+        teams: Vec<EncodableOwner>,
+    }
+
+    let (app, _) = TestApp::with_proxy().empty();
+    let owner = app.db_new_user("owner");
+    let member = app.db_new_user(&mock_user_on_only_one_team().gh_login);
+
+    app.db(|conn| {
+        let owner = owner.as_model();
+        let core = NewTeam::new("github:crates-test-org:core", 1_699_377, None, None)
+            .create_or_update(conn)
+            .unwrap();
+        let other_team = NewTeam::new(
+            "github:crates-test-org:just-for-crates-2",
+            1_699_379,
+            None,
+            None,
+        )
+        .create_or_update(conn)
+        .unwrap();
+        let krate = CrateBuilder::new("foo_my_access_via", owner.id).expect_build(conn);
+        add_team_to_crate(&core, &krate, owner, conn).unwrap();
+        add_team_to_crate(&other_team, &krate, owner, conn).unwrap();
+    });
+
+    let json: AccessResponse = member
+        .get("/api/v1/crates/foo_my_access_via/my_access_via")
+        .good();
+    assert!(!json.direct_owner);
+    let logins = json.teams.iter().map(|t| &*t.login).collect::<Vec<_>>();
+    assert_eq!(logins, vec!["github:crates-test-org:core"]);
+}
+
+#[test]
+fn my_access_via_lists_no_teams_for_blocked_members() {
+    use cargo_registry::schema::blocked_users;
+    use views::EncodableOwner;
+
+    #[derive(Deserialize)]
+    struct AccessResponse {
+        rights: String,
+        teams: Vec<EncodableOwner>,
+    }
+
+    let (app, _, owner) = TestApp::init().with_user();
+    let member = app.db_new_user("member");
+    app.db(|conn| {
+        let owner = owner.as_model();
+        let team = new_team("github:org:core").create_or_update(conn).unwrap();
+        let krate = CrateBuilder::new("foo_blocked_access", owner.id).expect_build(conn);
+        add_team_to_crate(&team, &krate, owner, conn).unwrap();
+        app.as_inner()
+            .team_memberships
+            .insert((team.github_id, member.as_model().id), true);
+        insert_into(blocked_users::table)
+            .values(blocked_users::user_id.eq(member.as_model().id))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let json: AccessResponse = member
+        .get("/api/v1/crates/foo_blocked_access/my_access_via")
+        .good();
+    assert_eq!(json.rights, "none");
+    assert!(json.teams.is_empty());
+}
+
+#[derive(Deserialize)]
+struct ElevateResponse {
+    rights: String,
+}
+
+#[test]
+fn elevated_rights_last_until_they_expire() {
+    use diesel::dsl::now;
+    use models::NewTeam;
+    use schema::rights_elevations;
+
+    let (app, _) = TestApp::with_proxy_and_config(|config| {
+        config.rights_elevation_duration = Duration::from_secs(60 * 60);
+    })
+    .empty();
+    let owner = app.db_new_user("owner");
+    let member = app.db_new_user(&mock_user_on_only_one_team().gh_login);
+    app.db_new_user("new_owner");
+
+    app.db(|conn| {
+        let owner = owner.as_model();
+        let core = NewTeam::new("github:crates-test-org:core", 1_699_377, None, None)
+            .create_or_update(conn)
+            .unwrap();
+        let krate = CrateBuilder::new("foo_elevate", owner.id).expect_build(conn);
+        add_team_to_crate(&core, &krate, owner, conn).unwrap();
+    });
+
+    let body = br#"{"justification": "the only owner lost access to their account"}"#;
+    let json: ElevateResponse = member
+        .post("/api/v1/crates/foo_elevate/elevate", body)
+        .good();
+    assert_eq!(json.rights, "full");
+
+    let justification = app.db(|conn| {
+        rights_elevations::table
+            .filter(rights_elevations::user_id.eq(member.as_model().id))
+            .select(rights_elevations::justification)
+            .first::<String>(conn)
+            .unwrap()
+    });
+    assert_eq!(justification, "the only owner lost access to their account");
+
+    // While elevated, the team member can change owners
+    let owners = br#"{"users": ["new_owner"]}"#;
+    member
+        .put::<OkBool>("/api/v1/crates/foo_elevate/owners", owners)
+        .good();
+
+    // Once the elevation expires, they're back to publish rights
+    app.db(|conn| {
+        update(rights_elevations::table)
+            .set(rights_elevations::expires_at.eq(now))
+            .execute(conn)
+            .unwrap();
+    });
+    let json = member
+        .put::<OkBool>("/api/v1/crates/foo_elevate/owners", owners)
+        .bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("team members don't have permission to modify owners"));
+}
+
+#[test]
+fn only_team_members_can_elevate_rights() {
+    let (app, _, owner) = TestApp::init_with_config(|config| {
+        config.rights_elevation_duration = Duration::from_secs(60 * 60);
+    })
+    .with_user();
+    let stranger = app.db_new_user("stranger");
+    app.db(|conn| {
+        CrateBuilder::new("foo_no_elevate", owner.as_model().id).expect_build(conn);
+    });
+
+    let url = "/api/v1/crates/foo_no_elevate/elevate";
+    let body = br#"{"justification": "just because"}"#;
+    let json = owner
+        .post::<ElevateResponse>(url, body)
+        .bad_with_status(200);
+    assert!(json.errors[0].detail.contains("already have full rights"));
+    let json = stranger
+        .post::<ElevateResponse>(url, body)
+        .bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("only members of a team that owns this crate"));
+
+    owner
+        .post::<ElevateResponse>(url, br#"{"justification": " "}"#)
+        .bad_with_status(400);
+}
+
+#[test]
+fn rights_elevation_is_off_by_default() {
+    let (app, _, owner) = TestApp::init().with_user();
+    app.db(|conn| {
+        CrateBuilder::new("foo_elevation_off", owner.as_model().id).expect_build(conn);
+    });
+
+    let json = owner
+        .post::<ElevateResponse>(
+            "/api/v1/crates/foo_elevation_off/elevate",
+            br#"{"justification": "just because"}"#,
+        )
+        .bad_with_status(400);
+    assert!(json.errors[0]
+        .detail
+        .contains("rights elevation is not allowed on this registry"));
+}
+
+#[test]
+fn team_crates_are_listed_for_members_only() {
+    use models::{CrateOwner, NewTeam, Owner};
+    use views::EncodableCrate;
+
+    #[derive(Deserialize)]
+    struct TeamCrates {
+        crates: Vec<EncodableCrate>,
+    }
+
+    let (app, _) = TestApp::with_proxy().empty();
+    let owner = app.db_new_user("owner");
+    let member = app.db_new_user(&mock_user_on_only_one_team().gh_login);
+    let stranger = app.db_new_user("stranger");
+
+    app.db(|conn| {
+        let owner = owner.as_model();
+        let core = NewTeam::new("github:crates-test-org:core", 1_699_377, None, None)
+            .create_or_update(conn)
+            .unwrap();
+        let mut removed = None;
+        for name in &["foo_team_crate", "bar_team_crate", "baz_team_crate"] {
+            let krate = CrateBuilder::new(name, owner.id).expect_build(conn);
+            add_team_to_crate(&core, &krate, owner, conn).unwrap();
+            removed = Some(krate);
+        }
+        CrateBuilder::new("foo_user_crate", owner.id).expect_build(conn);
+        CrateOwner::remove(conn, removed.unwrap().id, &Owner::Team(core), owner.id).unwrap();
+    });
+
+    let url = "/api/v1/teams/github:crates-test-org:core/crates";
+    let json: TeamCrates = member.get(url).good();
+    let names = json.crates.iter().map(|c| &*c.name).collect::<Vec<_>>();
+    assert_eq!(names, ["bar_team_crate", "foo_team_crate"]);
+
+    stranger.get::<()>(url).assert_forbidden();
+}
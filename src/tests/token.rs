@@ -1,15 +1,23 @@
 use std::collections::HashSet;
 
+use base64;
+use chrono;
 use diesel::prelude::*;
+use serde_json;
 
+use builders::CrateBuilder;
+use conduit::Method;
 use models::ApiToken;
 use schema::api_tokens;
+use util::{MockAnonymousUser, Response};
 use views::{EncodableApiTokenWithToken, EncodableMe};
-use {user::UserShowPrivateResponse, RequestHelper, TestApp};
+use {user::UserShowPrivateResponse, OkBool, RequestHelper, TestApp};
 
 #[derive(Deserialize)]
 struct DecodableApiToken {
     name: String,
+    #[serde(with = "::cargo_registry::util::rfc3339")]
+    created_at: chrono::NaiveDateTime,
 }
 
 #[derive(Deserialize)]
@@ -21,6 +29,10 @@ struct NewResponse {
     api_token: EncodableApiTokenWithToken,
 }
 #[derive(Deserialize)]
+struct ShowResponse {
+    api_token: DecodableApiToken,
+}
+#[derive(Deserialize)]
 struct RevokedResponse {}
 
 macro_rules! assert_contains {
@@ -54,8 +66,8 @@ fn list_tokens() {
     let id = user.as_model().id;
     let tokens = app.db(|conn| {
         vec![
-            t!(ApiToken::insert(conn, id, "bar")),
-            t!(ApiToken::insert(conn, id, "baz")),
+            t!(ApiToken::insert(conn, id, "bar")).model,
+            t!(ApiToken::insert(conn, id, "baz")).model,
         ]
     });
 
@@ -70,14 +82,46 @@ fn list_tokens() {
     );
 }
 
+#[test]
+fn list_tokens_newest_first_with_creation_times() {
+    use chrono::Duration;
+
+    let (app, _, user) = TestApp::init().with_user();
+
+    let older: NewResponse = user
+        .put(URL, br#"{ "api_token": { "name": "older" } }"#)
+        .good();
+    // Tokens created within one test share a timestamp, so the first is backdated
+    let created_at = older.api_token.created_at - Duration::days(1);
+    app.db(|conn| {
+        t!(diesel::update(api_tokens::table.find(older.api_token.id))
+            .set(api_tokens::created_at.eq(created_at))
+            .execute(conn))
+    });
+    let newer: NewResponse = user
+        .put(URL, br#"{ "api_token": { "name": "newer" } }"#)
+        .good();
+
+    let json: ListResponse = user.get(URL).good();
+    let tokens = json
+        .api_tokens
+        .iter()
+        .map(|t| (&*t.name, t.created_at))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tokens,
+        vec![("newer", newer.api_token.created_at), ("older", created_at),]
+    );
+}
+
 #[test]
 fn list_tokens_exclude_revoked() {
     let (app, _, user) = TestApp::init().with_user();
     let id = user.as_model().id;
     let tokens = app.db(|conn| {
         vec![
-            t!(ApiToken::insert(conn, id, "bar")),
-            t!(ApiToken::insert(conn, id, "baz")),
+            t!(ApiToken::insert(conn, id, "bar")).model,
+            t!(ApiToken::insert(conn, id, "baz")).model,
         ]
     });
 
@@ -100,6 +144,154 @@ fn list_tokens_exclude_revoked() {
         .is_none());
 }
 
+#[test]
+fn list_tokens_by_expiry() {
+    use chrono::{Duration, Utc};
+    use models::NewApiToken;
+
+    let (app, _, user) = TestApp::init().with_user();
+    let id = user.as_model().id;
+    let now = Utc::now().naive_utc();
+    app.db(|conn| {
+        let expiring = |name, expires_at| NewApiToken {
+            name,
+            expires_at: Some(expires_at),
+            ..NewApiToken::default()
+        };
+        t!(ApiToken::insert(conn, id, "never"));
+        t!(ApiToken::insert_full(
+            conn,
+            id,
+            &expiring("expired", now - Duration::days(1))
+        ));
+        t!(ApiToken::insert_full(
+            conn,
+            id,
+            &expiring("active", now + Duration::days(1))
+        ));
+    });
+
+    let names = |expiry: &str| {
+        let json: ListResponse = user
+            .get_with_query(URL, &format!("expiry={}", expiry))
+            .good();
+        let mut names = json
+            .api_tokens
+            .into_iter()
+            .map(|t| t.name)
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    };
+
+    assert_eq!(names("expired"), vec!["expired"]);
+    assert_eq!(names("active"), vec!["active", "never"]);
+    assert_eq!(names("never"), vec!["never"]);
+
+    let json: ListResponse = user.get(URL).good();
+    assert_eq!(json.api_tokens.len(), 3);
+
+    let json = user
+        .get_with_query::<()>(URL, "expiry=soon")
+        .bad_with_status(400);
+    assert_contains!(json.errors[0].detail, "invalid expiry filter `soon`");
+}
+
+#[test]
+fn list_tokens_with_filter_sort_and_pagination() {
+    use chrono::{Duration, Utc};
+    use diesel;
+    use models::NewApiToken;
+
+    #[derive(Deserialize)]
+    struct Meta {
+        more: bool,
+    }
+
+    #[derive(Deserialize)]
+    struct PagedListResponse {
+        api_tokens: Vec<DecodableApiToken>,
+        meta: Meta,
+    }
+
+    let (app, _, user) = TestApp::init().with_user();
+    let id = user.as_model().id;
+    let now = Utc::now().naive_utc();
+    app.db(|conn| {
+        t!(ApiToken::insert(conn, id, "alpha"));
+        let bravo = t!(ApiToken::insert(conn, id, "bravo")).model;
+        let charlie = t!(ApiToken::insert_full(
+            conn,
+            id,
+            &NewApiToken {
+                name: "charlie",
+                expires_at: Some(now - Duration::days(1)),
+                ..NewApiToken::default()
+            }
+        ))
+        .model;
+        let delta = t!(ApiToken::insert(conn, id, "delta")).model;
+        for &(token, hours) in &[(&bravo, 2), (&charlie, 1)] {
+            t!(diesel::update(token)
+                .set(api_tokens::last_used_at.eq(now - Duration::hours(hours)))
+                .execute(conn));
+        }
+        t!(diesel::update(&delta)
+            .set(api_tokens::revoked.eq(true))
+            .execute(conn));
+    });
+
+    let list = |query: &str| {
+        let json: PagedListResponse = user.get_with_query(URL, query).good();
+        let names = json
+            .api_tokens
+            .into_iter()
+            .map(|t| t.name)
+            .collect::<Vec<_>>();
+        (names, json.meta.more)
+    };
+
+    assert_eq!(list("").0, vec!["charlie", "bravo", "alpha"]);
+    assert_eq!(list("sort=name").0, vec!["alpha", "bravo", "charlie"]);
+    assert_eq!(list("sort=last_used").0, vec!["charlie", "bravo", "alpha"]);
+    assert_eq!(
+        list("filter=active&sort=last_used").0,
+        vec!["bravo", "alpha"]
+    );
+    assert_eq!(list("filter=active,never_used").0, vec!["alpha"]);
+    assert_eq!(list("filter=expired&sort=name").0, vec!["charlie"]);
+    assert_eq!(list("filter=revoked").0, vec!["delta"]);
+    assert_eq!(list("filter=revoked,never_used").0, vec!["delta"]);
+    assert_eq!(
+        list("sort=name&per_page=2"),
+        (vec!["alpha".to_string(), "bravo".to_string()], true)
+    );
+    assert_eq!(
+        list("sort=name&per_page=2&page=2"),
+        (vec!["charlie".to_string()], false)
+    );
+
+    let error = |query: &str| {
+        let json = user.get_with_query::<()>(URL, query).bad_with_status(400);
+        json.errors[0].detail.clone()
+    };
+
+    assert_contains!(
+        error("filter=active,expired"),
+        "the `active` and `expired` filters cannot be combined"
+    );
+    assert_contains!(
+        error("filter=never_used&sort=last_used"),
+        "cannot be sorted by when they were last used"
+    );
+    assert_contains!(error("filter=unused"), "invalid filter `unused`");
+    assert_contains!(error("sort=oldest"), "invalid sort `oldest`");
+    assert_contains!(
+        error("filter=active&expiry=never"),
+        "cannot be combined with `filter`"
+    );
+}
+
 #[test]
 fn create_token_logged_out() {
     let (_, anon) = TestApp::init().empty();
@@ -133,6 +325,199 @@ fn create_token_long_body() {
     assert_contains!(json.errors[0].detail, "max content length");
 }
 
+#[test]
+fn create_token_with_external_ref_and_filter_by_it() {
+    #[derive(Deserialize)]
+    struct ExternalRefToken {
+        name: String,
+        external_ref: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct ExternalRefList {
+        api_tokens: Vec<ExternalRefToken>,
+    }
+
+    let (_, _, user) = TestApp::init().with_user();
+    let body = br#"{ "api_token": { "name": "ci", "external_ref": "vault:secret/ci/crates-io" } }"#;
+    let json: NewResponse = user.put(URL, body).good();
+    assert_eq!(
+        json.api_token.external_ref,
+        Some("vault:secret/ci/crates-io".to_string())
+    );
+    let body = br#"{ "api_token": { "name": "release", "external_ref": "vault:secret/release" } }"#;
+    let _json: NewResponse = user.put(URL, body).good();
+    let _json: NewResponse = user.put(URL, NEW_BAR).good();
+
+    let json: ExternalRefList = user.get(URL).good();
+    let tokens = json
+        .api_tokens
+        .iter()
+        .map(|t| (&*t.name, t.external_ref.as_ref().map(|s| &**s)))
+        .collect::<HashSet<_>>();
+    let expected = vec![
+        ("ci", Some("vault:secret/ci/crates-io")),
+        ("release", Some("vault:secret/release")),
+        ("bar", None),
+    ];
+    assert_eq!(tokens, expected.into_iter().collect());
+
+    let json: ExternalRefList = user
+        .get_with_query(URL, "external_ref=vault:secret/ci/crates-io")
+        .good();
+    assert_eq!(json.api_tokens.len(), 1);
+    assert_eq!(json.api_tokens[0].name, "ci");
+
+    let body = json!({ "api_token": { "name": "long", "external_ref": "x".repeat(256) } });
+    let json = user
+        .put::<()>(URL, body.to_string().as_bytes())
+        .bad_with_status(400);
+    assert_contains!(
+        json.errors[0].detail,
+        "external_ref must be between 1 and 255"
+    );
+}
+
+#[test]
+fn creating_a_token_emails_the_user_without_its_value() {
+    use cargo_registry::email::token_created_email;
+    use chrono::NaiveDate;
+    use schema::emails;
+
+    // Users without a verified email still get their token, there's just nobody to tell.
+    let (app, _, user) = TestApp::init().with_user();
+    let body = br#"{ "api_token": { "name": "unannounced" } }"#;
+    let _: NewResponse = user.put(URL, body).good();
+
+    app.db(|conn| {
+        diesel::insert_into(emails::table)
+            .values((
+                emails::user_id.eq(user.as_model().id),
+                emails::email.eq("foo@example.com"),
+                emails::verified.eq(true),
+            ))
+            .execute(conn)
+            .unwrap();
+    });
+    let body = br#"{ "api_token": { "name": "announced" } }"#;
+    let json: NewResponse = user.put(URL, body).good();
+
+    let created_at = NaiveDate::from_ymd(2018, 12, 24).and_hms(9, 35, 17);
+    let rendered = token_created_email("foo", &json.api_token.name, created_at);
+    assert_contains!(rendered.body, "\"announced\"");
+    assert_contains!(rendered.body, "2018-12-24 09:35:17 UTC");
+    assert!(!rendered.body.contains(&json.api_token.token));
+}
+
+#[test]
+fn create_token_with_attribution_name() {
+    let (_, _, user) = TestApp::init().with_user();
+
+    let body = br#"{ "api_token": { "name": "ci", "attribution_name": " release-bot " } }"#;
+    let json: NewResponse = user.put(URL, body).good();
+    assert_eq!(
+        json.api_token.attribution_name,
+        Some("release-bot".to_string())
+    );
+
+    let body = br#"{ "api_token": { "name": "blank", "attribution_name": "  " } }"#;
+    let json = user.put::<()>(URL, body).bad_with_status(400);
+    assert_contains!(
+        json.errors[0].detail,
+        "attribution_name must be between 1 and 255"
+    );
+}
+
+#[test]
+fn list_tokens_unused_since() {
+    use chrono::{NaiveDate, Utc};
+
+    let (app, _, user) = TestApp::init().with_user();
+    let id = user.as_model().id;
+    app.db(|conn| {
+        t!(ApiToken::insert(conn, id, "never used"));
+        let recent = t!(ApiToken::insert(conn, id, "recently used")).model;
+        let stale = t!(ApiToken::insert(conn, id, "used long ago")).model;
+        for &(token, last_used_at) in &[
+            (&recent, Utc::now().naive_utc()),
+            (&stale, NaiveDate::from_ymd(2017, 3, 1).and_hms(12, 0, 0)),
+        ] {
+            t!(diesel::update(token)
+                .set(api_tokens::last_used_at.eq(last_used_at))
+                .execute(conn));
+        }
+    });
+
+    let json: ListResponse = user.get_with_query(URL, "unused_since=2018-01-01").good();
+    let names = json
+        .api_tokens
+        .into_iter()
+        .map(|t| t.name)
+        .collect::<HashSet<_>>();
+    let expected = ["never used", "used long ago"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<HashSet<_>>();
+    assert_eq!(names, expected);
+
+    let json = user
+        .get_with_query::<()>(URL, "unused_since=last-year")
+        .bad_with_status(400);
+    assert_contains!(json.errors[0].detail, "invalid unused_since date");
+}
+
+#[test]
+fn list_tokens_is_paginated() {
+    #[derive(Deserialize)]
+    struct Meta {
+        total: i64,
+        more: bool,
+    }
+
+    #[derive(Deserialize)]
+    struct PagedResponse {
+        api_tokens: Vec<DecodableApiToken>,
+        meta: Meta,
+    }
+
+    let (app, _, user) = TestApp::init().with_user();
+    let id = user.as_model().id;
+    app.db(|conn| {
+        for i in 0..101 {
+            t!(ApiToken::insert(conn, id, &format!("token {:03}", i)));
+        }
+    });
+
+    let json: PagedResponse = user.get(URL).good();
+    assert_eq!(json.api_tokens.len(), 100);
+    assert_eq!(json.meta.total, 101);
+    assert!(json.meta.more);
+
+    let json: PagedResponse = user
+        .get_with_query(URL, "sort=name&per_page=40&page=3")
+        .good();
+    let names = json
+        .api_tokens
+        .into_iter()
+        .map(|t| t.name)
+        .collect::<Vec<_>>();
+    assert_eq!(names.len(), 21);
+    assert_eq!(names[0], "token 080");
+    assert_eq!(names[20], "token 100");
+    assert_eq!(json.meta.total, 101);
+    assert!(!json.meta.more);
+
+    let json: PagedResponse = user.get_with_query(URL, "per_page=40&page=4").good();
+    assert!(json.api_tokens.is_empty());
+    assert_eq!(json.meta.total, 101);
+    assert!(!json.meta.more);
+
+    let json = user
+        .get_with_query::<()>(URL, "per_page=501")
+        .bad_with_status(200);
+    assert_contains!(json.errors[0].detail, "cannot request more than 500 items");
+}
+
 #[test]
 fn create_token_exceeded_tokens_per_user() {
     let (app, _, user) = TestApp::init().with_user();
@@ -147,6 +532,127 @@ fn create_token_exceeded_tokens_per_user() {
     assert_contains!(json.errors[0].detail, "maximum tokens per user");
 }
 
+#[test]
+fn create_token_respects_configured_tokens_per_user() {
+    let (_, _, user) = TestApp::init_with_config(|config| {
+        config.max_tokens_per_user = 2;
+    })
+    .with_user();
+
+    for _ in 0..2 {
+        let _: NewResponse = user.put(URL, NEW_BAR).good();
+    }
+    let json = user.put::<()>(URL, NEW_BAR).bad_with_status(400);
+    assert_eq!(json.errors[0].detail, "maximum tokens per user is: 2");
+}
+
+#[test]
+fn only_usable_tokens_count_towards_tokens_per_user() {
+    use chrono::Utc;
+    use models::NewApiToken;
+
+    // Revoked and expired tokens still count towards the hourly limit, exchanged ones don't
+    let (app, _, user) = TestApp::init_with_config(|config| {
+        config.max_tokens_per_user = 1;
+        config.token_creation_limit = 3;
+    })
+    .with_user();
+    let id = user.as_model().id;
+    app.db(|conn| {
+        let revoked = t!(ApiToken::insert(conn, id, "revoked")).model;
+        t!(ApiToken::revoke(conn, id, revoked.id, "testing"));
+        let expired = t!(ApiToken::insert(conn, id, "expired")).model;
+        t!(diesel::update(&expired)
+            .set(api_tokens::expires_at.eq(Some(Utc::now().naive_utc())))
+            .execute(conn));
+        // Like the tokens minted for every CI run
+        t!(ApiToken::insert_full(
+            conn,
+            id,
+            &NewApiToken {
+                name: "GitHub Actions: acme/widgets",
+                exchanged: true,
+                ..NewApiToken::default()
+            },
+        ));
+    });
+
+    let _: NewResponse = user.put(URL, NEW_BAR).good();
+    let json = user.put::<()>(URL, NEW_BAR).bad_with_status(400);
+    assert_eq!(json.errors[0].detail, "maximum tokens per user is: 1");
+}
+
+#[test]
+fn rotating_an_exchanged_token_counts_towards_tokens_per_user() {
+    use models::NewApiToken;
+
+    let (app, _, user) = TestApp::init_with_config(|config| {
+        config.max_tokens_per_user = 1;
+    })
+    .with_user();
+    let id = user.as_model().id;
+    let exchanged = app.db(|conn| {
+        t!(ApiToken::insert(conn, id, "ordinary"));
+        t!(ApiToken::insert_full(
+            conn,
+            id,
+            &NewApiToken {
+                name: "GitHub Actions: acme/widgets",
+                exchanged: true,
+                ..NewApiToken::default()
+            },
+        ))
+        .model
+    });
+
+    let url = format!("/api/v1/me/tokens/{}/rotate", exchanged.id);
+    let json = user.post::<()>(&url, b"").bad_with_status(400);
+    assert_eq!(json.errors[0].detail, "maximum tokens per user is: 1");
+
+    // Once there's room, the replacement is an ordinary token
+    app.db(|conn| {
+        t!(
+            diesel::update(api_tokens::table.filter(api_tokens::name.eq("ordinary")))
+                .set(api_tokens::revoked.eq(true))
+                .execute(conn)
+        );
+    });
+    let json: NewResponse = user.post(&url, b"").good();
+    let rotated = app.db(|conn| {
+        t!(api_tokens::table
+            .find(json.api_token.id)
+            .first::<ApiToken>(conn))
+    });
+    assert!(!rotated.exchanged);
+}
+
+#[test]
+fn create_token_rate_limited_per_user() {
+    let (app, _, user) = TestApp::init_with_config(|config| {
+        config.token_creation_limit = 3;
+    })
+    .with_user();
+    let id = user.as_model().id;
+
+    // Tokens created more than an hour ago don't count towards the limit.
+    app.db(|conn| {
+        let old = t!(ApiToken::insert(conn, id, "old")).model;
+        t!(diesel::update(&old)
+            .set(api_tokens::created_at.eq(old.created_at - chrono::Duration::hours(2)))
+            .execute(conn));
+    });
+
+    for _ in 0..3 {
+        let _: NewResponse = user.put(URL, NEW_BAR).good();
+    }
+    let json = user.put::<()>(URL, NEW_BAR).bad_with_status(429);
+    assert_contains!(json.errors[0].detail, "only 3 can be created per hour");
+
+    // Other users have their own limit
+    let other = app.db_new_user("other");
+    let _: NewResponse = other.put(URL, NEW_BAR).good();
+}
+
 #[test]
 fn create_token_success() {
     let (app, _, user) = TestApp::init().with_user();
@@ -158,83 +664,567 @@ fn create_token_success() {
     let tokens = app.db(|conn| t!(ApiToken::belonging_to(user.as_model()).load::<ApiToken>(conn)));
     assert_eq!(tokens.len(), 1);
     assert_eq!(tokens[0].name, "bar");
-    assert_eq!(tokens[0].token, json.api_token.token);
+    assert_eq!(tokens[0].token_hash, ApiToken::hash(&json.api_token.token));
     assert_eq!(tokens[0].revoked, false);
     assert_eq!(tokens[0].last_used_at, None);
 }
 
 #[test]
-fn create_token_multiple_have_different_values() {
+fn create_token_scoped_to_owned_crate() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db(|conn| CrateBuilder::new("scoped_crate", user.as_model().id).expect_build(conn));
+
+    let body = br#"{ "api_token": { "name": "bar", "crate_scopes": ["scoped_crate"] } }"#;
+    let json: NewResponse = user.put(URL, body).good();
+    assert_eq!(
+        json.api_token.crate_scopes,
+        Some(vec!["scoped_crate".to_string()])
+    );
+
+    let tokens = app.db(|conn| t!(ApiToken::belonging_to(user.as_model()).load::<ApiToken>(conn)));
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(
+        tokens[0].crate_scopes,
+        Some(vec!["scoped_crate".to_string()])
+    );
+}
+
+#[test]
+fn create_token_scoped_to_unowned_crate() {
+    let (app, _, user) = TestApp::init().with_user();
+    let other = app.db_new_user("other");
+    app.db(|conn| {
+        CrateBuilder::new("owned_crate", user.as_model().id).expect_build(conn);
+        CrateBuilder::new("unowned_crate", other.as_model().id).expect_build(conn);
+    });
+
+    let body = br#"{ "api_token": { "name": "bar", "crate_scopes": ["owned_crate", "unowned_crate", "missing_crate"] } }"#;
+    let json = user.put::<()>(URL, body).bad_with_status(400);
+    assert!(json.errors[0]
+        .detail
+        .ends_with("following crates: unowned_crate, missing_crate"));
+
+    let count = app.db(|conn| {
+        ApiToken::belonging_to(user.as_model())
+            .count()
+            .get_result(conn)
+    });
+    assert_eq!(count, Ok(0));
+}
+
+#[test]
+fn create_token_with_wildcard_scope() {
     let (_, _, user) = TestApp::init().with_user();
-    let first: NewResponse = user.put(URL, NEW_BAR).good();
-    let second: NewResponse = user.put(URL, NEW_BAR).good();
 
-    assert_ne!(first.api_token.token, second.api_token.token);
+    let body = br#"{ "api_token": { "name": "bar", "crate_scopes": ["acme-*"] } }"#;
+    let json: NewResponse = user.put(URL, body).good();
+    assert_eq!(
+        json.api_token.crate_scopes,
+        Some(vec!["acme-*".to_string()])
+    );
 }
 
 #[test]
-fn create_token_multiple_users_have_different_values() {
-    let (app, _, user1) = TestApp::init().with_user();
-    let first_token: NewResponse = user1.put(URL, NEW_BAR).good();
+fn create_token_with_invalid_wildcard_scope() {
+    let (app, _, user) = TestApp::init().with_user();
 
-    let user2 = app.db_new_user("bar");
-    let second_token: NewResponse = user2.put(URL, NEW_BAR).good();
+    let body = br#"{ "api_token": { "name": "bar", "crate_scopes": ["acme-*-utils"] } }"#;
+    let json = user.put::<()>(URL, body).bad_with_status(400);
+    assert_contains!(json.errors[0].detail, "invalid crate scope `acme-*-utils`");
 
-    assert_ne!(first_token.api_token.token, second_token.api_token.token);
+    let count = app.db(|conn| {
+        ApiToken::belonging_to(user.as_model())
+            .count()
+            .get_result(conn)
+    });
+    assert_eq!(count, Ok(0));
 }
 
 #[test]
-fn cannot_create_token_with_token() {
-    let (_, _, _, token) = TestApp::init().with_token();
-    let json = token
-        .put::<()>(
-            "/api/v1/me/tokens",
-            br#"{ "api_token": { "name": "baz" } }"#,
-        )
-        .bad_with_status(400);
+fn scoped_token_only_acts_on_matching_crates() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db_new_user("bar");
+    app.db(|conn| {
+        let user = user.as_model();
+        CrateBuilder::new("acme-utils", user.id).expect_build(conn);
+        CrateBuilder::new("exact", user.id).expect_build(conn);
+        CrateBuilder::new("other", user.id).expect_build(conn);
+    });
+    let token = user.db_new_scoped_token("scoped", &["acme-*", "exact"]);
 
+    token.add_named_owner("acme-utils", "bar").good();
+    token.add_named_owner("exact", "bar").good();
+
+    let json = token.add_named_owner("other", "bar").bad_with_status(403);
     assert_contains!(
         json.errors[0].detail,
-        "cannot use an API token to create a new API token"
+        "this API token is not scoped to the crate `other`"
     );
 }
 
 #[test]
-fn revoke_token_non_existing() {
-    let (_, _, user) = TestApp::init().with_user();
-    let _json: RevokedResponse = user.delete("/api/v1/me/tokens/5").good();
+fn create_token_with_cargo_style_scopes() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db(|conn| CrateBuilder::new("scoped_crate", user.as_model().id).expect_build(conn));
+
+    let body = br#"{ "api_token": { "name": "bar", "scopes": ["publish-update:scoped_crate", "yank:scoped_crate", "publish-update:acme-*", "yank:acme-*"] } }"#;
+    let json: NewResponse = user.put(URL, body).good();
+    assert_eq!(
+        json.api_token.crate_scopes,
+        Some(vec!["scoped_crate".to_string(), "acme-*".to_string()])
+    );
+    assert_eq!(
+        json.api_token.endpoint_scopes,
+        Some(vec!["publish-update".to_string(), "yank".to_string()])
+    );
+
+    let body = br#"{ "api_token": { "name": "baz", "scopes": ["yank:*"] } }"#;
+    let json: NewResponse = user.put(URL, body).good();
+    assert_eq!(json.api_token.crate_scopes, None);
+    assert_eq!(
+        json.api_token.endpoint_scopes,
+        Some(vec!["yank".to_string()])
+    );
 }
 
 #[test]
-fn revoke_token_doesnt_revoke_other_users_token() {
-    let (app, _, user1, token) = TestApp::init().with_token();
-    let user1 = user1.as_model();
-    let token = token.as_model();
-    let user2 = app.db_new_user("baz");
+fn create_token_with_malformed_cargo_style_scopes() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db(|conn| CrateBuilder::new("scoped_crate", user.as_model().id).expect_build(conn));
 
-    // List tokens for first user contains the token
-    app.db(|conn| {
-        let tokens = t!(ApiToken::belonging_to(user1).load::<ApiToken>(conn));
-        assert_eq!(tokens.len(), 1);
-        assert_eq!(tokens[0].name, token.name);
-    });
+    let body = br#"{ "api_token": { "name": "bar", "scopes": ["yank:scoped_crate", "unyank:scoped_crate"] } }"#;
+    let json = user.put::<()>(URL, body).bad_with_status(400);
+    assert_contains!(json.errors[0].detail, "invalid scope `unyank:scoped_crate`");
 
-    // Try revoke the token as second user
-    let _json: RevokedResponse = user2
-        .delete(&format!("/api/v1/me/tokens/{}", token.id))
-        .good();
+    let body = br#"{ "api_token": { "name": "bar", "scopes": ["publish-all"] } }"#;
+    let json = user.put::<()>(URL, body).bad_with_status(400);
+    assert_contains!(json.errors[0].detail, "invalid scope `publish-all`");
 
-    // List tokens for first user still contains the token
-    app.db(|conn| {
-        let tokens = t!(ApiToken::belonging_to(user1).load::<ApiToken>(conn));
-        assert_eq!(tokens.len(), 1);
-        assert_eq!(tokens[0].name, token.name);
+    let body = br#"{ "api_token": { "name": "bar", "scopes": ["yank:scoped*crate"] } }"#;
+    let json = user.put::<()>(URL, body).bad_with_status(400);
+    assert_contains!(json.errors[0].detail, "invalid scope `yank:scoped*crate`");
+
+    let body = br#"{ "api_token": { "name": "bar", "scopes": ["yank:scoped_crate"], "crate_scopes": ["scoped_crate"] } }"#;
+    let json = user.put::<()>(URL, body).bad_with_status(400);
+    assert_contains!(json.errors[0].detail, "cannot be given together");
+
+    let count = app.db(|conn| {
+        ApiToken::belonging_to(user.as_model())
+            .count()
+            .get_result(conn)
     });
+    assert_eq!(count, Ok(0));
 }
 
 #[test]
-fn revoke_token_success() {
-    let (app, _, user, token) = TestApp::init().with_token();
+fn create_token_with_scopes_for_different_crates() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        CrateBuilder::new("serde", user.as_model().id).expect_build(conn);
+        CrateBuilder::new("foo", user.as_model().id).expect_build(conn);
+    });
+
+    let body =
+        br#"{ "api_token": { "name": "bar", "scopes": ["publish-update:serde", "yank:foo"] } }"#;
+    let json = user.put::<()>(URL, body).bad_with_status(400);
+    assert_contains!(
+        json.errors[0].detail,
+        "scopes `publish-update` and `yank` are given for different crates"
+    );
+
+    let body =
+        br#"{ "api_token": { "name": "bar", "scopes": ["publish-update:serde", "yank:*"] } }"#;
+    let json = user.put::<()>(URL, body).bad_with_status(400);
+    assert_contains!(
+        json.errors[0].detail,
+        "scopes `publish-update` and `yank` are given for different crates"
+    );
+
+    let count = app.db(|conn| {
+        ApiToken::belonging_to(user.as_model())
+            .count()
+            .get_result(conn)
+    });
+    assert_eq!(count, Ok(0));
+
+    let body = br#"{ "api_token": { "name": "bar", "scopes": ["publish-update:serde", "yank:serde", "publish-update:foo", "yank:foo"] } }"#;
+    let json: NewResponse = user.put(URL, body).good();
+    assert_eq!(
+        json.api_token.crate_scopes,
+        Some(vec!["serde".to_string(), "foo".to_string()])
+    );
+}
+
+#[test]
+fn manage_tokens_scope_leaves_crate_scopes_alone() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db(|conn| CrateBuilder::new("serde", user.as_model().id).expect_build(conn));
+
+    let body =
+        br#"{ "api_token": { "name": "bar", "scopes": ["publish:serde", "manage-tokens"] } }"#;
+    let json: NewResponse = user.put(URL, body).good();
+    assert_eq!(json.api_token.crate_scopes, Some(vec!["serde".to_string()]));
+    assert_eq!(
+        json.api_token.endpoint_scopes,
+        Some(vec![
+            "publish-new".to_string(),
+            "publish-update".to_string(),
+            "manage-tokens".to_string(),
+        ])
+    );
+
+    let body = br#"{ "api_token": { "name": "baz", "scopes": ["manage-tokens:serde"] } }"#;
+    let json = user.put::<()>(URL, body).bad_with_status(400);
+    assert_contains!(json.errors[0].detail, "invalid scope `manage-tokens:serde`");
+}
+
+#[test]
+fn endpoint_scoped_token_only_makes_matching_changes() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db_new_user("bar");
+    app.db(|conn| CrateBuilder::new("foo_yank", user.as_model().id).expect_build(conn));
+    let token = user.db_new_endpoint_scoped_token("yank only", &["yank"]);
+
+    let json = token
+        .add_named_owner("foo_yank", "bar")
+        .bad_with_status(403);
+    assert_contains!(
+        json.errors[0].detail,
+        "this API token does not have the `change-owners` scope"
+    );
+
+    let token = user.db_new_endpoint_scoped_token("owners", &["change-owners"]);
+    token.add_named_owner("foo_yank", "bar").good();
+}
+
+#[test]
+fn create_token_with_endpoint_scopes() {
+    let (_, _, user) = TestApp::init().with_user();
+
+    let body = br#"{ "api_token": { "name": "ci", "scopes": ["publish", "yank"] } }"#;
+    let json: NewResponse = user.put(URL, body).good();
+    assert_eq!(json.api_token.crate_scopes, None);
+    assert_eq!(
+        json.api_token.endpoint_scopes,
+        Some(vec![
+            "publish-new".to_string(),
+            "publish-update".to_string(),
+            "yank".to_string(),
+        ])
+    );
+}
+
+#[test]
+fn new_tokens_get_the_configured_default_scopes() {
+    let (_, _, user) = TestApp::init().with_user();
+    let json: NewResponse = user.put(URL, NEW_BAR).good();
+    assert_eq!(json.api_token.endpoint_scopes, None);
+
+    let (app, _, user) = TestApp::init_with_config(|config| {
+        config.default_token_scopes = Some(vec!["publish-update".into()]);
+    })
+    .with_user();
+    app.db(|conn| CrateBuilder::new("scoped_crate", user.as_model().id).expect_build(conn));
+
+    let json: NewResponse = user.put(URL, NEW_BAR).good();
+    assert_eq!(json.api_token.crate_scopes, None);
+    assert_eq!(
+        json.api_token.endpoint_scopes,
+        Some(vec!["publish-update".to_string()])
+    );
+
+    let body = br#"{ "api_token": { "name": "scoped", "crate_scopes": ["scoped_crate"] } }"#;
+    let json: NewResponse = user.put(URL, body).good();
+    assert_eq!(
+        json.api_token.crate_scopes,
+        Some(vec!["scoped_crate".to_string()])
+    );
+    assert_eq!(
+        json.api_token.endpoint_scopes,
+        Some(vec!["publish-update".to_string()])
+    );
+
+    let body = br#"{ "api_token": { "name": "broad", "scopes": ["publish", "yank"] } }"#;
+    let json: NewResponse = user.put(URL, body).good();
+    assert_eq!(
+        json.api_token.endpoint_scopes,
+        Some(vec![
+            "publish-new".to_string(),
+            "publish-update".to_string(),
+            "yank".to_string(),
+        ])
+    );
+}
+
+#[test]
+fn managing_tokens_needs_the_manage_tokens_scope() {
+    let (_, _, user, legacy) = TestApp::init().with_token();
+    legacy.get::<ListResponse>(URL).good();
+
+    let publish_only = user.db_new_endpoint_scoped_token("ci", &["publish-update"]);
+    let json = publish_only.get::<()>(URL).bad_with_status(403);
+    assert_eq!(
+        json.errors[0].detail,
+        "this API token does not have the `manage-tokens` scope"
+    );
+    let url = format!("{}/{}", URL, legacy.as_model().id);
+    publish_only.delete::<()>(&url).bad_with_status(403);
+
+    let manager = user.db_new_endpoint_scoped_token("manager", &["manage-tokens"]);
+    manager.get::<ListResponse>(URL).good();
+    manager.delete::<RevokedResponse>(&url).good();
+}
+
+#[test]
+fn token_values_are_only_stored_hashed() {
+    use diesel::dsl::sql;
+    use diesel::sql_types::Text;
+
+    let (app, _, user) = TestApp::init().with_user();
+    let json: NewResponse = user.put(URL, NEW_BAR).good();
+    let plaintext = json.api_token.token;
+
+    app.db(|conn| {
+        let rows = t!(api_tokens::table
+            .select(sql::<Text>("row_to_json(api_tokens)::text"))
+            .load::<String>(conn));
+        assert_eq!(rows.len(), 1);
+        assert!(!rows[0].contains(&plaintext));
+
+        let found = t!(ApiToken::find_active(
+            conn,
+            &plaintext,
+            ::std::time::Duration::from_secs(0)
+        ));
+        assert_eq!(found.name, "bar");
+    });
+    let json: serde_json::Value = user.get(URL).good();
+    assert!(!json.to_string().contains(&plaintext));
+}
+
+#[test]
+fn tokens_revoked_after_being_found_are_not_recorded_as_used() {
+    use diesel;
+
+    let (app, _, user) = TestApp::init().with_user();
+    let id = user.as_model().id;
+    app.db(|conn| {
+        let created = t!(ApiToken::insert(conn, id, "bar"));
+        let grace = ::std::time::Duration::from_secs(0);
+        let found = t!(ApiToken::find_active(conn, &created.plaintext, grace));
+        assert!(t!(ApiToken::revoke(conn, id, found.id, "testing")));
+
+        let err = found.record_used(conn, "127.0.0.1", grace).unwrap_err();
+        assert_eq!(err, diesel::result::Error::NotFound);
+        let use_count = t!(api_tokens::table
+            .find(found.id)
+            .select(api_tokens::use_count)
+            .first::<i64>(conn));
+        assert_eq!(use_count, 0);
+    });
+}
+
+#[test]
+fn create_token_multiple_have_different_values() {
+    let (_, _, user) = TestApp::init().with_user();
+    let first: NewResponse = user.put(URL, NEW_BAR).good();
+    let second: NewResponse = user.put(URL, NEW_BAR).good();
+
+    assert_ne!(first.api_token.token, second.api_token.token);
+}
+
+#[test]
+fn create_token_multiple_users_have_different_values() {
+    let (app, _, user1) = TestApp::init().with_user();
+    let first_token: NewResponse = user1.put(URL, NEW_BAR).good();
+
+    let user2 = app.db_new_user("bar");
+    let second_token: NewResponse = user2.put(URL, NEW_BAR).good();
+
+    assert_ne!(first_token.api_token.token, second_token.api_token.token);
+}
+
+#[test]
+fn cannot_create_token_with_token() {
+    let (_, _, _, token) = TestApp::init().with_token();
+    let json = token
+        .put::<()>(
+            "/api/v1/me/tokens",
+            br#"{ "api_token": { "name": "baz" } }"#,
+        )
+        .bad_with_status(400);
+
+    assert_contains!(
+        json.errors[0].detail,
+        "cannot use an API token to create a new API token"
+    );
+}
+
+#[test]
+fn revoke_token_non_existing() {
+    let (_, _, user) = TestApp::init().with_user();
+    let _json: RevokedResponse = user.delete("/api/v1/me/tokens/5").good();
+}
+
+#[test]
+fn revoke_token_doesnt_revoke_other_users_token() {
+    let (app, _, user1, token) = TestApp::init().with_token();
+    let user1 = user1.as_model();
+    let token = token.as_model();
+    let user2 = app.db_new_user("baz");
+
+    // List tokens for first user contains the token
+    app.db(|conn| {
+        let tokens = t!(ApiToken::belonging_to(user1).load::<ApiToken>(conn));
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].name, token.name);
+    });
+
+    // Try revoke the token as second user
+    let _json: RevokedResponse = user2
+        .delete(&format!("/api/v1/me/tokens/{}", token.id))
+        .good();
+
+    // List tokens for first user still contains the token
+    app.db(|conn| {
+        let tokens = t!(ApiToken::belonging_to(user1).load::<ApiToken>(conn));
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].name, token.name);
+    });
+}
+
+#[test]
+fn rename_token_with_patch() {
+    let (app, _, user, token_user) = TestApp::init().with_token();
+    let token = token_user.as_model();
+    let url = format!("/api/v1/me/tokens/{}", token.id);
+
+    let json: serde_json::Value = user
+        .patch(&url, br#"{ "api_token": { "name": "renamed" } }"#)
+        .good();
+    assert!(!json.to_string().contains(token_user.plaintext()));
+    let json: ShowResponse = t!(serde_json::from_value(json));
+    assert_eq!(json.api_token.name, "renamed");
+
+    let empty_name = br#"{ "api_token": { "name": "" } }"#;
+    let json = user.patch::<()>(&url, empty_name).bad_with_status(400);
+    assert_eq!(json.errors[0].detail, "name must have a value");
+
+    let reloaded = app.db(|conn| t!(api_tokens::table.find(token.id).first::<ApiToken>(conn)));
+    assert_eq!(reloaded.name, "renamed");
+}
+
+#[test]
+fn rename_token_doesnt_rename_other_users_token() {
+    let (app, _, _, token) = TestApp::init().with_token();
+    let token = token.as_model();
+    let user2 = app.db_new_user("baz");
+
+    let body = br#"{ "api_token": { "name": "stolen" } }"#;
+    user2
+        .patch::<()>(&format!("/api/v1/me/tokens/{}", token.id), body)
+        .bad_with_status(404);
+
+    let reloaded = app.db(|conn| t!(api_tokens::table.find(token.id).first::<ApiToken>(conn)));
+    assert_eq!(reloaded.name, token.name);
+}
+
+#[derive(Deserialize)]
+struct SkippedToken {
+    id: i32,
+    reason: String,
+}
+
+#[derive(Deserialize)]
+struct BulkRenameResponse {
+    renamed: usize,
+    skipped: Vec<SkippedToken>,
+}
+
+#[test]
+fn bulk_rename_tokens_by_prefix() {
+    let (app, _, user) = TestApp::init().with_user();
+    let id = user.as_model().id;
+    app.db(|conn| {
+        t!(ApiToken::insert(conn, id, "oldci-linux"));
+        t!(ApiToken::insert(conn, id, "oldci-windows"));
+        t!(ApiToken::insert(conn, id, "laptop"));
+    });
+
+    let body = br#"{ "find": "oldci-", "replace": "newci-" }"#;
+    let json: BulkRenameResponse = user.patch(&format!("{}/rename", URL), body).good();
+    assert_eq!(json.renamed, 2);
+    assert!(json.skipped.is_empty());
+
+    let json: ListResponse = user.get(URL).good();
+    let names = json
+        .api_tokens
+        .into_iter()
+        .map(|t| t.name)
+        .collect::<HashSet<_>>();
+    let expected = ["newci-linux", "newci-windows", "laptop"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<HashSet<_>>();
+    assert_eq!(names, expected);
+}
+
+#[test]
+fn bulk_rename_tokens_skips_collisions() {
+    let (app, _, user) = TestApp::init().with_user();
+    let id = user.as_model().id;
+    let (colliding, other_users) = app.db(|conn| {
+        let colliding = t!(ApiToken::insert(conn, id, "oldci-linux")).model;
+        t!(ApiToken::insert(conn, id, "oldci-windows"));
+        t!(ApiToken::insert(conn, id, "newci-linux"));
+        let other = t!(::new_user("other").create_or_update(conn));
+        let other_users = t!(ApiToken::insert(conn, other.id, "oldci-mac")).model;
+        (colliding, other_users)
+    });
+
+    let body = br#"{ "find": "oldci", "replace": "newci" }"#;
+    let json: BulkRenameResponse = user.patch(&format!("{}/rename", URL), body).good();
+    assert_eq!(json.renamed, 1);
+    assert_eq!(json.skipped.len(), 1);
+    assert_eq!(json.skipped[0].id, colliding.id);
+    assert_contains!(
+        json.skipped[0].reason,
+        "a token named `newci-linux` already exists"
+    );
+
+    let names = app.db(|conn| {
+        t!(api_tokens::table
+            .filter(api_tokens::id.eq_any(vec![colliding.id, other_users.id]))
+            .order(api_tokens::id)
+            .select(api_tokens::name)
+            .load::<String>(conn))
+    });
+    assert_eq!(names, vec!["oldci-linux", "oldci-mac"]);
+
+    let body = br#"{ "find": "", "replace": "newci" }"#;
+    let json = user
+        .patch::<()>(&format!("{}/rename", URL), body)
+        .bad_with_status(400);
+    assert_contains!(json.errors[0].detail, "find must have a value");
+}
+
+#[test]
+fn bulk_rename_tokens_leaves_unchanged_names_alone() {
+    let (app, _, user) = TestApp::init().with_user();
+    let id = user.as_model().id;
+    app.db(|conn| {
+        t!(ApiToken::insert(conn, id, "ci-linux"));
+    });
+
+    let body = br#"{ "find": "ci-", "replace": "ci-" }"#;
+    let json: BulkRenameResponse = user.patch(&format!("{}/rename", URL), body).good();
+    assert_eq!(json.renamed, 0);
+    assert!(json.skipped.is_empty());
+}
+
+#[test]
+fn revoke_token_success() {
+    let (app, _, user, token) = TestApp::init().with_token();
 
     // List tokens contains the token
     app.db(|conn| {
@@ -243,19 +1233,514 @@ fn revoke_token_success() {
         assert_eq!(tokens[0].name, token.as_model().name);
     });
 
-    // Revoke the token
-    let _json: RevokedResponse = user
-        .delete(&format!("/api/v1/me/tokens/{}", token.as_model().id))
+    // Revoke the token
+    let _json: RevokedResponse = user
+        .delete(&format!("/api/v1/me/tokens/{}", token.as_model().id))
+        .good();
+
+    // List tokens no longer contains the token
+    app.db(|conn| {
+        let count = ApiToken::belonging_to(user.as_model())
+            .filter(api_tokens::revoked.eq(false))
+            .count()
+            .get_result(conn);
+        assert_eq!(count, Ok(0));
+    });
+}
+
+#[derive(Deserialize)]
+struct RevokedAllResponse {
+    revoked: usize,
+}
+
+fn active_token_count(app: &TestApp, user: &::util::MockCookieUser) -> i64 {
+    app.db(|conn| {
+        t!(ApiToken::belonging_to(user.as_model())
+            .filter(api_tokens::revoked.eq(false))
+            .count()
+            .get_result(conn))
+    })
+}
+
+#[test]
+fn revoke_all_tokens_requires_confirmation() {
+    let (app, _, user) = TestApp::init().with_user();
+    user.db_new_token("foo");
+    user.db_new_token("bar");
+
+    let json = user.delete::<()>(URL).bad_with_status(400);
+    assert_contains!(json.errors[0].detail, "confirmation required");
+    let json = user
+        .delete_with_body::<()>(URL, br#"{ "confirm": false }"#)
+        .bad_with_status(400);
+    assert_contains!(json.errors[0].detail, "confirmation required");
+    let mut request = user.request_builder(Method::Delete, URL);
+    request.with_query("confirm=yes");
+    user.run::<()>(&mut request).bad_with_status(400);
+
+    assert_eq!(active_token_count(&app, &user), 2);
+}
+
+#[test]
+fn revoke_all_tokens_when_confirmed() {
+    let (app, _, user) = TestApp::init().with_user();
+    user.db_new_token("foo");
+    user.db_new_token("bar");
+
+    let mut request = user.request_builder(Method::Delete, URL);
+    request.with_query("confirm=true");
+    let json: RevokedAllResponse = user.run(&mut request).good();
+    assert_eq!(json.revoked, 2);
+    assert_eq!(active_token_count(&app, &user), 0);
+
+    user.db_new_token("baz");
+    let json: RevokedAllResponse = user.delete_with_body(URL, br#"{ "confirm": true }"#).good();
+    assert_eq!(json.revoked, 1);
+    assert_eq!(active_token_count(&app, &user), 0);
+}
+
+#[test]
+fn revoke_all_tokens_only_revokes_the_callers_tokens_once() {
+    let (app, _, user) = TestApp::init().with_user();
+    let other = app.db_new_user("other");
+    user.db_new_token("foo");
+    user.db_new_token("bar");
+    other.db_new_token("baz");
+
+    let body = br#"{ "confirm": true }"#;
+    let json: RevokedAllResponse = user.delete_with_body(URL, body).good();
+    assert_eq!(json.revoked, 2);
+    assert_eq!(active_token_count(&app, &user), 0);
+    assert_eq!(active_token_count(&app, &other), 1);
+
+    let json: RevokedAllResponse = user.delete_with_body(URL, body).good();
+    assert_eq!(json.revoked, 0);
+    assert_eq!(active_token_count(&app, &other), 1);
+}
+
+#[test]
+fn insert_token_with_defaults() {
+    let (app, _, user) = TestApp::init().with_user();
+
+    let token = app.db(|conn| t!(ApiToken::insert(conn, user.as_model().id, "simple")).model);
+    assert_eq!(token.name, "simple");
+    assert!(!token.revoked);
+    assert_eq!(token.crate_scopes, None);
+    assert_eq!(token.expires_at, None);
+}
+
+#[test]
+fn insert_token_with_scopes_and_expiry() {
+    use chrono::NaiveDate;
+    use models::NewApiToken;
+
+    let (app, _, user) = TestApp::init().with_user();
+    let expires_at = NaiveDate::from_ymd(2030, 1, 1).and_hms(0, 0, 0);
+
+    let token = app.db(|conn| {
+        let new_token = NewApiToken {
+            name: "full",
+            crate_scopes: Some(vec!["foo".into(), "bar".into()]),
+            expires_at: Some(expires_at),
+            ..NewApiToken::default()
+        };
+        t!(ApiToken::insert_full(conn, user.as_model().id, &new_token)).model
+    });
+    assert_eq!(token.name, "full");
+    assert_eq!(
+        token.crate_scopes,
+        Some(vec!["foo".to_string(), "bar".to_string()])
+    );
+    assert_eq!(token.expires_at, Some(expires_at));
+
+    let reloaded = app.db(|conn| t!(api_tokens::table.find(token.id).first::<ApiToken>(conn)));
+    assert_eq!(reloaded, token);
+}
+
+/// Creates a token for `user` with the given crate and endpoint scopes
+fn scoped_token(
+    app: &TestApp,
+    user: &::util::MockCookieUser,
+    name: &str,
+    crate_scopes: &[&str],
+    endpoint_scopes: &[&str],
+) -> ApiToken {
+    use models::NewApiToken;
+
+    let to_strings = |scopes: &[&str]| scopes.iter().map(|s| s.to_string()).collect();
+    let new_token = NewApiToken {
+        name,
+        crate_scopes: Some(to_strings(crate_scopes)),
+        endpoint_scopes: Some(to_strings(endpoint_scopes)),
+        ..NewApiToken::default()
+    };
+    app.db(|conn| t!(ApiToken::insert_full(conn, user.as_model().id, &new_token)).model)
+}
+
+#[test]
+fn merge_tokens_combines_their_scopes() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        CrateBuilder::new("merged_a", user.as_model().id).expect_build(conn);
+        CrateBuilder::new("merged_b", user.as_model().id).expect_build(conn);
+        CrateBuilder::new("merged_c", user.as_model().id).expect_build(conn);
+    });
+    let first = scoped_token(
+        &app,
+        &user,
+        "a",
+        &["merged_a", "merged_b"],
+        &["publish-update"],
+    );
+    let second = scoped_token(&app, &user, "b", &["merged_b", "merged_a"], &["yank"]);
+
+    let body = json!({ "ids": [first.id, second.id], "name": "ci" });
+    let json: NewResponse = user
+        .post("/api/v1/me/tokens/merge", body.to_string().as_bytes())
+        .good();
+    assert_eq!(json.api_token.name, "ci");
+    assert_eq!(
+        json.api_token.crate_scopes,
+        Some(vec!["merged_a".to_string(), "merged_b".to_string()])
+    );
+    assert_eq!(
+        json.api_token.endpoint_scopes,
+        Some(vec!["publish-update".to_string(), "yank".to_string()])
+    );
+    assert_eq!(active_token_count(&app, &user), 3);
+
+    // Tokens for the same endpoints get their crates combined instead
+    let third = scoped_token(&app, &user, "c", &["merged_c"], &["yank"]);
+    let body = json!({ "ids": [second.id, third.id], "name": "yanker" });
+    let json: NewResponse = user
+        .post("/api/v1/me/tokens/merge", body.to_string().as_bytes())
+        .good();
+    assert_eq!(
+        json.api_token.crate_scopes,
+        Some(vec![
+            "merged_b".to_string(),
+            "merged_a".to_string(),
+            "merged_c".to_string(),
+        ])
+    );
+    assert_eq!(
+        json.api_token.endpoint_scopes,
+        Some(vec!["yank".to_string()])
+    );
+    assert_eq!(active_token_count(&app, &user), 5);
+
+    let body = json!({ "ids": [first.id, second.id], "name": "ci", "revoke_originals": true });
+    let merged: NewResponse = user
+        .post("/api/v1/me/tokens/merge", body.to_string().as_bytes())
+        .good();
+    assert_eq!(active_token_count(&app, &user), 4);
+    let json: HistoryResponse = user
+        .get(&format!("/api/v1/me/tokens/{}/history", first.id))
+        .good();
+    let last = json.events.last().unwrap();
+    assert_eq!(last.kind, "revoked");
+    assert_eq!(
+        last.detail,
+        Some(format!(
+            "revoked when merged into token {}",
+            merged.api_token.id
+        ))
+    );
+}
+
+#[test]
+fn merge_tokens_rejects_scopes_beyond_the_users_rights() {
+    let (app, _, user) = TestApp::init().with_user();
+    let other = app.db_new_user("other");
+    app.db(|conn| {
+        CrateBuilder::new("merged_a", user.as_model().id).expect_build(conn);
+        CrateBuilder::new("not_theirs", other.as_model().id).expect_build(conn);
+    });
+    let first = scoped_token(&app, &user, "a", &["merged_a"], &["publish-update"]);
+    let second = scoped_token(&app, &user, "b", &["not_theirs"], &["publish-update"]);
+
+    let body = json!({ "ids": [first.id, second.id], "name": "ci" });
+    let json = user
+        .post::<()>("/api/v1/me/tokens/merge", body.to_string().as_bytes())
+        .bad_with_status(400);
+    assert_contains!(json.errors[0].detail, "not_theirs");
+
+    let body = json!({ "ids": [first.id, first.id], "name": "ci" });
+    let json = user
+        .post::<()>("/api/v1/me/tokens/merge", body.to_string().as_bytes())
+        .bad_with_status(400);
+    assert_contains!(json.errors[0].detail, "exactly two different tokens");
+    assert_eq!(active_token_count(&app, &user), 2);
+}
+
+#[test]
+fn merge_tokens_rejects_tokens_differing_in_both_crates_and_endpoints() {
+    let (app, _, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        CrateBuilder::new("merged_a", user.as_model().id).expect_build(conn);
+        CrateBuilder::new("merged_b", user.as_model().id).expect_build(conn);
+    });
+    let first = scoped_token(&app, &user, "a", &["merged_a"], &["publish-update"]);
+    let second = scoped_token(&app, &user, "b", &["merged_b"], &["yank"]);
+
+    // Merging would allow publishing `merged_b` and yanking `merged_a`
+    let body = json!({ "ids": [first.id, second.id], "name": "ci" });
+    let json = user
+        .post::<()>("/api/v1/me/tokens/merge", body.to_string().as_bytes())
+        .bad_with_status(400);
+    assert_contains!(
+        json.errors[0].detail,
+        "same crates or to the same endpoints"
+    );
+    assert_eq!(active_token_count(&app, &user), 2);
+}
+
+#[derive(Deserialize)]
+struct TokenEvent {
+    kind: String,
+    detail: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct HistoryResponse {
+    events: Vec<TokenEvent>,
+}
+
+#[test]
+fn token_history_lists_events_in_order() {
+    let (_, _, user) = TestApp::init().with_user();
+
+    let json: NewResponse = user.put(URL, NEW_BAR).good();
+    let token = json.api_token;
+    let url = format!("/api/v1/me/tokens/{}", token.id);
+
+    let body = br#"{ "api_token": { "name": "baz" } }"#;
+    let json: ShowResponse = user.put(&url, body).good();
+    assert_eq!(json.api_token.name, "baz");
+
+    let _json: RevokedResponse = user.delete(&url).good();
+
+    let json: serde_json::Value = user.get(&format!("{}/history", url)).good();
+    assert!(!json.to_string().contains(&token.token));
+    let json: HistoryResponse = t!(serde_json::from_value(json));
+    let events = json
+        .events
+        .iter()
+        .map(|e| (&*e.kind, e.detail.as_ref().map(|s| &**s)))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        vec![
+            ("created", None),
+            ("renamed", Some("renamed from `bar` to `baz`")),
+            ("revoked", Some("revoked by its owner")),
+        ]
+    );
+}
+
+/// Creates a token for `user` scoped to `rotated_crate` and `acme-*` that expires in 10 days
+fn rotatable_token(app: &TestApp, user: &::util::MockCookieUser) -> ApiToken {
+    use chrono::{Duration, Utc};
+    use models::NewApiToken;
+
+    app.db(|conn| {
+        CrateBuilder::new("rotated_crate", user.as_model().id).expect_build(conn);
+        CrateBuilder::new("unscoped_crate", user.as_model().id).expect_build(conn);
+        let new_token = NewApiToken {
+            name: "ci",
+            crate_scopes: Some(vec!["rotated_crate".into(), "acme-*".into()]),
+            expires_at: Some(Utc::now().naive_utc() + Duration::days(10)),
+            ..NewApiToken::default()
+        };
+        ApiToken::insert_full(conn, user.as_model().id, &new_token)
+            .unwrap()
+            .model
+    })
+}
+
+#[test]
+fn rotated_token_inherits_scopes_and_expiry() {
+    let (app, _, user) = TestApp::init().with_user();
+    let token = rotatable_token(&app, &user);
+    let url = format!("/api/v1/me/tokens/{}", token.id);
+
+    let json: NewResponse = user.post(&format!("{}/rotate", url), b"").good();
+    let rotated = json.api_token;
+    assert_ne!(rotated.id, token.id);
+    assert_ne!(ApiToken::hash(&rotated.token), token.token_hash);
+    assert_eq!(rotated.name, "ci");
+    assert_eq!(rotated.crate_scopes, token.crate_scopes);
+    assert_eq!(rotated.expires_at, token.expires_at);
+
+    user.get::<()>(&url).bad_with_status(410);
+    let json: HistoryResponse = user.get(&format!("{}/history", url)).good();
+    let last = json.events.last().unwrap();
+    assert_eq!(last.kind, "rotated");
+    assert_eq!(
+        last.detail,
+        Some(format!("replaced by token {}", rotated.id))
+    );
+}
+
+#[test]
+fn rotated_token_takes_narrowing_overrides() {
+    use chrono::{Duration, Utc};
+
+    let (app, _, user) = TestApp::init().with_user();
+    let token = rotatable_token(&app, &user);
+    let expires_at = Utc::now().naive_utc() + Duration::days(1);
+
+    let body = json!({
+        "api_token": {
+            "name": "narrowed",
+            "crate_scopes": ["acme-utils-*"],
+            "expires_at": chrono::DateTime::<Utc>::from_utc(expires_at, Utc).to_rfc3339(),
+        }
+    });
+    let json: NewResponse = user
+        .post(
+            &format!("/api/v1/me/tokens/{}/rotate", token.id),
+            body.to_string().as_bytes(),
+        )
+        .good();
+    let rotated = json.api_token;
+    assert_eq!(rotated.name, "narrowed");
+    assert_eq!(rotated.crate_scopes, Some(vec!["acme-utils-*".to_string()]));
+    let rotated_expiry = rotated.expires_at.unwrap();
+    assert!(rotated_expiry.signed_duration_since(expires_at) < Duration::seconds(1));
+}
+
+#[test]
+fn rotated_token_cannot_be_broadened() {
+    use chrono::{Duration, Utc};
+
+    let (app, _, user) = TestApp::init().with_user();
+    let token = rotatable_token(&app, &user);
+    let url = format!("/api/v1/me/tokens/{}/rotate", token.id);
+
+    let body = br#"{ "api_token": { "crate_scopes": ["rotated_crate", "unscoped_crate"] } }"#;
+    let json = user.post::<()>(&url, body).bad_with_status(400);
+    assert_contains!(
+        json.errors[0].detail,
+        "`unscoped_crate` is not covered by the current scopes"
+    );
+
+    let expires_at = Utc::now() + Duration::days(30);
+    let body = json!({ "api_token": { "expires_at": expires_at.to_rfc3339() } });
+    let json = user
+        .post::<()>(&url, body.to_string().as_bytes())
+        .bad_with_status(400);
+    assert_contains!(
+        json.errors[0].detail,
+        "rotating a token cannot extend when it expires"
+    );
+
+    // The token is still usable after the failed rotations
+    user.get::<()>(&format!("/api/v1/me/tokens/{}", token.id))
+        .assert_status(200);
+}
+
+#[test]
+fn expired_token_cannot_be_rotated() {
+    use chrono::{Duration, Utc};
+
+    let (_, _, user) = TestApp::init_with_config(|config| {
+        config.narrow_rotated_tokens = false;
+    })
+    .with_user();
+    let expired = user.db_new_expiring_token("expired", Utc::now().naive_utc() - Duration::days(1));
+    let url = format!("/api/v1/me/tokens/{}/rotate", expired.as_model().id);
+
+    let expires_at = Utc::now() + Duration::days(30);
+    let body = json!({ "api_token": { "expires_at": expires_at.to_rfc3339() } });
+    let json = user
+        .post::<()>(&url, body.to_string().as_bytes())
+        .bad_with_status(410);
+    assert_contains!(
+        json.errors[0].detail,
+        "this token has expired and cannot be rotated"
+    );
+}
+
+#[test]
+fn token_history_of_other_user_is_not_found() {
+    let (app, _, _, token) = TestApp::init().with_token();
+    let other = app.db_new_user("other");
+
+    other
+        .get::<()>(&format!(
+            "/api/v1/me/tokens/{}/history",
+            token.as_model().id
+        ))
+        .assert_not_found();
+}
+
+#[test]
+fn show_token_success() {
+    let (_, _, user, token) = TestApp::init().with_token();
+
+    let json: ShowResponse = user
+        .get(&format!("/api/v1/me/tokens/{}", token.as_model().id))
         .good();
+    assert_eq!(json.api_token.name, token.as_model().name);
+}
 
-    // List tokens no longer contains the token
-    app.db(|conn| {
-        let count = ApiToken::belonging_to(user.as_model())
-            .filter(api_tokens::revoked.eq(false))
-            .count()
-            .get_result(conn);
-        assert_eq!(count, Ok(0));
-    });
+#[test]
+fn show_token_has_the_list_fields_but_not_the_value() {
+    #[derive(Deserialize)]
+    struct ShownToken {
+        name: String,
+        #[serde(with = "::cargo_registry::util::rfc3339")]
+        created_at: chrono::NaiveDateTime,
+        #[serde(with = "::cargo_registry::util::rfc3339::option")]
+        last_used_at: Option<chrono::NaiveDateTime>,
+        token: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct ShownResponse {
+        api_token: ShownToken,
+    }
+
+    let (_, _, user, token) = TestApp::init().with_token();
+    let url = format!("/api/v1/me/tokens/{}", token.as_model().id);
+
+    let json: ShownResponse = user.get(&url).good();
+    assert_eq!(json.api_token.name, token.as_model().name);
+    assert_eq!(json.api_token.created_at, token.as_model().created_at);
+    assert_eq!(json.api_token.last_used_at, None);
+    assert_eq!(json.api_token.token, None);
+
+    token.get::<EncodableMe>("/api/v1/me").good();
+    let json: ShownResponse = user.get(&url).good();
+    assert!(json.api_token.last_used_at.is_some());
+}
+
+#[test]
+fn show_revoked_token_is_gone() {
+    let (_, _, user, token) = TestApp::init().with_token();
+    let url = format!("/api/v1/me/tokens/{}", token.as_model().id);
+
+    let _json: RevokedResponse = user.delete(&url).good();
+
+    let json = user.get::<()>(&url).bad_with_status(410);
+    assert_contains!(json.errors[0].detail, "has been revoked");
+}
+
+#[test]
+fn show_token_non_existing() {
+    let (_, _, user) = TestApp::init().with_user();
+    user.get::<()>("/api/v1/me/tokens/5").assert_not_found();
+}
+
+#[test]
+fn show_token_of_other_user_is_not_found() {
+    let (app, _, _, token) = TestApp::init().with_token();
+    let user2 = app.db_new_user("baz");
+
+    user2
+        .get::<()>(&format!("/api/v1/me/tokens/{}", token.as_model().id))
+        .assert_not_found();
 }
 
 #[test]
@@ -288,3 +1773,684 @@ fn using_token_updates_last_used_at() {
     // based on the start of the database transaction so it doesn't work in
     // this test framework.
 }
+
+#[test]
+fn using_token_counts_its_uses() {
+    #[derive(Deserialize)]
+    struct CountedToken {
+        use_count: i64,
+    }
+
+    #[derive(Deserialize)]
+    struct CountedList {
+        api_tokens: Vec<CountedToken>,
+    }
+
+    #[derive(Deserialize)]
+    struct CountedShow {
+        api_token: CountedToken,
+    }
+
+    let (_, _, user, token) = TestApp::init().with_token();
+    let json: CountedList = user.get(URL).good();
+    assert_eq!(json.api_tokens[0].use_count, 0);
+
+    for _ in 0..3 {
+        token.get::<EncodableMe>("/api/v1/me").good();
+    }
+
+    let json: CountedList = user.get(URL).good();
+    assert_eq!(json.api_tokens[0].use_count, 3);
+    let url = format!("/api/v1/me/tokens/{}", token.as_model().id);
+    let json: CountedShow = user.get(&url).good();
+    assert_eq!(json.api_token.use_count, 3);
+}
+
+#[test]
+fn using_token_records_the_client_ip() {
+    #[derive(Deserialize)]
+    struct IpToken {
+        last_used_ip: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct IpList {
+        api_tokens: Vec<IpToken>,
+    }
+
+    let (_, anon, user, token) = TestApp::init().with_token();
+    let json: IpList = user.get(URL).good();
+    assert_eq!(json.api_tokens[0].last_used_ip, None);
+
+    // Only the address appended by the load balancer is trusted
+    get_me_from_ip::<EncodableMe>(&anon, "10.0.0.1, 203.0.113.7", token.plaintext()).good();
+
+    let json: IpList = user.get(URL).good();
+    assert_eq!(
+        json.api_tokens[0].last_used_ip,
+        Some("203.0.113.7".to_string())
+    );
+}
+
+fn get_me_from_ip<T>(anon: &MockAnonymousUser, ip: &str, token: &str) -> Response<T>
+where
+    for<'de> T: ::serde::Deserialize<'de>,
+{
+    let mut request = anon.request_builder(Method::Get, "/api/v1/me");
+    request.header("Authorization", token);
+    request.header("X-Forwarded-For", ip);
+    anon.run(&mut request)
+}
+
+#[test]
+fn repeated_invalid_tokens_lock_out_the_ip() {
+    let (_, anon, _, token) = TestApp::init_with_config(|config| {
+        config.token_lockout_threshold = 3;
+    })
+    .with_token();
+    let valid = token.plaintext();
+
+    for _ in 0..3 {
+        get_me_from_ip::<()>(&anon, "1.2.3.4", "not-a-token").assert_forbidden();
+    }
+    let json = get_me_from_ip::<()>(&anon, "1.2.3.4", "not-a-token").bad_with_status(429);
+    assert_contains!(json.errors[0].detail, "too many requests");
+    // Once locked out, tokens from the IP aren't checked at all
+    get_me_from_ip::<()>(&anon, "1.2.3.4", valid).bad_with_status(429);
+
+    // Other IPs are unaffected
+    get_me_from_ip::<EncodableMe>(&anon, "5.6.7.8", valid).good();
+    get_me_from_ip::<()>(&anon, "5.6.7.8", "not-a-token").assert_forbidden();
+}
+
+#[test]
+fn valid_tokens_do_not_count_towards_lockout() {
+    let (_, anon, _, token) = TestApp::init_with_config(|config| {
+        config.token_lockout_threshold = 3;
+    })
+    .with_token();
+    let valid = token.plaintext();
+
+    for _ in 0..5 {
+        get_me_from_ip::<EncodableMe>(&anon, "1.2.3.4", valid).good();
+    }
+    for _ in 0..2 {
+        get_me_from_ip::<()>(&anon, "1.2.3.4", "not-a-token").assert_forbidden();
+    }
+    get_me_from_ip::<EncodableMe>(&anon, "1.2.3.4", valid).good();
+}
+
+fn send_github_webhook(
+    anon: &MockAnonymousUser,
+    event: &str,
+    body: &str,
+    secret: &str,
+) -> Response<OkBool> {
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::sign::Signer;
+
+    let key = t!(PKey::hmac(secret.as_bytes()));
+    let mut signer = t!(Signer::new(MessageDigest::sha1(), &key));
+    t!(signer.update(body.as_bytes()));
+    let signature = format!("sha1={}", ::hex::encode(t!(signer.sign_to_vec())));
+
+    let mut request = anon.request_builder(Method::Post, "/api/v1/webhooks/github");
+    request.header("X-GitHub-Event", event);
+    request.header("X-Hub-Signature", &signature);
+    request.with_body(body.as_bytes());
+    anon.run(&mut request)
+}
+
+#[test]
+fn github_authorization_revoked_revokes_all_tokens() {
+    let (app, anon, user, token) = TestApp::init().with_token();
+    let user = user.as_model();
+    let other_token = app.db(|conn| t!(ApiToken::insert(conn, user.id, "baz")).model);
+    let bystander = app.db_new_user("bystander").db_new_token("bystander");
+
+    let body = json!({
+        "action": "revoked",
+        "sender": { "login": user.gh_login, "id": user.gh_id },
+    })
+    .to_string();
+
+    // Delivering the event twice is harmless
+    for _ in 0..2 {
+        let json = send_github_webhook(
+            &anon,
+            "github_app_authorization",
+            &body,
+            "test webhook secret",
+        )
+        .good();
+        assert!(json.ok);
+    }
+
+    app.db(|conn| {
+        let revoked = |id| {
+            t!(api_tokens::table
+                .find(id)
+                .select(api_tokens::revoked)
+                .first::<bool>(conn))
+        };
+        assert!(revoked(token.as_model().id));
+        assert!(revoked(other_token.id));
+        assert!(!revoked(bystander.as_model().id));
+    });
+    token.get::<()>("/api/v1/me").assert_forbidden();
+}
+
+#[test]
+fn github_webhook_with_bad_signature_is_rejected() {
+    let (app, anon, user, token) = TestApp::init().with_token();
+    let user = user.as_model();
+
+    let body = json!({
+        "action": "revoked",
+        "sender": { "login": user.gh_login, "id": user.gh_id },
+    })
+    .to_string();
+    send_github_webhook(&anon, "github_app_authorization", &body, "wrong secret")
+        .bad_with_status(400);
+
+    app.db(|conn| {
+        let revoked = t!(api_tokens::table
+            .find(token.as_model().id)
+            .select(api_tokens::revoked)
+            .first::<bool>(conn));
+        assert!(!revoked);
+    });
+}
+
+fn sign_github_actions_token(claims: &serde_json::Value) -> String {
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::sign::Signer;
+
+    fn encode(bytes: &[u8]) -> String {
+        base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+    }
+
+    let header = json!({ "alg": "RS256", "kid": "crates-io-test-key", "typ": "JWT" });
+    let signing_input = format!(
+        "{}.{}",
+        encode(header.to_string().as_bytes()),
+        encode(claims.to_string().as_bytes())
+    );
+
+    let key = t!(PKey::private_key_from_pem(include_bytes!(
+        "github_actions_key.pem"
+    )));
+    let mut signer = t!(Signer::new(MessageDigest::sha256(), &key));
+    t!(signer.update(signing_input.as_bytes()));
+    let signature = t!(signer.sign_to_vec());
+    format!("{}.{}", signing_input, encode(&signature))
+}
+
+fn github_actions_claims(repository: &str) -> serde_json::Value {
+    let now = chrono::Utc::now().timestamp();
+    json!({
+        "iss": "https://token.actions.githubusercontent.com",
+        "aud": "crates.io",
+        "iat": now,
+        "nbf": now,
+        "exp": now + 300,
+        "repository": repository,
+        "repository_id": "1296269",
+        "repository_owner": repository.split('/').next().unwrap(),
+        "repository_owner_id": "583231",
+    })
+}
+
+fn post_token_exchange(anon: &MockAnonymousUser, token: &str) -> Response<NewResponse> {
+    let body = json!({ "token": token }).to_string();
+    anon.post("/api/v1/tokens/exchange", body.as_bytes())
+}
+
+#[test]
+fn exchange_github_actions_token() {
+    use models::NewGitHubActionsBinding;
+
+    let (app, anon) = TestApp::with_proxy().empty();
+    let user = app.db_new_user("foo");
+    app.db(|conn| {
+        let user = user.as_model();
+        for name in &["foo_actions", "bar_actions"] {
+            let krate = CrateBuilder::new(name, user.id).expect_build(conn);
+            t!(NewGitHubActionsBinding {
+                crate_id: krate.id,
+                user_id: user.id,
+                repository: "Foo-Org/Foo-Repo",
+                repository_id: "1296269",
+                repository_owner_id: "583231",
+            }
+            .create(conn));
+        }
+    });
+
+    let token = sign_github_actions_token(&github_actions_claims("foo-org/foo-repo"));
+    let json = post_token_exchange(&anon, &token).good();
+    assert_eq!(json.api_token.name, "GitHub Actions: foo-org/foo-repo");
+    assert_eq!(
+        json.api_token.crate_scopes,
+        Some(vec!["bar_actions".to_string(), "foo_actions".to_string()])
+    );
+    let expires_at = json.api_token.expires_at.expect("token should expire");
+    assert!(expires_at > chrono::Utc::now().naive_utc());
+    assert!(expires_at < chrono::Utc::now().naive_utc() + chrono::Duration::hours(1));
+
+    let tokens = app.db(|conn| t!(ApiToken::belonging_to(user.as_model()).load::<ApiToken>(conn)));
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(tokens[0].token_hash, ApiToken::hash(&json.api_token.token));
+}
+
+#[test]
+fn exchange_rejects_bad_signature() {
+    let (_, anon) = TestApp::with_proxy().empty();
+
+    // Swap the claims of a validly signed token for someone else's
+    let token = sign_github_actions_token(&github_actions_claims("foo-org/foo-repo"));
+    let other = sign_github_actions_token(&github_actions_claims("evil-org/evil-repo"));
+    let parts = token.split('.').collect::<Vec<_>>();
+    let other_parts = other.split('.').collect::<Vec<_>>();
+    let forged = format!("{}.{}.{}", parts[0], other_parts[1], parts[2]);
+
+    let json = post_token_exchange(&anon, &forged).bad_with_status(400);
+    assert_contains!(json.errors[0].detail, "bad signature");
+}
+
+#[test]
+fn exchange_rejects_mis_claimed_tokens() {
+    let (_, anon) = TestApp::with_proxy().empty();
+
+    let mut claims = github_actions_claims("foo-org/foo-repo");
+    claims["aud"] = json!("https://github.com/foo-org");
+    let token = sign_github_actions_token(&claims);
+    let json = post_token_exchange(&anon, &token).bad_with_status(400);
+    assert_contains!(json.errors[0].detail, "not issued for crates.io");
+
+    let mut claims = github_actions_claims("foo-org/foo-repo");
+    claims["exp"] = json!(chrono::Utc::now().timestamp() - 60);
+    let token = sign_github_actions_token(&claims);
+    let json = post_token_exchange(&anon, &token).bad_with_status(400);
+    assert_contains!(json.errors[0].detail, "expired");
+
+    let token = sign_github_actions_token(&github_actions_claims("foo-org/unbound-repo"));
+    let json = post_token_exchange(&anon, &token).bad_with_status(400);
+    assert_contains!(
+        json.errors[0].detail,
+        "no crates can be published from the repository `foo-org/unbound-repo`"
+    );
+}
+
+#[test]
+fn exchange_rejects_repositories_that_only_share_the_bound_name() {
+    use models::NewGitHubActionsBinding;
+
+    let (app, anon) = TestApp::with_proxy().empty();
+    let user = app.db_new_user("foo");
+    app.db(|conn| {
+        let user = user.as_model();
+        let krate = CrateBuilder::new("foo_actions", user.id).expect_build(conn);
+        t!(NewGitHubActionsBinding {
+            crate_id: krate.id,
+            user_id: user.id,
+            repository: "foo-org/foo-repo",
+            repository_id: "1296269",
+            repository_owner_id: "583231",
+        }
+        .create(conn));
+    });
+
+    // The repository was deleted and someone made a new one with the same name
+    let mut claims = github_actions_claims("foo-org/foo-repo");
+    claims["repository_id"] = json!("9999999");
+    let token = sign_github_actions_token(&claims);
+    let json = post_token_exchange(&anon, &token).bad_with_status(400);
+    assert_contains!(
+        json.errors[0].detail,
+        "no crates can be published from the repository `foo-org/foo-repo`"
+    );
+
+    // The organization was deleted and someone else took its name
+    let mut claims = github_actions_claims("foo-org/foo-repo");
+    claims["repository_owner_id"] = json!("9999999");
+    let token = sign_github_actions_token(&claims);
+    let json = post_token_exchange(&anon, &token).bad_with_status(400);
+    assert_contains!(
+        json.errors[0].detail,
+        "no crates can be published from the repository `foo-org/foo-repo`"
+    );
+}
+
+#[test]
+fn admin_can_look_up_token_owner() {
+    use cargo_registry::schema::admin_users;
+    use diesel::insert_into;
+
+    let (app, _, user) = TestApp::init().with_user();
+    let token = user.db_new_token("reported");
+    let admin = app.db_new_user("admin");
+    app.db(|conn| {
+        insert_into(admin_users::table)
+            .values(admin_users::user_id.eq(admin.as_model().id))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let url = format!("/api/v1/admin/tokens/{}/owner", token.as_model().id);
+    let json: serde_json::Value = admin.get(&url).good();
+    assert_eq!(json["user"]["login"], user.as_model().gh_login.as_str());
+    assert_eq!(json["user"]["id"], user.as_model().id);
+    assert!(!json.to_string().contains(token.plaintext()));
+
+    admin
+        .get::<()>("/api/v1/admin/tokens/0/owner")
+        .assert_not_found();
+}
+
+#[test]
+fn non_admin_cannot_look_up_token_owner() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let token = user.db_new_token("reported");
+    let other = app.db_new_user("other");
+
+    let url = format!("/api/v1/admin/tokens/{}/owner", token.as_model().id);
+    other.get::<()>(&url).assert_forbidden();
+    user.get::<()>(&url).assert_forbidden();
+    anon.get::<()>(&url).assert_forbidden();
+}
+
+#[test]
+fn soon_to_expire_token_gets_a_warning_header() {
+    use chrono::{Duration, Utc};
+
+    let (_, _, user) = TestApp::init().with_user();
+    let now = Utc::now().naive_utc();
+    let token =
+        user.db_new_expiring_token("expiring", now + Duration::days(3) + Duration::hours(1));
+
+    let response = token.get::<EncodableMe>("/api/v1/me");
+    response.assert_header(
+        "Warning",
+        "299 crates.io \"this API token expires in 3 days\"",
+    );
+    let json = response.good();
+    assert_eq!(json.user.login, user.as_model().gh_login);
+}
+
+#[test]
+fn create_token_with_expiry() {
+    use chrono::{Duration, Utc};
+
+    let (app, _, user) = TestApp::init().with_user();
+    let expires_at = Utc::now() + Duration::days(30);
+    let body = json!({ "api_token": { "name": "ci", "expires_at": expires_at.to_rfc3339() } });
+    let json: NewResponse = user.put(URL, body.to_string().as_bytes()).good();
+    let token_expiry = json.api_token.expires_at.expect("token should expire");
+    assert!(token_expiry.signed_duration_since(expires_at.naive_utc()) < Duration::seconds(1));
+
+    let tokens = app.db(|conn| t!(ApiToken::belonging_to(user.as_model()).load::<ApiToken>(conn)));
+    assert_eq!(tokens[0].expires_at, Some(token_expiry));
+
+    let expired = Utc::now() - Duration::days(1);
+    let body = json!({ "api_token": { "name": "old", "expires_at": expired.to_rfc3339() } });
+    let json = user
+        .put::<()>(URL, body.to_string().as_bytes())
+        .bad_with_status(400);
+    assert_contains!(json.errors[0].detail, "expires_at must be in the future");
+}
+
+#[test]
+fn expired_tokens_cannot_be_used() {
+    use chrono::{Duration, Utc};
+    use diesel::{self, dsl::now};
+
+    let (app, _, user) = TestApp::init().with_user();
+    let now_ish = Utc::now().naive_utc();
+
+    let expired = user.db_new_expiring_token("expired", now_ish - Duration::days(1));
+    expired.get::<()>("/api/v1/me").assert_forbidden();
+
+    // A token that expires at the very moment it is used is already expired
+    let expiring = user.db_new_expiring_token("expiring", now_ish + Duration::days(1));
+    expiring.get::<EncodableMe>("/api/v1/me").good();
+    app.db(|conn| {
+        diesel::update(expiring.as_model())
+            .set(api_tokens::expires_at.eq(now.nullable()))
+            .execute(conn)
+    })
+    .unwrap();
+    expiring.get::<()>("/api/v1/me").assert_forbidden();
+}
+
+#[test]
+fn token_usage_is_counted_per_bucket() {
+    use cargo_registry::schema::api_token_usages;
+    use chrono::{Duration, NaiveDateTime, Timelike, Utc};
+    use diesel;
+    use std::collections::BTreeMap;
+
+    #[derive(Deserialize)]
+    struct Bucket {
+        time: String,
+        count: i64,
+    }
+
+    #[derive(Deserialize)]
+    struct UsageResponse {
+        usage: Vec<Bucket>,
+    }
+
+    let (app, _, user, token) = TestApp::init().with_token();
+    token.get::<EncodableMe>("/api/v1/me").good();
+    token.get::<EncodableMe>("/api/v1/me").good();
+
+    let now = Utc::now().naive_utc();
+    let this_hour = now.date().and_hms(now.hour(), 0, 0);
+    let earlier = vec![
+        (this_hour - Duration::hours(3), 4),
+        (this_hour - Duration::days(2), 5),
+        (this_hour - Duration::days(40), 7),
+    ];
+    app.db(|conn| {
+        for &(hour, uses) in &earlier {
+            t!(diesel::insert_into(api_token_usages::table)
+                .values((
+                    api_token_usages::api_token_id.eq(token.as_model().id),
+                    api_token_usages::hour.eq(hour),
+                    api_token_usages::uses.eq(uses),
+                ))
+                .execute(conn));
+        }
+    });
+
+    let url = format!("{}/{}/usage", URL, token.as_model().id);
+    let usage = |query: &str| {
+        let json: UsageResponse = user.get_with_query(&url, query).good();
+        json.usage
+            .into_iter()
+            .map(|b| (b.time, b.count))
+            .collect::<Vec<_>>()
+    };
+    let time = |t: NaiveDateTime| format!("{}+00:00", t.format("%Y-%m-%dT%H:%M:%S"));
+
+    assert_eq!(
+        usage("bucket=hour"),
+        vec![
+            (time(this_hour - Duration::hours(3)), 4),
+            (time(this_hour), 2)
+        ]
+    );
+
+    let mut days = BTreeMap::new();
+    for &(hour, uses) in &[
+        (this_hour - Duration::days(2), 5),
+        (this_hour - Duration::hours(3), 4),
+        (this_hour, 2),
+    ] {
+        *days.entry(time(hour.date().and_hms(0, 0, 0))).or_insert(0) += uses;
+    }
+    let days = days.into_iter().collect::<Vec<_>>();
+    assert_eq!(usage("bucket=day"), days);
+    assert_eq!(usage(""), days);
+
+    let json = user
+        .get_with_query::<()>(&url, "bucket=week")
+        .bad_with_status(400);
+    assert_contains!(json.errors[0].detail, "invalid bucket `week`");
+}
+
+#[test]
+fn head_request_on_token_list() {
+    use chrono::{Duration, Utc};
+
+    let (_, anon, user) = TestApp::init().with_user();
+    anon.head(URL).assert_forbidden();
+
+    let now = Utc::now().naive_utc();
+    let token =
+        user.db_new_expiring_token("expiring", now + Duration::days(3) + Duration::hours(1));
+
+    let get = token.get::<()>(URL);
+    let content_length = get.header("Content-Length").unwrap().to_owned();
+
+    let head = token.head(URL);
+    head.assert_status(200)
+        .assert_header("Content-Length", &content_length)
+        .assert_header(
+            "Warning",
+            "299 crates.io \"this API token expires in 3 days\"",
+        );
+    head.assert_empty_body();
+}
+
+#[test]
+fn no_warning_header_for_tokens_not_about_to_expire() {
+    use chrono::{Duration, Utc};
+
+    let (_, _, user) = TestApp::init().with_user();
+    let now = Utc::now().naive_utc();
+    let never = user.db_new_token("never");
+    let later = user.db_new_expiring_token("later", now + Duration::days(30));
+
+    for token in &[never, later] {
+        let response = token.get::<EncodableMe>("/api/v1/me");
+        assert_eq!(response.header("Warning"), None);
+        response.good();
+    }
+    let response = user.get::<EncodableMe>("/api/v1/me");
+    assert_eq!(response.header("Warning"), None);
+    response.good();
+}
+
+#[test]
+fn writes_fail_gracefully_while_the_database_is_read_only() {
+    use diesel::connection::SimpleConnection;
+
+    let (app, _, user) = TestApp::init().with_user();
+    let token = user.db_new_token("existing");
+
+    // Simulates a maintenance window, where only reads are accepted
+    app.db(|conn| t!(conn.batch_execute("SET TRANSACTION READ ONLY")));
+
+    let json = user.put::<()>(URL, NEW_BAR).bad_with_status(503);
+    assert_eq!(
+        json.errors[0].detail,
+        "registry is in maintenance (read-only) mode, please try again later"
+    );
+
+    let json: ListResponse = user.get(URL).good();
+    assert_eq!(json.api_tokens.len(), 1);
+    assert_eq!(json.api_tokens[0].name, "existing");
+
+    // Tokens still authenticate reads, without recording their use
+    let json: EncodableMe = token.get("/api/v1/me").good();
+    assert_eq!(json.user.login, user.as_model().gh_login);
+}
+
+#[test]
+fn tokens_of_deleted_users_are_rejected_and_listed_as_orphaned() {
+    use cargo_registry::schema::users;
+    use diesel;
+    use diesel::connection::SimpleConnection;
+
+    let (app, _, user) = TestApp::init().with_user();
+    let other = app.db_new_user("other");
+    let token = user.db_new_token("orphaned");
+    let other_token = other.db_new_token("kept");
+
+    app.db(|conn| {
+        // Foreign keys keep this from happening, so they're switched off to simulate a user
+        // row going missing regardless.
+        t!(conn.batch_execute("SET LOCAL session_replication_role = replica"));
+        t!(diesel::delete(users::table.find(user.as_model().id)).execute(conn));
+        t!(conn.batch_execute("SET LOCAL session_replication_role = DEFAULT"));
+    });
+
+    token.get::<()>("/api/v1/me").assert_forbidden();
+    other_token.get::<EncodableMe>("/api/v1/me").good();
+
+    let orphaned = app.db(|conn| t!(ApiToken::find_orphaned(conn)));
+    assert_eq!(orphaned.len(), 1);
+    assert_eq!(orphaned[0].id, token.as_model().id);
+}
+
+#[test]
+fn token_can_be_sent_raw_or_with_the_bearer_scheme() {
+    let (_, anon, _, token) = TestApp::init().with_token();
+    let with_header = |value: &str| {
+        let mut request = anon.request_builder(Method::Get, "/api/v1/me");
+        request.header("Authorization", value);
+        request
+    };
+
+    let json: EncodableMe = anon.run(&mut with_header(token.plaintext())).good();
+    assert_eq!(json.user.login, "foo");
+
+    let bearer = format!("Bearer {}", token.plaintext());
+    let json: EncodableMe = anon.run(&mut with_header(&bearer)).good();
+    assert_eq!(json.user.login, "foo");
+
+    let bearer = format!("bearer  {}", token.plaintext());
+    let json: EncodableMe = anon.run(&mut with_header(&bearer)).good();
+    assert_eq!(json.user.login, "foo");
+
+    let basic = format!("Basic {}", token.plaintext());
+    let json = anon
+        .run::<()>(&mut with_header(&basic))
+        .bad_with_status(400);
+    assert_contains!(
+        json.errors[0].detail,
+        "unsupported authorization scheme `Basic`"
+    );
+}
+
+#[test]
+fn expired_token_can_only_read_during_the_grace_period() {
+    use builders::PublishBuilder;
+    use chrono::{Duration, Utc};
+
+    let (_, _, user) = TestApp::init_with_config(|config| {
+        config.expired_token_grace_period = ::std::time::Duration::from_secs(60 * 60);
+    })
+    .with_user();
+    let now = Utc::now().naive_utc();
+    let just_expired = user.db_new_expiring_token("just expired", now - Duration::minutes(1));
+    let long_expired = user.db_new_expiring_token("long expired", now - Duration::hours(2));
+
+    let response = just_expired.get::<EncodableMe>("/api/v1/me");
+    response.assert_header(
+        "Warning",
+        "299 crates.io \"this API token has expired and only works for reads\"",
+    );
+    assert_eq!(response.good().user.login, user.as_model().gh_login);
+
+    let json = just_expired
+        .publish(PublishBuilder::new("grace_crate"))
+        .bad_with_status(403);
+    assert_contains!(json.errors[0].detail, "can only be used for reads");
+
+    long_expired.get::<()>("/api/v1/me").assert_forbidden();
+}
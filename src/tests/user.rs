@@ -3,7 +3,7 @@ use diesel::prelude::*;
 
 use builders::{CrateBuilder, VersionBuilder};
 use models::{Email, NewUser, User};
-use util::RequestHelper;
+use util::{RequestHelper, TestAppBuilder};
 use views::{EncodablePrivateUser, EncodablePublicUser, EncodableVersion};
 use {app, logout, new_user, req, sign_in_as, OkBool, TestApp};
 
@@ -54,6 +54,67 @@ fn me() {
     assert_eq!(json.user.email, user.as_model().email);
 }
 
+#[test]
+fn head_requests_on_user_endpoints() {
+    fn assert_head_matches_get<T: RequestHelper>(requester: &T, url: &str) {
+        let get = requester.get::<()>(url);
+        let content_length = get.header("Content-Length").unwrap().to_owned();
+
+        let head = requester.head(url);
+        head.assert_status(200)
+            .assert_header("Content-Type", "application/json; charset=utf-8")
+            .assert_header("Content-Length", &content_length);
+        head.assert_empty_body();
+    }
+
+    let (app, anon) = TestApp::init().empty();
+    anon.head("/api/v1/me").assert_forbidden();
+
+    let user = app.db_new_user("foo");
+    assert_head_matches_get(&user, "/api/v1/me");
+    assert_head_matches_get(&anon, "/api/v1/users/foo");
+}
+
+#[test]
+fn me_includes_rights_when_asked() {
+    use cargo_registry::schema::emails;
+    use diesel::insert_into;
+
+    let (app, _, user) = TestApp::init().with_user();
+    let other = app.db_new_user("other");
+    app.db(|conn| {
+        let user = user.as_model();
+        CrateBuilder::new("foo_rights_1", user.id).expect_build(conn);
+        CrateBuilder::new("foo_rights_2", user.id).expect_build(conn);
+        CrateBuilder::new("bar_rights", other.as_model().id).expect_build(conn);
+        insert_into(emails::table)
+            .values((
+                emails::user_id.eq(user.id),
+                emails::email.eq("foo@example.com"),
+                emails::verified.eq(true),
+            ))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let json: UserShowPrivateResponse = user.get("/api/v1/me").good();
+    assert_eq!(json.user.can_publish, None);
+    assert_eq!(json.user.publishable_crate_count, None);
+
+    let json: UserShowPrivateResponse = user.get_with_query("/api/v1/me", "include=rights").good();
+    assert_eq!(json.user.can_publish, Some(true));
+    assert_eq!(json.user.publishable_crate_count, Some(2));
+}
+
+#[test]
+fn me_rights_for_unverified_user() {
+    let (_, _, user) = TestApp::init().with_user();
+
+    let json: UserShowPrivateResponse = user.get_with_query("/api/v1/me", "include=rights").good();
+    assert_eq!(json.user.can_publish, Some(false));
+    assert_eq!(json.user.publishable_crate_count, Some(0));
+}
+
 #[test]
 fn show() {
     let (app, anon, _) = TestApp::init().with_user();
@@ -67,6 +128,38 @@ fn show() {
     assert_eq!(Some("https://github.com/bar".into()), json.user.url);
 }
 
+#[test]
+fn show_with_verified_badge() {
+    use cargo_registry::schema::emails;
+    use diesel::insert_into;
+
+    let (app, anon, user) = TestApp::init().with_user();
+    app.db_new_user("bar");
+    app.db(|conn| {
+        insert_into(emails::table)
+            .values((
+                emails::user_id.eq(user.as_model().id),
+                emails::email.eq("foo@example.com"),
+                emails::verified.eq(true),
+            ))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let json: UserShowPublicResponse = anon.get("/api/v1/users/foo").good();
+    assert_eq!(json.user.verified, None);
+
+    let json: UserShowPublicResponse = anon
+        .get_with_query("/api/v1/users/foo", "include=verified")
+        .good();
+    assert_eq!(json.user.verified, Some(true));
+
+    let json: UserShowPublicResponse = anon
+        .get_with_query("/api/v1/users/bar", "include=verified")
+        .good();
+    assert_eq!(json.user.verified, Some(false));
+}
+
 #[test]
 fn show_latest_user_case_insensitively() {
     let (app, anon) = TestApp::init().empty();
@@ -106,6 +199,49 @@ fn show_latest_user_case_insensitively() {
     );
 }
 
+#[test]
+fn login_case_changes_keep_the_same_account() {
+    let (app, anon) = TestApp::init().empty();
+
+    let (first, second) = app.db(|conn| {
+        let first = t!(NewUser::new(42, "Foo", None, None, None, "token").create_or_update(conn));
+        let second = t!(NewUser::new(42, "foo", None, None, None, "token").create_or_update(conn));
+        (first, second)
+    });
+    assert_eq!(first.id, second.id);
+    assert_eq!(second.gh_login, "foo");
+
+    app.db(|conn| {
+        assert_eq!(t!(User::find_by_login(conn, "Foo")).id, first.id);
+        assert_eq!(t!(User::find_by_login(conn, "foo")).id, first.id);
+    });
+    let json: UserShowPublicResponse = anon.get("/api/v1/users/FOO").good();
+    assert_eq!(json.user.id, first.id);
+    assert_eq!(json.user.login, "foo");
+}
+
+#[test]
+fn resolve_github_login_to_user_id() {
+    #[derive(Deserialize)]
+    struct ResolvedUser {
+        id: i32,
+        login: String,
+    }
+
+    let (app, anon) = TestApp::init().empty();
+    let user = app.db_new_user("Resolved_User");
+
+    let json: ResolvedUser = anon
+        .get_with_query("/api/v1/users/resolve", "gh_login=resolved_USER")
+        .good();
+    assert_eq!(json.id, user.as_model().id);
+    assert_eq!(json.login, "Resolved_User");
+
+    anon.get_with_query::<()>("/api/v1/users/resolve", "gh_login=nobody")
+        .assert_not_found();
+    anon.get::<()>("/api/v1/users/resolve").bad_with_status(400);
+}
+
 #[test]
 fn crates_by_user_id() {
     let (app, _, user) = TestApp::init().with_user();
@@ -234,18 +370,27 @@ fn user_total_downloads_no_crates() {
 fn updating_existing_user_doesnt_change_api_token() {
     let (app, _, user, token) = TestApp::init().with_token();
     let gh_id = user.as_model().gh_id;
-    let token = &token.as_model().token;
+    let token = token.plaintext();
 
     let user = app.db(|conn| {
         // Reuse gh_id but use new gh_login and gh_access_token
         t!(NewUser::new(gh_id, "bar", None, None, None, "bar_token").create_or_update(conn));
 
         // Use the original API token to find the now updated user
-        t!(User::find_by_api_token(conn, token))
+        t!(User::find_by_api_token(
+            conn,
+            token,
+            "127.0.0.1",
+            ::std::time::Duration::from_secs(0)
+        ))
+        .0
     });
 
     assert_eq!("bar", user.gh_login);
-    assert_eq!("bar_token", user.gh_access_token);
+    assert_eq!(
+        Some("bar_token"),
+        user.gh_access_token.as_ref().map(|t| &**t)
+    );
 }
 
 /*  Given a GitHub user, check that if the user logs in,
@@ -622,3 +767,1312 @@ fn test_existing_user_email() {
     assert!(!r.user.email_verified);
     assert!(!r.user.email_verification_sent);
 }
+
+fn stored_gh_access_token(app: &TestApp, user: &User) -> Option<String> {
+    use cargo_registry::schema::users;
+
+    app.db(|conn| {
+        users::table
+            .find(user.id)
+            .select(users::gh_access_token)
+            .first(conn)
+            .unwrap()
+    })
+}
+
+fn app_with_github_client() -> TestAppBuilder {
+    TestApp::with_proxy_and_config(|config| {
+        config.gh_client_id = String::from("test_client_id");
+        config.gh_client_secret = String::from("test_client_secret");
+    })
+}
+
+#[test]
+fn logout_revokes_the_github_token() {
+    let (app, _, user) = app_with_github_client().with_user();
+    assert!(stored_gh_access_token(&app, user.as_model()).is_some());
+
+    let json: bool = user.delete("/logout").good();
+    assert!(json);
+
+    // The rate limit headers of GitHub's response show the revocation request was made
+    let remaining = app
+        .as_inner()
+        .github_rate_limit_remaining
+        .get(&"test_client_id".to_string());
+    assert_eq!(remaining, Some(4999));
+    assert_eq!(stored_gh_access_token(&app, user.as_model()), None);
+}
+
+#[test]
+fn logout_forgets_the_github_token_even_if_revoking_fails() {
+    let (app, _, user) = app_with_github_client().with_user();
+
+    let json: bool = user.delete("/logout").good();
+    assert!(json);
+
+    let remaining = app
+        .as_inner()
+        .github_rate_limit_remaining
+        .get(&"test_client_id".to_string());
+    assert_eq!(remaining, Some(4998));
+    assert_eq!(stored_gh_access_token(&app, user.as_model()), None);
+}
+
+#[test]
+fn email_status_reports_verification_and_sent_flags() {
+    use cargo_registry::schema::emails;
+    use diesel::update;
+
+    let (app, _, no_email) = TestApp::init().with_user();
+
+    app.db(|conn| {
+        assert_eq!(
+            no_email.as_model().email_status(conn).unwrap(),
+            (false, false)
+        );
+
+        let user = NewUser {
+            email: Some("status@example.com"),
+            ..new_user("status")
+        }
+        .create_or_update(conn)
+        .unwrap();
+        assert_eq!(user.email_status(conn).unwrap(), (false, true));
+
+        update(Email::belonging_to(&user))
+            .set(emails::verified.eq(true))
+            .execute(conn)
+            .unwrap();
+        assert_eq!(user.email_status(conn).unwrap(), (true, true));
+    });
+}
+
+#[test]
+fn verified_emails_are_looked_up_in_bulk() {
+    use cargo_registry::schema::emails;
+    use diesel::insert_into;
+    use std::collections::HashSet;
+
+    let (app, _) = TestApp::init().empty();
+    let verified = app.db_new_user("verified");
+    let unverified = app.db_new_user("unverified");
+    let also_verified = app.db_new_user("also_verified");
+
+    app.db(|conn| {
+        insert_into(emails::table)
+            .values(&vec![
+                (
+                    emails::user_id.eq(verified.as_model().id),
+                    emails::email.eq("verified@example.com"),
+                    emails::verified.eq(true),
+                ),
+                (
+                    emails::user_id.eq(unverified.as_model().id),
+                    emails::email.eq("unverified@example.com"),
+                    emails::verified.eq(false),
+                ),
+                (
+                    emails::user_id.eq(also_verified.as_model().id),
+                    emails::email.eq("also_verified@example.com"),
+                    emails::verified.eq(true),
+                ),
+            ])
+            .execute(conn)
+            .unwrap();
+
+        let ids = [
+            verified.as_model().id,
+            unverified.as_model().id,
+            also_verified.as_model().id,
+        ];
+        let expected = vec![verified.as_model().id, also_verified.as_model().id]
+            .into_iter()
+            .collect::<HashSet<_>>();
+        assert_eq!(User::verified_emails_for(conn, &ids).unwrap(), expected);
+    });
+}
+
+#[test]
+fn notifications_go_to_chosen_verified_email() {
+    use cargo_registry::schema::emails;
+    use diesel::insert_into;
+
+    let (app, _, user) = TestApp::init().with_user();
+    let user_model = user.as_model();
+
+    app.db(|conn| {
+        insert_into(emails::table)
+            .values(&vec![
+                (
+                    emails::user_id.eq(user_model.id),
+                    emails::email.eq("primary@example.com"),
+                    emails::verified.eq(true),
+                    emails::is_primary.eq(true),
+                ),
+                (
+                    emails::user_id.eq(user_model.id),
+                    emails::email.eq("alerts@example.com"),
+                    emails::verified.eq(true),
+                    emails::is_primary.eq(false),
+                ),
+            ])
+            .execute(conn)
+            .unwrap();
+
+        let email = user_model.notification_email(conn).unwrap();
+        assert_eq!(email.as_ref().map(|s| &**s), Some("primary@example.com"));
+    });
+
+    let body = r#"{"email":"alerts@example.com"}"#;
+    let json: OkBool = user
+        .put("/api/v1/me/notification_email", body.as_bytes())
+        .good();
+    assert!(json.ok);
+
+    app.db(|conn| {
+        let email = user_model.notification_email(conn).unwrap();
+        assert_eq!(email.as_ref().map(|s| &**s), Some("alerts@example.com"));
+    });
+
+    // The login email is unaffected
+    let json: UserShowPrivateResponse = user.get("/api/v1/me").good();
+    assert_eq!(json.user.email.unwrap(), "primary@example.com");
+}
+
+#[test]
+fn notifications_cannot_go_to_unverified_email() {
+    use cargo_registry::schema::emails;
+    use diesel::insert_into;
+
+    let (app, _, user) = TestApp::init().with_user();
+    let user_model = user.as_model();
+
+    app.db(|conn| {
+        insert_into(emails::table)
+            .values(&vec![
+                (
+                    emails::user_id.eq(user_model.id),
+                    emails::email.eq("primary@example.com"),
+                    emails::verified.eq(true),
+                    emails::is_primary.eq(true),
+                ),
+                (
+                    emails::user_id.eq(user_model.id),
+                    emails::email.eq("unverified@example.com"),
+                    emails::verified.eq(false),
+                    emails::is_primary.eq(false),
+                ),
+            ])
+            .execute(conn)
+            .unwrap();
+    });
+
+    let body = r#"{"email":"unverified@example.com"}"#;
+    let json = user
+        .put::<()>("/api/v1/me/notification_email", body.as_bytes())
+        .bad_with_status(400);
+    assert!(json.errors[0]
+        .detail
+        .contains("one of your verified email addresses"));
+
+    app.db(|conn| {
+        let email = user_model.notification_email(conn).unwrap();
+        assert_eq!(email.as_ref().map(|s| &**s), Some("primary@example.com"));
+    });
+}
+
+#[test]
+fn recording_a_login_updates_last_login_at() {
+    use chrono::{Duration, Utc};
+
+    let (app, _, user) = TestApp::init().with_user();
+
+    let json: UserShowPrivateResponse = user.get("/api/v1/me").good();
+    assert_eq!(json.user.last_login_at, None);
+
+    let updated = app.db(|conn| user.as_model().record_login(conn).unwrap());
+    let last_login_at = updated.last_login_at.unwrap();
+    let elapsed = Utc::now().naive_utc().signed_duration_since(last_login_at);
+    assert!(elapsed < Duration::minutes(1));
+
+    let json: UserShowPrivateResponse = user.get("/api/v1/me").good();
+    assert_eq!(json.user.last_login_at, Some(last_login_at));
+}
+
+#[test]
+fn create_or_update_records_the_account_creation_date() {
+    use chrono::{Duration, Utc};
+
+    let (app, _, user) = TestApp::init().with_user();
+    let created_at = user.as_model().created_at;
+    let elapsed = Utc::now().naive_utc().signed_duration_since(created_at);
+    assert!(elapsed < Duration::minutes(1));
+
+    let updated = app.db(|conn| {
+        let mut new_user = new_user("updated_login");
+        new_user.gh_id = user.as_model().gh_id;
+        t!(new_user.create_or_update(conn))
+    });
+    assert_eq!(updated.gh_login, "updated_login");
+    assert_eq!(updated.created_at, created_at);
+
+    let json: UserShowPrivateResponse = user.get("/api/v1/me").good();
+    assert_eq!(json.user.created_at, created_at);
+}
+
+#[test]
+fn resending_confirmation_rotates_the_token() {
+    use cargo_registry::schema::emails;
+    use diesel::insert_into;
+
+    let (app, anon, user) = TestApp::init().with_user();
+    let user_id = user.as_model().id;
+    let email_token = |app: &TestApp| {
+        app.db(|conn| {
+            emails::table
+                .filter(emails::user_id.eq(user_id))
+                .select(emails::token)
+                .first::<String>(conn)
+                .unwrap()
+        })
+    };
+
+    app.db(|conn| {
+        insert_into(emails::table)
+            .values((
+                emails::user_id.eq(user_id),
+                emails::email.eq("foo@example.com"),
+            ))
+            .execute(conn)
+            .unwrap();
+    });
+    let old_token = email_token(&app);
+
+    let url = format!("/api/v1/users/{}/resend", user_id);
+    let json: OkBool = user.put(&url, &[]).good();
+    assert!(json.ok);
+
+    let new_token = email_token(&app);
+    assert_ne!(old_token, new_token);
+
+    // The link sent before the resend no longer works
+    anon.put::<()>(&format!("/api/v1/confirm/{}", old_token), &[])
+        .bad_with_status(400);
+
+    let json: OkBool = anon
+        .put(&format!("/api/v1/confirm/{}", new_token), &[])
+        .good();
+    assert!(json.ok);
+}
+
+#[test]
+fn regenerating_email_verification_rotates_the_token() {
+    use cargo_registry::email::TestEmailSender;
+    use cargo_registry::schema::emails;
+    use diesel::insert_into;
+
+    const URL: &str = "/api/v1/me/email_verification/regenerate";
+
+    let sender = TestEmailSender::default();
+    let (app, _, user) = TestApp::init_with_email_sender(Box::new(sender.clone())).with_user();
+    let user_id = user.as_model().id;
+
+    // Without an email there's nothing to verify
+    let json = user.post::<()>(URL, &[]).bad_with_status(400);
+    assert_eq!(json.errors[0].detail, "Email could not be found");
+
+    app.db(|conn| {
+        insert_into(emails::table)
+            .values((
+                emails::user_id.eq(user_id),
+                emails::email.eq("regenerate@example.com"),
+            ))
+            .execute(conn)
+            .unwrap();
+    });
+    let email = || {
+        app.db(|conn| {
+            Email::belonging_to(user.as_model())
+                .first::<Email>(conn)
+                .unwrap()
+        })
+    };
+    let old_token = email().token;
+
+    let json: serde_json::Value = user.post(URL, &[]).good();
+    assert_eq!(json, json!({ "ok": true }));
+    let new_token = email().token;
+    assert_ne!(old_token, new_token);
+    assert!(sender.sent().is_empty());
+
+    app.db(|conn| {
+        diesel::update(emails::table)
+            .set(emails::verified.eq(true))
+            .execute(conn)
+            .unwrap();
+    });
+    let json = user.post::<()>(URL, &[]).bad_with_status(409);
+    assert_eq!(json.errors[0].detail, "Email has already been verified");
+    assert_eq!(email().token, new_token);
+}
+
+#[test]
+fn rapid_create_or_update_sends_one_confirmation_email() {
+    let (app, _) = TestApp::init().empty();
+    let address = "confirm-once@example.com";
+
+    app.db(|conn| {
+        let new_user = NewUser {
+            email: Some(address),
+            ..new_user("confirm_once")
+        };
+        t!(new_user.create_or_update(conn));
+        t!(new_user.create_or_update(conn));
+        assert_eq!(emails_queued_for(conn, address), 1);
+    });
+}
+
+/// How many emails to `address` are waiting to be sent by `send-queued-emails`
+fn emails_queued_for(conn: &PgConnection, address: &str) -> i64 {
+    use cargo_registry::schema::queued_emails;
+
+    t!(queued_emails::table
+        .filter(queued_emails::recipient.eq(address))
+        .count()
+        .get_result(conn))
+}
+
+#[test]
+fn queued_confirmation_email_is_sent_with_its_token() {
+    use cargo_registry::email::TestEmailSender;
+    use cargo_registry::schema::{emails, queued_emails};
+    use models::QueuedEmail;
+
+    let app_sender = TestEmailSender::default();
+    let (app, _) = TestApp::init_with_email_sender(Box::new(app_sender.clone())).empty();
+    let address = "confirm-queued@example.com";
+
+    app.db(|conn| {
+        let user = t!(NewUser {
+            email: Some(address),
+            ..new_user("queued")
+        }
+        .create_or_update(conn));
+        let token = t!(Email::belonging_to(&user)
+            .select(emails::token)
+            .first::<String>(conn));
+
+        // Nothing is sent while the email is queued
+        assert_eq!(emails_queued_for(conn, address), 1);
+        assert!(app_sender.sent().is_empty());
+
+        let failing = TestEmailSender::failing();
+        assert_eq!(t!(QueuedEmail::send_queued(conn, &failing)), 0);
+        let (attempts, last_error) = t!(queued_emails::table
+            .filter(queued_emails::recipient.eq(address))
+            .select((queued_emails::attempts, queued_emails::last_error))
+            .first::<(i32, Option<String>)>(conn));
+        assert_eq!(attempts, 1);
+        assert_eq!(last_error, Some("Error in sending email".to_string()));
+
+        let sender = TestEmailSender::default();
+        assert_eq!(t!(QueuedEmail::send_queued(conn, &sender)), 1);
+        let sent = sender.sent();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].recipient, address);
+        assert_eq!(sent[0].subject, "Please confirm your email address");
+        assert!(sent[0]
+            .body
+            .starts_with("Hello queued! Welcome to Crates.io."));
+        assert!(sent[0]
+            .body
+            .ends_with(&format!("https://crates.io/confirm/{}", token)));
+        assert_eq!(emails_queued_for(conn, address), 0);
+    });
+}
+
+#[test]
+fn queued_emails_are_given_up_on_after_too_many_attempts() {
+    use cargo_registry::email::{Message, TestEmailSender};
+    use cargo_registry::schema::queued_emails;
+    use models::{QueuedEmail, MAX_SEND_ATTEMPTS};
+
+    let (app, _) = TestApp::init().empty();
+    let address = "undeliverable@example.com";
+    app.db(|conn| {
+        let message = Message {
+            recipient: address.to_string(),
+            subject: "Undeliverable".to_string(),
+            body: "Never arrives".to_string(),
+        };
+        t!(QueuedEmail::enqueue(conn, &message));
+
+        let failing = TestEmailSender::failing();
+        for _ in 0..MAX_SEND_ATTEMPTS + 1 {
+            t!(QueuedEmail::send_queued(conn, &failing));
+        }
+        let attempts = t!(queued_emails::table
+            .filter(queued_emails::recipient.eq(address))
+            .select(queued_emails::attempts)
+            .first::<i32>(conn));
+        assert_eq!(attempts, MAX_SEND_ATTEMPTS);
+        assert_eq!(t!(QueuedEmail::given_up_count(conn)), 1);
+
+        // Nor is it sent once sending works again
+        let sender = TestEmailSender::default();
+        assert_eq!(t!(QueuedEmail::send_queued(conn, &sender)), 0);
+        assert_eq!(emails_queued_for(conn, address), 1);
+    });
+}
+
+#[test]
+fn create_user_without_sending_confirmation_email() {
+    use cargo_registry::schema::emails;
+    use models::CreateUserOptions;
+
+    let (app, _) = TestApp::init().empty();
+
+    let (skipped, sent) = app.db(|conn| {
+        let options = CreateUserOptions {
+            send_confirmation_email: false,
+        };
+        let skipped = NewUser {
+            email: Some("confirm-skipped@example.com"),
+            ..new_user("skipped")
+        }
+        .create_or_update_with(conn, options)
+        .unwrap();
+        let sent = NewUser {
+            email: Some("confirm-sent@example.com"),
+            ..new_user("sent")
+        }
+        .create_or_update(conn)
+        .unwrap();
+        (skipped, sent)
+    });
+
+    // The address is still recorded with a token, so a confirmation can be
+    // requested later
+    app.db(|conn| {
+        assert_eq!(emails_queued_for(conn, "confirm-skipped@example.com"), 0);
+        assert_eq!(emails_queued_for(conn, "confirm-sent@example.com"), 1);
+        for user in &[skipped, sent] {
+            let (verified, token) = Email::belonging_to(user)
+                .select((emails::verified, emails::token))
+                .first::<(bool, String)>(conn)
+                .unwrap();
+            assert!(!verified);
+            assert!(!token.is_empty());
+        }
+    });
+}
+
+#[test]
+fn confirming_email_redirects_when_configured() {
+    use cargo_registry::schema::emails;
+    use diesel::insert_into;
+
+    let (app, anon, user) = TestApp::init_with_config(|config| {
+        config.email_confirmation_url = Some("https://crates.io/confirmed".to_string());
+    })
+    .with_user();
+    let user_id = user.as_model().id;
+    let token = app.db(|conn| {
+        insert_into(emails::table)
+            .values((
+                emails::user_id.eq(user_id),
+                emails::email.eq("foo@example.com"),
+            ))
+            .returning(emails::token)
+            .get_result::<String>(conn)
+            .unwrap()
+    });
+
+    anon.get::<()>(&format!("/api/v1/confirm/{}", token))
+        .assert_status(302)
+        .assert_header("Location", "https://crates.io/confirmed?status=success");
+    let verified = app.db(|conn| {
+        emails::table
+            .filter(emails::user_id.eq(user_id))
+            .select(emails::verified)
+            .first::<bool>(conn)
+            .unwrap()
+    });
+    assert!(verified);
+
+    anon.get::<()>("/api/v1/confirm/not-a-real-token")
+        .assert_status(302)
+        .assert_header("Location", "https://crates.io/confirmed?status=failure");
+}
+
+#[test]
+fn admin_can_preview_confirmation_email() {
+    use cargo_registry::email::TestEmailSender;
+    use cargo_registry::schema::{admin_users, emails};
+    use diesel::insert_into;
+    use models::CreateUserOptions;
+    use serde_json;
+
+    let sender = TestEmailSender::default();
+    let (app, anon) = TestApp::init_with_email_sender(Box::new(sender.clone())).empty();
+    let address = "confirm-preview@example.com";
+    let previewed = app.db(|conn| {
+        let options = CreateUserOptions {
+            send_confirmation_email: false,
+        };
+        NewUser {
+            email: Some(address),
+            ..new_user("previewed")
+        }
+        .create_or_update_with(conn, options)
+        .unwrap()
+    });
+    let admin = app.db_new_user("admin");
+    let token = app.db(|conn| {
+        insert_into(admin_users::table)
+            .values(admin_users::user_id.eq(admin.as_model().id))
+            .execute(conn)
+            .unwrap();
+        Email::belonging_to(&previewed)
+            .select(emails::token)
+            .first::<String>(conn)
+            .unwrap()
+    });
+
+    let url = format!("/api/v1/admin/users/{}/confirm_email/preview", previewed.id);
+    anon.get::<()>(&url).assert_forbidden();
+    app.db_new_user("other").get::<()>(&url).assert_forbidden();
+
+    let json: serde_json::Value = admin.get(&url).good();
+    assert_eq!(json["to"], address);
+    assert_eq!(json["subject"], "Please confirm your email address");
+    let body = json["body"].as_str().unwrap();
+    assert!(body.starts_with("Hello previewed! Welcome to Crates.io."));
+    assert!(body.ends_with("https://crates.io/confirm/[redacted]"));
+    assert!(!json.to_string().contains(&token));
+    assert!(sender.sent().is_empty());
+    assert_eq!(app.db(|conn| emails_queued_for(conn, address)), 0);
+}
+
+#[test]
+fn admin_can_count_pending_email_verifications() {
+    use cargo_registry::schema::{admin_users, emails};
+    use diesel::dsl::{now, IntervalDsl};
+    use diesel::{insert_into, update};
+    use models::CreateUserOptions;
+
+    #[derive(Deserialize)]
+    struct PendingCount {
+        count: i64,
+    }
+
+    let (app, anon) = TestApp::init().empty();
+    let admin = app.db_new_user("admin");
+    app.db(|conn| {
+        insert_into(admin_users::table)
+            .values(admin_users::user_id.eq(admin.as_model().id))
+            .execute(conn)
+            .unwrap();
+
+        let options = CreateUserOptions {
+            send_confirmation_email: false,
+        };
+        for &(login, address) in &[
+            ("verified", "pending-verified@example.com"),
+            ("fresh", "pending-fresh@example.com"),
+            ("stale", "pending-stale@example.com"),
+        ] {
+            NewUser {
+                email: Some(address),
+                ..new_user(login)
+            }
+            .create_or_update_with(conn, options)
+            .unwrap();
+        }
+        update(emails::table.filter(emails::email.eq("pending-verified@example.com")))
+            .set(emails::verified.eq(true))
+            .execute(conn)
+            .unwrap();
+        update(emails::table.filter(emails::email.eq("pending-stale@example.com")))
+            .set(emails::token_generated_at.eq((now - 2.days()).nullable()))
+            .execute(conn)
+            .unwrap();
+    });
+
+    let url = "/api/v1/admin/emails/pending_count";
+    let json: PendingCount = admin.get(url).good();
+    assert_eq!(json.count, 2);
+    let json: PendingCount = admin.get_with_query(url, "older_than_hours=24").good();
+    assert_eq!(json.count, 1);
+    admin
+        .get_with_query::<()>(url, "older_than_hours=soon")
+        .bad_with_status(400);
+
+    anon.get::<()>(url).assert_forbidden();
+    app.db_new_user("other").get::<()>(url).assert_forbidden();
+}
+
+#[test]
+fn admin_ban_revokes_tokens_and_blocks_auth_until_lifted() {
+    use cargo_registry::schema::{admin_users, user_ban_events};
+    use diesel::insert_into;
+
+    #[derive(Deserialize)]
+    struct BanResponse {
+        revoked_tokens: usize,
+    }
+
+    let (app, anon) = TestApp::init().empty();
+    let admin = app.db_new_user("admin");
+    app.db(|conn| {
+        insert_into(admin_users::table)
+            .values(admin_users::user_id.eq(admin.as_model().id))
+            .execute(conn)
+            .unwrap();
+    });
+    let user = app.db_new_user("banned");
+    let user_id = user.as_model().id;
+    let old_token = user.db_new_token("old");
+    old_token
+        .get::<UserShowPrivateResponse>("/api/v1/me")
+        .good();
+
+    let url = format!("/api/v1/admin/users/{}/ban", user_id);
+    let body = br#"{ "reason": "publishing malware" }"#;
+    anon.put::<()>(&url, body).assert_forbidden();
+    user.put::<()>(&url, body).assert_forbidden();
+    admin
+        .put::<()>(&url, br#"{ "reason": " " }"#)
+        .bad_with_status(400);
+
+    let json: BanResponse = admin.put(&url, body).good();
+    assert_eq!(json.revoked_tokens, 1);
+    admin.put::<()>(&url, body).bad_with_status(400);
+
+    old_token.get::<()>("/api/v1/me").assert_forbidden();
+    let banned_token = user.db_new_token("while banned");
+    banned_token.get::<()>("/api/v1/me").assert_forbidden();
+    app.db(|conn| {
+        assert!(User::find_by_api_token(
+            conn,
+            banned_token.plaintext(),
+            "127.0.0.1",
+            ::std::time::Duration::from_secs(0)
+        )
+        .is_err());
+    });
+
+    admin.delete::<OkBool>(&url).good();
+    admin.delete::<()>(&url).bad_with_status(400);
+
+    old_token.get::<()>("/api/v1/me").assert_forbidden();
+    banned_token
+        .get::<UserShowPrivateResponse>("/api/v1/me")
+        .good();
+    user.db_new_token("new")
+        .get::<UserShowPrivateResponse>("/api/v1/me")
+        .good();
+
+    let kinds = app.db(|conn| {
+        user_ban_events::table
+            .filter(user_ban_events::user_id.eq(user_id))
+            .order(user_ban_events::id)
+            .select((user_ban_events::kind, user_ban_events::reason))
+            .load::<(String, Option<String>)>(conn)
+            .unwrap()
+    });
+    assert_eq!(
+        kinds,
+        vec![
+            ("banned".to_string(), Some("publishing malware".to_string())),
+            ("unbanned".to_string(), None),
+        ]
+    );
+}
+
+#[test]
+fn delete_account_removes_the_user_and_their_ownerships() {
+    use models::{Crate, CrateOwner, OwnerKind};
+    use schema::{api_tokens, crate_owner_actions, emails, users};
+
+    let (app, _, user, token) = TestApp::init().with_token();
+    let other = app.db_new_user("other");
+    let id = user.as_model().id;
+    app.db(|conn| {
+        let krate = CrateBuilder::new("shared_crate", id).expect_build(conn);
+        let co_owner = CrateOwner {
+            crate_id: krate.id,
+            owner_id: other.as_model().id,
+            created_by: id,
+            owner_kind: OwnerKind::User as i32,
+        };
+        t!(co_owner.add(conn));
+        t!(diesel::insert_into(emails::table)
+            .values((emails::user_id.eq(id), emails::email.eq("foo@example.com")))
+            .execute(conn));
+    });
+
+    let json: OkBool = user.delete("/api/v1/me").good();
+    assert!(json.ok);
+    token.get::<()>("/api/v1/me").assert_forbidden();
+
+    app.db(|conn| {
+        let users = t!(users::table.find(id).count().get_result::<i64>(conn));
+        assert_eq!(users, 0);
+        let tokens = t!(api_tokens::table
+            .filter(api_tokens::user_id.eq(id))
+            .count()
+            .get_result::<i64>(conn));
+        assert_eq!(tokens, 0);
+        let emails = t!(emails::table
+            .filter(emails::user_id.eq(id))
+            .count()
+            .get_result::<i64>(conn));
+        assert_eq!(emails, 0);
+
+        let krate = t!(Crate::by_name("shared_crate").first::<Crate>(conn));
+        let owners = t!(krate.owners(conn));
+        assert_eq!(owners.len(), 1);
+        assert_eq!(owners[0].login(), "other");
+
+        // The removal is logged like any other
+        let actions = t!(crate_owner_actions::table
+            .filter(crate_owner_actions::crate_id.eq(krate.id))
+            .filter(crate_owner_actions::owner_id.eq(id))
+            .order(crate_owner_actions::id)
+            .select(crate_owner_actions::action)
+            .load::<String>(conn));
+        assert_eq!(actions, vec!["add", "remove"]);
+    });
+}
+
+#[test]
+fn delete_account_requires_another_user_owner() {
+    use {add_team_to_crate, new_team};
+
+    let (app, _, user) = TestApp::init().with_user();
+    app.db(|conn| {
+        let krate = CrateBuilder::new("team_crate", user.as_model().id).expect_build(conn);
+        let team = t!(new_team("github:org:core").create_or_update(conn));
+        t!(add_team_to_crate(&team, &krate, user.as_model(), conn));
+    });
+
+    let json = user.delete::<()>("/api/v1/me").bad_with_status(200);
+    assert!(json.errors[0]
+        .detail
+        .contains("add another user as an owner of `team_crate` first"));
+}
+
+#[test]
+fn delete_account_of_admin_keeps_their_ban_events() {
+    use models::{UserBan, UserBanEvent};
+
+    let (app, _, admin) = TestApp::init().with_user();
+    let banned = app.db_new_user("banned");
+    app.db(|conn| {
+        t!(UserBan::create(
+            conn,
+            banned.as_model().id,
+            admin.as_model().id,
+            "publishing malware"
+        ));
+    });
+
+    let json: OkBool = admin.delete("/api/v1/me").good();
+    assert!(json.ok);
+
+    let events =
+        app.db(|conn| t!(UserBanEvent::belonging_to(banned.as_model()).load::<UserBanEvent>(conn)));
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, "banned");
+    assert_eq!(events[0].admin_id, None);
+}
+
+#[test]
+fn delete_account_of_sole_owner_is_refused() {
+    use views::EncodableMe;
+
+    let (app, _, user, token) = TestApp::init().with_token();
+    let id = user.as_model().id;
+    app.db(|conn| {
+        CrateBuilder::new("solo_crate", id).expect_build(conn);
+        CrateBuilder::new("another_solo_crate", id).expect_build(conn);
+    });
+
+    let json = user.delete::<()>("/api/v1/me").bad_with_status(200);
+    assert!(json.errors[0].detail.contains(
+        "only user owning a crate, add another user as an owner of `another_solo_crate`, \
+         `solo_crate` first"
+    ));
+
+    let json = token.delete::<()>("/api/v1/me").bad_with_status(400);
+    assert!(json.errors[0]
+        .detail
+        .contains("cannot use an API token to delete an account"));
+
+    token.get::<EncodableMe>("/api/v1/me").good();
+}
+
+#[test]
+fn changing_email_keeps_the_old_address_until_the_new_one_is_confirmed() {
+    use schema::emails;
+    use views::EncodableMe;
+
+    let (app, anon, user) = TestApp::init().with_user();
+    let id = user.as_model().id;
+    app.db(|conn| {
+        t!(diesel::insert_into(emails::table)
+            .values((
+                emails::user_id.eq(id),
+                emails::email.eq("old@example.com"),
+                emails::verified.eq(true),
+            ))
+            .execute(conn));
+    });
+
+    let body = br#"{ "email": " new@example.com " }"#;
+    let json: OkBool = user.put("/api/v1/me/email", body).good();
+    assert!(json.ok);
+
+    let pending = app.db(|conn| {
+        t!(Email::belonging_to(user.as_model())
+            .filter(emails::email.eq("new@example.com"))
+            .first::<Email>(conn))
+    });
+    assert!(!pending.verified);
+    assert!(!pending.is_primary);
+    assert!(pending.replaces_primary);
+    assert!(!pending.token.is_empty());
+
+    let json: EncodableMe = user.get("/api/v1/me").good();
+    assert_eq!(json.user.email, Some("old@example.com".to_string()));
+    assert!(json.user.email_verified);
+
+    let _: OkBool = anon
+        .put(&format!("/api/v1/confirm/{}", pending.token), &[])
+        .good();
+
+    let json: EncodableMe = user.get("/api/v1/me").good();
+    assert_eq!(json.user.email, Some("new@example.com".to_string()));
+    assert!(json.user.email_verified);
+    let emails = app.db(|conn| {
+        t!(Email::belonging_to(user.as_model())
+            .select(emails::email)
+            .load::<String>(conn))
+    });
+    assert_eq!(emails, vec!["new@example.com"]);
+}
+
+#[test]
+fn changing_email_requires_a_session_cookie() {
+    let (_, _, _, token) = TestApp::init().with_token();
+
+    let body = br#"{ "email": "stolen@example.com" }"#;
+    let json = token
+        .put::<()>("/api/v1/me/email", body)
+        .bad_with_status(400);
+    assert!(json.errors[0]
+        .detail
+        .contains("cannot use an API token to change an email address"));
+}
+
+#[test]
+fn resending_email_verification_is_throttled() {
+    use diesel::dsl::{now, IntervalDsl};
+    use schema::emails;
+
+    let (app, _, user) = TestApp::init().with_user();
+    let id = user.as_model().id;
+    let old_token = app.db(|conn| {
+        t!(diesel::insert_into(emails::table)
+            .values((
+                emails::user_id.eq(id),
+                emails::email.eq("unverified@example.com"),
+            ))
+            .execute(conn));
+        // The previous link was sent long enough ago to allow a resend
+        t!(diesel::update(emails::table)
+            .set(emails::token_generated_at.eq((now - 2.minutes()).nullable()))
+            .returning(emails::token)
+            .get_result::<String>(conn))
+    });
+
+    let json: OkBool = user.put("/api/v1/me/email/resend", &[]).good();
+    assert!(json.ok);
+    let new_token = app.db(|conn| {
+        t!(Email::belonging_to(user.as_model())
+            .select(emails::token)
+            .first::<String>(conn))
+    });
+    assert_ne!(old_token, new_token);
+
+    let json = user
+        .put::<()>("/api/v1/me/email/resend", &[])
+        .bad_with_status(429);
+    assert!(json.errors[0].detail.contains("less than a minute ago"));
+}
+
+#[test]
+fn resending_email_verification_of_a_verified_email_is_refused() {
+    use schema::emails;
+
+    let (app, _, user) = TestApp::init().with_user();
+    let id = user.as_model().id;
+    app.db(|conn| {
+        t!(diesel::insert_into(emails::table)
+            .values((
+                emails::user_id.eq(id),
+                emails::email.eq("verified@example.com"),
+                emails::verified.eq(true),
+            ))
+            .execute(conn));
+    });
+
+    let json = user
+        .put::<()>("/api/v1/me/email/resend", &[])
+        .bad_with_status(409);
+    assert_eq!(json.errors[0].detail, "Email has already been verified");
+}
+
+#[test]
+fn only_admins_get_through_the_admin_guard() {
+    use cargo_registry::schema::admin_users;
+    use diesel::insert_into;
+
+    let (app, anon, user) = TestApp::init().with_user();
+    let admin = app.db_new_user("admin");
+    app.db(|conn| {
+        t!(insert_into(admin_users::table)
+            .values(admin_users::user_id.eq(admin.as_model().id))
+            .execute(conn));
+    });
+
+    let url = "/api/v1/admin/emails/pending_count";
+    anon.get::<()>(url).assert_forbidden();
+    let json = user.get::<()>(url).bad_with_status(403);
+    assert_eq!(
+        json.errors[0].detail,
+        "must be an admin to perform that action"
+    );
+    admin.get::<::serde_json::Value>(url).good();
+
+    let json: UserShowPrivateResponse = user.get("/api/v1/me").good();
+    assert!(!json.user.is_admin);
+    let json: UserShowPrivateResponse = admin.get("/api/v1/me").good();
+    assert!(json.user.is_admin);
+}
+
+#[test]
+fn suspended_user_cannot_use_their_tokens_until_the_suspension_is_cleared() {
+    use cargo_registry::schema::{admin_users, api_tokens};
+    use chrono::{DateTime, Duration, Utc};
+    use diesel::insert_into;
+
+    let (app, anon) = TestApp::init().empty();
+    let admin = app.db_new_user("admin");
+    app.db(|conn| {
+        t!(insert_into(admin_users::table)
+            .values(admin_users::user_id.eq(admin.as_model().id))
+            .execute(conn));
+    });
+    let user = app.db_new_user("suspended");
+    let token = user.db_new_token("kept");
+    token.get::<UserShowPrivateResponse>("/api/v1/me").good();
+
+    let url = format!("/api/v1/users/{}/suspend", user.as_model().id);
+    let until = (Utc::now() + Duration::days(7)).to_rfc3339();
+    let body = format!(r#"{{ "until": "{}" }}"#, until);
+    anon.post::<()>(&url, body.as_bytes()).assert_forbidden();
+    user.post::<()>(&url, body.as_bytes()).assert_forbidden();
+    let past = (Utc::now() - Duration::days(1)).to_rfc3339();
+    admin
+        .post::<()>(&url, format!(r#"{{ "until": "{}" }}"#, past).as_bytes())
+        .bad_with_status(400);
+
+    let json: ::serde_json::Value = admin.post(&url, body.as_bytes()).good();
+    let suspended_until = DateTime::parse_from_rfc3339(json["suspended_until"].as_str().unwrap());
+    assert_eq!(
+        t!(suspended_until).timestamp(),
+        t!(DateTime::parse_from_rfc3339(&until)).timestamp()
+    );
+
+    let json = token.get::<()>("/api/v1/me").bad_with_status(403);
+    assert!(json.errors[0]
+        .detail
+        .starts_with("this account is suspended until "));
+
+    // Refused requests don't count as uses of the token
+    let use_count = app.db(|conn| {
+        t!(api_tokens::table
+            .find(token.as_model().id)
+            .select(api_tokens::use_count)
+            .first::<i64>(conn))
+    });
+    assert_eq!(use_count, 1);
+
+    admin.delete::<OkBool>(&url).good();
+    admin.delete::<()>(&url).bad_with_status(400);
+
+    token.get::<UserShowPrivateResponse>("/api/v1/me").good();
+}
+
+#[test]
+fn backfill_gh_id_updates_a_placeholder_account() {
+    let (app, _) = TestApp::init().empty();
+    app.db(|conn| {
+        let placeholder = t!(NewUser {
+            gh_id: -1,
+            ..new_user("Placeholder")
+        }
+        .create_or_update(conn));
+
+        let user = t!(User::backfill_gh_id(conn, "placeholder", 4242));
+        assert_eq!(user.id, placeholder.id);
+        assert_eq!(user.gh_id, 4242);
+
+        let err = User::backfill_gh_id(conn, "placeholder", 4242).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no account for `placeholder` is missing its GitHub ID"
+        );
+    });
+}
+
+#[test]
+fn backfill_gh_id_refuses_to_pick_between_placeholder_accounts() {
+    let (app, _) = TestApp::init().empty();
+    app.db(|conn| {
+        for login in &["Placeholder", "placeholder"] {
+            t!(NewUser {
+                gh_id: -1,
+                ..new_user(login)
+            }
+            .create_or_update(conn));
+        }
+
+        let err = User::backfill_gh_id(conn, "placeholder", 4242).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "several accounts for `placeholder` are missing their GitHub ID"
+        );
+    });
+}
+
+#[test]
+fn backfill_gh_id_merges_a_placeholder_into_the_real_account() {
+    use cargo_registry::schema::{api_tokens, crate_owners, users};
+    use models::{ApiToken, CrateOwner};
+
+    let (app, _) = TestApp::init().empty();
+    app.db(|conn| {
+        let placeholder = t!(NewUser {
+            gh_id: -1,
+            ..new_user("merged")
+        }
+        .create_or_update(conn));
+        let real = t!(new_user("merged").create_or_update(conn));
+        let token = t!(ApiToken::insert(conn, placeholder.id, "from placeholder"));
+
+        CrateBuilder::new("placeholder_only", placeholder.id).expect_build(conn);
+        let shared = CrateBuilder::new("shared", real.id).expect_build(conn);
+        t!(diesel::insert_into(crate_owners::table)
+            .values(&CrateOwner {
+                crate_id: shared.id,
+                owner_id: placeholder.id,
+                created_by: placeholder.id,
+                owner_kind: 0,
+            })
+            .execute(conn));
+
+        let user = t!(User::backfill_gh_id(conn, "merged", real.gh_id));
+        assert_eq!(user.id, real.id);
+
+        let placeholder_left = t!(users::table
+            .find(placeholder.id)
+            .first::<User>(conn)
+            .optional());
+        assert_eq!(placeholder_left, None);
+        let token_owner = t!(api_tokens::table
+            .find(token.model.id)
+            .select(api_tokens::user_id)
+            .first::<i32>(conn));
+        assert_eq!(token_owner, real.id);
+        let owned = t!(crate_owners::table
+            .filter(crate_owners::owner_id.eq(real.id))
+            .filter(crate_owners::owner_kind.eq(0))
+            .select(crate_owners::crate_id)
+            .order(crate_owners::crate_id)
+            .load::<i32>(conn));
+        assert_eq!(owned.len(), 2);
+        assert!(owned.contains(&shared.id));
+    });
+}
+
+#[test]
+fn backfill_gh_id_keeps_crates_the_real_account_was_removed_from() {
+    use cargo_registry::schema::{crate_owner_actions, crate_owners};
+    use models::{CrateOwner, Owner};
+
+    let (app, _) = TestApp::init().empty();
+    app.db(|conn| {
+        let placeholder = t!(NewUser {
+            gh_id: -1,
+            ..new_user("merged")
+        }
+        .create_or_update(conn));
+        let real = t!(new_user("merged").create_or_update(conn));
+
+        let krate = CrateBuilder::new("formerly_real", real.id).expect_build(conn);
+        t!(CrateOwner {
+            crate_id: krate.id,
+            owner_id: placeholder.id,
+            created_by: real.id,
+            owner_kind: 0,
+        }
+        .add(conn));
+        t!(CrateOwner::remove(
+            conn,
+            krate.id,
+            &Owner::User(real.clone()),
+            placeholder.id
+        ));
+
+        t!(User::backfill_gh_id(conn, "merged", real.gh_id));
+
+        let owners = t!(crate_owners::table
+            .filter(crate_owners::crate_id.eq(krate.id))
+            .filter(crate_owners::deleted.eq(false))
+            .select(crate_owners::owner_id)
+            .load::<i32>(conn));
+        assert_eq!(owners, vec![real.id]);
+
+        // Moving the ownership is logged like any other change
+        let actions = t!(crate_owner_actions::table
+            .filter(crate_owner_actions::crate_id.eq(krate.id))
+            .order(crate_owner_actions::id)
+            .select((crate_owner_actions::owner_id, crate_owner_actions::action))
+            .load::<(i32, String)>(conn));
+        let last = &actions[actions.len() - 2..];
+        assert_eq!(
+            last,
+            &[
+                (real.id, "add".to_string()),
+                (placeholder.id, "remove".to_string())
+            ]
+        );
+    });
+}
+
+#[test]
+fn list_crates_owned_by_a_user() {
+    use models::{CrateOwner, Owner};
+    use util::MockCookieUser;
+    use CrateList;
+
+    let (app, anon) = TestApp::init().empty();
+    let owner = app.db_new_user("owner");
+    let former = app.db_new_user("former");
+    let nobody = app.db_new_user("nobody");
+    app.db(|conn| {
+        let owner = owner.as_model();
+        let former = former.as_model();
+        CrateBuilder::new("foo_owned", owner.id).expect_build(conn);
+        CrateBuilder::new("bar_owned", owner.id).expect_build(conn);
+        let removed = CrateBuilder::new("baz_removed", former.id).expect_build(conn);
+        t!(CrateOwner::remove(
+            conn,
+            removed.id,
+            &Owner::User(former.clone()),
+            former.id
+        ));
+    });
+
+    let url = |user: &MockCookieUser| format!("/api/v1/users/{}/crates", user.as_model().id);
+    let json: CrateList = anon.get(&url(&owner)).good();
+    let names = json.crates.iter().map(|c| &*c.name).collect::<Vec<_>>();
+    assert_eq!(names, ["bar_owned", "foo_owned"]);
+    assert_eq!(json.meta.total, 2);
+
+    let json: CrateList = anon
+        .get_with_query(&url(&owner), "per_page=1&page=2")
+        .good();
+    let names = json.crates.iter().map(|c| &*c.name).collect::<Vec<_>>();
+    assert_eq!(names, ["foo_owned"]);
+    assert_eq!(json.meta.total, 2);
+
+    let json: CrateList = anon.get(&url(&former)).good();
+    assert!(json.crates.is_empty());
+    assert_eq!(json.meta.total, 0);
+
+    let json: CrateList = anon.get(&url(&nobody)).good();
+    assert!(json.crates.is_empty());
+    assert_eq!(json.meta.total, 0);
+
+    anon.get::<()>("/api/v1/users/0/crates").assert_not_found();
+}
+
+#[test]
+fn display_name_survives_create_or_update() {
+    let (app, anon, user) = TestApp::init().with_user();
+    let login = user.as_model().gh_login.clone();
+
+    let body = br#"{ "display_name": " Display Name " }"#;
+    let json: OkBool = user.put("/api/v1/me/profile", body).good();
+    assert!(json.ok);
+
+    let updated = app.db(|conn| {
+        t!(NewUser {
+            gh_id: user.as_model().gh_id,
+            name: Some("GitHub Name"),
+            ..new_user(&login)
+        }
+        .create_or_update(conn))
+    });
+    assert_eq!(updated.name, Some("GitHub Name".to_string()));
+    assert_eq!(updated.display_name, Some("Display Name".to_string()));
+
+    let json: UserShowPrivateResponse = user.get("/api/v1/me").good();
+    assert_eq!(json.user.name, Some("Display Name".to_string()));
+    let json: UserShowPublicResponse = anon.get(&format!("/api/v1/users/{}", login)).good();
+    assert_eq!(json.user.name, Some("Display Name".to_string()));
+
+    let long = format!(r#"{{ "display_name": "{}" }}"#, "a".repeat(101));
+    user.put::<()>("/api/v1/me/profile", long.as_bytes())
+        .bad_with_status(400);
+
+    let json: OkBool = user
+        .put("/api/v1/me/profile", br#"{ "display_name": null }"#)
+        .good();
+    assert!(json.ok);
+    let json: UserShowPrivateResponse = user.get("/api/v1/me").good();
+    assert_eq!(json.user.name, Some("GitHub Name".to_string()));
+}
+
+#[test]
+fn failing_mailer_does_not_block_signup_and_reports_a_server_error() {
+    use cargo_registry::email::TestEmailSender;
+    use cargo_registry::schema::users;
+
+    let (app, _) = TestApp::init_with_email_sender(Box::new(TestEmailSender::failing())).empty();
+    let address = "failing-mailer@example.com";
+    let user = app.db(|conn| {
+        t!(NewUser {
+            email: Some(address),
+            ..new_user("failing_mailer")
+        }
+        .create_or_update(conn))
+    });
+    app.db(|conn| {
+        let found = t!(users::table.find(user.id).first::<User>(conn));
+        assert_eq!(found.email, Some(address.to_string()));
+        assert_eq!(emails_queued_for(conn, address), 1);
+    });
+
+    let user = app.db_new_user("changes_email");
+    let body = br#"{ "email": "unreachable@example.com" }"#;
+    let json = user
+        .put::<()>("/api/v1/me/email", body)
+        .bad_with_status(500);
+    assert_eq!(json.errors[0].detail, "failed to send confirmation email");
+}
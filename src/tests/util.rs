@@ -23,13 +23,16 @@ use std::{self, rc::Rc, sync::Arc};
 
 use {cargo_registry, conduit, conduit_middleware, diesel, dotenv, serde};
 
+use chrono::NaiveDateTime;
 use conduit::{Handler, Method, Request};
 use conduit_test::MockRequest;
 
 use builders::PublishBuilder;
 use cargo_registry::app::App;
+use cargo_registry::email::EmailSender;
 use cargo_registry::middleware::current_user::AuthenticationSource;
-use models::{ApiToken, User};
+use cargo_registry::Config;
+use models::{ApiToken, CreatedApiToken, NewApiToken, NewUser, User};
 
 use super::{app, record, CrateList, CrateResponse, GoodCrate, OkBool, VersionResponse};
 
@@ -56,6 +59,35 @@ impl TestApp {
         TestAppBuilder(TestApp(inner))
     }
 
+    /// Initialize an application with an `Uploader` that panics, after letting the caller adjust
+    /// its configuration
+    pub fn init_with_config<F: FnOnce(&mut Config)>(f: F) -> TestAppBuilder {
+        dotenv::dotenv().ok();
+        let mut config = ::simple_config(cargo_registry::Uploader::Panic);
+        f(&mut config);
+        let (app, middle) = ::build_app(&config);
+        let inner = Rc::new(TestAppInner {
+            app,
+            _bomb: None,
+            middle,
+        });
+        TestAppBuilder(TestApp(inner))
+    }
+
+    /// Initialize an application with an `Uploader` that panics, which sends the emails that
+    /// aren't queued with `email_sender` instead of writing them to `/tmp`
+    pub fn init_with_email_sender(email_sender: Box<dyn EmailSender>) -> TestAppBuilder {
+        dotenv::dotenv().ok();
+        let config = ::simple_config(cargo_registry::Uploader::Panic);
+        let (app, middle) = ::build_app_with(&config, |app| app.email_sender = email_sender);
+        let inner = Rc::new(TestAppInner {
+            app,
+            _bomb: None,
+            middle,
+        });
+        TestAppBuilder(TestApp(inner))
+    }
+
     /// Initialize a full application that can record and playback outgoing HTTP requests
     pub fn with_proxy() -> TestAppBuilder {
         let (bomb, app, middle) = app();
@@ -67,6 +99,17 @@ impl TestApp {
         TestAppBuilder(TestApp(inner))
     }
 
+    /// Like `with_proxy`, after letting the caller adjust the configuration
+    pub fn with_proxy_and_config<F: FnOnce(&mut Config)>(f: F) -> TestAppBuilder {
+        let (bomb, app, middle) = ::app_with_config(f);
+        let inner = Rc::new(TestAppInner {
+            app,
+            _bomb: Some(bomb),
+            middle,
+        });
+        TestAppBuilder(TestApp(inner))
+    }
+
     /// Obtain the database connection and pass it to the closure
     ///
     /// Within each test, the connection pool only has 1 connection so it is necessary to drop the
@@ -88,6 +131,23 @@ impl TestApp {
         }
     }
 
+    /// Create a new user with the given GitHub id, for tests whose recorded GitHub responses
+    /// refer to users by id.
+    pub fn db_new_user_with_gh_id(&self, user: &str, gh_id: i32) -> MockCookieUser {
+        let user = self.db(|conn| {
+            NewUser {
+                gh_id,
+                ..::new_user(user)
+            }
+            .create_or_update(conn)
+            .unwrap()
+        });
+        MockCookieUser {
+            app: TestApp(Rc::clone(&self.0)),
+            user,
+        }
+    }
+
     /// Obtain a reference to the inner `App` value
     pub fn as_inner(&self) -> &App {
         &*self.0.app
@@ -128,6 +188,15 @@ pub trait RequestHelper {
     fn request_builder(&self, method: Method, path: &str) -> MockRequest;
     fn app(&self) -> &TestApp;
 
+    /// Run a request built with `request_builder`, for requests needing more customization
+    /// than the other helpers provide
+    fn run<T>(&self, request: &mut MockRequest) -> Response<T>
+    where
+        for<'de> T: serde::Deserialize<'de>,
+    {
+        Response::new(self.app().0.middle.call(request))
+    }
+
     /// Issue a GET request
     fn get<T>(&self, path: &str) -> Response<T>
     where
@@ -137,6 +206,12 @@ pub trait RequestHelper {
         Response::new(self.app().0.middle.call(&mut request))
     }
 
+    /// Issue a HEAD request
+    fn head(&self, path: &str) -> Response<()> {
+        let mut request = self.request_builder(Method::Head, path);
+        Response::new(self.app().0.middle.call(&mut request))
+    }
+
     /// Issue a GET request that includes query parameters
     fn get_with_query<T>(&self, path: &str, query: &str) -> Response<T>
     where
@@ -157,6 +232,26 @@ pub trait RequestHelper {
         Response::new(self.app().0.middle.call(request))
     }
 
+    /// Issue a POST request
+    fn post<T>(&self, path: &str, body: &[u8]) -> Response<T>
+    where
+        for<'de> T: serde::Deserialize<'de>,
+    {
+        let mut builder = self.request_builder(Method::Post, path);
+        let request = builder.with_body(body);
+        Response::new(self.app().0.middle.call(request))
+    }
+
+    /// Issue a PATCH request
+    fn patch<T>(&self, path: &str, body: &[u8]) -> Response<T>
+    where
+        for<'de> T: serde::Deserialize<'de>,
+    {
+        let mut builder = self.request_builder(Method::Patch, path);
+        let request = builder.with_body(body);
+        Response::new(self.app().0.middle.call(request))
+    }
+
     /// Issue a DELETE request
     fn delete<T>(&self, path: &str) -> Response<T>
     where
@@ -264,18 +359,94 @@ impl MockCookieUser {
             token,
         }
     }
+
+    /// Creates a token that expires at the given time
+    ///
+    /// This method updates the database directly
+    pub fn db_new_expiring_token(&self, name: &str, expires_at: NaiveDateTime) -> MockTokenUser {
+        let new_token = NewApiToken {
+            name,
+            expires_at: Some(expires_at),
+            ..NewApiToken::default()
+        };
+        let token = self
+            .app
+            .db(|conn| ApiToken::insert_full(conn, self.user.id, &new_token).unwrap());
+        MockTokenUser {
+            app: TestApp(Rc::clone(&self.app.0)),
+            token,
+        }
+    }
+
+    /// Creates a token that may only be used for the given crates
+    ///
+    /// This method updates the database directly
+    pub fn db_new_scoped_token(&self, name: &str, crate_scopes: &[&str]) -> MockTokenUser {
+        let new_token = NewApiToken {
+            name,
+            crate_scopes: Some(crate_scopes.iter().map(|s| s.to_string()).collect()),
+            ..NewApiToken::default()
+        };
+        let token = self
+            .app
+            .db(|conn| ApiToken::insert_full(conn, self.user.id, &new_token).unwrap());
+        MockTokenUser {
+            app: TestApp(Rc::clone(&self.app.0)),
+            token,
+        }
+    }
+
+    /// Creates a token whose publishes are attributed to the given name
+    ///
+    /// This method updates the database directly
+    pub fn db_new_attributed_token(&self, name: &str, attribution_name: &str) -> MockTokenUser {
+        let new_token = NewApiToken {
+            name,
+            attribution_name: Some(attribution_name.to_string()),
+            ..NewApiToken::default()
+        };
+        let token = self
+            .app
+            .db(|conn| ApiToken::insert_full(conn, self.user.id, &new_token).unwrap());
+        MockTokenUser {
+            app: TestApp(Rc::clone(&self.app.0)),
+            token,
+        }
+    }
+
+    /// Creates a token that may only be used for the given kinds of request, such as `yank`
+    ///
+    /// This method updates the database directly
+    pub fn db_new_endpoint_scoped_token(
+        &self,
+        name: &str,
+        endpoint_scopes: &[&str],
+    ) -> MockTokenUser {
+        let new_token = NewApiToken {
+            name,
+            endpoint_scopes: Some(endpoint_scopes.iter().map(|s| s.to_string()).collect()),
+            ..NewApiToken::default()
+        };
+        let token = self
+            .app
+            .db(|conn| ApiToken::insert_full(conn, self.user.id, &new_token).unwrap());
+        MockTokenUser {
+            app: TestApp(Rc::clone(&self.app.0)),
+            token,
+        }
+    }
 }
 
 /// A type that can generate token authenticated requests
 pub struct MockTokenUser {
     app: TestApp,
-    token: ApiToken,
+    token: CreatedApiToken,
 }
 
 impl RequestHelper for MockTokenUser {
     fn request_builder(&self, method: Method, path: &str) -> MockRequest {
         let mut request = ::req(method, path);
-        request.header("Authorization", &self.token.token);
+        request.header("Authorization", &self.token.plaintext);
         request
     }
 
@@ -287,7 +458,12 @@ impl RequestHelper for MockTokenUser {
 impl MockTokenUser {
     /// Returns a reference to the database `ApiToken` model
     pub fn as_model(&self) -> &ApiToken {
-        &self.token
+        &self.token.model
+    }
+
+    /// Returns the token's value, which is only known because the token was just created
+    pub fn plaintext(&self) -> &str {
+        &self.token.plaintext
     }
 
     /// Add to the specified crate the specified owner.
@@ -376,6 +552,24 @@ where
         assert_eq!(status, self.response.status.0);
         self
     }
+
+    /// The value of the named response header, if it was set. Header names
+    /// are matched case-insensitively, and only the first value is returned
+    /// for headers that were set more than once.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.response
+            .headers
+            .iter()
+            .find(|&(key, _)| key.eq_ignore_ascii_case(name))
+            .and_then(|(_, values)| values.first())
+            .map(|value| value.as_str())
+    }
+
+    /// Assert that the named response header was set to the given value
+    pub fn assert_header(&self, name: &str, value: &str) -> &Self {
+        assert_eq!(self.header(name), Some(value), "header `{}`", name);
+        self
+    }
 }
 
 impl Response<()> {
@@ -388,4 +582,21 @@ impl Response<()> {
     pub fn assert_forbidden(&self) {
         assert_eq!((403, "Forbidden"), self.response.status);
     }
+
+    /// Assert that the response has no body, as for a HEAD request
+    pub fn assert_empty_body(mut self) {
+        let mut body = Vec::new();
+        t!(self.response.body.write_body(&mut body));
+        assert!(body.is_empty(), "unexpected body: {:?}", body);
+    }
+}
+
+#[test]
+fn response_header_helpers() {
+    let (_, anon) = TestApp::init().empty();
+    let response = anon.get::<()>("/api/v1/crates");
+    response
+        .assert_header("Content-Type", "application/json; charset=utf-8")
+        .assert_header("content-type", "application/json; charset=utf-8");
+    assert_eq!(response.header("Warning"), None);
 }
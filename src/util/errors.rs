@@ -157,11 +157,12 @@ impl<E: Error + Send + 'static> CargoError for E {
 
 impl<E: Any + Error + Send + 'static> From<E> for Box<dyn CargoError> {
     fn from(err: E) -> Box<dyn CargoError> {
-        if let Some(DieselError::NotFound) = Any::downcast_ref::<DieselError>(&err) {
-            Box::new(NotFound)
-        } else {
-            Box::new(err)
+        match Any::downcast_ref::<DieselError>(&err) {
+            Some(&DieselError::NotFound) => return Box::new(NotFound),
+            Some(e) if is_read_only(e) => return Box::new(ReadOnlyMode),
+            _ => {}
         }
+        Box::new(err)
     }
 }
 // =============================================================================
@@ -247,6 +248,73 @@ impl fmt::Display for Unauthorized {
     }
 }
 
+/// Returned when the GitHub API took too long to respond. This is a
+/// transient failure, so clients are asked to try again later.
+#[derive(Debug, Clone, Copy)]
+pub struct GitHubTimeout;
+
+impl CargoError for GitHubTimeout {
+    fn description(&self) -> &str {
+        "timed out waiting for GitHub"
+    }
+
+    fn response(&self) -> Option<Response> {
+        let mut response = json_response(&Bad {
+            errors: vec![StringError {
+                detail: "timed out waiting for a response from GitHub, please try again later"
+                    .to_string(),
+            }],
+        });
+        response.status = (503, "Service Unavailable");
+        Some(response)
+    }
+}
+
+impl fmt::Display for GitHubTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "timed out waiting for a response from GitHub".fmt(f)
+    }
+}
+
+/// Returned when a write is attempted while the database only accepts reads,
+/// as it does during maintenance. Reads keep working in the meantime.
+#[derive(Debug, Clone, Copy)]
+pub struct ReadOnlyMode;
+
+impl CargoError for ReadOnlyMode {
+    fn description(&self) -> &str {
+        "the database is read-only"
+    }
+
+    fn response(&self) -> Option<Response> {
+        let mut response = json_response(&Bad {
+            errors: vec![StringError {
+                detail: "registry is in maintenance (read-only) mode, please try again later"
+                    .to_string(),
+            }],
+        });
+        response.status = (503, "Service Unavailable");
+        Some(response)
+    }
+}
+
+impl fmt::Display for ReadOnlyMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "registry is in maintenance (read-only) mode".fmt(f)
+    }
+}
+
+/// Whether the error is PostgreSQL refusing a write because the transaction,
+/// or the whole server, is read-only.
+pub fn is_read_only(error: &DieselError) -> bool {
+    match *error {
+        DieselError::DatabaseError(_, ref info) => {
+            info.message().ends_with("in a read-only transaction")
+        }
+        _ => false,
+    }
+}
+
 #[derive(Debug)]
 struct BadRequest(String);
 
@@ -272,6 +340,143 @@ impl fmt::Display for BadRequest {
     }
 }
 
+/// Returned for resources that used to exist but have since been removed,
+/// such as revoked API tokens.
+#[derive(Debug)]
+struct Gone(String);
+
+impl CargoError for Gone {
+    fn description(&self) -> &str {
+        self.0.as_ref()
+    }
+
+    fn response(&self) -> Option<Response> {
+        let mut response = json_response(&Bad {
+            errors: vec![StringError {
+                detail: self.0.clone(),
+            }],
+        });
+        response.status = (410, "Gone");
+        Some(response)
+    }
+}
+
+impl fmt::Display for Gone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Returned when the user is signed in but isn't allowed to do what they
+/// asked, for example because their API token lacks the scope for it.
+#[derive(Debug)]
+pub struct Forbidden(String);
+
+impl CargoError for Forbidden {
+    fn description(&self) -> &str {
+        self.0.as_ref()
+    }
+
+    fn response(&self) -> Option<Response> {
+        let mut response = json_response(&Bad {
+            errors: vec![StringError {
+                detail: self.0.clone(),
+            }],
+        });
+        response.status = (403, "Forbidden");
+        Some(response)
+    }
+}
+
+impl fmt::Display for Forbidden {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Returned when a request can't be carried out because of the current state
+/// of what it asks to change, for example verifying an email twice.
+#[derive(Debug)]
+struct Conflict(String);
+
+impl CargoError for Conflict {
+    fn description(&self) -> &str {
+        self.0.as_ref()
+    }
+
+    fn response(&self) -> Option<Response> {
+        let mut response = json_response(&Bad {
+            errors: vec![StringError {
+                detail: self.0.clone(),
+            }],
+        });
+        response.status = (409, "Conflict");
+        Some(response)
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Debug)]
+struct TooManyRequests(String);
+
+impl CargoError for TooManyRequests {
+    fn description(&self) -> &str {
+        self.0.as_ref()
+    }
+
+    fn response(&self) -> Option<Response> {
+        let mut response = json_response(&Bad {
+            errors: vec![StringError {
+                detail: self.0.clone(),
+            }],
+        });
+        response.status = (429, "Too Many Requests");
+        Some(response)
+    }
+}
+
+impl fmt::Display for TooManyRequests {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Returned when an email couldn't be sent. The client is only told that, while the error from
+/// the mailer is kept as the cause for the logs.
+#[derive(Debug)]
+pub struct EmailDeliveryFailed(Box<dyn CargoError>);
+
+impl CargoError for EmailDeliveryFailed {
+    fn description(&self) -> &str {
+        "failed to send confirmation email"
+    }
+
+    fn cause(&self) -> Option<&dyn CargoError> {
+        Some(&*self.0)
+    }
+
+    fn response(&self) -> Option<Response> {
+        let mut response = json_response(&Bad {
+            errors: vec![StringError {
+                detail: self.description().to_string(),
+            }],
+        });
+        response.status = (500, "Internal Server Error");
+        Some(response)
+    }
+}
+
+impl fmt::Display for EmailDeliveryFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.description(), self.0)
+    }
+}
+
 pub fn internal_error(error: &str, detail: &str) -> Box<dyn CargoError> {
     Box::new(ConcreteCargoError {
         description: error.to_string(),
@@ -310,6 +515,26 @@ pub fn bad_request<S: ToString + ?Sized>(error: &S) -> Box<dyn CargoError> {
     Box::new(BadRequest(error.to_string()))
 }
 
+pub fn gone<S: ToString + ?Sized>(error: &S) -> Box<dyn CargoError> {
+    Box::new(Gone(error.to_string()))
+}
+
+pub fn forbidden<S: ToString + ?Sized>(error: &S) -> Box<dyn CargoError> {
+    Box::new(Forbidden(error.to_string()))
+}
+
+pub fn conflict<S: ToString + ?Sized>(error: &S) -> Box<dyn CargoError> {
+    Box::new(Conflict(error.to_string()))
+}
+
+pub fn too_many_requests<S: ToString + ?Sized>(error: &S) -> Box<dyn CargoError> {
+    Box::new(TooManyRequests(error.to_string()))
+}
+
+pub fn email_delivery_failed(cause: Box<dyn CargoError>) -> Box<dyn CargoError> {
+    Box::new(EmailDeliveryFailed(cause))
+}
+
 pub fn std_error(e: Box<dyn CargoError>) -> Box<dyn Error + Send> {
     #[derive(Debug)]
     struct E(Box<dyn CargoError>);
@@ -7,17 +7,24 @@ use serde_json;
 
 use conduit::Response;
 
-pub use self::errors::{bad_request, human, internal, internal_error, CargoError, CargoResult};
+pub use self::errors::{
+    bad_request, conflict, forbidden, gone, human, internal, internal_error, too_many_requests,
+    CargoError, CargoResult,
+};
 pub use self::errors::{std_error, ChainError};
 pub use self::io_util::{read_fill, read_le_u32, LimitErrorReader};
+pub use self::rate_limit::RateLimit;
 pub use self::request_helpers::*;
 pub use self::request_proxy::RequestProxy;
+pub use self::ttl_cache::TtlCache;
 
 pub mod errors;
 mod io_util;
+mod rate_limit;
 mod request_helpers;
 mod request_proxy;
 pub mod rfc3339;
+mod ttl_cache;
 
 pub fn json_response<T: Serialize>(t: &T) -> Response {
     let json = serde_json::to_string(t).unwrap();
@@ -0,0 +1,102 @@
+//! Counts events per key, such as invalid API tokens sent from an IP address, so that further
+//! events can be refused once too many have happened recently.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub struct RateLimit {
+    limit: u32,
+    window: Duration,
+    events: Mutex<HashMap<String, Events>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Events {
+    count: u32,
+    window_start: Instant,
+}
+
+impl RateLimit {
+    /// Creates a `RateLimit` allowing `limit` events per key within `window`. The window starts
+    /// with the first event recorded for a key, and the count resets once it has ended.
+    pub fn new(limit: u32, window: Duration) -> RateLimit {
+        RateLimit {
+            limit,
+            window,
+            events: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns whether `n` more events for `key` would stay within the limit.
+    pub fn allows(&self, key: &str, n: u32) -> bool {
+        let now = Instant::now();
+        let mut events = self.events.lock().unwrap();
+        match events.get(key).cloned() {
+            Some(ref e) if now.duration_since(e.window_start) >= self.window => {
+                events.remove(key);
+                n <= self.limit
+            }
+            Some(e) => e.count.saturating_add(n) <= self.limit,
+            None => n <= self.limit,
+        }
+    }
+
+    /// Records `n` events for `key`, starting a new window if the previous one has ended.
+    pub fn record(&self, key: &str, n: u32) {
+        let mut events = self.events.lock().unwrap();
+        let entry = self.current_window(&mut events, key);
+        entry.count = entry.count.saturating_add(n);
+    }
+
+    /// Records `n` events for `key` if that stays within the limit, returning whether they were
+    /// recorded. Checking and recording happen under one lock, so concurrent callers can't both
+    /// squeeze in under the limit.
+    pub fn try_record(&self, key: &str, n: u32) -> bool {
+        let mut events = self.events.lock().unwrap();
+        let entry = self.current_window(&mut events, key);
+        if entry.count.saturating_add(n) > self.limit {
+            return false;
+        }
+        entry.count += n;
+        true
+    }
+
+    /// Returns the events recorded for `key` in the current window, starting a new window if
+    /// the previous one has ended. Keys whose window has ended are forgotten when a new key
+    /// arrives, so the map only holds keys that saw an event within the last window.
+    fn current_window<'a>(
+        &self,
+        events: &'a mut HashMap<String, Events>,
+        key: &str,
+    ) -> &'a mut Events {
+        let now = Instant::now();
+        let window = self.window;
+        if !events.contains_key(key) {
+            events.retain(|_, e| now.duration_since(e.window_start) < window);
+        }
+        let entry = events.entry(key.to_string()).or_insert(Events {
+            count: 0,
+            window_start: now,
+        });
+        if now.duration_since(entry.window_start) >= window {
+            *entry = Events {
+                count: 0,
+                window_start: now,
+            };
+        }
+        entry
+    }
+
+    /// Takes back `n` events recorded for `key` that didn't happen after all. Does nothing if
+    /// the window they were recorded in has since ended.
+    pub fn release(&self, key: &str, n: u32) {
+        let mut events = self.events.lock().unwrap();
+        if let Some(entry) = events.get_mut(key) {
+            if entry.window_start.elapsed() < self.window {
+                entry.count = entry.count.saturating_sub(n);
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! A small in-memory cache whose entries expire a fixed time after they were stored.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub struct TtlCache<K: Eq + Hash, V> {
+    ttl: Duration,
+    entries: Mutex<HashMap<K, (V, Instant)>>,
+}
+
+impl<K: Eq + Hash, V: Clone> TtlCache<K, V> {
+    /// Creates a cache whose entries are forgotten `ttl` after they were inserted. A `ttl` of
+    /// zero disables caching.
+    pub fn new(ttl: Duration) -> TtlCache<K, V> {
+        TtlCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the value stored for `key`, unless it has expired.
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut entries = self.entries.lock().unwrap();
+        let expired = match entries.get(key) {
+            Some(&(ref value, stored_at)) if stored_at.elapsed() < self.ttl => {
+                return Some(value.clone());
+            }
+            Some(_) => true,
+            None => false,
+        };
+        if expired {
+            entries.remove(key);
+        }
+        None
+    }
+
+    /// Stores `value` for `key`. Expired entries are forgotten whenever a new key is stored, so
+    /// the cache doesn't grow with keys that are never looked up again.
+    pub fn insert(&self, key: K, value: V) {
+        if self.ttl == Duration::from_secs(0) {
+            return;
+        }
+        let ttl = self.ttl;
+        let mut entries = self.entries.lock().unwrap();
+        if !entries.contains_key(&key) {
+            entries.retain(|_, &mut (_, stored_at)| stored_at.elapsed() < ttl);
+        }
+        entries.insert(key, (value, Instant::now()));
+    }
+
+    pub fn remove(&self, key: &K) {
+        self.entries.lock().unwrap().remove(key);
+    }
+
+    /// Forgets every entry whose key doesn't satisfy `keep`.
+    pub fn retain<F: Fn(&K) -> bool>(&self, keep: F) {
+        self.entries.lock().unwrap().retain(|key, _| keep(key));
+    }
+}
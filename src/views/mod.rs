@@ -2,7 +2,7 @@ use chrono::NaiveDateTime;
 use serde_json;
 use std::collections::HashMap;
 
-use models::DependencyKind;
+use models::{DependencyKind, OwnerAction};
 use util::rfc3339;
 
 #[derive(PartialEq, Debug, Serialize, Deserialize)]
@@ -45,6 +45,32 @@ pub struct EncodableCrateOwnerInvitation {
     pub created_at: NaiveDateTime,
 }
 
+/// The serialization format for a pending `CrateOwnerInvitation`, as listed
+/// next to the confirmed owners of a crate.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct EncodablePendingOwner {
+    pub login: String,
+    pub invited_by: String,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "rfc3339")]
+    pub expires_at: NaiveDateTime,
+}
+
+/// The serialization format for a `CrateOwnerAction`, an entry in the log of
+/// changes to the owners of a crate.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct EncodableOwnerAction {
+    #[serde(rename = "crate")]
+    pub crate_name: String,
+    pub action: OwnerAction,
+    pub owner: Option<String>,
+    pub owner_kind: String,
+    pub performed_by: Option<String>,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+}
+
 #[derive(Deserialize, Serialize, Debug, Copy, Clone)]
 pub struct InvitationResponse {
     pub crate_id: i32,
@@ -125,6 +151,10 @@ pub struct EncodableOwner {
     pub url: Option<String>,
     pub name: Option<String>,
     pub avatar: Option<String>,
+    /// Whether a user has a verified email address, for the verified publisher badge. Only
+    /// given for users listed by `GET /crates/:crate_id/owners`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verified_email: Option<bool>,
 }
 
 #[derive(Serialize, Debug)]
@@ -149,6 +179,12 @@ pub struct EncodableApiTokenWithToken {
     pub created_at: NaiveDateTime,
     #[serde(with = "rfc3339::option")]
     pub last_used_at: Option<NaiveDateTime>,
+    pub crate_scopes: Option<Vec<String>>,
+    #[serde(with = "rfc3339::option")]
+    pub expires_at: Option<NaiveDateTime>,
+    pub external_ref: Option<String>,
+    pub endpoint_scopes: Option<Vec<String>>,
+    pub attribution_name: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -169,6 +205,17 @@ pub struct EncodablePrivateUser {
     pub name: Option<String>,
     pub avatar: Option<String>,
     pub url: Option<String>,
+    #[serde(with = "rfc3339::option")]
+    pub last_login_at: Option<NaiveDateTime>,
+    #[serde(with = "rfc3339")]
+    pub created_at: NaiveDateTime,
+    pub is_admin: bool,
+    /// Only included when requested with `include=rights`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub can_publish: Option<bool>,
+    /// Only included when requested with `include=rights`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publishable_crate_count: Option<i64>,
 }
 
 /// The serialization format for the `User` model.
@@ -180,6 +227,19 @@ pub struct EncodablePublicUser {
     pub name: Option<String>,
     pub avatar: Option<String>,
     pub url: Option<String>,
+    /// Only included when requested with `include=verified`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verified: Option<bool>,
+}
+
+/// A minimal reference to a user, for owner lists that only need to show who
+/// someone is rather than their whole public profile.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct EncodableOwnerRef {
+    pub id: i32,
+    pub login: String,
+    pub avatar: Option<String>,
+    pub url: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -202,6 +262,9 @@ pub struct EncodableVersion {
     pub license: Option<String>,
     pub links: EncodableVersionLinks,
     pub crate_size: Option<i32>,
+    /// The service account the version was published as, if the API token used to publish it
+    /// had an attribution name.
+    pub published_as: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -305,6 +368,7 @@ mod tests {
                 authors: "".to_string(),
             },
             crate_size: Some(1234),
+            published_as: None,
         };
         let json = serde_json::to_string(&ver).unwrap();
         assert!(json
@@ -356,6 +420,21 @@ mod tests {
             .is_some());
     }
 
+    #[test]
+    fn owner_ref_serializes_only_identifying_fields() {
+        let owner = EncodableOwnerRef {
+            id: 1,
+            login: "foo".to_string(),
+            avatar: None,
+            url: Some("https://github.com/foo".to_string()),
+        };
+        let json = serde_json::to_string(&owner).unwrap();
+        assert_eq!(
+            json,
+            r#"{"id":1,"login":"foo","avatar":null,"url":"https://github.com/foo"}"#
+        );
+    }
+
     #[test]
     fn crate_owner_invitation_serializes_to_rfc3339() {
         let inv = EncodableCrateOwnerInvitation {